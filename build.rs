@@ -39,16 +39,34 @@ fn main() {
     println!("cargo:rerun-if-changed=src/lfs-sys");
     println!("cargo:rerun-if-changed=src/lfs-rambd");
 
-    let files = glob("*.o").unwrap().chain(glob("*.a").unwrap());
+    // every target gets its own copy of the library, they are not compatible
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let pattern = |ext: &str| out_dir.join(ext).to_string_lossy().into_owned();
+    let files = glob(&pattern("*.o")).unwrap().chain(glob(&pattern("*.a")).unwrap());
     files.for_each(|f| {
         let f = f.unwrap();
         Command::new("rm").arg("-f").arg(f).output().unwrap();
     });
 
-    let clang_ar = get_wasi_ar();
-    let clang_cc = get_wasi_cc();
+    // WebAssembly builds use WASI-SDK, native builds (cargo test) the host's cc
+    let wasm = env::var("TARGET").unwrap().starts_with("wasm32");
+    let (cc, ar) = if wasm {
+        (get_wasi_cc(), get_wasi_ar())
+    } else {
+        (PathBuf::from("cc"), PathBuf::from("ar"))
+    };
+    let wasm_flags: &[&str] = if wasm {
+        &["-mmutable-globals", "-matomics", "-mbulk-memory"]
+    } else {
+        &[]
+    };
 
-    let out = Command::new(clang_cc)
+    let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let src = |path: &str| dir.join(path);
+    let include = |path: &str| format!("-I{}", dir.join(path).display());
+
+    let out = Command::new(cc)
+        .current_dir(&out_dir)
         .arg("-std=c11")
         .arg("-Wall")
         .arg("-Wextra")
@@ -62,17 +80,15 @@ fn main() {
         .arg("-DLFS_NO_ERROR")
         .arg("-DLFS_NO_ASSERT")
         .arg("-DLFS_THREADSAFE")
-        .arg("-mmutable-globals")
-        .arg("-matomics")
-        .arg("-mbulk-memory")
+        .args(wasm_flags)
         .arg("-c")
-        .arg("-Isrc/lfs")
-        .arg("-Isrc/lfs-sys")
-        .arg("-Isrc/lfs-rambd")
-        .arg("src/lfs/lfs.c")
-        .arg("src/lfs/lfs_util.c")
-        .arg("src/lfs-sys/lfs_sys.c")
-        .arg("src/lfs-rambd/lfs_rambd.c")
+        .arg(include("src/lfs"))
+        .arg(include("src/lfs-sys"))
+        .arg(include("src/lfs-rambd"))
+        .arg(src("src/lfs/lfs.c"))
+        .arg(src("src/lfs/lfs_util.c"))
+        .arg(src("src/lfs-sys/lfs_sys.c"))
+        .arg(src("src/lfs-rambd/lfs_rambd.c"))
         .output()
         .unwrap();
 
@@ -82,7 +98,8 @@ fn main() {
         panic!("failed to compile lfs");
     }
 
-    let out = Command::new(clang_ar)
+    let out = Command::new(ar)
+        .current_dir(&out_dir)
         .arg("-r")
         .arg("liblfs.a")
         .arg("lfs.o")
@@ -98,7 +115,6 @@ fn main() {
         panic!("failed to compile lfs");
     }
 
-    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-    println!("cargo:rustc-link-search=native={}", dir);
+    println!("cargo:rustc-link-search=native={}", out_dir.display());
     println!("cargo:rustc-link-lib=lfs");
 }
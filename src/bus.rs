@@ -1,4 +1,5 @@
 #![allow(non_snake_case)]
+use crate::host;
 use crossbeam_queue::SegQueue;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
@@ -10,8 +11,8 @@ static mut BUSES: Lazy<Vec<BusImpl>> = Lazy::new(|| Vec::new());
 static mut EMITTERS: Lazy<HashMap<String, EventEmitterInternal>> = Lazy::new(|| HashMap::new());
 
 pub unsafe fn sab_bus_diag() {
-    web_sys::console::log_1(&format!("[WASABIO:BUS] BUSES: {:?}", BUSES).into());
-    web_sys::console::log_1(&format!("[WASABIO:BUS] EMITTERS: {:?}", EMITTERS).into());
+    host::log(&format!("[WASABIO:BUS] BUSES: {:?}", BUSES));
+    host::log(&format!("[WASABIO:BUS] EMITTERS: {:?}", EMITTERS));
 }
 
 pub unsafe fn sab_bus_reboot() {
@@ -1,9 +1,40 @@
 #![allow(non_snake_case)]
 
+mod backup;
+mod changes;
+mod clock;
+#[cfg(target_arch = "wasm32")]
 mod crt;
+mod cwd;
+mod dev;
+pub(crate) mod errno;
+mod fifo;
+mod journal;
 mod lfs;
+mod lz4;
+mod names;
+mod native;
+mod proc;
+mod sha256;
+mod tmp;
+mod transaction;
+#[cfg(target_arch = "wasm32")]
+mod wasi;
 
 use crate::bus::EventEmitter;
+use crate::host;
+pub use backup::{backup, backup_incremental, restore};
+pub use errno::{error_code, SysError};
+pub use lfs::{ChecksumAlgorithm, Compression, DiskUsage, StatFs, Timestamp, NAME_MAX, PATH_MAX};
+pub use names::{name_matching, set_name_matching, NameMatching, Normalization};
+pub use native::{
+    append, checksum, chown, copy, create_dir, create_dir_all, create_temp_dir, create_temp_file,
+    disk_usage, metadata, read, read_dir, remove_dir, remove_file, set_compression,
+    set_permissions, set_symlink_times, set_times, statfs, symlink_metadata, write, DirEntry, File,
+    FileType, Metadata, OpenOptions, ReadDir,
+};
+pub use transaction::Transaction;
+use journal::Journaled;
 use defr::defr;
 use js_sys::Reflect;
 use nameof::name_of;
@@ -14,14 +45,25 @@ use wasm_bindgen::prelude::*;
 static mut EMITTER: Lazy<EventEmitter> = Lazy::new(|| EventEmitter::new("fs"));
 
 pub unsafe fn sab_fs_diag() {
-    let stat = statfsSync("/".to_string(), Some(false));
-    web_sys::console::log_1(&format!("[WASABIO:FS] statfsSync: {}", stat.json()).into());
+    if let Ok(stat) = lfs::statfs_sync("/") {
+        host::log(&format!("[WASABIO:FS] statfsSync: {}", json!(stat)));
+    }
     lfs::lfs_diag();
+    journal::journal_diag();
+    changes::changes_diag();
+    fifo::fifo_diag();
+    tmp::tmp_diag();
 }
 
 pub unsafe fn sab_fs_reboot() {
     EMITTER = Lazy::new(|| EventEmitter::new("fs"));
     lfs::lfs_reset();
+    journal::journal_reset();
+    changes::changes_reset();
+    fifo::fifo_reset();
+    clock::clock_reset();
+    tmp::tmp_reset();
+    names::names_reset();
 }
 
 pub unsafe fn sab_fs_locked() -> bool {
@@ -51,11 +93,25 @@ fn parse_filesystem_mode(mode: String) -> i32 {
     }
 }
 
+/// The path behind `fd`, empty if it is not open. The call itself then
+/// fails with EBADF.
 fn path_from_fd(fd: usize) -> String {
-    lfs::readlink_sync(format!("/proc/self/fd/{}", fd).as_str())
-        .unwrap()
-        .as_string()
-        .unwrap()
+    lfs::readlink_sync(format!("/proc/self/fd/{}", fd).as_str()).unwrap_or_default()
+}
+
+/// Converts a filesystem error into what Node throws: an Error carrying code,
+/// path and syscall. `syscall` is used if the error does not name one.
+fn to_js(err: std::io::Error, syscall: &str) -> JsValue {
+    let js: JsValue = JsError::new(&err.to_string()).into();
+    let sys = errno::sys_error_of(&err);
+    let code = sys.map_or("EIO", |e| e.code.as_str());
+    let _ = Reflect::set(&js, &"code".into(), &code.into());
+    if let Some(path) = sys.and_then(|e| e.path.as_deref()) {
+        let _ = Reflect::set(&js, &"path".into(), &path.into());
+    }
+    let syscall = sys.and_then(|e| e.syscall.as_deref()).unwrap_or(syscall);
+    let _ = Reflect::set(&js, &"syscall".into(), &syscall.into());
+    js
 }
 
 #[wasm_bindgen]
@@ -75,14 +131,22 @@ macro_rules! debug_log {
 	($name:expr, $($arg:expr),*) => {
 		if TRACE_FILESYSTEM_CALLS {
 			let ev = json!([ $($arg),* ]);
-			let log_time = js_sys::Date::new_0().get_time();
-			web_sys::console::log_1(&format!("[fs:{}] {}({})", log_time, $name, ev.to_string()).into());
+			let log_time = host::now();
+			host::log(&format!("[fs:{}] {}({})", log_time, $name, ev.to_string()));
 		}
 	};
 }
 
 macro_rules! broadcast_defer {
+    (name_of!($name:ident), $($arg:expr),*) => {
+        let seq = journal::begin(name_of!($name), json!([ $($arg),* ]));
+        defr!(journal::end(seq));
+        broadcast_defer!(@emit name_of!($name), $($arg),*);
+    };
     ($name:expr, $($arg:expr),*) => {
+        broadcast_defer!(@emit $name, $($arg),*);
+    };
+    (@emit $name:expr, $($arg:expr),*) => {
 		debug_log!($name, $($arg),*);
         let ev = json!([ $($arg),* ]);
         defr!(unsafe {
@@ -95,9 +159,9 @@ macro_rules! broadcast_defer {
 
 macro_rules! broadcast_watch {
     ($path:expr) => {
-        let prevStat = lfs::stat_sync($path.as_str());
+        let prevStat = lfs::stat_sync($path.as_str()).ok();
         defr!(unsafe {
-            let currStat = lfs::stat_sync($path.as_str());
+            let currStat = lfs::stat_sync($path.as_str()).ok();
             let ev = json!([$path.to_string(), prevStat, currStat]);
             EMITTER
                 .emit(ChangeType::WATCH_.to_string(), ev.to_string())
@@ -108,22 +172,32 @@ macro_rules! broadcast_watch {
 
 #[wasm_bindgen]
 pub fn linkSync(existing: String, path: String) -> Result<JsValue, JsValue> {
+    let existing = cwd::resolve(&existing);
+    let path = cwd::resolve(&path);
     broadcast_watch!(path);
     broadcast_watch!(existing);
     broadcast_defer!(ChangeType::RENAME, path);
     broadcast_defer!(ChangeType::CHANGE, existing);
     broadcast_defer!(name_of!(linkSync), existing, path);
     lfs::link_sync(existing.as_str(), path.as_str())
+        .map(|_| JsValue::undefined())
+        .map_err(|e| to_js(e, "link"))
+        .journaled()
 }
 
 #[wasm_bindgen]
 pub fn symlinkSync(target: String, path: String) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
+    let followed = lfs::symlink_target(&path, &target);
     broadcast_watch!(path);
-    broadcast_watch!(target);
+    broadcast_watch!(followed);
     broadcast_defer!(ChangeType::RENAME, path);
-    broadcast_defer!(ChangeType::CHANGE, target);
+    broadcast_defer!(ChangeType::CHANGE, followed);
     broadcast_defer!(name_of!(symlinkSync), target, path);
     lfs::symlink_sync(target.as_str(), path.as_str())
+        .map(|_| JsValue::undefined())
+        .map_err(|e| to_js(e, "symlink"))
+        .journaled()
 }
 
 #[wasm_bindgen]
@@ -142,6 +216,10 @@ pub struct NodeStats {
     pub mtimeMs: f64,
     pub ctimeMs: f64,
     pub birthtimeMs: f64,
+    pub atimeNs: i64,
+    pub mtimeNs: i64,
+    pub ctimeNs: i64,
+    pub birthtimeNs: i64,
 }
 
 #[wasm_bindgen]
@@ -156,10 +234,10 @@ impl NodeStats {
         false
     }
     pub fn isCharacterDevice(&self) -> bool {
-        false
+        (self.mode as u32) & lfs::S_IFMT == lfs::S_IFCHR
     }
     pub fn isFIFO(&self) -> bool {
-        false
+        (self.mode as u32) & lfs::S_IFMT == lfs::S_IFIFO
     }
     pub fn isSocket(&self) -> bool {
         false
@@ -169,12 +247,31 @@ impl NodeStats {
     }
 }
 
+/// Accepts both the string flags ("r", "wx+", ...) and their numeric form.
+fn parse_open_flags(flags: Option<&UnionStringNumber>, path: &str) -> Result<lfs::OpenFlags, JsValue> {
+    match flags {
+        None => Ok(lfs::OpenFlags(lfs::OpenFlags::RDONLY)),
+        Some(flags) if flags.is_string() => {
+            let flags = flags.as_string().unwrap();
+            lfs::OpenFlags::parse(&flags).ok_or_else(|| {
+                let err: JsValue = JsError::new(&format!("EINVAL: invalid flags '{}', open '{}'", flags, path)).into();
+                Reflect::set(&err, &"code".into(), &"EINVAL".into()).unwrap();
+                Reflect::set(&err, &"path".into(), &path.into()).unwrap();
+                Reflect::set(&err, &"syscall".into(), &"open".into()).unwrap();
+                err
+            })
+        }
+        Some(flags) => Ok(lfs::OpenFlags(flags.as_f64().unwrap_or(0.0) as u32)),
+    }
+}
+
 #[wasm_bindgen]
 pub unsafe fn openSync(
     path: String,
-    flags: Option<String>,
+    flags: Option<UnionStringNumber>,
     mode: Option<UnionStringNumber>,
 ) -> Result<usize, JsValue> {
+    let path = cwd::resolve(&path);
     let mode = if let Some(mode) = mode {
         Some(if mode.is_string() {
             parse_filesystem_mode(mode.as_string().unwrap())
@@ -186,45 +283,52 @@ pub unsafe fn openSync(
     };
     let pathClone = path.clone();
     broadcast_watch!(pathClone);
-    broadcast_defer!(name_of!(openSync), path, flags, mode);
-    if let Some(fd) = lfs::open_sync(path.as_str(), flags.as_ref().map(|s| s.as_str()), mode) {
-        return Ok(fd);
-    } else {
-        let err: JsValue = JsError::new("ENOENT: no such file or directory").into();
-        Reflect::set(&err, &"path".into(), &path.into()).unwrap();
-        Reflect::set(&err, &"code".into(), &"ENOENT".into()).unwrap();
-        Reflect::set(&err, &"syscall".into(), &"open".into()).unwrap();
-        return Err(err);
-    }
+    let flags = parse_open_flags(flags.as_ref(), &path);
+    let flagsNumber = flags.as_ref().ok().map(|f| f.0);
+    broadcast_defer!(name_of!(openSync), path, flagsNumber, mode);
+    let fd = lfs::open_sync(path.as_str(), flags.journaled()?, mode)
+        .map_err(|e| to_js(e, "open"))
+        .journaled()?;
+    journal::result(json!(fd));
+    Ok(fd)
 }
 
 #[wasm_bindgen]
 pub unsafe fn opendirSync(path: String) -> Result<usize, JsValue> {
+    let path = cwd::resolve(&path);
     broadcast_defer!(name_of!(opendirSync), path);
-    openSync(path, None, None)
+    let fd = openSync(path, None, None)?;
+    journal::result(json!(fd));
+    Ok(fd)
 }
 
 #[wasm_bindgen]
 pub unsafe fn openfileSync(
     path: String,
-    flags: Option<String>,
+    flags: Option<UnionStringNumber>,
     mode: Option<UnionStringNumber>,
 ) -> Result<usize, JsValue> {
-    broadcast_defer!(name_of!(openfileSync), path);
-    openSync(path, flags, mode)
+    let path = cwd::resolve(&path);
+    let modeNumber = mode.as_ref().map(|mode| {
+        if mode.is_string() {
+            parse_filesystem_mode(mode.as_string().unwrap())
+        } else {
+            mode.as_f64().unwrap() as i32
+        }
+    });
+    let flagsNumber = parse_open_flags(flags.as_ref(), &path).ok().map(|f| f.0);
+    broadcast_defer!(name_of!(openfileSync), path, flagsNumber, modeNumber);
+    let fd = openSync(path, flags, mode)?;
+    journal::result(json!(fd));
+    Ok(fd)
 }
 
 #[wasm_bindgen]
 pub unsafe fn closeSync(fd: usize) -> Result<(), JsValue> {
     broadcast_defer!(name_of!(closeSync), fd);
-    if lfs::close_sync(fd).is_some() {
-        Ok(())
-    } else {
-        let err: JsValue = JsError::new("EBADF: bad file descriptor").into();
-        Reflect::set(&err, &"code".into(), &"EBADF".into()).unwrap();
-        Reflect::set(&err, &"syscall".into(), &"close".into()).unwrap();
-        Err(err)
-    }
+    native::close_fd(fd)
+        .map_err(|e| to_js(e, "close"))
+        .journaled()
 }
 
 #[wasm_bindgen]
@@ -240,9 +344,16 @@ pub unsafe fn readSync(
     offset: Option<usize>,
     length: Option<usize>,
     position: Option<i32>,
-) -> usize {
+) -> Result<usize, JsValue> {
     broadcast_defer!(name_of!(readSync), fd, offset, length, position);
-    lfs::read_sync(fd, buffer, offset, length, position).unwrap_or(0)
+    let range = lfs::buffer_range(buffer.len(), offset, length)
+        .map_err(|e| to_js(e, "read"))
+        .journaled()?;
+    let read = native::read_fd(fd, &mut buffer[range], position.filter(|p| *p >= 0))
+        .map_err(|e| to_js(e, "read"))
+        .journaled()?;
+    journal::result(json!(read));
+    Ok(read)
 }
 
 #[wasm_bindgen]
@@ -252,18 +363,26 @@ pub unsafe fn writeSync(
     offset: Option<usize>,
     length: Option<usize>,
     position: Option<i32>,
-) -> usize {
+) -> Result<usize, JsValue> {
     broadcast_watch!(path_from_fd(fd));
     broadcast_defer!(name_of!(writeSync), fd, offset, length, position);
     broadcast_defer!(ChangeType::CHANGE, path_from_fd(fd));
-    lfs::write_sync(fd, buffer, offset, length, position).unwrap_or(0)
+    let range = lfs::buffer_range(buffer.len(), offset, length)
+        .map_err(|e| to_js(e, "write"))
+        .journaled()?;
+    journal::attach(&buffer[range.clone()]);
+    native::write_fd(fd, &buffer[range], position.filter(|p| *p >= 0))
+        .map_err(|e| to_js(e, "write"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn fstatSync(fd: usize) -> NodeStats {
+pub fn fstatSync(fd: usize) -> Result<NodeStats, JsValue> {
     broadcast_defer!(name_of!(fstatSync), fd);
-    let stat = lfs::fstat(fd).unwrap();
-    NodeStats {
+    let stat = native::fstat_fd(fd)
+        .map_err(|e| to_js(e, "fstat"))
+        .journaled()?;
+    Ok(NodeStats {
         dev: stat.dev,
         ino: stat.ino,
         mode: stat.mode as f64,
@@ -278,11 +397,15 @@ pub unsafe fn fstatSync(fd: usize) -> NodeStats {
         mtimeMs: stat.mtimeMs,
         ctimeMs: stat.ctimeMs,
         birthtimeMs: stat.birthtimeMs,
-    }
+        atimeNs: stat.atimeNs,
+        mtimeNs: stat.mtimeNs,
+        ctimeNs: stat.ctimeNs,
+        birthtimeNs: stat.birthtimeNs,
+    })
 }
 
 #[wasm_bindgen]
-pub unsafe fn fchmodSync(fd: usize, mode: UnionStringNumber) {
+pub fn fchmodSync(fd: usize, mode: UnionStringNumber) -> Result<(), JsValue> {
     let mode = if mode.is_string() {
         parse_filesystem_mode(mode.as_string().unwrap())
     } else {
@@ -291,50 +414,85 @@ pub unsafe fn fchmodSync(fd: usize, mode: UnionStringNumber) {
     broadcast_watch!(path_from_fd(fd));
     broadcast_defer!(name_of!(fchmodSync), fd, mode);
     broadcast_defer!(ChangeType::CHANGE, path_from_fd(fd));
-    lfs::fchmod(fd, mode).unwrap();
+    native::fchmod_fd(fd, mode)
+        .map_err(|e| to_js(e, "fchmod"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn fchownSync(fd: usize, uid: usize, gid: usize) {
+pub fn fchownSync(fd: usize, uid: usize, gid: usize) -> Result<(), JsValue> {
     broadcast_watch!(path_from_fd(fd));
     broadcast_defer!(name_of!(fchownSync), fd, uid, gid);
     broadcast_defer!(ChangeType::CHANGE, path_from_fd(fd));
-    lfs::fchown(fd, uid as i32, gid as i32).unwrap();
+    native::fchown_fd(fd, uid as i32, gid as i32)
+        .map_err(|e| to_js(e, "fchown"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn ftruncateSync(fd: usize, len: Option<usize>) {
+pub fn ftruncateSync(fd: usize, len: Option<usize>) -> Result<(), JsValue> {
     let len = len.unwrap_or(0);
     broadcast_watch!(path_from_fd(fd));
     broadcast_defer!(name_of!(ftruncateSync), fd, len);
     broadcast_defer!(ChangeType::CHANGE, path_from_fd(fd));
-    lfs::ftruncate(fd, len).unwrap();
+    lfs::ftruncate(fd, len)
+        .map_err(|e| to_js(e, "ftruncate"))
+        .journaled()
+}
+
+#[wasm_bindgen]
+pub fn fallocateSync(
+    fd: usize,
+    offset: usize,
+    len: usize,
+    options: Option<UnionObjectUndefined>,
+) -> Result<(), JsValue> {
+    let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
+    let keepSize = Reflect::get(&options, &"keepSize".into())
+        .unwrap_or(JsValue::UNDEFINED)
+        .is_truthy();
+    let punchHole = Reflect::get(&options, &"punchHole".into())
+        .unwrap_or(JsValue::UNDEFINED)
+        .is_truthy();
+    broadcast_watch!(path_from_fd(fd));
+    broadcast_defer!(name_of!(fallocateSync), fd, offset, len, keepSize, punchHole);
+    broadcast_defer!(ChangeType::CHANGE, path_from_fd(fd));
+    lfs::fallocate(fd, offset, len, keepSize, punchHole)
+        .map_err(|e| to_js(e, "fallocate"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn futimesSync(fd: usize, atime: f64, mtime: f64) {
+pub fn futimesSync(fd: usize, atime: f64, mtime: f64) -> Result<(), JsValue> {
     broadcast_watch!(path_from_fd(fd));
     broadcast_defer!(name_of!(futimesSync), fd, atime, mtime);
     broadcast_defer!(ChangeType::CHANGE, path_from_fd(fd));
-    lfs::futimes(fd, atime, mtime).unwrap();
+    native::futimes_fd(fd, Timestamp::from_secs(atime), Timestamp::from_secs(mtime))
+        .map_err(|e| to_js(e, "futime"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn fsyncSync(fd: usize) {
+pub fn fsyncSync(fd: usize) -> Result<(), JsValue> {
     broadcast_watch!(path_from_fd(fd));
     broadcast_defer!(name_of!(fsyncSync), fd);
-    lfs::fsync(fd).unwrap();
+    native::fsync_fd(fd)
+        .map_err(|e| to_js(e, "fsync"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn fdatasyncSync(fd: usize) {
+pub fn fdatasyncSync(fd: usize) -> Result<(), JsValue> {
     broadcast_watch!(path_from_fd(fd));
     broadcast_defer!(name_of!(fdatasyncSync), fd);
-    lfs::fdatasync(fd).unwrap()
+    native::fdatasync_fd(fd)
+        .map_err(|e| to_js(e, "fdatasync"))
+        .journaled()
 }
 
 #[wasm_bindgen]
 pub unsafe fn existsSync(path: String) -> bool {
+    let path = cwd::resolve(&path);
     broadcast_watch!(path);
     broadcast_defer!(name_of!(existsSync), path);
     lfs::exists_sync(path.as_str())
@@ -346,6 +504,8 @@ pub struct Dirent {
     path: String,
     file: bool,
     symlink: bool,
+    device: bool,
+    fifo: bool,
 }
 
 #[wasm_bindgen]
@@ -354,16 +514,16 @@ impl Dirent {
         self.file
     }
     pub fn isDirectory(&self) -> bool {
-        !self.isFile()
+        !self.isFile() && !self.device && !self.fifo
     }
     pub fn isBlockDevice(&self) -> bool {
         false
     }
     pub fn isCharacterDevice(&self) -> bool {
-        false
+        self.device
     }
     pub fn isFIFO(&self) -> bool {
-        false
+        self.fifo
     }
     pub fn isSocket(&self) -> bool {
         false
@@ -394,6 +554,8 @@ pub unsafe fn freaddirSync(fd: usize) -> Option<Dirent> {
             path: ent.path,
             name: ent.name,
             symlink: ent.symlink,
+            device: ent.device,
+            fifo: ent.fifo,
         })
         .into(),
     )
@@ -404,6 +566,7 @@ pub unsafe fn readdirSync(
     path: String,
     options: Option<UnionObjectUndefined>,
 ) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
     let pathClone = path.clone();
     broadcast_watch!(pathClone);
     if !existsSync(path.clone()) {
@@ -413,7 +576,7 @@ pub unsafe fn readdirSync(
         Reflect::set(&err, &"syscall".into(), &"readdir".into()).unwrap();
         return Err(err);
     }
-    let stat = lfs::stat_sync(path.as_str()).unwrap();
+    let stat = lfs::stat_sync(path.as_str()).map_err(|e| to_js(e, "readdir"))?;
     if (stat.mode as u32) & lfs::S_IFMT != lfs::S_IFDIR {
         let err: JsValue = JsError::new("Error: ENOTDIR: not a directory").into();
         Reflect::set(&err, &"code".into(), &"ENOTDIR".into()).unwrap();
@@ -435,6 +598,8 @@ pub unsafe fn readdirSync(
                     path: dirent.path,
                     name: dirent.name,
                     symlink: dirent.symlink,
+                    device: dirent.device,
+                    fifo: dirent.fifo,
                 })
                 .into(),
             );
@@ -453,6 +618,7 @@ pub unsafe fn mkdirSync(
     path: String,
     options: Option<UnionObjectUndefined>,
 ) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
     let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
     let recursive = Reflect::get(&options, &"recursive".into())
         .unwrap_or_default()
@@ -466,17 +632,55 @@ pub unsafe fn mkdirSync(
     broadcast_defer!(ChangeType::RENAME, path);
     broadcast_defer!(name_of!(mkdirSync), path, recursive, mode);
     lfs::mkdir_sync(path.as_str(), recursive, mode)
+        .map(JsValue::from)
+        .map_err(|e| to_js(e, "mkdir"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn mkdtempSync(prefix: String) -> String {
-    let ret = lfs::mkdtemp_sync(prefix.as_str());
+pub unsafe fn mkfifoSync(path: String, mode: Option<i32>) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
+    let mode = mode.unwrap_or(lfs::DEFAULT_PERM_FILE);
+    broadcast_watch!(path);
+    broadcast_defer!(ChangeType::RENAME, path);
+    broadcast_defer!(name_of!(mkfifoSync), path, mode);
+    lfs::mkfifo_sync(path.as_str(), mode)
+        .map(|_| JsValue::undefined())
+        .map_err(|e| to_js(e, "mkfifo"))
+        .journaled()
+}
+
+#[wasm_bindgen]
+pub unsafe fn mkdtempSync(prefix: String) -> Result<String, JsValue> {
+    let prefix = cwd::resolve(&prefix);
+    let ret = lfs::mkdtemp_sync(prefix.as_str())
+        .map_err(|e| to_js(e, "mkdtemp"))?;
     let retClone = ret.clone();
-    // note: this is the only function that broadcasts the result
+    // note: these are the only functions that broadcast the result
     broadcast_watch!(retClone);
     broadcast_defer!(ChangeType::RENAME, ret);
     broadcast_defer!(name_of!(mkdtempSync), prefix, ret);
-    ret
+    Ok(ret)
+}
+
+/// Returns `{ path, fd }` of a new file opened for reading and writing.
+#[wasm_bindgen]
+pub unsafe fn mkstempSync(prefix: String) -> Result<JsValue, JsValue> {
+    let prefix = cwd::resolve(&prefix);
+    let (ret, fd) = lfs::mkstemp_sync(prefix.as_str())
+        .map_err(|e| to_js(e, "mkstemp"))?;
+    broadcast_watch!(ret);
+    broadcast_defer!(ChangeType::RENAME, ret);
+    broadcast_defer!(name_of!(mkstempSync), prefix, ret);
+    journal::result(json!(fd));
+    js_sys::JSON::parse(&json!({ "path": ret, "fd": fd }).to_string())
+}
+
+/// Has a temporary file or directory removed the next time the filesystem
+/// reboots, in case it is not removed before then.
+#[wasm_bindgen]
+pub fn tmpCleanupOnReboot(path: String) {
+    tmp::cleanup_on_reboot(&cwd::resolve(&path));
 }
 
 #[wasm_bindgen]
@@ -484,7 +688,8 @@ pub unsafe fn writeFileSync(
     path: String,
     data: UnionStringUint8Array,
     options: Option<UnionObjectUndefined>,
-) -> Result<(), JsError> {
+) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
     let encoding = Reflect::get(&options, &"encoding".into())
         .unwrap_or(JsValue::UNDEFINED)
@@ -502,23 +707,33 @@ pub unsafe fn writeFileSync(
         .to_lowercase();
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
-    broadcast_defer!(name_of!(writeFileSync), path);
+    broadcast_defer!(name_of!(writeFileSync), path, flag, mode);
+    if lfs::is_proc(&path) {
+        journal::fail("EACCES");
+        return Err(JsError::new("EACCES: permission denied, open").into());
+    }
+    if lfs::OpenFlags::parse(&flag).is_none() {
+        journal::fail("EINVAL");
+        return Err(JsError::new(&format!("EINVAL: invalid flags '{}', open", flag)).into());
+    }
     if encoding != "utf8" && encoding != "utf-8" && encoding != "buffer" {
-        return Err(JsError::new("unsupported encoding"));
+        journal::fail("unsupported encoding");
+        return Err(JsError::new("unsupported encoding").into());
     }
     let data = if data.is_string() {
         data.as_string().unwrap().into_bytes()
     } else {
         js_sys::Uint8Array::new(&JsValue::from(&data)).to_vec()
     };
+    journal::attach(&data);
     lfs::write_file_sync(
         path.as_str(),
         data.as_slice(),
         Some(flag.as_str()),
         Some(mode),
     )
-    .unwrap();
-    Ok(())
+    .map_err(|e| to_js(e, "open"))
+    .journaled()
 }
 
 #[wasm_bindgen]
@@ -526,6 +741,7 @@ pub unsafe fn readFileSync(
     path: String,
     options: Option<UnionObjectUndefined>,
 ) -> Result<UnionStringUint8Array, JsValue> {
+    let path = cwd::resolve(&path);
     let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
     let encoding = Reflect::get(&options, &"encoding".into())
         .unwrap_or(JsValue::UNDEFINED)
@@ -548,7 +764,7 @@ pub unsafe fn readFileSync(
     let pathClone = path.clone();
     broadcast_watch!(pathClone);
     broadcast_defer!(name_of!(readFileSync), path);
-    let data = lfs::read_file_sync(path.as_str()).unwrap();
+    let data = lfs::read_file_sync(path.as_str()).map_err(|e| to_js(e, "open"))?;
     let out = match encoding.as_str() {
         "utf8" | "utf-8" => {
             if String::from_utf8(data.clone()).is_ok() {
@@ -568,7 +784,8 @@ pub unsafe fn appendFileSync(
     path: String,
     data: UnionStringUint8Array,
     options: Option<UnionObjectUndefined>,
-) -> Result<(), JsError> {
+) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
     let encoding = Reflect::get(&options, &"encoding".into())
         .unwrap_or(JsValue::UNDEFINED)
@@ -586,69 +803,206 @@ pub unsafe fn appendFileSync(
         .to_lowercase();
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
-    broadcast_defer!(name_of!(appendFileSync), path);
+    broadcast_defer!(name_of!(appendFileSync), path, flag, mode);
+    if lfs::is_proc(&path) {
+        journal::fail("EACCES");
+        return Err(JsError::new("EACCES: permission denied, open").into());
+    }
+    if lfs::OpenFlags::parse(&flag).is_none() {
+        journal::fail("EINVAL");
+        return Err(JsError::new(&format!("EINVAL: invalid flags '{}', open", flag)).into());
+    }
     if encoding != "utf8" && encoding != "utf-8" && encoding != "buffer" {
-        return Err(JsError::new("unsupported encoding"));
+        journal::fail("unsupported encoding");
+        return Err(JsError::new("unsupported encoding").into());
     }
     let data = if data.is_string() {
         data.as_string().unwrap().into_bytes()
     } else {
         js_sys::Uint8Array::new(&JsValue::from(&data)).to_vec()
     };
-    lfs::append_file_sync(path.as_str(), &data, Some(flag.as_str()), Some(mode)).unwrap();
-    Ok(())
+    journal::attach(&data);
+    lfs::append_file_sync(path.as_str(), &data, Some(flag.as_str()), Some(mode))
+        .map_err(|e| to_js(e, "open"))
+        .journaled()
 }
 
+/// Returns a plain object shaped like Node's `StatFs`, numbers only. The
+/// `bigint` option is applied by the JavaScript side.
 #[wasm_bindgen]
-pub struct StatFs {
-    #[wasm_bindgen]
-    pub bsize: usize,
-    #[wasm_bindgen]
-    pub blocks: usize,
-    #[wasm_bindgen]
-    pub bfree: usize,
-    #[wasm_bindgen]
-    pub bavail: usize,
-    #[wasm_bindgen]
-    pub files: usize,
-    #[wasm_bindgen]
-    pub ffree: usize,
-    #[wasm_bindgen]
-    pub dirs: usize,
-    json: String,
+pub unsafe fn statfsSync(path: String) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
+    broadcast_defer!(name_of!(statfsSync), path);
+    let stat = lfs::statfs_sync(path.as_str())
+        .map_err(|e| to_js(e, "statfs"))
+        .journaled()?;
+    js_sys::JSON::parse(&json!(stat).to_string())
 }
 
+/// Returns `[{ path, size }]` for `path` and the directories under it, see
+/// `lfs::du_sync`.
 #[wasm_bindgen]
-impl StatFs {
-    #[wasm_bindgen(getter)]
-    pub fn json(&self) -> String {
-        self.json.clone()
+pub unsafe fn duSync(
+    path: String,
+    options: Option<UnionObjectUndefined>,
+) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
+    let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
+    let apparent = Reflect::get(&options, &"apparent".into())
+        .unwrap_or(JsValue::UNDEFINED)
+        .as_bool()
+        .unwrap_or(false);
+    let depth = Reflect::get(&options, &"depth".into())
+        .unwrap_or(JsValue::UNDEFINED)
+        .as_f64()
+        .map(|depth| depth.max(0.0) as usize);
+    broadcast_defer!(name_of!(duSync), path, apparent, depth);
+    let usage = lfs::du_sync(path.as_str(), apparent, depth)
+        .map_err(|e| to_js(e, "du"))
+        .journaled()?;
+    js_sys::JSON::parse(&json!(usage).to_string())
+}
+
+#[wasm_bindgen]
+pub unsafe fn fsckSync(options: Option<UnionObjectUndefined>) -> Result<JsValue, JsValue> {
+    let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
+    let repair = Reflect::get(&options, &"repair".into())
+        .unwrap_or(JsValue::UNDEFINED)
+        .as_bool()
+        .unwrap_or(false);
+    broadcast_defer!(name_of!(fsckSync), repair);
+    let report = lfs::fsck_sync(repair);
+    js_sys::JSON::parse(&json!(report).to_string())
+}
+
+/// Hands what is written to a JavaScript callback, a chunk at a time.
+struct ChunkWriter<'a> {
+    callback: &'a js_sys::Function,
+    chunk: Vec<u8>,
+}
+
+impl std::io::Write for ChunkWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.chunk.extend_from_slice(data);
+        if self.chunk.len() >= BACKUP_CHUNK {
+            self.flush()?;
+        }
+        Ok(data.len())
     }
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = js_sys::Uint8Array::from(&self.chunk[..]);
+        self.chunk.clear();
+        self.callback
+            .call1(&JsValue::NULL, &chunk)
+            .map(|_| ())
+            .map_err(|_| errno::io_error("EIO", "backup callback failed"))
+    }
+}
+
+/// Reads a JavaScript array without copying all of it into this memory first.
+struct ArrayReader<'a> {
+    data: &'a js_sys::Uint8Array,
+    pos: u32,
+}
+
+impl std::io::Read for ArrayReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let end = self.data.length().min(self.pos + buf.len() as u32);
+        let len = (end - self.pos) as usize;
+        self.data.subarray(self.pos, end).copy_to(&mut buf[..len]);
+        self.pos = end;
+        Ok(len)
+    }
+}
+
+const BACKUP_CHUNK: usize = 64 * 1024;
 
-    #[wasm_bindgen(setter)]
-    pub fn set_json(&mut self, json: String) {
-        self.json = json;
+/// Backs the volume up. With `on_chunk` the backup is handed over in pieces
+/// as it is taken and nothing is returned, otherwise it comes back whole.
+#[wasm_bindgen]
+pub fn backupSync(on_chunk: Option<js_sys::Function>) -> Result<Option<Vec<u8>>, JsValue> {
+    match on_chunk {
+        Some(callback) => {
+            let mut out = ChunkWriter {
+                callback: &callback,
+                chunk: Vec::with_capacity(BACKUP_CHUNK),
+            };
+            backup::backup(&mut out).map_err(|e| to_js(e, "backup"))?;
+            Ok(None)
+        }
+        None => {
+            let mut out = vec![];
+            backup::backup(&mut out).map_err(|e| to_js(e, "backup"))?;
+            Ok(Some(out))
+        }
     }
 }
 
 #[wasm_bindgen]
-pub unsafe fn statfsSync(path: String, dump: Option<bool>) -> StatFs {
-    broadcast_defer!(name_of!(statfsSync), path, dump);
-    let stat = lfs::statfs_sync(path.as_str(), dump);
-    StatFs {
-        bsize: stat.bsize,
-        blocks: stat.blocks,
-        bfree: stat.bfree,
-        bavail: stat.bavail,
-        files: stat.files,
-        ffree: stat.ffree,
-        json: stat.json.unwrap_or("{}".to_string()),
-        dirs: stat.dirs,
+pub fn restoreSync(data: &js_sys::Uint8Array) -> Result<usize, JsValue> {
+    broadcast_defer!(name_of!(restoreSync), data.length());
+    let input = std::io::BufReader::with_capacity(BACKUP_CHUNK, ArrayReader { data, pos: 0 });
+    backup::restore(input)
+        .map_err(|e| to_js(e, "restore"))
+        .journaled()
+}
+
+#[wasm_bindgen]
+pub fn backupIncrementalSync(generation: f64) -> Result<Vec<u8>, JsValue> {
+    let mut out = vec![];
+    backup::backup_incremental(generation as u64, &mut out).map_err(|e| to_js(e, "backup"))?;
+    Ok(out)
+}
+
+#[wasm_bindgen]
+pub fn changesSince(generation: Option<f64>) -> Result<JsValue, JsValue> {
+    let changes = changes::since(generation.unwrap_or(0.0) as u64)
+        .map_err(|e| to_js(e, "changes"))?;
+    js_sys::JSON::parse(&json!(changes).to_string())
+}
+
+#[wasm_bindgen]
+pub fn changesGeneration() -> f64 {
+    changes::generation() as f64
+}
+
+#[wasm_bindgen]
+pub fn setDeviceSink(device: String, sink: Option<js_sys::Function>) -> Result<(), JsValue> {
+    match dev::Device::from_name(device.as_str()) {
+        Some(device @ (dev::Device::Stdout | dev::Device::Stderr)) => {
+            dev::set_sink(device, sink);
+            Ok(())
+        }
+        _ => {
+            let err: JsValue = JsError::new("EINVAL: not an output device").into();
+            Reflect::set(&err, &"code".into(), &"EINVAL".into())?;
+            Err(err)
+        }
     }
 }
 
 #[wasm_bindgen]
-pub unsafe fn chmodSync(path: String, mode: UnionStringNumber) {
+pub fn journalRead(since: Option<f64>) -> Result<JsValue, JsValue> {
+    let entries = journal::read(since.unwrap_or(0.0) as u64);
+    js_sys::JSON::parse(&json!(entries).to_string())
+}
+
+#[wasm_bindgen]
+pub fn journalClear() {
+    journal::clear();
+}
+
+#[wasm_bindgen]
+pub fn journalCapacity(capacity: Option<usize>) -> usize {
+    journal::capacity(capacity)
+}
+
+#[wasm_bindgen]
+pub unsafe fn chmodSync(path: String, mode: UnionStringNumber) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     let mode = if mode.is_string() {
         parse_filesystem_mode(mode.as_string().unwrap())
     } else {
@@ -657,50 +1011,71 @@ pub unsafe fn chmodSync(path: String, mode: UnionStringNumber) {
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
     broadcast_defer!(name_of!(chmodSync), path, mode);
-    lfs::chmod_sync(path.as_str(), mode).unwrap();
+    lfs::chmod_sync(path.as_str(), mode)
+        .map_err(|e| to_js(e, "chmod"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn chownSync(path: String, uid: usize, gid: usize) {
+pub unsafe fn chownSync(path: String, uid: usize, gid: usize) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
     broadcast_defer!(name_of!(chownSync), path, uid, gid);
-    lfs::chown_sync(path.as_str(), uid as i32, gid as i32).unwrap();
+    lfs::chown_sync(path.as_str(), uid as i32, gid as i32)
+        .map_err(|e| to_js(e, "chown"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn truncateSync(path: String, len: Option<usize>) {
+pub unsafe fn truncateSync(path: String, len: Option<usize>) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     let len = len.unwrap_or(0);
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
     broadcast_defer!(name_of!(truncateSync), path, len);
-    lfs::truncate_sync(path.as_str(), len).unwrap();
+    lfs::truncate_sync(path.as_str(), len)
+        .map_err(|e| to_js(e, "truncate"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn utimesSync(path: String, atime: f64, mtime: f64) {
+pub unsafe fn utimesSync(path: String, atime: f64, mtime: f64) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
     broadcast_defer!(name_of!(utimesSync), path, atime, mtime);
-    lfs::utimes_sync(path.as_str(), atime, mtime).unwrap();
+    let (atime, mtime) = (Timestamp::from_secs(atime), Timestamp::from_secs(mtime));
+    lfs::utimes_sync(path.as_str(), atime, mtime)
+        .map_err(|e| to_js(e, "utime"))
+        .journaled()
 }
 
 #[wasm_bindgen]
 pub unsafe fn unlinkSync(path: String) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::RENAME, path);
     broadcast_defer!(name_of!(unlinkSync), path);
     lfs::unlink_sync(path.as_str(), Some(true))
+        .map(|_| JsValue::undefined())
+        .map_err(|e| to_js(e, "unlink"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn renameSync(old_path: String, new_path: String) {
+pub unsafe fn renameSync(old_path: String, new_path: String) -> Result<(), JsValue> {
+    let old_path = cwd::resolve(&old_path);
+    let new_path = names::rename_target(&old_path, &cwd::absolute(&new_path))
+        .map_err(|e| to_js(e, "rename"))?;
     broadcast_watch!(old_path);
     broadcast_watch!(new_path);
     broadcast_defer!(ChangeType::RENAME, old_path);
     broadcast_defer!(ChangeType::RENAME, new_path);
     broadcast_defer!(name_of!(renameSync), old_path, new_path);
-    lfs::rename_sync(old_path.as_str(), new_path.as_str()).unwrap();
+    lfs::rename_sync(old_path.as_str(), new_path.as_str())
+        .map_err(|e| to_js(e, "rename"))
+        .journaled()
 }
 
 #[wasm_bindgen]
@@ -709,31 +1084,40 @@ pub unsafe fn copyFileSync(
     dest: String,
     options: Option<UnionObjectUndefined>,
 ) -> Result<JsValue, JsValue> {
+    let src = cwd::resolve(&src);
+    let dest = cwd::resolve(&dest);
     let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
     let COPYFILE_EXCL = 1;
     let mode = Reflect::get(&options, &"mode".into())
         .unwrap_or(JsValue::UNDEFINED)
         .as_f64()
         .unwrap_or(0.0) as i32;
-    let excl = mode | COPYFILE_EXCL != 0;
+    let excl = mode & COPYFILE_EXCL != 0;
     broadcast_watch!(src);
     broadcast_watch!(dest);
     broadcast_defer!(ChangeType::CHANGE, src);
     broadcast_defer!(ChangeType::RENAME, dest);
-    broadcast_defer!(name_of!(copyFileSync), src, dest);
+    broadcast_defer!(name_of!(copyFileSync), src, dest, mode);
     lfs::copy_file_sync(src.as_str(), dest.as_str(), excl)
+        .map(|_| JsValue::undefined())
+        .map_err(|e| to_js(e, "copyfile"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn rmdirSync(path: String) {
+pub unsafe fn rmdirSync(path: String) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::RENAME, path);
     broadcast_defer!(name_of!(rmdirSync), path);
-    lfs::rmdir_sync(path.as_str(), Some(false)).unwrap();
+    lfs::rmdir_sync(path.as_str(), Some(false))
+        .map_err(|e| to_js(e, "rmdir"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn rmSync(path: String, options: Option<UnionObjectUndefined>) {
+pub unsafe fn rmSync(path: String, options: Option<UnionObjectUndefined>) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
     let recursive = Reflect::get(&options, &"recursive".into())
         .unwrap_or(JsValue::UNDEFINED)
@@ -746,26 +1130,328 @@ pub unsafe fn rmSync(path: String, options: Option<UnionObjectUndefined>) {
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::RENAME, path);
     broadcast_defer!(name_of!(rmSync), path, recursive, force);
-    lfs::rm_sync(path.as_str(), recursive, force).unwrap();
+    lfs::rm_sync(path.as_str(), recursive, force)
+        .map_err(|e| to_js(e, "rm"))
+        .journaled()
 }
 
 #[wasm_bindgen]
 pub unsafe fn accessSync(path: String, mode: Option<i32>) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
     // broadcast_watch!(path); // deadlocks?
     broadcast_defer!(name_of!(accessSync), path, mode);
     lfs::access_sync(path.as_str(), mode)
+        .map(|_| JsValue::undefined())
+        .map_err(|e| to_js(e, "access"))
+        .journaled()
+}
+
+#[wasm_bindgen]
+pub unsafe fn checksumSync(
+    path: String,
+    algorithm: Option<String>,
+    options: Option<UnionObjectUndefined>,
+) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
+    let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
+    let cache = Reflect::get(&options, &"cache".into())
+        .unwrap_or(JsValue::UNDEFINED)
+        .as_bool()
+        .unwrap_or(false);
+    let algorithm = algorithm.unwrap_or("sha256".to_string());
+    broadcast_defer!(name_of!(checksumSync), path, algorithm, cache);
+    let Some(algo) = lfs::ChecksumAlgorithm::parse(algorithm.as_str()) else {
+        let err: JsValue = JsError::new(&format!(
+            "EINVAL: unsupported checksum algorithm '{}'",
+            algorithm
+        ))
+        .into();
+        Reflect::set(&err, &"code".into(), &"EINVAL".into())?;
+        Reflect::set(&err, &"syscall".into(), &"checksum".into())?;
+        return Err(err).journaled();
+    };
+    lfs::checksum_sync(path.as_str(), algo, cache)
+        .map(JsValue::from)
+        .map_err(|e| to_js(e, "checksum"))
+        .journaled()
+}
+
+#[wasm_bindgen]
+pub fn setCompressionSync(path: String, algorithm: Option<String>) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
+    broadcast_watch!(path);
+    broadcast_defer!(ChangeType::CHANGE, path);
+    broadcast_defer!(name_of!(setCompressionSync), path, algorithm);
+    let compression = match algorithm.as_deref() {
+        None | Some("none") => None,
+        Some(name) => match lfs::Compression::parse(name) {
+            Some(compression) => Some(compression),
+            None => {
+                let message = format!("unsupported compression algorithm '{}'", name);
+                let err = errno::errno("EINVAL", &message, Some(&path), Some("chattr"));
+                return Err(to_js(err, "chattr")).journaled();
+            }
+        },
+    };
+    lfs::set_compression_sync(path.as_str(), compression)
+        .map_err(|e| to_js(e, "chattr"))
+        .journaled()
+}
+
+#[wasm_bindgen]
+pub fn getCompressionSync(path: String) -> Option<String> {
+    let path = cwd::resolve(&path);
+    lfs::compression_sync(path.as_str()).map(|compression| compression.name().to_string())
+}
+
+/// A `Transaction` for JavaScript. Committing or aborting it frees it.
+#[wasm_bindgen]
+#[derive(Debug, Default)]
+pub struct FsTransaction(Transaction);
+
+#[wasm_bindgen]
+impl FsTransaction {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> FsTransaction {
+        FsTransaction::default()
+    }
+
+    pub fn writeFile(&mut self, path: String, data: UnionStringUint8Array, mode: Option<i32>) {
+        let data = if data.is_string() {
+            data.as_string().unwrap().into_bytes()
+        } else {
+            js_sys::Uint8Array::new(&JsValue::from(&data)).to_vec()
+        };
+        self.0.write_file(&path, &data, mode);
+    }
+
+    pub fn mkdir(&mut self, path: String, mode: Option<i32>) {
+        self.0.mkdir(&path, mode);
+    }
+
+    pub fn rename(&mut self, old_path: String, new_path: String) {
+        self.0.rename(&old_path, &new_path);
+    }
+
+    pub fn unlink(&mut self, path: String) {
+        self.0.unlink(&path);
+    }
+
+    /// Journaled as a single entry, the contents of the files it writes are
+    /// attached one after the other.
+    pub fn commit(self) -> Result<(), JsValue> {
+        let mut data = vec![];
+        let ops: Vec<_> = self
+            .0
+            .ops()
+            .iter()
+            .map(|op| match op {
+                transaction::Op::WriteFile {
+                    path,
+                    data: contents,
+                    mode,
+                } => {
+                    data.extend_from_slice(contents);
+                    json!(["writeFile", path, mode, contents.len()])
+                }
+                transaction::Op::Mkdir { path, mode } => json!(["mkdir", path, mode]),
+                transaction::Op::Rename { old_path, new_path } => {
+                    json!(["rename", old_path, new_path])
+                }
+                transaction::Op::Unlink { path } => json!(["unlink", path]),
+            })
+            .collect();
+        let seq = journal::begin("transactionCommit", json!([ops]));
+        defr!(journal::end(seq));
+        journal::attach(&data);
+        let watched: Vec<_> = self
+            .0
+            .paths()
+            .into_iter()
+            .map(|(path, contents)| {
+                let prevStat = lfs::stat_sync(&path).ok();
+                (path, contents, prevStat)
+            })
+            .collect();
+        self.0
+            .commit()
+            .map_err(|e| to_js(e, "commit"))
+            .journaled()?;
+        for (path, contents, prevStat) in watched {
+            let currStat = lfs::stat_sync(&path).ok();
+            let change = if contents {
+                ChangeType::CHANGE
+            } else {
+                ChangeType::RENAME
+            };
+            let events = [
+                (change, json!([path])),
+                (ChangeType::WATCH_, json!([path, prevStat, currStat])),
+            ];
+            for (name, ev) in events.iter() {
+                unsafe { EMITTER.emit(name.to_string(), ev.to_string()) }.unwrap_or(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Discards the staged changes.
+    pub fn abort(self) {
+        self.0.abort();
+    }
 }
 
 #[wasm_bindgen]
 pub unsafe fn realpathSync(path: String) -> String {
+    let path = cwd::resolve(&path);
     broadcast_defer!(name_of!(realpathSync), path);
     lfs::realpath_sync(path.as_str(), None)
 }
 
+#[wasm_bindgen]
+/// Returns the working directory of the calling worker.
+pub fn cwd() -> String {
+    cwd::cwd()
+}
+
+#[wasm_bindgen]
+/// Changes the working directory of the calling worker. Relative paths passed
+/// to any other function of this module are resolved against it.
+pub fn chdir(path: String) -> Result<(), JsValue> {
+    let path = lfs::realpath_sync(&cwd::resolve(&path), None);
+    if !lfs::exists_sync(&path) {
+        let err: JsValue = JsError::new(&format!("ENOENT: no such file or directory, chdir '{}'", path)).into();
+        Reflect::set(&err, &"code".into(), &"ENOENT".into())?;
+        Reflect::set(&err, &"path".into(), &path.into())?;
+        Reflect::set(&err, &"syscall".into(), &"chdir".into())?;
+        return Err(err);
+    }
+    if !lfs::is_directory(&path) {
+        let err: JsValue = JsError::new(&format!("ENOTDIR: not a directory, chdir '{}'", path)).into();
+        Reflect::set(&err, &"code".into(), &"ENOTDIR".into())?;
+        Reflect::set(&err, &"path".into(), &path.into())?;
+        Reflect::set(&err, &"syscall".into(), &"chdir".into())?;
+        return Err(err);
+    }
+    cwd::set(&path);
+    Ok(())
+}
+
+#[wasm_bindgen]
+/// Returns the file mode creation mask of the calling worker. If `mask` is
+/// given it becomes the new mask and the previous one is returned.
+pub fn umask(mask: Option<UnionStringNumber>) -> i32 {
+    let mask = mask.map(|mask| {
+        if mask.is_string() {
+            parse_filesystem_mode(mask.as_string().unwrap())
+        } else {
+            mask.as_f64().unwrap() as i32
+        }
+    });
+    lfs::umask(mask)
+}
+
+#[wasm_bindgen]
+/// Throws for a path the filesystem cannot take, see `cwd::check`.
+pub fn checkPath(path: String) -> Result<(), JsValue> {
+    cwd::check(&path).map_err(|e| {
+        let err: JsValue = JsError::new(&e.to_string()).into();
+        let code = errno::error_code(&e).unwrap_or("EINVAL");
+        Reflect::set(&err, &"code".into(), &code.into()).unwrap();
+        Reflect::set(&err, &"path".into(), &path.as_str().into()).unwrap();
+        err
+    })
+}
+
+#[wasm_bindgen]
+/// Returns the atime policy of the mount: "strictatime", "relatime" or
+/// "noatime". If `policy` is given it becomes the new policy.
+pub fn atimePolicy(policy: Option<String>) -> Result<String, JsValue> {
+    let policy = match policy {
+        Some(name) => Some(lfs::AtimePolicy::parse(&name).ok_or_else(|| {
+            let err: JsValue = JsError::new(&format!("EINVAL: unknown atime policy '{}'", name)).into();
+            Reflect::set(&err, &"code".into(), &"EINVAL".into()).unwrap();
+            err
+        })?),
+        None => None,
+    };
+    Ok(lfs::atime_policy(policy).name().to_string())
+}
+
+#[wasm_bindgen]
+/// Returns the name matching options of the mount as `{ caseInsensitive,
+/// normalization }`, normalization being "none", "nfc" or "nfd". Options that
+/// are given replace the current ones.
+pub fn nameMatching(options: Option<UnionObjectUndefined>) -> Result<JsValue, JsValue> {
+    let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
+    let mut matching = names::name_matching();
+    if let Some(case_insensitive) = Reflect::get(&options, &"caseInsensitive".into())
+        .unwrap_or(JsValue::UNDEFINED)
+        .as_bool()
+    {
+        matching.case_insensitive = case_insensitive;
+    }
+    if let Some(name) = Reflect::get(&options, &"normalization".into())
+        .unwrap_or(JsValue::UNDEFINED)
+        .as_string()
+    {
+        matching.normalization = names::Normalization::parse(&name).ok_or_else(|| {
+            let err: JsValue = JsError::new(&format!("EINVAL: unknown normalization '{}'", name)).into();
+            Reflect::set(&err, &"code".into(), &"EINVAL".into()).unwrap();
+            err
+        })?;
+    }
+    names::set_name_matching(matching).map_err(|e| to_js(e, "mount"))?;
+    let matching = json!({
+        "caseInsensitive": matching.case_insensitive,
+        "normalization": matching.normalization.name(),
+    });
+    Ok(js_sys::JSON::parse(&matching.to_string()).unwrap())
+}
+
+#[wasm_bindgen]
+/// Selects the clock used for timestamps: "system", "frozen", "manual" or
+/// "monotonic". Frozen and manual clocks start at `time`. With `worker` only
+/// the calling worker switches, "inherit" makes it follow the shared clock.
+pub fn setClock(kind: String, time: Option<f64>, worker: Option<bool>) -> Result<(), JsValue> {
+    let worker = worker.unwrap_or(false);
+    let clock = if worker && kind == "inherit" {
+        None
+    } else {
+        Some(clock::Clock::parse(&kind, time).ok_or_else(|| {
+            let err: JsValue = JsError::new(&format!("EINVAL: unknown clock '{}'", kind)).into();
+            Reflect::set(&err, &"code".into(), &"EINVAL".into()).unwrap();
+            err
+        })?)
+    };
+    clock::set(clock, worker);
+    Ok(())
+}
+
+#[wasm_bindgen]
+/// Moves a manual clock forward by `ms` and returns the new time.
+pub fn advanceClock(ms: f64) -> Result<f64, JsValue> {
+    clock::advance(ms).ok_or_else(|| {
+        let err: JsValue = JsError::new("EINVAL: clock is not a manual clock").into();
+        Reflect::set(&err, &"code".into(), &"EINVAL".into()).unwrap();
+        err
+    })
+}
+
+#[wasm_bindgen]
+/// Current time of the clock the calling worker uses, in milliseconds.
+pub fn clockNow() -> f64 {
+    clock::now()
+}
+
 #[wasm_bindgen]
 pub unsafe fn readlinkSync(path: String) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
     broadcast_defer!(name_of!(readlinkSync), path);
     lfs::readlink_sync(path.as_str())
+        .map(JsValue::from)
+        .map_err(|e| to_js(e, "readlink"))
+        .journaled()
 }
 
 #[wasm_bindgen]
@@ -773,6 +1459,7 @@ pub unsafe fn statSync(
     path: String,
     options: Option<UnionObjectUndefined>,
 ) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
     let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
     broadcast_defer!(name_of!(statSync), path);
     let throw_if_no_entry = Reflect::get(&options, &"throwIfNoEntry".into())
@@ -785,12 +1472,12 @@ pub unsafe fn statSync(
             Reflect::set(&err, &"path".into(), &path.into()).unwrap();
             Reflect::set(&err, &"code".into(), &"ENOENT".into()).unwrap();
             Reflect::set(&err, &"syscall".into(), &"stat".into()).unwrap();
-            return Err(err);
+            return Err(err).journaled();
         } else {
             return Ok(JsValue::undefined());
         }
     }
-    let stat = lfs::stat_sync(path.as_str()).unwrap();
+    let stat = lfs::stat_sync(path.as_str()).map_err(|e| to_js(e, "stat"))?;
     let stat = (NodeStats {
         dev: stat.dev,
         ino: stat.ino,
@@ -806,13 +1493,18 @@ pub unsafe fn statSync(
         mtimeMs: stat.mtimeMs,
         ctimeMs: stat.ctimeMs,
         birthtimeMs: stat.birthtimeMs,
+        atimeNs: stat.atimeNs,
+        mtimeNs: stat.mtimeNs,
+        ctimeNs: stat.ctimeNs,
+        birthtimeNs: stat.birthtimeNs,
     })
     .into();
     Ok(stat)
 }
 
 #[wasm_bindgen]
-pub unsafe fn lchmodSync(path: String, mode: UnionStringNumber) {
+pub unsafe fn lchmodSync(path: String, mode: UnionStringNumber) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     let mode = if mode.is_string() {
         parse_filesystem_mode(mode.as_string().unwrap())
     } else {
@@ -821,23 +1513,32 @@ pub unsafe fn lchmodSync(path: String, mode: UnionStringNumber) {
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
     broadcast_defer!(name_of!(lchmodSync), path, mode);
-    lfs::lchmod_sync(path.as_str(), mode).unwrap();
+    lfs::lchmod_sync(path.as_str(), mode)
+        .map_err(|e| to_js(e, "chmod"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn lchownSync(path: String, uid: usize, gid: usize) {
+pub unsafe fn lchownSync(path: String, uid: usize, gid: usize) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
     broadcast_defer!(name_of!(lchownSync), path, uid, gid);
-    lfs::lchown_sync(path.as_str(), uid as i32, gid as i32).unwrap();
+    lfs::lchown_sync(path.as_str(), uid as i32, gid as i32)
+        .map_err(|e| to_js(e, "chown"))
+        .journaled()
 }
 
 #[wasm_bindgen]
-pub unsafe fn lutimesSync(path: String, atime: f64, mtime: f64) {
+pub unsafe fn lutimesSync(path: String, atime: f64, mtime: f64) -> Result<(), JsValue> {
+    let path = cwd::resolve(&path);
     broadcast_watch!(path);
     broadcast_defer!(ChangeType::CHANGE, path);
     broadcast_defer!(name_of!(lutimesSync), path, atime, mtime);
-    lfs::lutimes_sync(path.as_str(), atime, mtime).unwrap();
+    let (atime, mtime) = (Timestamp::from_secs(atime), Timestamp::from_secs(mtime));
+    lfs::lutimes_sync(path.as_str(), atime, mtime)
+        .map_err(|e| to_js(e, "utime"))
+        .journaled()
 }
 
 #[wasm_bindgen]
@@ -845,6 +1546,7 @@ pub unsafe fn lstatSync(
     path: String,
     options: Option<UnionObjectUndefined>,
 ) -> Result<JsValue, JsValue> {
+    let path = cwd::resolve(&path);
     let options = options.unwrap_or(UnionObjectUndefined::from(JsValue::undefined()));
     broadcast_defer!(name_of!(lstatSync), path);
     let throw_if_no_entry = Reflect::get(&options, &"throwIfNoEntry".into())
@@ -857,12 +1559,12 @@ pub unsafe fn lstatSync(
             Reflect::set(&err, &"path".into(), &path.into()).unwrap();
             Reflect::set(&err, &"code".into(), &"ENOENT".into()).unwrap();
             Reflect::set(&err, &"syscall".into(), &"lstat".into()).unwrap();
-            return Err(err);
+            return Err(err).journaled();
         } else {
             return Ok(JsValue::undefined());
         }
     }
-    let stat = lfs::lstat_sync(path.as_str()).unwrap();
+    let stat = lfs::lstat_sync(path.as_str()).map_err(|e| to_js(e, "lstat"))?;
     let stat = (NodeStats {
         dev: stat.dev,
         ino: stat.ino,
@@ -878,6 +1580,10 @@ pub unsafe fn lstatSync(
         mtimeMs: stat.mtimeMs,
         ctimeMs: stat.ctimeMs,
         birthtimeMs: stat.birthtimeMs,
+        atimeNs: stat.atimeNs,
+        mtimeNs: stat.mtimeNs,
+        ctimeNs: stat.ctimeNs,
+        birthtimeNs: stat.birthtimeNs,
     })
     .into();
     Ok(stat)
@@ -0,0 +1,479 @@
+//! Logical backups: the volume as a stream of records, one per node, that any
+//! later version can replay onto a fresh volume. Unlike a snapshot nothing in
+//! it depends on how memory or littlefs blocks are laid out.
+//!
+//! The stream is `WSBACKUP`, a u32 version and then records:
+//!
+//! | size | field                                                  |
+//! |------|--------------------------------------------------------|
+//! | 1    | kind: 1 directory, 2 file, 3 symlink, 4 hard link, 5 fifo |
+//! | 4+n  | absolute path, length prefixed                         |
+//! | 20   | ino, mode, uid, gid and nlink as u32                   |
+//! | 32   | birthtime, atime, mtime and ctime as f64 milliseconds  |
+//! | 16   | the nanoseconds past those milliseconds as u32 (v2)    |
+//! | 9    | compression: 0 none or 1 lz4, then the u64 size (v3)   |
+//! | 8+n  | contents (files) or target (links), length prefixed    |
+//! | 8    | checksum of the record                                 |
+//!
+//! A record of kind 0 ends the stream, it holds the number of records before
+//! it and its own checksum. Integers are little endian, checksums are FNV-1a.
+//! Version 1 streams have no nanoseconds, their milliseconds may be fractional.
+//! From version 3 the contents of files are kept as stored, compressed when
+//! the compression field says so, and are copied a chunk at a time.
+//!
+//! Incremental backups start with `WSDELTA\0`, the version and the
+//! generations of changes they go from and to. Their records are the same plus
+//! kind 6, a removed path with every other field zero, and come before the
+//! nodes created or modified since.
+
+use super::changes;
+use super::errno::{errno, io_error};
+use super::lfs::{self, Compression, DiskGuard, RawAttrs, RawFile, Timestamp, S_IFIFO, S_IFMT};
+use crate::snapshot::{fnv1a, CHECKSUM_SEED};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+
+pub const MAGIC: &[u8; 8] = b"WSBACKUP";
+/// Starts incremental backups instead of `MAGIC`.
+pub const DELTA_MAGIC: &[u8; 8] = b"WSDELTA\0";
+/// Version of the stream layout, bumped whenever it changes.
+pub const VERSION: u32 = 3;
+/// Bytes of contents copied at a time.
+const CHUNK: usize = 64 * 1024;
+
+const KIND_END: u8 = 0;
+const KIND_DIRECTORY: u8 = 1;
+const KIND_FILE: u8 = 2;
+const KIND_SYMLINK: u8 = 3;
+const KIND_HARD_LINK: u8 = 4;
+const KIND_FIFO: u8 = 5;
+const KIND_REMOVED: u8 = 6;
+
+/// Writes records while keeping the checksum of the current one.
+struct RecordWriter<W: Write> {
+    out: W,
+    hash: u64,
+}
+
+impl<W: Write> RecordWriter<W> {
+    fn put(&mut self, data: &[u8]) -> io::Result<()> {
+        self.hash = fnv1a(self.hash, data);
+        self.out.write_all(data)
+    }
+    fn put_bytes(&mut self, data: &[u8]) -> io::Result<()> {
+        self.put(&(data.len() as u64).to_le_bytes())?;
+        self.put(data)
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        let hash = self.hash;
+        self.hash = CHECKSUM_SEED;
+        self.out.write_all(&hash.to_le_bytes())
+    }
+}
+
+struct RecordReader<R: Read> {
+    input: R,
+    hash: u64,
+}
+
+fn corrupt() -> io::Error {
+    io_error("EIO", "backup is truncated or corrupt")
+}
+
+impl<R: Read> RecordReader<R> {
+    fn take(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut data = vec![];
+        (&mut self.input).take(len as u64).read_to_end(&mut data)?;
+        if data.len() != len {
+            return Err(corrupt());
+        }
+        self.hash = fnv1a(self.hash, &data);
+        Ok(data)
+    }
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.u64()?;
+        self.take(len as usize)
+    }
+    /// Copies `len` bytes into `file` a chunk at a time.
+    fn copy_to(&mut self, len: usize, file: &mut RawFile, path: &str) -> io::Result<()> {
+        let mut left = len;
+        while left > 0 {
+            let chunk = self.take(left.min(CHUNK))?;
+            file.write(&chunk)
+                .ok_or_else(|| errno("EIO", "cannot write node", Some(path), Some("restore")))?;
+            left -= chunk.len();
+        }
+        Ok(())
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        let expected = self.hash;
+        let mut stored = [0; 8];
+        self.input.read_exact(&mut stored).map_err(|_| corrupt())?;
+        self.hash = CHECKSUM_SEED;
+        if u64::from_le_bytes(stored) != expected {
+            return Err(corrupt());
+        }
+        Ok(())
+    }
+}
+
+fn kind_of(is_dir: bool, attrs: &RawAttrs) -> u8 {
+    if is_dir {
+        KIND_DIRECTORY
+    } else if attrs.symlink {
+        KIND_SYMLINK
+    } else if attrs.link {
+        KIND_HARD_LINK
+    } else if attrs.mode as u32 & S_IFMT == S_IFIFO {
+        KIND_FIFO
+    } else {
+        KIND_FILE
+    }
+}
+
+impl<W: Write> RecordWriter<W> {
+    fn node(&mut self, path: &str, is_dir: bool) -> io::Result<()> {
+        let cannot_read = || errno("EIO", "cannot read node", Some(path), Some("backup"));
+        let attrs = lfs::raw_attrs(path);
+        let kind = kind_of(is_dir, &attrs);
+        // links are short and kept decompressed, they are read back as paths
+        let (data, mut file) = match kind {
+            KIND_DIRECTORY | KIND_FIFO => (vec![], None),
+            KIND_FILE => (
+                vec![],
+                Some(RawFile::open(path, false).ok_or_else(cannot_read)?),
+            ),
+            _ => (lfs::read_raw(path).ok_or_else(cannot_read)?, None),
+        };
+        let compression = match kind {
+            KIND_DIRECTORY | KIND_FILE => Compression::of(path),
+            _ => None,
+        };
+        self.put(&[kind])?;
+        self.put_bytes(path.as_bytes())?;
+        for field in [attrs.ino, attrs.mode, attrs.uid, attrs.gid, attrs.nlink] {
+            self.put(&field.to_le_bytes())?;
+        }
+        let times = [attrs.birthtime, attrs.atime, attrs.mtime, attrs.ctime].map(|t| t.parts());
+        for (ms, _) in times {
+            self.put(&ms.to_le_bytes())?;
+        }
+        for (_, ns) in times {
+            self.put(&(ns as u32).to_le_bytes())?;
+        }
+        let (algorithm, size) = match compression {
+            Some((Compression::Lz4, size)) => (1u8, size as u64),
+            None => (0, 0),
+        };
+        self.put(&[algorithm])?;
+        self.put(&size.to_le_bytes())?;
+        match file.as_mut() {
+            Some(file) => {
+                let size = file.size();
+                self.put(&(size as u64).to_le_bytes())?;
+                let mut chunk = vec![0; CHUNK.min(size)];
+                let mut left = size;
+                while left > 0 {
+                    let len = file
+                        .read(&mut chunk)
+                        .filter(|len| *len > 0)
+                        .ok_or_else(cannot_read)?;
+                    let len = len.min(left);
+                    self.put(&chunk[..len])?;
+                    left -= len;
+                }
+            }
+            None => self.put_bytes(&data)?,
+        }
+        self.finish()
+    }
+
+    fn removed(&mut self, path: &str) -> io::Result<()> {
+        self.put(&[KIND_REMOVED])?;
+        self.put_bytes(path.as_bytes())?;
+        self.put(&[0; 20 + 32 + 16 + 9])?;
+        self.put_bytes(&[])?;
+        self.finish()
+    }
+
+    fn end(&mut self, count: usize) -> io::Result<()> {
+        self.put(&[KIND_END])?;
+        self.put(&(count as u64).to_le_bytes())?;
+        self.finish()?;
+        self.out.flush()
+    }
+}
+
+/// Writes a backup of the whole volume to `out` and returns how many nodes it
+/// holds. Nothing is opened through descriptors, so access times stay as they
+/// are and the backup records them untouched. Other workers are kept off the
+/// disk until it is done, `out` must not write to the volume.
+pub fn backup<W: Write>(out: W) -> io::Result<usize> {
+    let _disk = DiskGuard::new()?;
+    let mut out = RecordWriter {
+        out,
+        hash: CHECKSUM_SEED,
+    };
+    out.out.write_all(MAGIC)?;
+    out.out.write_all(&VERSION.to_le_bytes())?;
+    let mut entries = vec![("/".to_string(), true)];
+    lfs::walk_raw("/", &mut entries);
+    for (path, is_dir) in entries.iter() {
+        out.node(path, *is_dir)?;
+    }
+    out.end(entries.len())?;
+    Ok(entries.len())
+}
+
+/// Writes what changed after `generation` to `out`, to be restored on top of
+/// a backup taken at that generation (or of an older one with every delta in
+/// between). Returns the generation the delta goes up to.
+pub fn backup_incremental<W: Write>(generation: u64, out: W) -> io::Result<u64> {
+    let _disk = DiskGuard::new()?;
+    let changes = changes::since(generation)?;
+    let mut out = RecordWriter {
+        out,
+        hash: CHECKSUM_SEED,
+    };
+    out.out.write_all(DELTA_MAGIC)?;
+    out.out.write_all(&VERSION.to_le_bytes())?;
+    out.out.write_all(&generation.to_le_bytes())?;
+    out.out.write_all(&changes.generation.to_le_bytes())?;
+    let mut count = 0;
+    for path in changes.removed.iter().chain(changes.replaced.iter()) {
+        out.removed(path)?;
+        count += 1;
+    }
+    // created nodes come with everything under them, as renamed directories
+    // bring their children along, and with their parents, as recursive mkdir
+    // only reports the innermost directory
+    let mut nodes = BTreeMap::new();
+    for path in changes.added.iter().chain(changes.replaced.iter()) {
+        if !lfs::exists_sync_no_follow(path) {
+            continue;
+        }
+        let mut parent = path.as_str();
+        while let Some(end) = parent.rfind('/') {
+            parent = &parent[..end.max(1)];
+            nodes.insert(parent.to_string(), true);
+            if parent == "/" {
+                break;
+            }
+        }
+        let is_dir = lfs::is_directory(path);
+        nodes.insert(path.clone(), is_dir);
+        if is_dir {
+            let mut children = vec![];
+            lfs::walk_raw(path, &mut children);
+            nodes.extend(children);
+        }
+    }
+    for path in changes.modified.iter() {
+        if lfs::exists_sync_no_follow(path) {
+            nodes.insert(path.clone(), lfs::is_directory(path));
+        }
+    }
+    for (path, is_dir) in nodes.iter() {
+        out.node(path, *is_dir)?;
+    }
+    out.end(count + nodes.len())?;
+    Ok(changes.generation)
+}
+
+/// Replays a backup onto the volume and returns how many records it applied.
+/// Every record is checked before it is applied, except for the contents of
+/// files: those are written as they come and the file is removed again if its
+/// record turns out corrupt. Nodes keep their inode numbers unless the volume
+/// already uses them. Incremental backups replace and remove nodes, full ones
+/// refuse to touch what already exists.
+pub fn restore<R: Read>(input: R) -> io::Result<usize> {
+    let _disk = DiskGuard::new()?;
+    let mut input = RecordReader {
+        input,
+        hash: CHECKSUM_SEED,
+    };
+    let mut magic = [0; 8];
+    input.input.read_exact(&mut magic).map_err(|_| corrupt())?;
+    let delta = &magic == DELTA_MAGIC;
+    if &magic != MAGIC && !delta {
+        return Err(io_error("EINVAL", "not a wasabio backup"));
+    }
+    let mut version = [0; 4];
+    input
+        .input
+        .read_exact(&mut version)
+        .map_err(|_| corrupt())?;
+    let version = u32::from_le_bytes(version);
+    if version > VERSION {
+        let message = format!("backup version {} is newer than {}", version, VERSION);
+        return Err(io_error("EOPNOTSUPP", &message));
+    }
+    if delta {
+        // the generations it goes from and to, only of use to whoever took it
+        let mut generations = [0; 16];
+        input
+            .input
+            .read_exact(&mut generations)
+            .map_err(|_| corrupt())?;
+    }
+    // inode numbers of what is already on the volume, the pool cannot tell as
+    // it never gets the numbers of removed nodes back
+    let mut existing = vec![];
+    lfs::walk_raw("/", &mut existing);
+    let in_use: HashMap<usize, String> = existing
+        .into_iter()
+        .map(|(path, _)| (lfs::raw_attrs(&path).ino as usize, path))
+        .collect();
+    let mut inos: Vec<(usize, String)> = vec![];
+    let mut restored = 0;
+    loop {
+        let kind = input.u8()?;
+        if kind == KIND_END {
+            let count = input.u64()?;
+            input.finish()?;
+            if count != restored as u64 {
+                return Err(corrupt());
+            }
+            break;
+        }
+        let path = String::from_utf8(input.bytes()?).map_err(|_| corrupt())?;
+        let mut fields = [0i32; 5];
+        for field in fields.iter_mut() {
+            *field = input.u32()? as i32;
+        }
+        let mut times = [(0f64, 0i32); 4];
+        for (ms, _) in times.iter_mut() {
+            *ms = input.f64()?;
+        }
+        if version >= 2 {
+            for (_, ns) in times.iter_mut() {
+                *ns = input.u32()? as i32;
+            }
+        }
+        let compression = if version >= 3 {
+            let algorithm = input.u8()?;
+            let size = input.u64()? as usize;
+            match algorithm {
+                0 => None,
+                1 => Some((Compression::Lz4, size)),
+                _ => return Err(corrupt()),
+            }
+        } else {
+            None
+        };
+        let len = input.u64()? as usize;
+        // files are streamed once the node is known to be creatable
+        let data = if kind == KIND_FILE {
+            vec![]
+        } else {
+            let data = input.take(len)?;
+            input.finish()?;
+            data
+        };
+        if !path.starts_with('/') || path.contains('\0') || lfs::path_normalize(&path) != path {
+            return Err(errno(
+                "EINVAL",
+                "invalid path in backup",
+                Some(&path),
+                Some("restore"),
+            ));
+        }
+        let [ino, mode, uid, gid, nlink] = fields;
+        let [birthtime, atime, mtime, ctime] = times.map(|(ms, ns)| Timestamp::load(ms, ns));
+        let attrs = RawAttrs {
+            ino,
+            mode,
+            uid,
+            gid,
+            nlink,
+            birthtime,
+            atime,
+            mtime,
+            ctime,
+            link: kind == KIND_HARD_LINK,
+            symlink: kind == KIND_SYMLINK,
+        };
+        let mut exists = lfs::exists_sync_no_follow(&path);
+        if kind == KIND_REMOVED {
+            if !delta {
+                return Err(corrupt());
+            }
+            if exists {
+                lfs::remove_raw(&path);
+            }
+            restored += 1;
+            continue;
+        }
+        // a delta replaces what the backup it goes on top of had
+        if delta && exists && !(kind == KIND_DIRECTORY && lfs::is_directory(&path)) {
+            lfs::remove_raw(&path);
+            exists = false;
+        }
+        let created = match kind {
+            KIND_DIRECTORY if exists && lfs::is_directory(&path) => Some(()),
+            _ if exists => {
+                let message = "file already exists, restore into a fresh volume";
+                return Err(errno("EEXIST", message, Some(&path), Some("restore")));
+            }
+            KIND_DIRECTORY => lfs::mkdir_raw(&path),
+            KIND_FILE => {
+                let cannot_create =
+                    || errno("EIO", "cannot create node", Some(&path), Some("restore"));
+                let mut file = RawFile::open(&path, true).ok_or_else(cannot_create)?;
+                let copied = input
+                    .copy_to(len, &mut file, &path)
+                    .and_then(|_| file.close().ok_or_else(cannot_create))
+                    .and_then(|_| input.finish());
+                if let Err(err) = copied {
+                    lfs::remove_raw(&path);
+                    return Err(err);
+                }
+                Some(())
+            }
+            KIND_SYMLINK | KIND_HARD_LINK | KIND_FIFO => lfs::write_raw(&path, &data),
+            _ => return Err(corrupt()),
+        };
+        created.ok_or_else(|| errno("EIO", "cannot create node", Some(&path), Some("restore")))?;
+        lfs::set_raw_attrs(&path, &attrs);
+        Compression::store(&path, compression);
+        if kind == KIND_HARD_LINK {
+            let target = String::from_utf8_lossy(&data);
+            lfs::record_hard_link(&lfs::path_normalize(&target), &path);
+        }
+        if ino > 0 && path != "/" {
+            inos.push((ino as usize, path));
+        }
+        restored += 1;
+    }
+    // the inode numbers are only taken out of the pool now that all of them
+    // are known, the ones used by nodes the volume already had are handed out anew
+    let wanted: Vec<usize> = inos.iter().map(|(ino, _)| *ino).collect();
+    lfs::claim_inos(&wanted);
+    for (ino, path) in inos.iter() {
+        let taken = in_use.get(ino).map_or(false, |other| {
+            other != path
+                && lfs::exists_sync_no_follow(other)
+                && lfs::raw_attrs(other).ino as usize == *ino
+        });
+        if !taken {
+            continue;
+        }
+        let mut attrs = lfs::raw_attrs(path);
+        attrs.ino = lfs::request_ino().ok_or_else(|| io_error("ENOSPC", "out of inode numbers"))?;
+        lfs::set_raw_attrs(path, &attrs);
+    }
+    Ok(restored)
+}
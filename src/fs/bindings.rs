@@ -1924,6 +1924,7 @@ extern "C" {
     ) -> ::core::ffi::c_int;
 }
 pub const lfs_sys_file_type_LFS_SYS_S_IFIFO: lfs_sys_file_type = 4096;
+pub const lfs_sys_file_type_LFS_SYS_S_IFCHR: lfs_sys_file_type = 8192;
 pub const lfs_sys_file_type_LFS_SYS_S_IFREG: lfs_sys_file_type = 32768;
 pub const lfs_sys_file_type_LFS_SYS_S_IFDIR: lfs_sys_file_type = 16384;
 pub const lfs_sys_file_type_LFS_SYS_S_IFLNK: lfs_sys_file_type = 40960;
@@ -1967,6 +1968,13 @@ pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_CTIME: lfs_sys_attr_type_t = 7;
 pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_NLINK: lfs_sys_attr_type_t = 8;
 pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_LINK: lfs_sys_attr_type_t = 9;
 pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_SYMLINK: lfs_sys_attr_type_t = 10;
+pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_CRC32: lfs_sys_attr_type_t = 11;
+pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_SHA256: lfs_sys_attr_type_t = 12;
+pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_COMPRESSION: lfs_sys_attr_type_t = 13;
+pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_BIRTHTIME_NS: lfs_sys_attr_type_t = 14;
+pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_ATIME_NS: lfs_sys_attr_type_t = 15;
+pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_MTIME_NS: lfs_sys_attr_type_t = 16;
+pub const lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_CTIME_NS: lfs_sys_attr_type_t = 17;
 pub type lfs_sys_attr_type_t = ::core::ffi::c_uint;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -1979,6 +1987,10 @@ pub struct lfs_sys_query_t {
     pub atime: f64,
     pub mtime: f64,
     pub ctime: f64,
+    pub birthtime_ns: ::core::ffi::c_int,
+    pub atime_ns: ::core::ffi::c_int,
+    pub mtime_ns: ::core::ffi::c_int,
+    pub ctime_ns: ::core::ffi::c_int,
     pub nlink: ::core::ffi::c_int,
     pub link: bool,
     pub symlink: bool,
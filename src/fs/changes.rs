@@ -0,0 +1,172 @@
+use super::errno::io_error;
+use super::lfs;
+use crate::{guard, host, lock::Lock};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+
+/// Every path the filesystem created, modified or removed, shared by all
+/// workers. Each change bumps the generation, so a caller remembering the
+/// generation it last saw can ask for whatever happened since.
+static mut CHANGES: Lazy<BTreeMap<String, PathChanges>> = Lazy::new(BTreeMap::new);
+static mut CHANGES_LOCK: Lazy<Lock> = Lazy::new(|| Lock::new().unwrap());
+static mut CHANGES_GENERATION: u64 = 0;
+/// Generation the history goes back to, older changes were let go.
+static mut CHANGES_FLOOR: u64 = 0;
+/// Paths plus transitions held, see `MAX_WEIGHT`.
+static mut CHANGES_WEIGHT: usize = 0;
+
+/// How many paths and transitions are kept before the oldest half of the
+/// history is let go.
+const MAX_WEIGHT: usize = 64 * 1024;
+
+#[derive(Debug, Default)]
+struct PathChanges {
+    /// generation of the last change
+    generation: u64,
+    /// generations at which the path came to exist (true) or went away (false)
+    transitions: Vec<(u64, bool)>,
+}
+
+impl PathChanges {
+    /// Whether the path existed at `generation`. A path without transitions
+    /// was only ever modified, so it existed all along.
+    fn existed_at(&self, generation: u64) -> bool {
+        match self
+            .transitions
+            .iter()
+            .rev()
+            .find(|(g, _)| *g <= generation)
+        {
+            Some((_, exists)) => *exists,
+            None => self.transitions.first().map_or(true, |(_, exists)| !exists),
+        }
+    }
+
+    fn exists(&self) -> bool {
+        self.transitions.last().map_or(true, |(_, exists)| *exists)
+    }
+}
+
+/// Paths changed after a generation, by what happened to them overall.
+#[derive(Debug, Default, Serialize)]
+pub struct Changes {
+    /// generation the changes go up to
+    pub generation: u64,
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+    /// modified paths that were removed and created again in between, their
+    /// old contents (and children) are gone
+    #[serde(skip)]
+    pub replaced: Vec<String>,
+}
+
+pub unsafe fn changes_diag() {
+    host::log(&format!(
+        "[WASABIO:CHANGES] GENERATION: {} FLOOR: {} CHANGES: {:?}",
+        CHANGES_GENERATION, CHANGES_FLOOR, CHANGES
+    ));
+}
+
+pub unsafe fn changes_reset() {
+    CHANGES_LOCK = Lazy::new(|| Lock::new().unwrap());
+}
+
+/// What an operation did to a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Created,
+    Modified,
+    Removed,
+}
+
+/// Records a change to `path`. Called by the lfs layer as it makes changes,
+/// so that every way of writing to the disk is seen.
+pub fn record(path: &str, change: Change) {
+    if path == "/" || lfs::is_virtual(path) || lfs::is_spare(path) {
+        return;
+    }
+    guard!(CHANGES_LOCK);
+    unsafe {
+        CHANGES_GENERATION += 1;
+        let changes = CHANGES.entry(path.to_string()).or_insert_with(|| {
+            CHANGES_WEIGHT += 1;
+            PathChanges::default()
+        });
+        changes.generation = CHANGES_GENERATION;
+        if change != Change::Modified {
+            changes.transitions.push((CHANGES_GENERATION, change == Change::Created));
+            CHANGES_WEIGHT += 1;
+        }
+        if CHANGES_WEIGHT > MAX_WEIGHT {
+            trim();
+        }
+    }
+}
+
+/// Lets go of the older half of the history until it fits `MAX_WEIGHT`.
+/// Paths keep the last transition before the new floor, which is all
+/// `existed_at` needs to answer for the generations still covered.
+unsafe fn trim() {
+    while CHANGES_WEIGHT > MAX_WEIGHT {
+        let floor = CHANGES_FLOOR + (CHANGES_GENERATION - CHANGES_FLOOR + 1) / 2;
+        CHANGES.retain(|_, changes| changes.generation > floor);
+        for changes in CHANGES.values_mut() {
+            let older = changes
+                .transitions
+                .iter()
+                .take_while(|(g, _)| *g <= floor)
+                .count();
+            changes.transitions.drain(..older.saturating_sub(1));
+        }
+        CHANGES_FLOOR = floor;
+        CHANGES_WEIGHT = CHANGES
+            .values()
+            .map(|changes| 1 + changes.transitions.len())
+            .sum();
+    }
+}
+
+/// The generation of the most recent change.
+pub fn generation() -> u64 {
+    guard!(CHANGES_LOCK);
+    unsafe { CHANGES_GENERATION }
+}
+
+/// What changed after `generation`. Paths created and removed again in
+/// between are left out. Fails with ESTALE once the history no longer goes
+/// back that far, a full backup is needed then.
+pub fn since(generation: u64) -> io::Result<Changes> {
+    guard!(CHANGES_LOCK);
+    if generation < unsafe { CHANGES_FLOOR } {
+        let message = format!(
+            "changes before generation {} are no longer kept",
+            unsafe { CHANGES_FLOOR }
+        );
+        return Err(io_error("ESTALE", &message));
+    }
+    let mut out = Changes {
+        generation: unsafe { CHANGES_GENERATION },
+        ..Default::default()
+    };
+    for (path, changes) in unsafe { CHANGES.iter() } {
+        if changes.generation <= generation {
+            continue;
+        }
+        let path = path.clone();
+        match (changes.existed_at(generation), changes.exists()) {
+            (false, true) => out.added.push(path),
+            (true, false) => out.removed.push(path),
+            (true, true) => {
+                if changes.transitions.iter().any(|(g, _)| *g > generation) {
+                    out.replaced.push(path.clone());
+                }
+                out.modified.push(path);
+            }
+            (false, false) => {}
+        }
+    }
+    Ok(out)
+}
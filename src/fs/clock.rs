@@ -0,0 +1,107 @@
+use crate::{guard, host, lock::Lock};
+use once_cell::sync::Lazy;
+use std::cell::Cell;
+
+/// Time source for all atime/mtime/ctime/birthtime updates, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clock {
+    /// wall clock of the host, `Date.now()`
+    System,
+    /// always returns the same time
+    Frozen(f64),
+    /// only moves when `advance` is called
+    Manual(f64),
+    /// wall clock that never goes backwards, holds the last reading
+    Monotonic(f64),
+}
+
+impl Clock {
+    /// Builds a clock from its name. `time` is the starting point of frozen and
+    /// manual clocks and defaults to the current wall clock time.
+    pub fn parse(kind: &str, time: Option<f64>) -> Option<Self> {
+        let time = time.unwrap_or_else(host::now);
+        match kind {
+            "system" => Some(Self::System),
+            "frozen" => Some(Self::Frozen(time)),
+            "manual" => Some(Self::Manual(time)),
+            "monotonic" => Some(Self::Monotonic(0.0)),
+            _ => None,
+        }
+    }
+    fn read(&mut self) -> f64 {
+        match self {
+            Self::System => host::now(),
+            Self::Frozen(t) | Self::Manual(t) => *t,
+            Self::Monotonic(last) => {
+                *last = last.max(host::now());
+                *last
+            }
+        }
+    }
+}
+
+/// shared by all workers unless they pick their own.
+static mut CLOCK: Clock = Clock::System;
+static mut CLOCK_LOCK: Lazy<Lock> = Lazy::new(|| Lock::new().unwrap());
+
+thread_local! {
+    /// per worker override of the shared clock.
+    static WORKER_CLOCK: Cell<Option<Clock>> = Cell::new(None);
+}
+
+pub unsafe fn clock_reset() {
+    CLOCK_LOCK = Lazy::new(|| Lock::new().unwrap());
+    CLOCK = Clock::System;
+}
+
+/// Current time of the clock the calling worker uses.
+pub fn now() -> f64 {
+    if let Some(mut clock) = WORKER_CLOCK.with(|c| c.get()) {
+        let t = clock.read();
+        WORKER_CLOCK.with(|c| c.set(Some(clock)));
+        return t;
+    }
+    guard!(CLOCK_LOCK);
+    unsafe { CLOCK.read() }
+}
+
+/// True when timestamps come from the host's wall clock.
+pub fn is_system() -> bool {
+    match WORKER_CLOCK.with(|c| c.get()) {
+        Some(clock) => clock == Clock::System,
+        None => unsafe { CLOCK == Clock::System },
+    }
+}
+
+/// Replaces the shared clock, or the calling worker's one. `None` drops the
+/// worker's override so it follows the shared clock again.
+pub fn set(clock: Option<Clock>, worker: bool) {
+    if worker {
+        WORKER_CLOCK.with(|c| c.set(clock));
+    } else {
+        guard!(CLOCK_LOCK);
+        unsafe { CLOCK = clock.unwrap_or(Clock::System) };
+    }
+}
+
+/// Moves a manual clock forward by `ms` and returns the new time. None if the
+/// calling worker's clock is not a manual one.
+pub fn advance(ms: f64) -> Option<f64> {
+    if let Some(clock) = WORKER_CLOCK.with(|c| c.get()) {
+        return match clock {
+            Clock::Manual(t) => {
+                WORKER_CLOCK.with(|c| c.set(Some(Clock::Manual(t + ms))));
+                Some(t + ms)
+            }
+            _ => None,
+        };
+    }
+    guard!(CLOCK_LOCK);
+    match unsafe { CLOCK } {
+        Clock::Manual(t) => {
+            unsafe { CLOCK = Clock::Manual(t + ms) };
+            Some(t + ms)
+        }
+        _ => None,
+    }
+}
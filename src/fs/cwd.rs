@@ -0,0 +1,65 @@
+use super::errno::errno;
+use super::lfs::{path_normalize, NAME_MAX, PATH_MAX};
+use super::names;
+use std::cell::RefCell;
+use std::io;
+
+thread_local! {
+    /// every worker has its own working directory, like a process would.
+    static CWD: RefCell<String> = RefCell::new("/".to_string());
+}
+
+/// Returns the working directory of the calling worker.
+pub fn cwd() -> String {
+    CWD.with(|c| c.borrow().clone())
+}
+
+/// Changes the working directory of the calling worker. The caller is expected
+/// to have checked that `path` is an existing directory.
+pub fn set(path: &str) {
+    CWD.with(|c| *c.borrow_mut() = resolve(path));
+}
+
+/// Turns `path` into a normalized absolute path. Relative paths are taken
+/// relative to the working directory of the calling worker.
+pub fn absolute(path: &str) -> String {
+    if path.starts_with('/') {
+        path_normalize(path)
+    } else {
+        path_normalize(&format!("{}/{}", cwd(), path))
+    }
+}
+
+/// Refuses a path before it gets near the disk: NUL bytes have no place in
+/// names, and LittleFS would turn away names longer than `NAME_MAX`.
+pub fn check(path: &str) -> io::Result<()> {
+    if path.contains('\0') {
+        return Err(nul_error(path));
+    }
+    if path.len() >= PATH_MAX || path.split('/').any(|name| name.len() > NAME_MAX) {
+        let message = format!("name too long, '{}'", path);
+        return Err(errno("ENAMETOOLONG", &message, Some(path), None));
+    }
+    Ok(())
+}
+
+/// The error for a path with a NUL byte in it, as Node words it.
+pub fn nul_error(path: &str) -> io::Error {
+    let message = format!(
+        "The argument 'path' must be a string, Uint8Array, or URL without null bytes. Received {:?}",
+        path
+    );
+    errno("ERR_INVALID_ARG_VALUE", &message, None, None)
+}
+
+/// `resolve` for a path that was not checked yet.
+pub fn checked(path: &str) -> io::Result<String> {
+    check(path)?;
+    Ok(resolve(path))
+}
+
+/// Like `absolute`, with the names matched against the disk as the mount's
+/// name matching options ask for.
+pub fn resolve(path: &str) -> String {
+    names::resolve(&absolute(path))
+}
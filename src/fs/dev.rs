@@ -0,0 +1,126 @@
+use crate::host;
+use js_sys::Function;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use wasm_bindgen::JsValue;
+
+/// Virtual directory that hosts the character devices. It does not exist on
+/// disk and is therefore not listed under "/".
+pub const DEV_DIR: &str = "/dev";
+
+/// Largest request `crypto.getRandomValues` accepts in one call.
+const MAX_RANDOM_BYTES: usize = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Device {
+    Null,
+    Zero,
+    Urandom,
+    Stdout,
+    Stderr,
+}
+
+thread_local! {
+    /// sinks are JS functions, so they can only ever be called from the worker
+    /// that registered them. every worker has its own set.
+    static SINKS: RefCell<HashMap<Device, Function>> = RefCell::new(HashMap::new());
+}
+
+impl Device {
+    pub const ALL: [Device; 5] = [
+        Device::Null,
+        Device::Zero,
+        Device::Urandom,
+        Device::Stdout,
+        Device::Stderr,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Device::Null => "null",
+            Device::Zero => "zero",
+            Device::Urandom => "urandom",
+            Device::Stdout => "stdout",
+            Device::Stderr => "stderr",
+        }
+    }
+
+    pub fn path(&self) -> String {
+        format!("{}/{}", DEV_DIR, self.name())
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().find(|d| d.name() == name).copied()
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        Self::from_name(path.strip_prefix(DEV_DIR)?.strip_prefix('/')?)
+    }
+
+    /// Standard streams are always open, like they are in a process.
+    pub fn from_fd(fd: usize) -> Option<Self> {
+        match fd {
+            1 => Some(Device::Stdout),
+            2 => Some(Device::Stderr),
+            _ => None,
+        }
+    }
+
+    /// Device number in the classic `major << 8 | minor` encoding Linux uses.
+    pub fn rdev(&self) -> usize {
+        let (major, minor) = match self {
+            Device::Null => (1, 3),
+            Device::Zero => (1, 5),
+            Device::Urandom => (1, 9),
+            Device::Stdout | Device::Stderr => (5, 1),
+        };
+        major << 8 | minor
+    }
+
+    /// Fills `buf` and returns the number of bytes read. Output devices and
+    /// /dev/null are always at end of file.
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        match self {
+            Device::Zero => {
+                buf.fill(0);
+                buf.len()
+            }
+            Device::Urandom => {
+                for chunk in buf.chunks_mut(MAX_RANDOM_BYTES) {
+                    host::random_fill(chunk);
+                }
+                buf.len()
+            }
+            _ => 0,
+        }
+    }
+
+    /// Consumes `data` and returns the number of bytes written.
+    pub fn write(&self, data: &[u8]) -> usize {
+        if let Device::Stdout | Device::Stderr = self {
+            let text = String::from_utf8_lossy(data);
+            let sink = SINKS.with(|s| s.borrow().get(self).cloned());
+            if let Some(sink) = sink {
+                let _ = sink.call1(&JsValue::NULL, &JsValue::from_str(&text));
+            } else {
+                // console appends its own line break
+                let text = text.strip_suffix('\n').unwrap_or(&text);
+                if *self == Device::Stdout {
+                    host::log(text);
+                } else {
+                    host::error(text);
+                }
+            }
+        }
+        data.len()
+    }
+}
+
+/// Registers (or with `None` removes) the sink of an output device for the
+/// calling worker.
+pub fn set_sink(device: Device, sink: Option<Function>) {
+    SINKS.with(|s| match sink {
+        Some(sink) => s.borrow_mut().insert(device, sink),
+        None => s.borrow_mut().remove(&device),
+    });
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+use std::io::{self, ErrorKind};
+
+/// Errno-style error carried inside `io::Error`, so the code, path and syscall
+/// Node puts on its errors survive until they reach JavaScript.
+#[derive(Debug, Clone)]
+pub struct SysError {
+    pub code: String,
+    pub message: String,
+    pub path: Option<String>,
+    pub syscall: Option<String>,
+}
+
+impl fmt::Display for SysError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for SysError {}
+
+/// Maps an errno name onto the closest `ErrorKind`.
+pub fn error_kind(code: &str) -> ErrorKind {
+    match code {
+        "ENOENT" => ErrorKind::NotFound,
+        "EEXIST" => ErrorKind::AlreadyExists,
+        "EACCES" | "EPERM" => ErrorKind::PermissionDenied,
+        "EINVAL" | "ERR_INVALID_ARG_VALUE" | "ERR_OUT_OF_RANGE" => ErrorKind::InvalidInput,
+        "EAGAIN" => ErrorKind::WouldBlock,
+        "EPIPE" => ErrorKind::BrokenPipe,
+        "ENOTDIR" => ErrorKind::NotADirectory,
+        "EISDIR" => ErrorKind::IsADirectory,
+        "ENOTEMPTY" => ErrorKind::DirectoryNotEmpty,
+        "ENOSPC" => ErrorKind::StorageFull,
+        "EBUSY" => ErrorKind::ResourceBusy,
+        "EDEADLK" => ErrorKind::Deadlock,
+        "ELOOP" => ErrorKind::FilesystemLoop,
+        "ENAMETOOLONG" => ErrorKind::InvalidFilename,
+        "EOPNOTSUPP" | "ENOSYS" => ErrorKind::Unsupported,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Builds an `io::Error` carrying all the errno details. `message` is taken
+/// verbatim, it is what ends up after "CODE: " in the JavaScript error.
+pub fn errno(code: &str, message: &str, path: Option<&str>, syscall: Option<&str>) -> io::Error {
+    io::Error::new(
+        error_kind(code),
+        SysError {
+            code: code.to_string(),
+            message: message.to_string(),
+            path: path.map(str::to_string),
+            syscall: syscall.map(str::to_string),
+        },
+    )
+}
+
+/// Builds an `io::Error` out of an errno name and a message.
+pub fn io_error(code: &str, message: &str) -> io::Error {
+    errno(code, message, None, None)
+}
+
+/// Like `io_error`, for a failed call on a path. The message reads like Node's:
+/// "ENOENT: no such file or directory, open '/missing'".
+pub fn sys_error(code: &str, message: &str, path: &str, syscall: &str) -> io::Error {
+    let message = format!("{}, {} '{}'", message, syscall, path);
+    errno(code, &message, Some(path), Some(syscall))
+}
+
+/// Returns the errno details of an error, if it came from the filesystem.
+pub fn sys_error_of(err: &io::Error) -> Option<&SysError> {
+    err.get_ref()?.downcast_ref::<SysError>()
+}
+
+/// Returns the errno name of an error, if it came from the filesystem.
+pub fn error_code(err: &io::Error) -> Option<&str> {
+    sys_error_of(err).map(|e| e.code.as_str())
+}
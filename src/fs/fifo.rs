@@ -0,0 +1,145 @@
+use crate::{guard, host, lock::Lock};
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+/// In-memory side of a named pipe. The node itself lives on disk, the data
+/// only exists while at least one end of the pipe is open. Only the ends of
+/// the pipe reach its buffer.
+struct Pipe {
+    buffer: VecDeque<u8>,
+    /// bumped whenever there is something new for readers, who sleep on it
+    generation: Arc<AtomicI32>,
+    readers: usize,
+    writers: usize,
+}
+
+impl Pipe {
+    fn wake_readers(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        host::notify(&self.generation);
+    }
+}
+
+static mut PIPES: Lazy<HashMap<String, Pipe>> = Lazy::new(HashMap::new);
+static mut PIPES_LOCK: Lazy<Lock> = Lazy::new(|| Lock::new().unwrap());
+
+pub unsafe fn fifo_diag() {
+    for (path, pipe) in PIPES.iter() {
+        host::log(&format!(
+            "[WASABIO:FIFO] {}: buffered={} readers={} writers={}",
+            path,
+            pipe.buffer.len(),
+            pipe.readers,
+            pipe.writers
+        ));
+    }
+}
+
+pub unsafe fn fifo_reset() {
+    PIPES_LOCK = Lazy::new(|| Lock::new().unwrap());
+    PIPES = Lazy::new(HashMap::new);
+}
+
+#[derive(Debug)]
+pub enum FifoError {
+    /// nothing to read yet and the end is non-blocking
+    Again,
+    /// nobody has the pipe open for reading
+    BrokenPipe,
+    /// the end was not opened for this direction
+    BadDescriptor,
+}
+
+/// One open end of a named pipe, owned by a file descriptor.
+#[derive(Debug, Clone)]
+pub struct FifoEnd {
+    pub path: String,
+    pub read: bool,
+    pub write: bool,
+    pub nonblock: bool,
+}
+
+impl FifoEnd {
+    pub fn open(path: &str, read: bool, write: bool, nonblock: bool) -> Self {
+        guard!(PIPES_LOCK);
+        let pipe = unsafe { PIPES.entry(path.to_string()) }.or_insert_with(|| Pipe {
+            buffer: VecDeque::new(),
+            generation: Arc::new(AtomicI32::new(0)),
+            readers: 0,
+            writers: 0,
+        });
+        pipe.readers += read as usize;
+        pipe.writers += write as usize;
+        Self {
+            path: path.to_string(),
+            read,
+            write,
+            nonblock,
+        }
+    }
+
+    /// Reads whatever is buffered, up to `buf.len()`. Returns 0 once every
+    /// writer is gone and blocks (or fails with `Again`) while writers exist.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, FifoError> {
+        if !self.read {
+            return Err(FifoError::BadDescriptor);
+        }
+        loop {
+            let (generation, seen) = {
+                guard!(PIPES_LOCK);
+                let pipe = unsafe { PIPES.get_mut(&self.path) }.ok_or(FifoError::BadDescriptor)?;
+                if !pipe.buffer.is_empty() || buf.is_empty() {
+                    let len = buf.len().min(pipe.buffer.len());
+                    for (dst, src) in buf.iter_mut().zip(pipe.buffer.drain(..len)) {
+                        *dst = src;
+                    }
+                    return Ok(len);
+                }
+                if pipe.writers == 0 {
+                    return Ok(0);
+                }
+                if self.nonblock {
+                    return Err(FifoError::Again);
+                }
+                let generation = pipe.generation.clone();
+                let seen = generation.load(Ordering::SeqCst);
+                (generation, seen)
+            };
+            // a write or close in between has bumped it and does not sleep
+            host::wait(&generation, seen);
+        }
+    }
+
+    /// Queues `data` for the readers of the pipe.
+    pub fn write(&self, data: &[u8]) -> Result<usize, FifoError> {
+        if !self.write {
+            return Err(FifoError::BadDescriptor);
+        }
+        guard!(PIPES_LOCK);
+        let pipe = unsafe { PIPES.get_mut(&self.path) }.ok_or(FifoError::BadDescriptor)?;
+        if pipe.readers == 0 {
+            return Err(FifoError::BrokenPipe);
+        }
+        pipe.buffer.extend(data);
+        pipe.wake_readers();
+        Ok(data.len())
+    }
+
+    /// Releases this end. Once no end is left, unread data is discarded.
+    pub fn close(&self) {
+        guard!(PIPES_LOCK);
+        let Some(pipe) = (unsafe { PIPES.get_mut(&self.path) }) else {
+            return;
+        };
+        pipe.readers -= self.read as usize;
+        pipe.writers -= self.write as usize;
+        if pipe.readers == 0 && pipe.writers == 0 {
+            unsafe { PIPES.remove(&self.path) };
+        } else if self.write && pipe.writers == 0 {
+            // readers blocked on an empty pipe see the end of the data now
+            pipe.wake_readers();
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use super::{lfs, sha256::Sha256};
+use crate::{guard, host, lock::Lock};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+/// Ring buffer of every exported filesystem call, shared by all workers. The
+/// oldest entries are dropped to stay within a budget in bytes, a budget of
+/// zero turns it off.
+static mut JOURNAL: Lazy<VecDeque<JournalEntry>> = Lazy::new(VecDeque::new);
+static mut JOURNAL_LOCK: Lazy<Lock> = Lazy::new(|| Lock::new().unwrap());
+static mut JOURNAL_BUDGET: usize = DEFAULT_BUDGET;
+static mut JOURNAL_BYTES: usize = 0;
+static mut JOURNAL_SEQ: u64 = 0;
+
+/// what an entry costs besides its strings, roughly its serialized field names
+const ENTRY_OVERHEAD: usize = 128;
+/// budget the journal starts out with
+const DEFAULT_BUDGET: usize = 1 << 20;
+
+thread_local! {
+    /// calls currently in flight on this worker, innermost last. exported
+    /// functions call each other, nested calls are recorded with depth > 0.
+    static IN_FLIGHT: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+}
+
+/// The bytes an operation wrote, base64 encoded so a shipped journal replays
+/// on its own. They count against the budget; bytes that alone would not fit
+/// it are left out and the payload marked `truncated`, a replay then gets
+/// them from its caller.
+#[derive(Debug, Clone, Serialize)]
+pub struct Payload {
+    pub length: usize,
+    pub sha256: String,
+    pub bytes: Option<String>,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub op: String,
+    pub args: Value,
+    pub data: Option<Payload>,
+    pub time: f64,
+    pub worker: usize,
+    pub depth: usize,
+    pub status: &'static str,
+    pub result: Value,
+    pub error: Option<String>,
+    #[serde(skip)]
+    weight: usize,
+}
+
+impl JournalEntry {
+    /// Roughly what keeping the entry costs, what the budget is counted in.
+    fn weigh(&self) -> usize {
+        ENTRY_OVERHEAD
+            + self.op.len()
+            + self.args.to_string().len()
+            + self.data.as_ref().map_or(0, |d| {
+                d.sha256.len() + d.bytes.as_ref().map_or(0, String::len)
+            })
+            + self.result.to_string().len()
+            + self.error.as_ref().map_or(0, String::len)
+    }
+}
+
+pub unsafe fn journal_diag() {
+    host::log(&format!("[WASABIO:JOURNAL] JOURNAL: {:?}", JOURNAL));
+}
+
+/// Starts over with an empty journal, sequence numbers restart at one. The
+/// budget is kept.
+pub unsafe fn journal_reset() {
+    JOURNAL_LOCK = Lazy::new(|| Lock::new().unwrap());
+    JOURNAL = Lazy::new(VecDeque::new);
+    JOURNAL_BYTES = 0;
+    JOURNAL_SEQ = 0;
+}
+
+/// Drops the oldest entries until the journal fits its budget again.
+unsafe fn trim() {
+    while JOURNAL_BYTES > JOURNAL_BUDGET {
+        match JOURNAL.pop_front() {
+            Some(entry) => JOURNAL_BYTES -= entry.weight,
+            None => break,
+        }
+    }
+}
+
+fn with_entry(seq: u64, f: impl FnOnce(&mut JournalEntry)) {
+    guard!(JOURNAL_LOCK);
+    unsafe {
+        if let Some(entry) = JOURNAL.iter_mut().rev().find(|e| e.seq == seq) {
+            f(entry);
+            let weight = entry.weigh();
+            JOURNAL_BYTES = JOURNAL_BYTES + weight - entry.weight;
+            entry.weight = weight;
+            trim();
+        }
+    }
+}
+
+/// Runs `f` against the innermost in-flight entry of this worker.
+fn with_current(f: impl FnOnce(&mut JournalEntry)) {
+    if let Some(seq) = IN_FLIGHT.with(|s| s.borrow().last().copied()) {
+        with_entry(seq, f);
+    }
+}
+
+/// Records the start of an operation. The entry stays "pending" until `end`.
+/// Returns zero without recording anything while journaling is off.
+pub fn begin(op: &str, args: Value) -> u64 {
+    if unsafe { JOURNAL_BUDGET } == 0 {
+        return 0;
+    }
+    let depth = IN_FLIGHT.with(|s| s.borrow().len());
+    let seq = {
+        guard!(JOURNAL_LOCK);
+        unsafe {
+            JOURNAL_SEQ += 1;
+            let mut entry = JournalEntry {
+                seq: JOURNAL_SEQ,
+                op: op.to_string(),
+                args,
+                data: None,
+                time: host::now(),
+                worker: crate::worker_id(),
+                depth,
+                status: "pending",
+                result: Value::Null,
+                error: None,
+                weight: 0,
+            };
+            entry.weight = entry.weigh();
+            JOURNAL_BYTES += entry.weight;
+            JOURNAL.push_back(entry);
+            trim();
+            JOURNAL_SEQ
+        }
+    };
+    IN_FLIGHT.with(|s| s.borrow_mut().push(seq));
+    seq
+}
+
+/// Marks an operation as finished. Entries nobody failed are marked "ok".
+pub fn end(seq: u64) {
+    if seq == 0 {
+        return;
+    }
+    with_entry(seq, |entry| {
+        if entry.status == "pending" {
+            entry.status = "ok";
+        }
+    });
+    IN_FLIGHT.with(|s| s.borrow_mut().retain(|&s| s != seq));
+}
+
+/// Keeps the bytes written by the current operation, or only their length
+/// and digest if they do not fit the budget.
+pub fn attach(data: &[u8]) {
+    if IN_FLIGHT.with(|s| s.borrow().is_empty()) {
+        return;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    let sha256 = lfs::hex_string(&hasher.finalize());
+    let encoded_len = (data.len() + 2) / 3 * 4;
+    with_current(|entry| {
+        let truncated = entry.weigh() + sha256.len() + encoded_len > unsafe { JOURNAL_BUDGET };
+        entry.data = Some(Payload {
+            length: data.len(),
+            sha256,
+            bytes: if truncated { None } else { Some(base64(data)) },
+            truncated,
+        });
+    });
+}
+
+/// Standard base64 with padding, what `atob` takes.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Records the return value of the current operation.
+pub fn result(value: Value) {
+    with_current(|entry| entry.result = value);
+}
+
+/// Records the failure of the current operation.
+pub fn fail(error: &str) {
+    with_current(|entry| {
+        entry.status = "error";
+        entry.error = Some(error.to_string());
+    });
+}
+
+/// Returns all entries with a sequence number greater than `since`.
+pub fn read(since: u64) -> Vec<JournalEntry> {
+    guard!(JOURNAL_LOCK);
+    unsafe { JOURNAL.iter().filter(|e| e.seq > since).cloned().collect() }
+}
+
+pub fn clear() {
+    guard!(JOURNAL_LOCK);
+    unsafe {
+        JOURNAL.clear();
+        JOURNAL_BYTES = 0;
+    }
+}
+
+/// Returns the budget of the journal in bytes, optionally changing it first.
+/// It starts out at `DEFAULT_BUDGET`, a budget of zero turns journaling off.
+pub fn capacity(budget: Option<usize>) -> usize {
+    guard!(JOURNAL_LOCK);
+    unsafe {
+        if let Some(budget) = budget {
+            JOURNAL_BUDGET = budget;
+            trim();
+        }
+        JOURNAL_BUDGET
+    }
+}
+
+/// Marks the current journal entry as failed when an exported function errors.
+pub trait Journaled {
+    fn journaled(self) -> Self;
+}
+
+impl<T> Journaled for Result<T, wasm_bindgen::JsValue> {
+    fn journaled(self) -> Self {
+        if let Err(err) = &self {
+            let code = js_sys::Reflect::get(err, &"code".into()).ok();
+            let message = js_sys::Reflect::get(err, &"message".into()).ok();
+            let error = code
+                .and_then(|c| c.as_string())
+                .or(message.and_then(|m| m.as_string()))
+                .unwrap_or("error".to_string());
+            fail(&error);
+        }
+        self
+    }
+}
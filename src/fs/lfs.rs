@@ -3,18 +3,29 @@
 mod lfs {
     include!("bindings.rs");
 }
-use crate::{guard, lock::Lock};
+use super::changes::{self, Change};
+use super::clock;
+use super::cwd;
+use super::dev::{Device, DEV_DIR};
+use super::errno::{errno, error_code, io_error, sys_error};
+use super::fifo::{FifoEnd, FifoError};
+use super::proc::{self, ProcNode, PROC_DIR};
+use super::lz4;
+use super::names;
+use super::sha256::Sha256;
+use super::tmp;
+use crate::{guard, host, lock::Lock};
 #[deny(warnings)]
 use either::{Either, Left, Right};
 use id_pool::IdPool;
-use js_sys::Reflect;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
 use std::ffi::{c_void, CStr, CString};
+use std::io;
 use std::sync::Once;
-use wasm_bindgen::{JsError, JsValue};
 
 /// multiple workers may be competing to initially mount the filesystem, this
 /// lock ensures that only one worker does the work and the others wait for it.
@@ -23,13 +34,46 @@ static mut LFS_SYS_INIT_LOCK: Lazy<Lock> = Lazy::new(|| Lock::new().unwrap());
 static INIT: Once = Once::new();
 /// This lock is passed to LittleFS internals, do not use.
 static mut LFS_SYS_DISK_LOCK: Lazy<Lock> = Lazy::new(|| Lock::new().unwrap());
+thread_local! {
+    /// set while this worker holds the disk lock through a `DiskGuard`, the
+    /// LittleFS calls it makes meanwhile must not take it again.
+    static DISK_HELD: Cell<bool> = Cell::new(false);
+}
 /// LittleFS calls this function to lock the disk.
 unsafe extern "C" fn lock() {
-    LFS_SYS_DISK_LOCK.acquire();
+    if !DISK_HELD.with(Cell::get) {
+        LFS_SYS_DISK_LOCK.acquire();
+    }
 }
 /// LittleFS calls this function to unlock the disk.
 unsafe extern "C" fn unlock() {
-    LFS_SYS_DISK_LOCK.release();
+    if !DISK_HELD.with(Cell::get) {
+        LFS_SYS_DISK_LOCK.release();
+    }
+}
+
+/// Keeps every other worker off the disk until dropped, so that a series of
+/// operations is seen by them all at once or not at all. Cannot be nested,
+/// taking the disk again while holding it fails with EDEADLK.
+pub(super) struct DiskGuard(());
+
+impl DiskGuard {
+    pub(super) fn new() -> io::Result<DiskGuard> {
+        disk();
+        if DISK_HELD.with(Cell::get) {
+            return Err(io_error("EDEADLK", "the disk is already held by this worker"));
+        }
+        unsafe { LFS_SYS_DISK_LOCK.acquire() };
+        DISK_HELD.with(|held| held.set(true));
+        Ok(DiskGuard(()))
+    }
+}
+
+impl Drop for DiskGuard {
+    fn drop(&mut self) {
+        DISK_HELD.with(|held| held.set(false));
+        unsafe { LFS_SYS_DISK_LOCK.release() };
+    }
 }
 
 pub unsafe fn lfs_locked() -> bool {
@@ -37,34 +81,41 @@ pub unsafe fn lfs_locked() -> bool {
 }
 
 pub unsafe fn lfs_diag() {
-    web_sys::console::log_1(
-        &format!("[WASABIO:LFS] LFS_SYS_OPEN_FDS: {:?}", LFS_SYS_OPEN_FDS).into(),
-    );
-    web_sys::console::log_1(
-        &format!("[WASABIO:LFS] LFS_SYS_FD_POOL: {:?}", LFS_SYS_FD_POOL).into(),
-    );
-    web_sys::console::log_1(
-        &format!("[WASABIO:LFS] LFS_SYS_INO_POOL: {:?}", LFS_SYS_INO_POOL).into(),
-    );
-    web_sys::console::log_1(
-        &format!("[WASABIO:LFS] LFS_SYS_HARD_LINKS: {:?}", LFS_SYS_HARD_LINKS).into(),
-    );
+    host::log(&format!("[WASABIO:LFS] LFS_SYS_OPEN_FDS: {:?}", LFS_SYS_OPEN_FDS));
+    host::log(&format!("[WASABIO:LFS] LFS_SYS_FD_POOL: {:?}", LFS_SYS_FD_POOL));
+    host::log(&format!("[WASABIO:LFS] LFS_SYS_INO_POOL: {:?}", LFS_SYS_INO_POOL));
+    host::log(&format!("[WASABIO:LFS] LFS_SYS_HARD_LINKS: {:?}", LFS_SYS_HARD_LINKS));
+    host::log(&format!("[WASABIO:LFS] LFS_SYS_DEVICE_FDS: {:?}", LFS_SYS_DEVICE_FDS));
+    host::log(&format!("[WASABIO:LFS] LFS_SYS_FIFO_FDS: {:?}", LFS_SYS_FIFO_FDS));
+    host::log(&format!("[WASABIO:LFS] LFS_SYS_PROC_FDS: {:?}", LFS_SYS_PROC_FDS));
+    host::log(&format!("[WASABIO:LFS] LFS_SYS_SPARES: {:?}", LFS_SYS_SPARES));
 }
 
 pub unsafe fn lfs_reset() {
     LFS_SYS_DISK_LOCK = Lazy::new(|| Lock::new().unwrap());
     LFS_SYS_INIT_LOCK = Lazy::new(|| Lock::new().unwrap());
     LFS_SYS_OPEN_FDS = Lazy::new(|| HashMap::new());
+    LFS_SYS_DEVICE_FDS = Lazy::new(|| HashMap::new());
+    LFS_SYS_FIFO_FDS = Lazy::new(|| HashMap::new());
+    LFS_SYS_PROC_FDS = Lazy::new(|| HashMap::new());
+    LFS_SYS_SPARES = Lazy::new(|| HashSet::new());
     LFS_SYS_FD_POOL = Lazy::new(|| IdPool::new());
+    ATIME_POLICY = AtimePolicy::Strict;
 }
 
 // generates a `const BUILD_TIME: &str`
 build_timestamp::build_time!("%s");
 
+pub const SEEK_SET: i32 = lfs::lfs_whence_flags_LFS_SEEK_SET as i32;
+pub const SEEK_CUR: i32 = lfs::lfs_whence_flags_LFS_SEEK_CUR as i32;
+pub const SEEK_END: i32 = lfs::lfs_whence_flags_LFS_SEEK_END as i32;
+
 pub const S_IFMT: u32 = lfs::lfs_sys_file_type_LFS_SYS_S_IFMT;
 pub const S_IFDIR: u32 = lfs::lfs_sys_file_type_LFS_SYS_S_IFDIR;
 pub const S_IFREG: u32 = lfs::lfs_sys_file_type_LFS_SYS_S_IFREG;
 pub const S_IFLNK: u32 = lfs::lfs_sys_file_type_LFS_SYS_S_IFLNK;
+pub const S_IFCHR: u32 = lfs::lfs_sys_file_type_LFS_SYS_S_IFCHR;
+pub const S_IFIFO: u32 = lfs::lfs_sys_file_type_LFS_SYS_S_IFIFO;
 
 // Read permission for the owner
 // pub const S_IRUSR: u32 = 0o400;
@@ -85,8 +136,32 @@ pub const S_IWOTH: u32 = 0o002;
 // Execute permission for others
 // pub const S_IXOTH: u32 = 0o001;
 
+/// Longest name of a single entry LittleFS takes, in bytes.
+pub const NAME_MAX: usize = lfs::LFS_NAME_MAX as usize;
+/// Longest path taken, in bytes, as Linux' PATH_MAX which counts the NUL.
+pub const PATH_MAX: usize = 4096;
 pub const DEFAULT_PERM_DIR: i32 = 0o777;
 pub const DEFAULT_PERM_FILE: i32 = 0o666;
+pub const DEFAULT_UMASK: i32 = 0o022;
+
+thread_local! {
+    /// every worker has its own file mode creation mask, like a process would.
+    static UMASK: Cell<i32> = Cell::new(DEFAULT_UMASK);
+}
+
+/// Returns the file mode creation mask of the calling worker. If `mask` is
+/// given it replaces the current one and the previous mask is returned.
+pub fn umask(mask: Option<i32>) -> i32 {
+    UMASK.with(|m| match mask {
+        Some(mask) => m.replace(mask & 0o777),
+        None => m.get(),
+    })
+}
+
+/// Clears the bits of `mode` that are set in the creation mask.
+fn apply_umask(mode: i32) -> i32 {
+    mode & !umask(None)
+}
 
 fn get_default_permissions(is_dir: bool) -> i32 {
     if is_dir {
@@ -109,14 +184,21 @@ fn disk() -> *mut lfs::lfs_t {
                 let sizeMB = 256; // 256MB of in-memory storage. Cannot be resized.
                 lfs::lfs_sys_mount(sizeMB, Some(lock), Some(unlock));
                 let root = "/";
-                let c_root = CString::new(root).unwrap();
+                let c_root = c_string(root).expect("'/' has no NUL byte");
                 lfs::lfs_sys_attr_reset(c_root.as_ptr());
                 let q = AttrQueryHandle::new(root);
                 (*q.0).mode = S_IFDIR as i32 | DEFAULT_PERM_DIR;
                 (*q.0).uid = 0;
                 (*q.0).gid = 0;
                 lfs::lfs_sys_attr_patch(c_root.as_ptr(), q.0);
-                Touch::birthtime(root, Some(BUILD_TIME.parse::<f64>().unwrap()));
+                // the root is as old as the build, unless a clock was configured
+                let born = if clock::is_system() {
+                    BUILD_TIME.parse::<f64>().unwrap()
+                } else {
+                    clock::now()
+                };
+                Touch::birthtime(root, Some(born));
+                #[cfg(target_arch = "wasm32")]
                 console_error_panic_hook::set_once();
                 INITIALIZED = true;
             });
@@ -125,34 +207,144 @@ fn disk() -> *mut lfs::lfs_t {
     unsafe { lfs::lfs_sys_disk() }
 }
 
+/// Mount option deciding when reads and lookups update the access time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtimePolicy {
+    /// every access writes the atime (default)
+    Strict,
+    /// only if the atime is not newer than mtime/ctime, or older than a day
+    Relative,
+    /// the atime only changes through utimes
+    Never,
+}
+
+impl AtimePolicy {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "strictatime" => Some(Self::Strict),
+            "relatime" => Some(Self::Relative),
+            "noatime" => Some(Self::Never),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Strict => "strictatime",
+            Self::Relative => "relatime",
+            Self::Never => "noatime",
+        }
+    }
+}
+
+static mut ATIME_POLICY: AtimePolicy = AtimePolicy::Strict;
+
+/// Returns the atime policy of the mount, replacing it if `policy` is given.
+pub fn atime_policy(policy: Option<AtimePolicy>) -> AtimePolicy {
+    unsafe {
+        if let Some(policy) = policy {
+            ATIME_POLICY = policy;
+        }
+        ATIME_POLICY
+    }
+}
+
+/// Options of the LittleFS mount as /proc/mounts lists them.
+fn mount_options() -> String {
+    let names = names::name_matching().mount_options();
+    if names.is_empty() {
+        atime_policy(None).name().to_string()
+    } else {
+        format!("{},{}", atime_policy(None).name(), names)
+    }
+}
+
+/// A point in time as the attributes keep it: milliseconds since the epoch,
+/// and the nanoseconds past them that an f64 of milliseconds cannot hold.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Timestamp {
+    ms: f64,
+    ns: i32,
+}
+
+impl Timestamp {
+    const NANOS_PER_MS: i128 = 1_000_000;
+
+    /// Splits fractional milliseconds, rounded to the nanosecond.
+    pub fn from_ms(ms: f64) -> Self {
+        let whole = ms.floor();
+        let ns = ((ms - whole) * 1e6).round() as i32;
+        if ns >= Self::NANOS_PER_MS as i32 {
+            Self { ms: whole + 1.0, ns: 0 }
+        } else {
+            Self { ms: whole, ns }
+        }
+    }
+    /// Seconds as Node's time arguments carry them.
+    pub fn from_secs(secs: f64) -> Self {
+        Self::from_ms(secs * 1000.0)
+    }
+    pub fn from_nanos(nanos: i128) -> Self {
+        Self {
+            ms: nanos.div_euclid(Self::NANOS_PER_MS) as f64,
+            ns: nanos.rem_euclid(Self::NANOS_PER_MS) as i32,
+        }
+    }
+    /// Reads an attribute pair. Nodes written before the nanoseconds were kept
+    /// have fractional milliseconds and no nanoseconds.
+    pub(super) fn load(ms: f64, ns: i32) -> Self {
+        Self::from_nanos(Self::from_ms(ms).as_nanos() + ns as i128)
+    }
+    pub(super) fn store(self, ms: &mut f64, ns: &mut i32) {
+        *ms = self.ms;
+        *ns = self.ns;
+    }
+    /// Whole milliseconds and the nanoseconds past them.
+    pub(super) fn parts(self) -> (f64, i32) {
+        (self.ms, self.ns)
+    }
+    pub fn as_ms(self) -> f64 {
+        self.ms + self.ns as f64 / 1e6
+    }
+    pub fn as_nanos(self) -> i128 {
+        self.ms as i128 * Self::NANOS_PER_MS + self.ns as i128
+    }
+}
+
 struct Touch {/* utility to change attributes */}
 
 impl Touch {
-    /// only dependency we have on js_sys is for Date::now()
+    /// Current time of the worker's clock, unless `t` is given.
     pub fn time(t: Option<f64>) -> f64 {
-        t.unwrap_or(js_sys::Date::now())
+        t.unwrap_or_else(clock::now)
     }
     /// Updates all timing info for a given path.
     pub fn birthtime(path: &str, t: Option<f64>) {
-        let t = Self::time(t);
-        let c_path = CString::new(path).unwrap();
+        let t = Timestamp::from_ms(Self::time(t));
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => return,
+        };
         let q = AttrQueryHandle::new(path);
         unsafe {
             // when birthtime is set, all other times are set to the same value.
-            (*q.0).birthtime = t;
-            (*q.0).mtime = t;
-            (*q.0).atime = t;
-            (*q.0).ctime = t;
+            t.store(&mut (*q.0).birthtime, &mut (*q.0).birthtime_ns);
+            t.store(&mut (*q.0).mtime, &mut (*q.0).mtime_ns);
+            t.store(&mut (*q.0).atime, &mut (*q.0).atime_ns);
+            t.store(&mut (*q.0).ctime, &mut (*q.0).ctime_ns);
             lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
         }
     }
     /// Updates the mtime for a given path (the time the file was last modified)
     /// The mtime refers to the last time when a file’s content was modified.
     pub fn mtime(path: &str, t: Option<f64>) {
-        let c_path = CString::new(path).unwrap();
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => return,
+        };
         let q = AttrQueryHandle::new(path);
         unsafe {
-            (*q.0).mtime = Self::time(t);
+            let t = Timestamp::from_ms(Self::time(t));
+            t.store(&mut (*q.0).mtime, &mut (*q.0).mtime_ns);
             lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
         }
     }
@@ -160,10 +352,44 @@ impl Touch {
     /// The atime indicates the last time when a file was read, including reading
     /// by users directly or through commands or scripts.
     pub fn atime(path: &str, t: Option<f64>) {
-        let c_path = CString::new(path).unwrap();
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => return,
+        };
+        let q = AttrQueryHandle::new(path);
+        unsafe {
+            let t = Timestamp::from_ms(Self::time(t));
+            t.store(&mut (*q.0).atime, &mut (*q.0).atime_ns);
+            lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+        }
+    }
+    /// Records an access to `path`, as far as the atime policy of the mount asks
+    /// for it. Skipping saves an attribute patch under the disk lock.
+    pub fn access(path: &str) {
+        const DAY: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+        match atime_policy(None) {
+            AtimePolicy::Strict => Self::atime(path, None),
+            AtimePolicy::Never => {}
+            AtimePolicy::Relative => {
+                let q = AttrQueryHandle::new(path);
+                let (atime, mtime, ctime) = unsafe { ((*q.0).atime, (*q.0).mtime, (*q.0).ctime) };
+                let now = Self::time(None);
+                if atime <= mtime || atime <= ctime || now - atime >= DAY {
+                    Self::atime(path, Some(now));
+                }
+            }
+        }
+    }
+    /// Sets the atime and mtime of `path` as `utimes` does.
+    pub fn times(path: &str, atime: Timestamp, mtime: Timestamp) {
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => return,
+        };
         let q = AttrQueryHandle::new(path);
         unsafe {
-            (*q.0).atime = Self::time(t);
+            atime.store(&mut (*q.0).atime, &mut (*q.0).atime_ns);
+            mtime.store(&mut (*q.0).mtime, &mut (*q.0).mtime_ns);
             lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
         }
     }
@@ -171,15 +397,235 @@ impl Touch {
     /// The ctime refers to the last time when a file’s metadata, such as its
     /// ownership, location, file type and permission settings, was changed.
     pub fn ctime(path: &str, t: Option<f64>) {
-        let c_path = CString::new(path).unwrap();
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => return,
+        };
         let q = AttrQueryHandle::new(path);
         unsafe {
-            (*q.0).ctime = Self::time(t);
+            let t = Timestamp::from_ms(Self::time(t));
+            t.store(&mut (*q.0).ctime, &mut (*q.0).ctime_ns);
             lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
         }
     }
 }
 
+/// Content checksums supported by `checksum_sync`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Crc32,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "crc32" => Some(Self::Crc32),
+            "sha256" | "sha-256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+    fn attr(&self) -> u8 {
+        match self {
+            Self::Crc32 => lfs::lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_CRC32 as u8,
+            Self::Sha256 => lfs::lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_SHA256 as u8,
+        }
+    }
+    fn size(&self) -> usize {
+        match self {
+            Self::Crc32 => 4,
+            Self::Sha256 => 32,
+        }
+    }
+}
+
+struct Digest {/* utility to cache content checksums in user attributes */}
+
+impl Digest {
+    /// Returns the cached checksum of a path, if one was stored since the last write.
+    pub fn cached(path: &str, algorithm: ChecksumAlgorithm) -> Option<Vec<u8>> {
+        let c_path = c_string(path).ok()?;
+        let mut buf = vec![0u8; algorithm.size()];
+        let res = unsafe {
+            lfs::lfs_getattr(
+                disk(),
+                c_path.as_ptr(),
+                algorithm.attr(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len() as u32,
+            )
+        };
+        if res == buf.len() as i32 {
+            Some(buf)
+        } else {
+            None
+        }
+    }
+    /// Stores a checksum of a path so it can be returned without reading the file.
+    pub fn store(path: &str, algorithm: ChecksumAlgorithm, digest: &[u8]) {
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => return,
+        };
+        unsafe {
+            lfs::lfs_setattr(
+                disk(),
+                c_path.as_ptr(),
+                algorithm.attr(),
+                digest.as_ptr() as *const c_void,
+                digest.len() as u32,
+            );
+        }
+    }
+    /// Drops all cached checksums of a path. Called whenever content changes.
+    pub fn invalidate(path: &str) {
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => return,
+        };
+        for algorithm in [ChecksumAlgorithm::Crc32, ChecksumAlgorithm::Sha256] {
+            // removing an attribute is a metadata commit, only pay for it if needed
+            if Self::cached(path, algorithm).is_some() {
+                unsafe { lfs::lfs_removeattr(disk(), c_path.as_ptr(), algorithm.attr()) };
+            }
+        }
+    }
+}
+
+/// Transparent compression of file contents, after `chattr +c`. Set on a
+/// directory, it is inherited by whatever gets created in it afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Lz4,
+}
+
+impl Compression {
+    /// Logical bytes compressed at a time, so that nothing needs to be held
+    /// twice in full while encoding.
+    const BLOCK: usize = 64 * 1024;
+    /// set on the stored length of blocks kept as they are
+    const RAW: u32 = 1 << 31;
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "lz4" => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lz4 => "lz4",
+        }
+    }
+    fn attr() -> u8 {
+        lfs::lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_COMPRESSION as u8
+    }
+    /// Compression of `path` and, for files, the size of their contents once
+    /// decompressed. Stored as the algorithm byte and a little endian u64.
+    pub fn of(path: &str) -> Option<(Self, usize)> {
+        let c_path = c_string(path).ok()?;
+        let mut buf = [0u8; 9];
+        let res = unsafe {
+            lfs::lfs_getattr(
+                disk(),
+                c_path.as_ptr(),
+                Self::attr(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len() as u32,
+            )
+        };
+        let compression = match (res, buf[0]) {
+            (9, 1) => Self::Lz4,
+            _ => return None,
+        };
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&buf[1..]);
+        Some((compression, u64::from_le_bytes(size) as usize))
+    }
+    /// Replaces the compression attribute of `path`, None drops it.
+    pub(super) fn store(path: &str, compression: Option<(Self, usize)>) {
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => return,
+        };
+        let (compression, size) = match compression {
+            Some(compression) => compression,
+            None => {
+                if Self::of(path).is_some() {
+                    unsafe { lfs::lfs_removeattr(disk(), c_path.as_ptr(), Self::attr()) };
+                }
+                return;
+            }
+        };
+        let mut buf = [0u8; 9];
+        buf[0] = match compression {
+            Self::Lz4 => 1,
+        };
+        buf[1..].copy_from_slice(&(size as u64).to_le_bytes());
+        unsafe {
+            lfs::lfs_setattr(
+                disk(),
+                c_path.as_ptr(),
+                Self::attr(),
+                buf.as_ptr() as *const c_void,
+                buf.len() as u32,
+            );
+        }
+    }
+    /// What gets stored for `data`: every block as a little endian u32 length
+    /// followed by the block, compressed unless that would not make it smaller.
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 16);
+        for block in data.chunks(Self::BLOCK) {
+            let packed = match self {
+                Self::Lz4 => lz4::compress(block),
+            };
+            if packed.len() < block.len() {
+                out.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+                out.extend_from_slice(&packed);
+            } else {
+                out.extend_from_slice(&(block.len() as u32 | Self::RAW).to_le_bytes());
+                out.extend_from_slice(block);
+            }
+        }
+        out
+    }
+    /// Inverse of `encode`, for contents of `size` bytes. None if corrupt.
+    fn decode(&self, stored: &[u8], size: usize) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(size);
+        let mut at = 0;
+        while out.len() < size {
+            let len = u32::from_le_bytes(stored.get(at..at + 4)?.try_into().ok()?);
+            at += 4;
+            let block = stored.get(at..at + (len & !Self::RAW) as usize)?;
+            at += block.len();
+            if len & Self::RAW != 0 {
+                out.extend_from_slice(block);
+            } else {
+                let expected = Self::BLOCK.min(size - out.len());
+                out.extend(match self {
+                    Self::Lz4 => lz4::decompress(block, expected)?,
+                });
+            }
+        }
+        if out.len() == size && at == stored.len() {
+            Some(out)
+        } else {
+            None
+        }
+    }
+}
+
+/// Logical contents of a compressed file while it is open. They go back to
+/// the disk, compressed, when the file is synced or closed.
+#[derive(Debug)]
+struct Inflated {
+    compression: Compression,
+    data: Vec<u8>,
+    pos: Cell<usize>,
+    dirty: bool,
+}
+
 const O_RDONLY: u32 = lfs::lfs_open_flags_LFS_O_RDONLY;
 const O_WRONLY: u32 = lfs::lfs_open_flags_LFS_O_WRONLY;
 const O_RDWR: u32 = lfs::lfs_open_flags_LFS_O_RDWR;
@@ -188,35 +634,99 @@ const O_TRUNC: u32 = lfs::lfs_open_flags_LFS_O_TRUNC;
 const O_APPEND: u32 = lfs::lfs_open_flags_LFS_O_APPEND;
 const O_EXCL: u32 = lfs::lfs_open_flags_LFS_O_EXCL;
 
-/// Goes from "rwx" that Node uses to i32 that LittleFS uses.
-/// See: https://nodejs.org/api/fs.html#file-system-flags
-/// See: https://github.com/streamich/memfs/blob/48f6fbcdce51f62d005648a4beb2eece52d0c1f8/src/volume.ts#L155
-pub fn fs_flag_node_to_lfs(flags: Option<&str>) -> i32 {
-    let flags = flags.unwrap_or("r");
-    let flags = match flags {
-        // Open file for reading. An exception occurs if the file does not exist.
-        "r" | "rs" | "sr" => O_RDONLY,
-        // Open file for reading and writing. An exception occurs if the file does not exist.
-        "r+" | "rs+" | "sr+" => O_RDWR,
-        // Open file for writing. The file is created (if it does not exist) or truncated (if it exists).
-        "w" => O_WRONLY | O_CREAT | O_TRUNC,
-        // Like 'w' but fails if path exists.
-        "wx" | "xw" => O_WRONLY | O_CREAT | O_TRUNC | O_EXCL,
-        // Open file for reading and writing. The file is created (if it does not exist) or truncated (if it exists).
-        "w+" => O_RDWR | O_CREAT | O_TRUNC,
-        // Like 'w+' but fails if path exists.
-        "wx+" | "xw+" => O_RDWR | O_CREAT | O_TRUNC | O_EXCL,
-        // Open file for appending. The file is created if it does not exist.
-        "a" => O_WRONLY | O_APPEND | O_CREAT,
-        // Like 'a' but fails if path exists.
-        "ax" | "xa" => O_WRONLY | O_APPEND | O_CREAT | O_EXCL,
-        // Open file for reading and appending. The file is created if it does not exist.
-        "a+" => O_RDWR | O_APPEND | O_CREAT,
-        // Like 'a+' but fails if path exists.
-        "ax+" | "xa+" => O_RDWR | O_APPEND | O_CREAT | O_EXCL,
-        _ => O_RDONLY, // Default to read only.
-    };
-    flags as i32
+/// Open flags the way Node hands them out, with the values they have on Linux.
+/// Only the access mode and the creation flags have a LittleFS counterpart, the
+/// rest are honored by wasabio itself. O_NOCTTY and O_DIRECT are accepted and
+/// ignored.
+/// See: https://nodejs.org/api/fs.html#file-open-constants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenFlags(pub u32);
+
+impl OpenFlags {
+    pub const RDONLY: u32 = 0o0;
+    pub const WRONLY: u32 = 0o1;
+    pub const RDWR: u32 = 0o2;
+    pub const ACCMODE: u32 = 0o3;
+    pub const CREAT: u32 = 0o100;
+    pub const EXCL: u32 = 0o200;
+    pub const TRUNC: u32 = 0o1000;
+    pub const APPEND: u32 = 0o2000;
+    pub const NONBLOCK: u32 = 0o4000;
+    pub const DSYNC: u32 = 0o10000;
+    pub const DIRECTORY: u32 = 0o200000;
+    pub const NOFOLLOW: u32 = 0o400000;
+    pub const NOATIME: u32 = 0o1000000;
+    pub const SYNC: u32 = 0o4010000;
+
+    /// Goes from "rwx" that Node uses to numeric flags. None for unknown strings.
+    /// See: https://nodejs.org/api/fs.html#file-system-flags
+    /// See: https://github.com/streamich/memfs/blob/48f6fbcdce51f62d005648a4beb2eece52d0c1f8/src/volume.ts#L155
+    pub fn parse(flags: &str) -> Option<Self> {
+        let flags = match flags {
+            // Open file for reading. An exception occurs if the file does not exist.
+            "r" => Self::RDONLY,
+            // Open file for reading in synchronous mode.
+            "rs" | "sr" => Self::RDONLY | Self::SYNC,
+            // Open file for reading and writing. An exception occurs if the file does not exist.
+            "r+" => Self::RDWR,
+            // Open file for reading and writing in synchronous mode.
+            "rs+" | "sr+" => Self::RDWR | Self::SYNC,
+            // Open file for writing. The file is created (if it does not exist) or truncated (if it exists).
+            "w" => Self::WRONLY | Self::CREAT | Self::TRUNC,
+            // Like 'w' but fails if path exists.
+            "wx" | "xw" => Self::WRONLY | Self::CREAT | Self::TRUNC | Self::EXCL,
+            // Open file for reading and writing. The file is created (if it does not exist) or truncated (if it exists).
+            "w+" => Self::RDWR | Self::CREAT | Self::TRUNC,
+            // Like 'w+' but fails if path exists.
+            "wx+" | "xw+" => Self::RDWR | Self::CREAT | Self::TRUNC | Self::EXCL,
+            // Open file for appending. The file is created if it does not exist.
+            "a" => Self::WRONLY | Self::APPEND | Self::CREAT,
+            // Like 'a' but fails if path exists.
+            "ax" | "xa" => Self::WRONLY | Self::APPEND | Self::CREAT | Self::EXCL,
+            // Open file for appending in synchronous mode.
+            "as" | "sa" => Self::WRONLY | Self::APPEND | Self::CREAT | Self::SYNC,
+            // Open file for reading and appending. The file is created if it does not exist.
+            "a+" => Self::RDWR | Self::APPEND | Self::CREAT,
+            // Like 'a+' but fails if path exists.
+            "ax+" | "xa+" => Self::RDWR | Self::APPEND | Self::CREAT | Self::EXCL,
+            // Open file for reading and appending in synchronous mode.
+            "as+" | "sa+" => Self::RDWR | Self::APPEND | Self::CREAT | Self::SYNC,
+            _ => return None,
+        };
+        Some(Self(flags))
+    }
+    pub fn has(self, flag: u32) -> bool {
+        self.0 & flag == flag
+    }
+    /// RDONLY | WRONLY | RDWR is not an access mode.
+    pub fn valid(self) -> bool {
+        self.0 & Self::ACCMODE != Self::ACCMODE
+    }
+    pub fn readable(self) -> bool {
+        self.0 & Self::ACCMODE != Self::WRONLY
+    }
+    pub fn writable(self) -> bool {
+        self.0 & Self::ACCMODE != Self::RDONLY
+    }
+    /// Flags LittleFS understands.
+    pub fn to_lfs(self) -> i32 {
+        let mut flags = match self.0 & Self::ACCMODE {
+            Self::WRONLY => O_WRONLY,
+            Self::RDWR => O_RDWR,
+            _ => O_RDONLY,
+        };
+        for (node, lfs) in [
+            (Self::CREAT, O_CREAT),
+            (Self::EXCL, O_EXCL),
+            (Self::TRUNC, O_TRUNC),
+            (Self::APPEND, O_APPEND),
+        ] {
+            if self.has(node) {
+                flags |= lfs;
+            }
+        }
+        flags as i32
+    }
 }
 
 #[derive(Debug)]
@@ -250,6 +760,15 @@ pub struct NodeStats {
     pub mtimeMs: f64,
     pub ctimeMs: f64,
     pub birthtimeMs: f64,
+    /// The times above as nanoseconds since the epoch, to full precision.
+    #[serde(default)]
+    pub atimeNs: i64,
+    #[serde(default)]
+    pub mtimeNs: i64,
+    #[serde(default)]
+    pub ctimeNs: i64,
+    #[serde(default)]
+    pub birthtimeNs: i64,
 }
 
 /// Universal handle to either a directory or a file.
@@ -260,6 +779,109 @@ static mut LFS_SYS_FD_POOL: Lazy<IdPool> = Lazy::new(|| IdPool::new());
 static mut LFS_SYS_INO_POOL: Lazy<IdPool> = Lazy::new(|| IdPool::new());
 static mut LFS_SYS_OPEN_FDS: Lazy<HashMap<usize, Handle>> = Lazy::new(|| HashMap::new());
 static mut LFS_SYS_HARD_LINKS: Lazy<HashMap<String, Vec<String>>> = Lazy::new(|| HashMap::new());
+static mut LFS_SYS_DEVICE_FDS: Lazy<HashMap<usize, Device>> = Lazy::new(|| HashMap::new());
+static mut LFS_SYS_FIFO_FDS: Lazy<HashMap<usize, FifoEnd>> = Lazy::new(|| HashMap::new());
+static mut LFS_SYS_PROC_FDS: Lazy<HashMap<usize, ProcFile>> = Lazy::new(|| HashMap::new());
+/// Names handed out by `spare_name` whose nodes may still be on the disk.
+static mut LFS_SYS_SPARES: Lazy<HashSet<String>> = Lazy::new(|| HashSet::new());
+
+/// A /proc file opened for reading. Its content is captured when it is opened.
+#[derive(Debug)]
+struct ProcFile {
+    path: String,
+    data: Vec<u8>,
+    pos: usize,
+}
+
+/// Returns every open file descriptor, including the standard streams.
+fn open_fds() -> Vec<usize> {
+    let mut fds = vec![1, 2];
+    unsafe {
+        fds.extend(LFS_SYS_OPEN_FDS.keys());
+        fds.extend(LFS_SYS_DEVICE_FDS.keys());
+        fds.extend(LFS_SYS_FIFO_FDS.keys());
+        fds.extend(LFS_SYS_PROC_FDS.keys());
+    }
+    fds.sort_unstable();
+    fds
+}
+
+/// Returns the path a file descriptor was opened with.
+fn path_by_fd(fd: usize) -> Option<String> {
+    if let Some(device) = device_by_fd(fd) {
+        return Some(device.path());
+    }
+    if let Some(end) = unsafe { LFS_SYS_FIFO_FDS.get(&fd) } {
+        return Some(end.path.clone());
+    }
+    if let Some(file) = unsafe { LFS_SYS_PROC_FDS.get(&fd) } {
+        return Some(file.path.clone());
+    }
+    lookup_by_fd(fd).map(|handle| match handle {
+        Either::Left(file) => file.path.clone(),
+        Either::Right(dir) => dir.path.clone(),
+    })
+}
+
+/// Looks up an existing /proc node. Descriptor links only exist while open.
+fn proc_node(path: &str) -> Option<ProcNode> {
+    match ProcNode::from_path(path)? {
+        ProcNode::Fd(_, fd) if !open_fds().contains(&fd) => None,
+        node => Some(node),
+    }
+}
+
+pub fn is_proc(path: &str) -> bool {
+    proc_node(path).is_some()
+}
+
+/// Generates the content of a /proc file.
+fn proc_read(node: ProcNode) -> Option<Vec<u8>> {
+    let text = match node {
+        ProcNode::Mounts => proc::mounts(
+            unsafe { lfs::lfs_sys_get_block_size() } as usize,
+            unsafe { lfs::lfs_sys_get_block_count() } as usize,
+            &mount_options(),
+        ),
+        ProcNode::Meminfo => proc::meminfo(),
+        ProcNode::Locks => proc::locks(),
+        ProcNode::Status(worker) => proc::status(worker, &open_fds()),
+        ProcNode::Fd(_, fd) => return read_file_sync(&path_by_fd(fd)?).ok(),
+        _ => return None,
+    };
+    Some(text.into_bytes())
+}
+
+pub(super) fn is_virtual(path: &str) -> bool {
+    path == DEV_DIR || is_device(path) || is_proc(path)
+}
+
+/// Looks up the character device behind a file descriptor, if any.
+fn device_by_fd(fd: usize) -> Option<Device> {
+    Device::from_fd(fd).or_else(|| unsafe { LFS_SYS_DEVICE_FDS.get(&fd).copied() })
+}
+
+/// True if `fd` is open, whatever it points to.
+pub fn is_open_fd(fd: usize) -> bool {
+    device_by_fd(fd).is_some()
+        || unsafe { LFS_SYS_FIFO_FDS.contains_key(&fd) }
+        || unsafe { LFS_SYS_PROC_FDS.contains_key(&fd) }
+        || lookup_by_fd(fd).is_some()
+}
+
+/// True if `fd` is an open directory.
+pub fn is_dir_fd(fd: usize) -> bool {
+    matches!(lookup_by_fd(fd), Some(Either::Right(_)))
+}
+
+/// True if `fd` accepts writes. /proc files never do.
+pub fn is_writable_fd(fd: usize) -> bool {
+    match lookup_by_fd(fd) {
+        Some(Either::Left(file)) => file.writable,
+        Some(Either::Right(_)) => false,
+        None => device_by_fd(fd).is_some() || unsafe { LFS_SYS_FIFO_FDS.contains_key(&fd) },
+    }
+}
 
 /// Looks up a file descriptor in the global map of open files.
 /// This is thread and worker safe. Locks the FD lock.
@@ -273,6 +895,7 @@ fn lookup_by_fd(fd: usize) -> Option<&'static mut Handle> {
 /// Looks up a file descriptor in the global map of open files.
 /// This is thread and worker safe. Locks the FD lock.
 fn lookup_by_path(path: &str) -> Option<&'static mut Handle> {
+    let path = names::resolve(path);
     unsafe {
         LFS_SYS_OPEN_FDS
             .iter_mut()
@@ -285,11 +908,17 @@ fn lookup_by_path(path: &str) -> Option<&'static mut Handle> {
 }
 
 pub fn is_directory(path: &str) -> bool {
-    if path == "/" {
+    if path == "/" || path == DEV_DIR {
         return true;
     }
+    if let Some(node) = proc_node(path) {
+        return node.is_dir();
+    }
     let disk = disk();
-    let path = CString::new(path.clone()).unwrap();
+    let path = match c_string(path) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
     let info = InfoHandle::new();
     let res = unsafe { lfs::lfs_stat(disk, path.as_ptr(), info.0) };
     res == lfs::lfs_error_LFS_ERR_OK
@@ -297,13 +926,28 @@ pub fn is_directory(path: &str) -> bool {
 }
 
 pub fn is_file(path: &str) -> bool {
-    if path == "/" || !exists_sync_no_follow(path) {
+    if path == "/" || is_device(path) || !exists_sync_no_follow(path) {
         return false;
     }
     !is_directory(path)
 }
 
+pub fn is_device(path: &str) -> bool {
+    Device::from_path(path).is_some()
+}
+
+pub fn is_fifo(path: &str) -> bool {
+    if path == "/" || is_virtual(path) || !exists_sync_no_follow(path) {
+        return false;
+    }
+    let q = AttrQueryHandle::new(path);
+    unsafe { (*q.0).mode as u32 & S_IFMT == S_IFIFO }
+}
+
 fn is_symlink(path: &str) -> bool {
+    if let Some(node) = proc_node(path) {
+        return matches!(node, ProcNode::Fd(..));
+    }
     if path == "/" || !exists_sync_no_follow(path) {
         return false;
     }
@@ -312,15 +956,17 @@ fn is_symlink(path: &str) -> bool {
 }
 
 fn is_link(path: &str) -> bool {
-    if path == "/" || !exists_sync_no_follow(path) {
+    if path == "/" || is_virtual(path) || !exists_sync_no_follow(path) {
         return false;
     }
     let q = AttrQueryHandle::new(path);
     unsafe { (*q.0).link }
 }
 
-fn is_open(path: &str) -> bool {
+pub(super) fn is_open(path: &str) -> bool {
     lookup_by_path(path).is_some()
+        || unsafe { LFS_SYS_DEVICE_FDS.values().any(|d| d.path() == path) }
+        || unsafe { LFS_SYS_FIFO_FDS.values().any(|f| f.path == path) }
         || if unsafe { LFS_SYS_HARD_LINKS.contains_key(path) } {
             unsafe {
                 LFS_SYS_HARD_LINKS.get(path).unwrap().iter().any(|link| {
@@ -363,24 +1009,54 @@ impl Drop for StatHandle {
     }
 }
 
-#[derive(Debug)]
+/// What `statfs(2)` reports about the mount containing a path, in the shape
+/// of Node's `StatFs`.
+#[derive(Debug, Clone, Serialize)]
 pub struct StatFs {
+    #[serde(rename = "type")]
+    pub kind: usize,
     pub bsize: usize,
     pub blocks: usize,
     pub bfree: usize,
     pub bavail: usize,
     pub files: usize,
     pub ffree: usize,
-    pub dirs: usize,
-    pub json: Option<String>,
+}
+
+impl StatFs {
+    /// A mount that only lives in memory and has no blocks to give out.
+    fn empty(kind: usize, bsize: usize, files: usize) -> Self {
+        StatFs {
+            kind,
+            bsize,
+            blocks: 0,
+            bfree: 0,
+            bavail: 0,
+            files,
+            ffree: 0,
+        }
+    }
+}
+
+/// `f_type` of /dev, as Linux reports it for devtmpfs.
+pub const TMPFS_MAGIC: usize = 0x0102_1994;
+/// `f_type` of /proc.
+pub const PROC_SUPER_MAGIC: usize = 0x9fa0;
+
+/// Bytes taken by the subtree at `path`, one per directory as `du` lists them.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskUsage {
+    pub path: String,
+    pub size: usize,
 }
 
 #[derive(Debug)]
 struct AttrQueryHandle(*mut lfs::lfs_sys_query_t);
 
 impl AttrQueryHandle {
+    /// A path that cannot be passed on queries nothing, like a missing one.
     fn new(path: &str) -> Self {
-        let path = CString::new(path).unwrap();
+        let path = c_string(path).unwrap_or_default();
         let ptr = unsafe { lfs::lfs_sys_attr_query_new(path.as_ptr()) };
         Self { 0: ptr }
     }
@@ -400,12 +1076,16 @@ pub struct Dirent {
     pub path: String,
     pub file: bool,
     pub symlink: bool,
+    pub device: bool,
+    pub fifo: bool,
 }
 
 impl Dirent {
     fn new(path: &str) -> Self {
-        let file = is_file(path);
+        let fifo = is_fifo(path);
+        let file = is_file(path) && !fifo;
         let symlink = is_symlink(path);
+        let device = is_device(path);
         let name = path_basename(path);
         let path = path_dirname(path);
         Self {
@@ -413,6 +1093,8 @@ impl Dirent {
             path,
             file,
             symlink,
+            device,
+            fifo,
         }
     }
 }
@@ -422,11 +1104,22 @@ struct FileHandle {
     file: *mut lfs::lfs_file_t,
     pub path: String,
     pub fd: usize,
+    /// O_SYNC / O_DSYNC: every write is flushed to disk before returning
+    pub sync_writes: bool,
+    /// O_NOATIME: reads leave the access time alone
+    pub noatime: bool,
+    /// opened with O_WRONLY or O_RDWR
+    pub writable: bool,
+    /// O_APPEND: every write goes to the end of the file
+    pub append: bool,
+    /// contents of a compressed file, None for plain ones
+    inflated: Option<Inflated>,
 }
 
 impl Drop for FileHandle {
     fn drop(&mut self) {
         let disk = disk();
+        self.deflate();
         unsafe {
             if LFS_SYS_OPEN_FDS.contains_key(&(self.fd)) {
                 LFS_SYS_FD_POOL.return_id(self.fd - 2).unwrap();
@@ -441,8 +1134,16 @@ impl Drop for FileHandle {
 impl FileHandle {
     /// Opens a file. Returns None if the file is already open (maybe by another thread)
     pub fn open(path: &str, flags: Option<&str>, mode: Option<i32>) -> Option<Self> {
-        // todo: check for exclusive access ??
+        let flags = OpenFlags::parse(flags.unwrap_or("r")).unwrap_or(OpenFlags(OpenFlags::RDONLY));
+        Self::open_with(path, flags, mode)
+    }
+    /// Opens a file with numeric flags. Returns None if the file cannot be opened
+    pub fn open_with(path: &str, open_flags: OpenFlags, mode: Option<i32>) -> Option<Self> {
+        if compressed_busy(path, open_flags.writable()) {
+            return None;
+        }
         let disk = disk();
+        let c_path = c_string(path).ok()?;
         let existed = exists_sync_no_follow(path);
         let file = unsafe { lfs::lfs_sys_file_new() };
         let fd = (unsafe { LFS_SYS_FD_POOL.request_id() }).unwrap() + 2; // todo: randomize this
@@ -450,11 +1151,19 @@ impl FileHandle {
             fd,
             file,
             path: path.to_string(),
+            sync_writes: open_flags.has(OpenFlags::DSYNC),
+            noatime: open_flags.has(OpenFlags::NOATIME),
+            writable: open_flags.writable(),
+            append: open_flags.has(OpenFlags::APPEND),
+            inflated: None,
         };
-        let c_path = CString::new(path).unwrap();
-        let flags = fs_flag_node_to_lfs(flags);
+        let flags = open_flags.to_lfs();
         let res = unsafe { lfs::lfs_file_open(disk, handle.file, c_path.as_ptr(), flags) };
         if res == lfs::lfs_error_LFS_ERR_OK {
+            if existed && (flags as u32 & O_TRUNC) != 0 {
+                Digest::invalidate(path);
+                changes::record(path, Change::Modified);
+            }
             if !existed {
                 unsafe { lfs::lfs_sys_attr_reset(c_path.as_ptr()) };
                 let q = AttrQueryHandle::new(path);
@@ -462,21 +1171,126 @@ impl FileHandle {
                     (*q.0).ino = LFS_SYS_INO_POOL.request_id()? as i32;
                     lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
                 };
-                handle.chmod(mode.unwrap_or(DEFAULT_PERM_FILE));
+                handle.chmod(apply_umask(mode.unwrap_or(DEFAULT_PERM_FILE)));
                 Touch::birthtime(path, None);
-            } else {
-                Touch::atime(path, None);
+                if let Some((compression, _)) = Compression::of(&path_dirname(path)) {
+                    Compression::store(path, Some((compression, 0)));
+                }
+                changes::record(path, Change::Created);
+            } else if !handle.noatime {
+                Touch::access(path);
             }
+            handle.inflate((flags as u32 & O_TRUNC) != 0)?;
             Some(handle)
         } else {
             None
         }
     }
-    /// Appends data to the file.
-    pub fn append(&mut self, data: &[u8], length: Option<usize>) -> Option<()> {
+    /// Reads the whole of a compressed file into memory, or nothing if it
+    /// is `truncated` by opening it.
+    fn inflate(&mut self, truncated: bool) -> Option<()> {
+        let (compression, size) = match Compression::of(&self.path) {
+            Some(compression) => compression,
+            None => return Some(()),
+        };
+        let data = if truncated {
+            vec![]
+        } else {
+            let disk = disk();
+            let mut stored = vec![];
+            let mut buf = vec![0u8; 4096];
+            loop {
+                let len = unsafe {
+                    lfs::lfs_file_read(disk, self.file, buf.as_mut_ptr() as *mut c_void, buf.len() as u32)
+                };
+                if len <= 0 {
+                    break;
+                }
+                stored.extend_from_slice(&buf[..len as usize]);
+            }
+            compression.decode(&stored, size)?
+        };
+        self.inflated = Some(Inflated {
+            compression,
+            data,
+            pos: Cell::new(0),
+            dirty: truncated && size > 0,
+        });
+        Some(())
+    }
+    /// Writes the contents of a compressed file back to the disk if they
+    /// changed since it was opened or last synced. They go to a file next to
+    /// it first, which then takes its place along with its attributes, so
+    /// the stored contents stay whole if the write fails.
+    fn deflate(&mut self) -> Option<()> {
+        let inflated = match self.inflated.as_mut() {
+            Some(inflated) if inflated.dirty => inflated,
+            _ => return Some(()),
+        };
+        let stored = inflated.compression.encode(&inflated.data);
+        let spare = spare_name(&self.path, "deflate");
+        let written = RawFile::open(&spare, true).and_then(|mut file| {
+            file.write(&stored)?;
+            file.close()
+        });
+        if written.is_none() {
+            remove_raw(&spare);
+            return None;
+        }
+        set_raw_attrs(&spare, &raw_attrs(&self.path));
+        Compression::store(&spare, Some((inflated.compression, inflated.data.len())));
+        let disk = disk();
+        let c_path = c_string(&self.path).ok()?;
+        let c_spare = c_string(&spare).ok()?;
+        let flags = if self.writable { O_RDWR } else { O_RDONLY };
+        let res = unsafe {
+            lfs::lfs_file_close(disk, self.file);
+            let res = lfs::lfs_rename(disk, c_spare.as_ptr(), c_path.as_ptr());
+            lfs::lfs_file_open(disk, self.file, c_path.as_ptr(), flags as i32);
+            res
+        };
+        if res != lfs::lfs_error_LFS_ERR_OK {
+            remove_raw(&spare);
+            return None;
+        }
+        unsafe { LFS_SYS_SPARES.remove(&spare) };
+        inflated.dirty = false;
+        Some(())
+    }
+    /// Writes the contents back if needed and closes the file. Unlike
+    /// dropping the handle, this reports a failed write.
+    pub fn close(mut self) -> Option<()> {
+        let deflated = self.deflate();
+        drop(self);
+        deflated
+    }
+    /// Bookkeeping once the contents changed.
+    fn touched(&self) {
+        Touch::mtime(self.path.as_str(), None);
+        Digest::invalidate(self.path.as_str());
+        changes::record(&self.path, Change::Modified);
+    }
+    /// Applies a change to the contents of a compressed file.
+    fn changed(&mut self) {
+        if let Some(inflated) = self.inflated.as_mut() {
+            inflated.dirty = true;
+        }
+        self.touched();
+        if self.sync_writes {
+            self.sync();
+        }
+    }
+    /// Appends the first `length` bytes of `data` to the file.
+    pub fn append(&mut self, data: &[u8], length: Option<usize>) -> io::Result<()> {
+        let data = &data[buffer_range(data.len(), None, length)?];
+        if let Some(inflated) = self.inflated.as_mut() {
+            inflated.data.extend_from_slice(data);
+            self.changed();
+            return Ok(());
+        }
         let disk = disk();
         self.sync();
-        let length = length.unwrap_or(data.len()) as u32;
+        let length = data.len() as u32;
         let res = unsafe {
             let res = lfs::lfs_file_seek(
                 disk,
@@ -487,36 +1301,56 @@ impl FileHandle {
             assert_eq!(res, lfs::lfs_error_LFS_ERR_OK);
             lfs::lfs_file_write(disk, self.file, data.as_ptr() as *const c_void, length)
         };
-        if res == lfs::lfs_error_LFS_ERR_OK {
-            Touch::mtime(self.path.as_str(), None);
-            Some(())
-        } else {
-            None
+        if res < 0 {
+            return lfs_result(res, &self.path, "write");
         }
+        self.touched();
+        Ok(())
     }
     /// Returns information about the file.
     pub fn stat(&self) -> Option<NodeStats> {
-        stat_sync(self.path.as_str())
+        let mut stat = stat_sync(self.path.as_str()).ok()?;
+        if let Some(inflated) = self.inflated.as_ref() {
+            stat.size = inflated.data.len();
+        }
+        Some(stat)
     }
     /// Changes the file's mode (permissions)
     pub fn chmod(&mut self, mode: i32) -> Option<()> {
-        chmod_sync(self.path.as_str(), mode)
+        chmod_sync(self.path.as_str(), mode).ok()
     }
     /// Changes the file's owner and group
     pub fn chown(&mut self, uid: i32, gid: i32) -> Option<()> {
-        chown_sync(self.path.as_str(), uid, gid)
+        chown_sync(self.path.as_str(), uid, gid).ok()
     }
     /// Changes the file's access and modification times
-    pub fn utimes(&mut self, atime: f64, mtime: f64) -> Option<()> {
-        utimes_sync(self.path.as_str(), atime, mtime)
+    pub fn utimes(&mut self, atime: Timestamp, mtime: Timestamp) -> Option<()> {
+        utimes_sync(self.path.as_str(), atime, mtime).ok()
+    }
+    /// Current size of the file, including writes not yet flushed
+    pub fn size(&self) -> Option<usize> {
+        if let Some(inflated) = self.inflated.as_ref() {
+            return Some(inflated.data.len());
+        }
+        let res = unsafe { lfs::lfs_file_size(disk(), self.file) };
+        if res >= 0 {
+            Some(res as usize)
+        } else {
+            None
+        }
     }
-    /// Truncates the file to the specified length
+    /// Truncates the file to the specified length. Growing pads it with zeros.
     pub fn truncate(&mut self, len: u32) -> Option<()> {
+        if let Some(inflated) = self.inflated.as_mut() {
+            inflated.data.resize(len as usize, 0);
+            self.changed();
+            return Some(());
+        }
         let disk = disk();
         self.sync();
         let res = unsafe { lfs::lfs_file_truncate(disk, self.file, len) };
         if res == lfs::lfs_error_LFS_ERR_OK {
-            Touch::mtime(self.path.as_str(), None);
+            self.touched();
             Some(())
         } else {
             None
@@ -524,6 +1358,7 @@ impl FileHandle {
     }
     /// Synchronizes the file's contents to disk
     pub fn sync(&mut self) -> Option<()> {
+        self.deflate()?;
         let disk = disk();
         let res = unsafe { lfs::lfs_file_sync(disk, self.file) };
         if res == lfs::lfs_error_LFS_ERR_OK {
@@ -534,10 +1369,24 @@ impl FileHandle {
     }
     /// Changes the file's offset
     pub fn lseek(&mut self, offset: i32, whence: i32) -> Option<i32> {
+        if let Some(inflated) = self.inflated.as_ref() {
+            let base = match whence as u32 {
+                lfs::lfs_whence_flags_LFS_SEEK_CUR => inflated.pos.get(),
+                lfs::lfs_whence_flags_LFS_SEEK_END => inflated.data.len(),
+                _ => 0,
+            };
+            let pos = base as i64 + offset as i64;
+            if pos < 0 || pos > i32::MAX as i64 {
+                return None;
+            }
+            inflated.pos.set(pos as usize);
+            return Some(pos as i32);
+        }
         let disk = disk();
         self.sync();
+        // seeking past the end is fine, the next write fills the gap with zeros
         let res = unsafe { lfs::lfs_file_seek(disk, self.file, offset, whence) };
-        if res == lfs::lfs_error_LFS_ERR_OK {
+        if res >= 0 {
             Some(res)
         } else {
             None
@@ -556,17 +1405,22 @@ impl FileHandle {
         position: Option<i32>,
     ) -> Option<usize> {
         let disk = disk();
-        let seek = if let Some(position) = position {
-            if position == -1 {
-                false
-            } else {
-                true
+        let seek = matches!(position, Some(p) if p >= 0);
+        let range = buffer_range(buf.len(), offset, length).ok()?;
+        let (offset, length) = (range.start, range.len() as u32);
+        if let Some(inflated) = self.inflated.as_ref() {
+            let start = if seek { position.unwrap() as usize } else { inflated.pos.get() };
+            let start = start.min(inflated.data.len());
+            let len = (length as usize).min(inflated.data.len() - start);
+            buf[offset..offset + len].copy_from_slice(&inflated.data[start..start + len]);
+            if !seek {
+                inflated.pos.set(start + len);
             }
-        } else {
-            false
-        };
-        let offset = offset.unwrap_or(0);
-        let length = length.unwrap_or(buf.len() - offset) as u32;
+            if !self.noatime {
+                Touch::access(self.path.as_str());
+            }
+            return Some(len);
+        }
         let whence = lfs::lfs_whence_flags_LFS_SEEK_SET as i32;
         let buffer = unsafe { buf.as_mut_ptr().add(offset) as *mut c_void };
         if seek {
@@ -580,7 +1434,9 @@ impl FileHandle {
                 let res = lfs::lfs_file_read(disk, self.file, buffer, length);
                 if res >= 0 {
                     lfs::lfs_file_seek(disk, self.file, curr, whence);
-                    Touch::atime(self.path.as_str(), None);
+                    if !self.noatime {
+                        Touch::access(self.path.as_str());
+                    }
                     Some(res as usize)
                 } else {
                     None
@@ -590,7 +1446,9 @@ impl FileHandle {
             unsafe {
                 let res = lfs::lfs_file_read(disk, self.file, buffer, length);
                 if res >= 0 {
-                    Touch::atime(self.path.as_str(), None);
+                    if !self.noatime {
+                        Touch::access(self.path.as_str());
+                    }
                     Some(res as usize)
                 } else {
                     None
@@ -606,19 +1464,14 @@ impl FileHandle {
         length: Option<usize>,
         position: Option<i32>,
     ) -> Option<usize> {
+        if self.inflated.is_some() {
+            return self.write_inflated(buf, offset, length, position);
+        }
         let disk = disk();
         self.sync();
-        let seek = if let Some(position) = position {
-            if position == -1 {
-                false
-            } else {
-                true
-            }
-        } else {
-            false
-        };
-        let offset = offset.unwrap_or(0);
-        let length = length.unwrap_or(buf.len() - offset) as u32;
+        let seek = matches!(position, Some(p) if p >= 0);
+        let range = buffer_range(buf.len(), offset, length).ok()?;
+        let (offset, length) = (range.start, range.len() as u32);
         let whence = lfs::lfs_whence_flags_LFS_SEEK_SET as i32;
         let buffer = unsafe { buf.as_ptr().add(offset) as *const c_void };
         if seek {
@@ -632,7 +1485,10 @@ impl FileHandle {
                 let res = lfs::lfs_file_write(disk, self.file, buffer, length);
                 if res >= 0 {
                     lfs::lfs_file_seek(disk, self.file, curr, whence);
-                    Touch::mtime(self.path.as_str(), None);
+                    self.touched();
+                    if self.sync_writes {
+                        self.sync();
+                    }
                     Some(res as usize)
                 } else {
                     None
@@ -642,7 +1498,10 @@ impl FileHandle {
             unsafe {
                 let res = lfs::lfs_file_write(disk, self.file, buffer, length);
                 if res >= 0 {
-                    Touch::mtime(self.path.as_str(), None);
+                    self.touched();
+                    if self.sync_writes {
+                        self.sync();
+                    }
                     Some(res as usize)
                 } else {
                     None
@@ -650,6 +1509,36 @@ impl FileHandle {
             }
         }
     }
+    /// `write` for compressed files, same semantics on the inflated contents.
+    fn write_inflated(
+        &mut self,
+        buf: &[u8],
+        offset: Option<usize>,
+        length: Option<usize>,
+        position: Option<i32>,
+    ) -> Option<usize> {
+        let inflated = self.inflated.as_mut()?;
+        let data = &buf[buffer_range(buf.len(), offset, length).ok()?];
+        let seek = matches!(position, Some(p) if p >= 0);
+        let start = if seek {
+            position.unwrap() as usize
+        } else if self.append {
+            inflated.data.len()
+        } else {
+            inflated.pos.get()
+        };
+        let end = start + data.len();
+        if inflated.data.len() < end {
+            reserve(end - inflated.data.len(), &self.path, "write").ok()?;
+            inflated.data.resize(end, 0);
+        }
+        inflated.data[start..end].copy_from_slice(data);
+        if !seek {
+            inflated.pos.set(end);
+        }
+        self.changed();
+        Some(data.len())
+    }
 }
 
 #[derive(Debug)]
@@ -679,6 +1568,7 @@ impl Drop for DirHandle {
 impl DirHandle {
     pub fn open(path: &str) -> Option<Self> {
         // todo: check for exclusive access ??
+        let c_path = c_string(path).ok()?;
         let dir = unsafe { lfs::lfs_sys_dir_new() };
         let handle = Self {
             fd: (unsafe { LFS_SYS_FD_POOL.request_id() }).unwrap() + 2,
@@ -688,12 +1578,11 @@ impl DirHandle {
             told: 0,
         };
         let disk = disk();
-        let c_path = CString::new(handle.path.as_str()).unwrap();
         let res = unsafe { lfs::lfs_dir_open(disk, handle.dir, c_path.as_ptr()) };
         if res != lfs::lfs_error_LFS_ERR_OK {
             return None;
         }
-        Touch::atime(path, None);
+        Touch::access(path);
         Some(handle)
     }
     pub fn read(&mut self) -> Option<Dirent> {
@@ -706,7 +1595,7 @@ impl DirHandle {
             if res == 0 {
                 return None;
             }
-            Touch::atime(self.path.as_str(), None);
+            Touch::access(self.path.as_str());
             let tell = unsafe { lfs::lfs_dir_tell(disk, self.dir) };
             if tell <= self.told {
                 return None;
@@ -724,19 +1613,19 @@ impl DirHandle {
     }
     /// Returns information about the directory
     pub fn stat(&self) -> Option<NodeStats> {
-        stat_sync(self.path.as_str())
+        stat_sync(self.path.as_str()).ok()
     }
     /// Changes the directory's mode (permissions)
     pub fn chmod(&mut self, mode: i32) -> Option<()> {
-        chmod_sync(self.path.as_str(), mode)
+        chmod_sync(self.path.as_str(), mode).ok()
     }
     /// Changes the directory's owner and group
     pub fn chown(&mut self, uid: i32, gid: i32) -> Option<()> {
-        chown_sync(self.path.as_str(), uid, gid)
+        chown_sync(self.path.as_str(), uid, gid).ok()
     }
     /// Changes the directory's access and modification times
-    pub fn utimes(&mut self, atime: f64, mtime: f64) -> Option<()> {
-        utimes_sync(self.path.as_str(), atime, mtime)
+    pub fn utimes(&mut self, atime: Timestamp, mtime: Timestamp) -> Option<()> {
+        utimes_sync(self.path.as_str(), atime, mtime).ok()
     }
 }
 
@@ -757,6 +1646,96 @@ pub fn lseek_sync(fd: usize, offset: i32, whence: i32) -> Option<i32> {
     }
 }
 
+/// Reads from the named pipe behind `fd`. Returns None if `fd` is not a pipe.
+pub fn fifo_read_sync(
+    fd: usize,
+    buf: &mut [u8],
+    offset: Option<usize>,
+    length: Option<usize>,
+) -> Option<io::Result<usize>> {
+    let end = unsafe { LFS_SYS_FIFO_FDS.get(&fd) }?.clone();
+    let buf = match buffer_range(buf.len(), offset, length) {
+        Ok(range) => &mut buf[range],
+        Err(err) => return Some(Err(err)),
+    };
+    Some(end.read(buf).map_err(|e| fifo_error(e, &end.path, "read")))
+}
+
+/// Writes into the named pipe behind `fd`. Returns None if `fd` is not a pipe.
+pub fn fifo_write_sync(
+    fd: usize,
+    buf: &[u8],
+    offset: Option<usize>,
+    length: Option<usize>,
+) -> Option<io::Result<usize>> {
+    let end = unsafe { LFS_SYS_FIFO_FDS.get(&fd) }?.clone();
+    let buf = match buffer_range(buf.len(), offset, length) {
+        Ok(range) => &buf[range],
+        Err(err) => return Some(Err(err)),
+    };
+    Some(end.write(buf).map_err(|e| fifo_error(e, &end.path, "write")))
+}
+
+/// `path` as LittleFS takes it. A NUL byte cannot be passed on, the path is
+/// refused as `cwd::check` would instead of aborting the worker.
+fn c_string(path: &str) -> io::Result<CString> {
+    CString::new(path).map_err(|_| cwd::nul_error(path))
+}
+
+/// Turns a LittleFS return code into the errno it stands for.
+fn lfs_result(res: i32, path: &str, syscall: &str) -> io::Result<()> {
+    let (code, message) = match res {
+        lfs::lfs_error_LFS_ERR_OK => return Ok(()),
+        lfs::lfs_error_LFS_ERR_NOENT => ("ENOENT", "no such file or directory"),
+        lfs::lfs_error_LFS_ERR_EXIST => ("EEXIST", "file already exists"),
+        lfs::lfs_error_LFS_ERR_NOTDIR => ("ENOTDIR", "not a directory"),
+        lfs::lfs_error_LFS_ERR_ISDIR => ("EISDIR", "illegal operation on a directory"),
+        lfs::lfs_error_LFS_ERR_NOTEMPTY => ("ENOTEMPTY", "directory not empty"),
+        lfs::lfs_error_LFS_ERR_BADF => ("EBADF", "bad file descriptor"),
+        lfs::lfs_error_LFS_ERR_FBIG => ("EFBIG", "file too large"),
+        lfs::lfs_error_LFS_ERR_INVAL => ("EINVAL", "invalid argument"),
+        lfs::lfs_error_LFS_ERR_NOSPC => ("ENOSPC", "no space left on device"),
+        lfs::lfs_error_LFS_ERR_NOMEM => ("ENOMEM", "not enough memory"),
+        lfs::lfs_error_LFS_ERR_NAMETOOLONG => ("ENAMETOOLONG", "name too long"),
+        _ => ("EIO", "i/o error"),
+    };
+    Err(sys_error(code, message, path, syscall))
+}
+
+/// The bytes of a `len` bytes long buffer that `offset` and `length` select.
+/// Reaching past its end fails with ERR_OUT_OF_RANGE, the way Node does.
+pub(super) fn buffer_range(
+    len: usize,
+    offset: Option<usize>,
+    length: Option<usize>,
+) -> io::Result<std::ops::Range<usize>> {
+    let out_of_range = |name: &str, max: usize, value: usize| {
+        let message = format!(
+            "The value of \"{}\" is out of range. It must be >= 0 && <= {}. Received {}",
+            name, max, value
+        );
+        errno("ERR_OUT_OF_RANGE", &message, None, None)
+    };
+    let offset = offset.unwrap_or(0);
+    if offset > len {
+        return Err(out_of_range("offset", len, offset));
+    }
+    let length = length.unwrap_or(len - offset);
+    match offset.checked_add(length) {
+        Some(end) if end <= len => Ok(offset..end),
+        _ => Err(out_of_range("length", len - offset, length)),
+    }
+}
+
+fn fifo_error(error: FifoError, path: &str, syscall: &str) -> io::Error {
+    let (code, message) = match error {
+        FifoError::Again => ("EAGAIN", "resource temporarily unavailable"),
+        FifoError::BrokenPipe => ("EPIPE", "broken pipe"),
+        FifoError::BadDescriptor => ("EBADF", "bad file descriptor"),
+    };
+    sys_error(code, message, path, syscall)
+}
+
 pub fn read_sync(
     fd: usize,
     buf: &mut [u8],
@@ -764,6 +1743,25 @@ pub fn read_sync(
     length: Option<usize>,
     position: Option<i32>,
 ) -> Option<usize> {
+    if let Some(device) = device_by_fd(fd) {
+        let range = buffer_range(buf.len(), offset, length).ok()?;
+        return Some(device.read(&mut buf[range]));
+    }
+    if let Some(file) = unsafe { LFS_SYS_PROC_FDS.get_mut(&fd) } {
+        let range = buffer_range(buf.len(), offset, length).ok()?;
+        // a negative position reads at the cursor, like no position
+        let position = match position {
+            Some(p) if p >= 0 => Some(p as usize),
+            _ => None,
+        };
+        let start = position.unwrap_or(file.pos).min(file.data.len());
+        let len = range.len().min(file.data.len() - start);
+        buf[range.start..range.start + len].copy_from_slice(&file.data[start..start + len]);
+        if position.is_none() {
+            file.pos = start + len;
+        }
+        return Some(len);
+    }
     match lookup_by_fd(fd) {
         None => None,
         Some(_) => {
@@ -780,6 +1778,10 @@ pub fn write_sync(
     length: Option<usize>,
     position: Option<i32>,
 ) -> Option<usize> {
+    if let Some(device) = device_by_fd(fd) {
+        let range = buffer_range(buf.len(), offset, length).ok()?;
+        return Some(device.write(&buf[range]));
+    }
     match lookup_by_fd(fd) {
         None => None,
         Some(_) => {
@@ -790,6 +1792,15 @@ pub fn write_sync(
 }
 
 pub fn fstat(fd: usize) -> Option<NodeStats> {
+    if let Some(device) = device_by_fd(fd) {
+        return Some(device_stat(device));
+    }
+    if let Some(end) = unsafe { LFS_SYS_FIFO_FDS.get(&fd) } {
+        return stat_sync(&end.path).ok();
+    }
+    if let Some(file) = unsafe { LFS_SYS_PROC_FDS.get(&fd) } {
+        return stat_sync(&file.path).ok();
+    }
     match lookup_by_fd(fd) {
         None => None,
         Some(_) => {
@@ -848,18 +1859,107 @@ pub fn fchmod(fd: usize, mode: i32) -> Option<()> {
     }
 }
 
-pub fn ftruncate(fd: usize, len: usize) -> Option<()> {
-    match lookup_by_fd(fd) {
-        None => None,
-        Some(_) => {
-            let handle = unsafe { LFS_SYS_OPEN_FDS.get_mut(&fd) }?;
-            handle.as_mut().left()?.truncate(len as u32);
-            handle.as_mut().left()?.sync() // ??
+/// Bytes that can still be allocated. LittleFS only hands out whole blocks.
+fn free_space() -> usize {
+    let stat = StatHandle::new();
+    unsafe { (*stat.0).bfree.saturating_mul((*stat.0).bsize) }
+}
+
+/// Fails with ENOSPC if growing a file by `grow` bytes cannot possibly fit.
+pub(super) fn reserve(grow: usize, path: &str, syscall: &str) -> io::Result<()> {
+    if grow > free_space() {
+        Err(sys_error("ENOSPC", "no space left on device", path, syscall))
+    } else {
+        Ok(())
+    }
+}
+
+/// Shrinks or grows a file to `len` bytes. Growing checks for space up front.
+fn resize(handle: &mut FileHandle, len: usize, syscall: &str) -> io::Result<()> {
+    let path = handle.path.clone();
+    let size = handle
+        .size()
+        .ok_or_else(|| sys_error("EIO", "i/o error", &path, syscall))?;
+    reserve(len.saturating_sub(size), &path, syscall)?;
+    handle
+        .truncate(len as u32)
+        .and_then(|_| handle.sync())
+        .ok_or_else(|| sys_error("EIO", "i/o error", &path, syscall))
+}
+
+/// Looks up a regular file opened for writing behind `fd`.
+fn writable_file_by_fd(fd: usize, syscall: &str) -> io::Result<&'static mut FileHandle> {
+    if !is_open_fd(fd) {
+        return Err(sys_error("EBADF", "bad file descriptor", &fd.to_string(), syscall));
+    }
+    // pipes, devices and directories cannot be resized
+    let handle = lookup_by_fd(fd)
+        .and_then(|handle| handle.as_mut().left())
+        .ok_or_else(|| sys_error("EINVAL", "invalid argument", &fd.to_string(), syscall))?;
+    if handle.writable {
+        Ok(handle)
+    } else {
+        let path = handle.path.clone();
+        Err(sys_error("EINVAL", "invalid argument", &path, syscall))
+    }
+}
+
+pub fn ftruncate(fd: usize, len: usize) -> io::Result<()> {
+    resize(writable_file_by_fd(fd, "ftruncate")?, len, "ftruncate")
+}
+
+/// Manipulates the space allocated to a file, after fallocate(2).
+/// - by default `[offset, offset + len)` is allocated and the file grows to
+///   cover it, the new bytes read as zeros.
+/// - with `keep_size` only the space check happens, the size stays the same.
+/// - with `punch_hole` (requires `keep_size`) the range is zeroed. LittleFS has
+///   no holes, so the blocks stay allocated.
+/// LittleFS is copy-on-write and cannot truly reserve blocks, but a download
+/// that is too big for the disk fails here instead of halfway through.
+pub fn fallocate(
+    fd: usize,
+    offset: usize,
+    len: usize,
+    keep_size: bool,
+    punch_hole: bool,
+) -> io::Result<()> {
+    let handle = writable_file_by_fd(fd, "fallocate")
+        .map_err(|_| sys_error("EBADF", "bad file descriptor", &fd.to_string(), "fallocate"))?;
+    let path = handle.path.clone();
+    if len == 0 {
+        return Err(sys_error("EINVAL", "invalid argument", &path, "fallocate"));
+    }
+    if punch_hole && !keep_size {
+        return Err(sys_error("EOPNOTSUPP", "operation not supported", &path, "fallocate"));
+    }
+    let size = handle
+        .size()
+        .ok_or_else(|| sys_error("EIO", "i/o error", &path, "fallocate"))?;
+    let end = offset
+        .checked_add(len)
+        .ok_or_else(|| sys_error("EINVAL", "invalid argument", &path, "fallocate"))?;
+    if punch_hole {
+        let zeros = [0u8; 4096];
+        let mut pos = offset;
+        while pos < end.min(size) {
+            let chunk = zeros.len().min(end.min(size) - pos);
+            handle
+                .write(&zeros, None, Some(chunk), Some(pos as i32))
+                .ok_or_else(|| sys_error("EIO", "i/o error", &path, "fallocate"))?;
+            pos += chunk;
         }
+        return handle
+            .sync()
+            .ok_or_else(|| sys_error("EIO", "i/o error", &path, "fallocate"));
+    }
+    if keep_size || end <= size {
+        reserve(end.saturating_sub(size), &path, "fallocate")
+    } else {
+        resize(handle, end, "fallocate")
     }
 }
 
-pub fn futimes(fd: usize, atime: f64, mtime: f64) -> Option<()> {
+pub fn futimes(fd: usize, atime: Timestamp, mtime: Timestamp) -> Option<()> {
     match lookup_by_fd(fd) {
         None => None,
         Some(_) => {
@@ -884,8 +1984,19 @@ pub fn freaddir_sync(fd: usize) -> Option<Dirent> {
 
 /// -------------------------------------------------- **Sync(**) api functions
 
-pub fn open_file_sync(path: &str, flags: Option<&str>, mode: Option<i32>) -> Option<usize> {
-    let handle = FileHandle::open(path, flags, mode)?;
+/// A compressed file is held in memory by each handle to it, so it is
+/// either open for writing once or open for reading only. More handles would
+/// drift apart, and writing it back replaces the file under the others.
+fn compressed_busy(path: &str, writable: bool) -> bool {
+    Compression::of(path).is_some()
+        && unsafe { LFS_SYS_OPEN_FDS.values() }.any(|handle| match handle {
+            Left(file) => file.path == path && (writable || file.writable),
+            Right(_) => false,
+        })
+}
+
+pub fn open_file_sync(path: &str, flags: OpenFlags, mode: Option<i32>) -> Option<usize> {
+    let handle = FileHandle::open_with(path, flags, mode)?;
     let fd = handle.fd;
     unsafe {
         LFS_SYS_OPEN_FDS.insert(fd, Left(handle));
@@ -902,26 +2013,106 @@ pub fn open_dir_sync(path: &str) -> Option<usize> {
     Some(fd)
 }
 
-pub fn open_sync(path: &str, flags: Option<&str>, mode: Option<i32>) -> Option<usize> {
-    if is_directory(path) {
+pub fn open_device_sync(device: Device) -> Option<usize> {
+    let fd = unsafe { LFS_SYS_FD_POOL.request_id() }? + 2;
+    unsafe { LFS_SYS_DEVICE_FDS.insert(fd, device) };
+    Some(fd)
+}
+
+/// Opens one end of a named pipe, or both with O_RDWR. Opening never waits for
+/// the other end. With O_NONBLOCK reads fail with EAGAIN instead of blocking.
+pub fn open_fifo_sync(path: &str, flags: OpenFlags) -> Option<usize> {
+    let fd = unsafe { LFS_SYS_FD_POOL.request_id() }? + 2;
+    let end = FifoEnd::open(
+        path,
+        flags.readable(),
+        flags.writable(),
+        flags.has(OpenFlags::NONBLOCK),
+    );
+    unsafe { LFS_SYS_FIFO_FDS.insert(fd, end) };
+    Touch::access(path);
+    Some(fd)
+}
+
+/// Opens a /proc file read-only. Descriptor links are resolved by the caller.
+pub fn open_proc_sync(node: ProcNode) -> Option<usize> {
+    if node.is_dir() {
+        return None;
+    }
+    let data = proc_read(node)?;
+    let fd = unsafe { LFS_SYS_FD_POOL.request_id() }? + 2;
+    let path = node_path(node);
+    unsafe { LFS_SYS_PROC_FDS.insert(fd, ProcFile { path, data, pos: 0 }) };
+    Some(fd)
+}
+
+pub fn open_sync(path: &str, flags: OpenFlags, mode: Option<i32>) -> io::Result<usize> {
+    cwd::check(path)?;
+    if !flags.valid() {
+        return Err(sys_error("EINVAL", "invalid argument", path, "open"));
+    }
+    if let Some(ProcNode::Fd(_, fd)) = proc_node(path) {
+        let target = path_by_fd(fd).unwrap_or_default();
+        return open_sync(&target, flags, mode);
+    }
+    let exists = exists_sync(path);
+    if flags.has(OpenFlags::NOFOLLOW) && is_symlink(path) {
+        return Err(sys_error("ELOOP", "too many symbolic links encountered", path, "open"));
+    }
+    if flags.has(OpenFlags::CREAT | OpenFlags::EXCL) && exists {
+        return Err(sys_error("EEXIST", "file already exists", path, "open"));
+    }
+    if flags.has(OpenFlags::DIRECTORY) && exists && !is_directory(path) {
+        return Err(sys_error("ENOTDIR", "not a directory", path, "open"));
+    }
+    if is_directory(path) && flags.writable() {
+        return Err(sys_error("EISDIR", "illegal operation on a directory", path, "open"));
+    }
+    if is_proc(path) && flags.writable() {
+        return Err(sys_error("EACCES", "permission denied", path, "open"));
+    }
+    let fd = if let Some(device) = Device::from_path(path) {
+        open_device_sync(device)
+    } else if let Some(node) = proc_node(path) {
+        open_proc_sync(node)
+    } else if is_fifo(path) {
+        open_fifo_sync(path, flags)
+    } else if is_directory(path) {
         open_dir_sync(path)
+    } else if flags.has(OpenFlags::DIRECTORY) {
+        None
+    } else if compressed_busy(path, flags.writable()) {
+        return Err(sys_error("EBUSY", "resource busy or locked", path, "open"));
     } else {
         open_file_sync(path, flags, mode)
-    }
+    };
+    fd.ok_or_else(|| sys_error("ENOENT", "no such file or directory", path, "open"))
 }
 
-pub fn close_sync(fd: usize) -> Option<()> {
-    match lookup_by_fd(fd) {
-        None => None,
-        Some(_) => {
-            unsafe { LFS_SYS_OPEN_FDS.remove(&fd)? };
-            return Some(());
-        }
+pub fn close_sync(fd: usize) -> io::Result<()> {
+    let ebadf = || errno("EBADF", "bad file descriptor, close", None, Some("close"));
+    if unsafe { LFS_SYS_DEVICE_FDS.remove(&fd) }.is_some() {
+        return unsafe { LFS_SYS_FD_POOL.return_id(fd - 2) }.map_err(|_| ebadf());
+    }
+    if let Some(end) = unsafe { LFS_SYS_FIFO_FDS.remove(&fd) } {
+        end.close();
+        return unsafe { LFS_SYS_FD_POOL.return_id(fd - 2) }.map_err(|_| ebadf());
+    }
+    if unsafe { LFS_SYS_PROC_FDS.remove(&fd) }.is_some() {
+        return unsafe { LFS_SYS_FD_POOL.return_id(fd - 2) }.map_err(|_| ebadf());
+    }
+    match unsafe { LFS_SYS_OPEN_FDS.remove(&fd) } {
+        None => Err(ebadf()),
+        // a compressed file is written back here, which can fail
+        Some(Left(handle)) => handle
+            .close()
+            .ok_or_else(|| errno("EIO", "i/o error, close", None, Some("close"))),
+        Some(Right(_)) => Ok(()),
     }
 }
 
-fn exists_sync_no_follow(path: &str) -> bool {
-    if path == "/" {
+pub fn exists_sync_no_follow(path: &str) -> bool {
+    if path == "/" || is_virtual(path) {
         return true;
     }
     if path == "" {
@@ -929,7 +2120,10 @@ fn exists_sync_no_follow(path: &str) -> bool {
     }
     let disk = disk();
     let info = InfoHandle::new();
-    let path = CString::new(path).unwrap();
+    let path = match c_string(&names::resolve(path)) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
     let res = unsafe { lfs::lfs_stat(disk, path.as_ptr(), info.0) };
     res == lfs::lfs_error_LFS_ERR_OK
 }
@@ -939,24 +2133,18 @@ pub fn exists_sync(path: &str) -> bool {
     exists_sync_no_follow(&path)
 }
 
-pub fn link_sync(old_path: &str, new_path: &str) -> Result<JsValue, JsValue> {
+pub fn link_sync(old_path: &str, new_path: &str) -> io::Result<()> {
+    cwd::check(old_path)?;
+    cwd::check(new_path)?;
     if !exists_sync(&old_path) {
-        let err: JsValue = JsError::new("ENOENT: old_path does not exist").into();
-        Reflect::set(&err, &"code".into(), &"ENOENT".into()).unwrap();
-        Reflect::set(&err, &"path".into(), &old_path.into()).unwrap();
-        Reflect::set(&err, &"syscall".into(), &"link".into()).unwrap();
-        return Err(err);
+        return Err(errno("ENOENT", "old_path does not exist", Some(old_path), Some("link")));
     }
     if exists_sync(&new_path) {
-        let err: JsValue = JsError::new("EEXIST: new_path already exists").into();
-        Reflect::set(&err, &"code".into(), &"EEXIST".into()).unwrap();
-        Reflect::set(&err, &"path".into(), &new_path.into()).unwrap();
-        Reflect::set(&err, &"syscall".into(), &"link".into()).unwrap();
-        return Err(err);
-    }
-    write_file_sync_no_follow(&new_path, old_path.as_bytes(), None, None);
-    let n_path = CString::new(new_path).unwrap();
-    let o_path = CString::new(old_path).unwrap();
+        return Err(errno("EEXIST", "new_path already exists", Some(new_path), Some("link")));
+    }
+    write_file_sync_no_follow(&new_path, old_path.as_bytes(), None, None)?;
+    let n_path = c_string(new_path)?;
+    let o_path = c_string(old_path)?;
     let n_attr = AttrQueryHandle::new(new_path);
     let o_attr = AttrQueryHandle::new(old_path);
     unsafe {
@@ -970,12 +2158,17 @@ pub fn link_sync(old_path: &str, new_path: &str) -> Result<JsValue, JsValue> {
         (*n_attr.0).atime = (*o_attr.0).atime;
         (*n_attr.0).mtime = (*o_attr.0).mtime;
         (*n_attr.0).ctime = (*o_attr.0).ctime;
+        (*n_attr.0).birthtime_ns = (*o_attr.0).birthtime_ns;
+        (*n_attr.0).atime_ns = (*o_attr.0).atime_ns;
+        (*n_attr.0).mtime_ns = (*o_attr.0).mtime_ns;
+        (*n_attr.0).ctime_ns = (*o_attr.0).ctime_ns;
         (*n_attr.0).uid = (*o_attr.0).uid;
         (*n_attr.0).gid = (*o_attr.0).gid;
         (*n_attr.0).mode = (*o_attr.0).mode;
         lfs::lfs_sys_attr_patch(n_path.as_ptr(), n_attr.0);
         (*o_attr.0).nlink += 1; // todo: handle deletes
         lfs::lfs_sys_attr_patch(o_path.as_ptr(), o_attr.0);
+        changes::record(old_path, Change::Modified);
         if !LFS_SYS_HARD_LINKS.contains_key(old_path) {
             LFS_SYS_HARD_LINKS.insert(old_path.to_string(), vec![new_path.to_string()]);
         } else {
@@ -983,27 +2176,33 @@ pub fn link_sync(old_path: &str, new_path: &str) -> Result<JsValue, JsValue> {
             links.push(new_path.to_string());
         }
     }
-    Ok(JsValue::undefined())
+    Ok(())
 }
 
-pub fn symlink_sync(old_path: &str, new_path: &str) -> Result<JsValue, JsValue> {
+/// Where the target stored in `link` leads: relative targets start at the
+/// directory holding the link, as they do when Node follows one.
+pub(super) fn symlink_target(link: &str, target: &str) -> String {
+    if target.starts_with('/') {
+        path_normalize(target)
+    } else {
+        path_normalize(&format!("{}/{}", path_dirname(link), target))
+    }
+}
+
+/// Creates a symlink at `new_path` storing `target` as given, like Node does.
+pub fn symlink_sync(target: &str, new_path: &str) -> io::Result<()> {
+    cwd::check(target)?;
+    cwd::check(new_path)?;
+    let old_path = &symlink_target(new_path, target);
     if !exists_sync(&old_path) {
-        let err: JsValue = JsError::new("ENOENT: old_path does not exist").into();
-        Reflect::set(&err, &"code".into(), &"ENOENT".into()).unwrap();
-        Reflect::set(&err, &"path".into(), &old_path.into()).unwrap();
-        Reflect::set(&err, &"syscall".into(), &"link".into()).unwrap();
-        return Err(err);
+        return Err(errno("ENOENT", "old_path does not exist", Some(old_path), Some("link")));
     }
     if exists_sync(&new_path) {
-        let err: JsValue = JsError::new("EEXIST: new_path already exists").into();
-        Reflect::set(&err, &"code".into(), &"EEXIST".into()).unwrap();
-        Reflect::set(&err, &"path".into(), &new_path.into()).unwrap();
-        Reflect::set(&err, &"syscall".into(), &"link".into()).unwrap();
-        return Err(err);
-    }
-    write_file_sync_no_follow(&new_path, old_path.as_bytes(), None, None);
-    let n_path = CString::new(new_path).unwrap();
-    let o_path = CString::new(old_path).unwrap();
+        return Err(errno("EEXIST", "new_path already exists", Some(new_path), Some("link")));
+    }
+    write_file_sync_no_follow(&new_path, target.as_bytes(), None, None)?;
+    let n_path = c_string(new_path)?;
+    let o_path = c_string(&old_path)?;
     let n_attr = AttrQueryHandle::new(new_path);
     let o_attr = AttrQueryHandle::new(old_path);
     unsafe {
@@ -1017,22 +2216,21 @@ pub fn symlink_sync(old_path: &str, new_path: &str) -> Result<JsValue, JsValue>
         lfs::lfs_sys_attr_patch(n_path.as_ptr(), n_attr.0);
         let is_dir = is_directory(old_path);
         let perms = get_default_permissions(is_dir);
-        chmod_sync(new_path, perms);
+        chmod_sync(new_path, apply_umask(perms))?;
         (*o_attr.0).nlink += 1; // todo: handle deletes
         lfs::lfs_sys_attr_patch(o_path.as_ptr(), o_attr.0);
     }
-    Ok(JsValue::undefined())
+    changes::record(old_path, Change::Modified);
+    Ok(())
 }
 
-pub fn mkdir_sync(path: &str, recursive: bool, mode: i32) -> Result<JsValue, JsValue> {
+pub fn mkdir_sync(path: &str, recursive: bool, mode: i32) -> io::Result<Option<String>> {
+    cwd::check(path)?;
     if exists_sync(path) {
         if is_file(path) {
-            let err: JsValue = JsError::new("EEXIST: file already exists, mkdir").into();
-            Reflect::set(&err, &"code".into(), &"EEXIST".into()).unwrap();
-            Reflect::set(&err, &"syscall".into(), &"mkdir".into()).unwrap();
-            Err(err)
+            Err(errno("EEXIST", "file already exists, mkdir", None, Some("mkdir")))
         } else {
-            Ok(JsValue::undefined())
+            Ok(None)
         }
     } else if recursive {
         let paths = path_split(path);
@@ -1041,70 +2239,135 @@ pub fn mkdir_sync(path: &str, recursive: bool, mode: i32) -> Result<JsValue, JsV
             match mkdir_sync(p, false, mode) {
                 Ok(_) => created_any = true,
                 Err(err) => {
-                    let code = Reflect::get(&err, &"code".into())
-                        .unwrap()
-                        .as_string()
-                        .unwrap();
-                    if code != "EEXIST" {
+                    if error_code(&err) != Some("EEXIST") {
                         return Err(err);
                     }
-                    let err: JsValue = JsError::new("ENOTDIR: not a directory, mkdir").into();
-                    Reflect::set(&err, &"code".into(), &"ENOTDIR".into()).unwrap();
-                    Reflect::set(&err, &"syscall".into(), &"mkdir".into()).unwrap();
-                    Reflect::set(&err, &"path".into(), &path.into()).unwrap();
-                    return Err(err);
+                    let message = "not a directory, mkdir";
+                    return Err(errno("ENOTDIR", message, Some(path), Some("mkdir")));
                 }
             }
         }
         if created_any {
-            let ret = JsValue::from(paths.last().unwrap());
-            Ok(ret)
+            Ok(paths.last().cloned())
         } else {
-            Ok(JsValue::undefined())
+            Ok(None)
         }
     } else {
-        let path = follow_link(path, None);
-        let parent = path_dirname(&path);
-        let parent_q = AttrQueryHandle::new(&parent);
-        let parent_perm: u32 = sanitize_permissions(unsafe { (*parent_q.0).mode });
-        if (parent_perm & S_IWUSR) == 0
-            && (parent_perm & S_IWGRP) == 0
-            && (parent_perm & S_IWOTH) == 0
-        {
-            let err: JsValue = JsError::new("EACCES: permission denied, mkdir").into();
-            Reflect::set(&err, &"code".into(), &"EACCES".into()).unwrap();
-            Reflect::set(&err, &"syscall".into(), &"mkdir".into()).unwrap();
-            Reflect::set(&err, &"path".into(), &path.into()).unwrap();
-            return Err(err);
-        }
-        let disk = disk();
-        let c_path = CString::new(path.clone()).unwrap();
-        let res = unsafe { lfs::lfs_mkdir(disk, c_path.as_ptr()) };
-        assert_eq!(res, lfs::lfs_error_LFS_ERR_OK);
-        unsafe {
-            lfs::lfs_sys_attr_reset(c_path.as_ptr());
-            chmod_sync(&path, mode);
-        };
-        let q = AttrQueryHandle::new(&path);
-        unsafe {
-            (*q.0).ino = LFS_SYS_INO_POOL.request_id().unwrap() as i32;
-            lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+        mkdir_exclusive(path, mode).map(Some)
+    }
+}
+
+/// Creates a single directory, failing with EEXIST if anything is already
+/// there, and returns its path with links followed.
+fn mkdir_exclusive(path: &str, mode: i32) -> io::Result<String> {
+    let path = follow_link(path, None);
+    let parent = path_dirname(&path);
+    let parent_q = AttrQueryHandle::new(&parent);
+    let parent_perm: u32 = sanitize_permissions(unsafe { (*parent_q.0).mode });
+    if (parent_perm & S_IWUSR) == 0
+        && (parent_perm & S_IWGRP) == 0
+        && (parent_perm & S_IWOTH) == 0
+    {
+        return Err(errno("EACCES", "permission denied, mkdir", Some(&path), Some("mkdir")));
+    }
+    let disk = disk();
+    let c_path = c_string(&path)?;
+    let res = unsafe { lfs::lfs_mkdir(disk, c_path.as_ptr()) };
+    lfs_result(res, &path, "mkdir")?;
+    unsafe {
+        lfs::lfs_sys_attr_reset(c_path.as_ptr());
+        chmod_sync(&path, apply_umask(mode))?;
+    };
+    let q = AttrQueryHandle::new(&path);
+    unsafe {
+        (*q.0).ino = LFS_SYS_INO_POOL.request_id().unwrap() as i32;
+        lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+    }
+    Touch::birthtime(&path, None);
+    if let Some((compression, _)) = Compression::of(&parent) {
+        Compression::store(&path, Some((compression, 0)));
+    }
+    changes::record(&path, Change::Created);
+    Ok(path)
+}
+
+/// Creates `prefix` followed by a random suffix with `create`, trying other
+/// suffixes while it fails with EEXIST. The disk is held meanwhile so that no
+/// other worker takes the name between the check and the creation.
+fn create_temp<T>(
+    prefix: &str,
+    syscall: &str,
+    mut create: impl FnMut(&str) -> io::Result<T>,
+) -> io::Result<(String, T)> {
+    let _disk = DiskGuard::new()?;
+    for _ in 0..tmp::ATTEMPTS {
+        let path = format!("{}{}", prefix, tmp::suffix());
+        match create(&path) {
+            Err(err) if error_code(&err) == Some("EEXIST") => continue,
+            created => return created.map(|created| (path, created)),
         }
-        Touch::birthtime(&path, None);
-        let ret = JsValue::from(path);
-        Ok(ret)
     }
+    let path = format!("{}{}", prefix, "X".repeat(tmp::SUFFIX_LEN));
+    Err(sys_error("EEXIST", "file already exists", &path, syscall))
+}
+
+/// Creates a directory only the owner can access and returns its path.
+pub fn mkdtemp_sync(prefix: &str) -> io::Result<String> {
+    if !is_directory(&path_dirname(prefix)) || is_virtual(&path_dirname(prefix)) {
+        let message = "no such file or directory";
+        return Err(sys_error("ENOENT", message, prefix, "mkdtemp"));
+    }
+    create_temp(prefix, "mkdtemp", |path| mkdir_exclusive(path, 0o700)).map(|(path, _)| path)
+}
+
+/// Creates and opens a file only the owner can access, like `mkstemp(3)`.
+/// Returns its path and a read-write descriptor.
+pub fn mkstemp_sync(prefix: &str) -> io::Result<(String, usize)> {
+    if !is_directory(&path_dirname(prefix)) || is_virtual(&path_dirname(prefix)) {
+        let message = "no such file or directory";
+        return Err(sys_error("ENOENT", message, prefix, "mkstemp"));
+    }
+    let flags = OpenFlags(OpenFlags::RDWR | OpenFlags::CREAT | OpenFlags::EXCL);
+    create_temp(prefix, "mkstemp", |path| open_sync(path, flags, Some(0o600)))
 }
 
-pub fn mkdtemp_sync(prefix: &str) -> String {
-    let path = format!("{}{}", prefix, rand_string());
-    mkdir_sync(&path, false, DEFAULT_PERM_DIR).unwrap();
-    path
+/// Creates a named pipe. The node is an empty file on disk typed S_IFIFO, the
+/// data written to it never touches the disk.
+pub fn mkfifo_sync(path: &str, mode: i32) -> io::Result<()> {
+    cwd::check(path)?;
+    if exists_sync_no_follow(path) {
+        return Err(sys_error("EEXIST", "file already exists", path, "mkfifo"));
+    }
+    if !is_directory(&path_dirname(path)) || is_virtual(&path_dirname(path)) {
+        return Err(sys_error("ENOENT", "no such file or directory", path, "mkfifo"));
+    }
+    let handle = FileHandle::open(path, Some("wx"), Some(mode))
+        .ok_or_else(|| errno("EIO", "i/o error, mkfifo", Some(path), None))?;
+    drop(handle);
+    let c_path = c_string(path)?;
+    let q = AttrQueryHandle::new(path);
+    unsafe {
+        (*q.0).mode = (S_IFIFO | sanitize_permissions(apply_umask(mode))) as i32;
+        lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+    }
+    Ok(())
 }
 
 pub fn readdir_sync(path: &str) -> Vec<Dirent> {
     let mut res = vec![];
-    let handle = DirHandle::open(path);
+    if path == DEV_DIR {
+        for device in Device::ALL.iter() {
+            res.push(Dirent::new(&device.path()));
+        }
+        return res;
+    }
+    if let Some(node) = proc_node(path) {
+        for name in node.children(&open_fds()) {
+            res.push(Dirent::new(&format!("{}/{}", node_path(node), name)));
+        }
+        return res;
+    }
+    let handle = DirHandle::open(&names::resolve(path));
     if handle.is_none() {
         return res;
     }
@@ -1120,12 +2383,23 @@ fn write_file_sync_no_follow(
     data: &[u8],
     flags: Option<&str>,
     mode: Option<i32>,
-) -> Option<()> {
+) -> io::Result<()> {
     let flags = Some(flags.unwrap_or("w"));
     let mode = Some(mode.unwrap_or(DEFAULT_PERM_FILE));
-    let mut handle = FileHandle::open(path, flags, mode)?;
+    let mut handle = FileHandle::open(path, flags, mode).ok_or_else(|| open_error(path))?;
     handle.write(data, None, None, None);
-    handle.sync() // ??
+    handle.sync().ok_or_else(|| sys_error("EIO", "i/o error", path, "write"))
+}
+
+/// Why `FileHandle::open` refused `path`, worded as `open_sync` would.
+fn open_error(path: &str) -> io::Error {
+    if compressed_busy(path, true) {
+        sys_error("EBUSY", "resource busy or locked", path, "open")
+    } else if is_directory(path) {
+        sys_error("EISDIR", "illegal operation on a directory", path, "open")
+    } else {
+        sys_error("ENOENT", "no such file or directory", path, "open")
+    }
 }
 
 pub fn write_file_sync(
@@ -1133,8 +2407,13 @@ pub fn write_file_sync(
     data: &[u8],
     flags: Option<&str>,
     mode: Option<i32>,
-) -> Option<()> {
+) -> io::Result<()> {
+    cwd::check(path)?;
     let path = follow_link(&path, None);
+    if let Some(device) = Device::from_path(&path) {
+        device.write(data);
+        return Ok(());
+    }
     write_file_sync_no_follow(&path, data, flags, mode)
 }
 
@@ -1154,9 +2433,23 @@ fn read_file_sync_no_follow(path: &str) -> Option<Vec<u8>> {
     }
 }
 
-pub fn read_file_sync(path: &str) -> Option<Vec<u8>> {
+pub fn read_file_sync(path: &str) -> io::Result<Vec<u8>> {
+    cwd::check(path)?;
     let path = follow_link(&path, None);
-    read_file_sync_no_follow(&path)
+    if let Some(device) = Device::from_path(&path) {
+        // endless devices would never reach end of file, hand out one block
+        let mut data = vec![0; unsafe { lfs::lfs_sys_get_block_size() } as usize];
+        let len = device.read(&mut data);
+        data.truncate(len);
+        return Ok(data);
+    }
+    if let Some(node) = proc_node(&path) {
+        return proc_read(node).ok_or_else(|| open_error(&path));
+    }
+    if is_directory(&path) {
+        return Err(sys_error("EISDIR", "illegal operation on a directory", &path, "read"));
+    }
+    read_file_sync_no_follow(&path).ok_or_else(|| open_error(&path))
 }
 
 fn append_file_sync_no_follow(
@@ -1164,11 +2457,10 @@ fn append_file_sync_no_follow(
     data: &[u8],
     flags: Option<&str>,
     mode: Option<i32>,
-) -> Option<()> {
+) -> io::Result<()> {
     let flags = Some(flags.unwrap_or("a"));
-    let mut handle = FileHandle::open(path, flags, mode)?;
-    handle.append(data, None);
-    Some(())
+    let mut handle = FileHandle::open(path, flags, mode).ok_or_else(|| open_error(path))?;
+    handle.append(data, None)
 }
 
 pub fn append_file_sync(
@@ -1176,36 +2468,135 @@ pub fn append_file_sync(
     data: &[u8],
     flags: Option<&str>,
     mode: Option<i32>,
-) -> Option<()> {
+) -> io::Result<()> {
+    cwd::check(path)?;
     let path = follow_link(&path, None);
+    if let Some(device) = Device::from_path(&path) {
+        device.write(data);
+        return Ok(());
+    }
     append_file_sync_no_follow(&path, data, flags, mode)
 }
 
-pub fn statfs_sync(path: &str, dump: Option<bool>) -> StatFs {
+/// Reports on the mount holding `path`: the LittleFS volume, or the virtual
+/// /dev and /proc trees that only live in memory.
+pub fn statfs_sync(path: &str) -> io::Result<StatFs> {
+    cwd::check(path)?;
+    if !exists_sync(path) {
+        return Err(sys_error("ENOENT", "no such file or directory", path, "statfs"));
+    }
+    let path = follow_link(path, None);
+    let bsize = unsafe { lfs::lfs_sys_get_block_size() } as usize;
+    if path == DEV_DIR || is_device(&path) {
+        let files = readdir_sync(DEV_DIR).len() + 1;
+        return Ok(StatFs::empty(TMPFS_MAGIC, bsize, files));
+    }
+    if !matches!(proc_node(&path), None | Some(ProcNode::Fd(..))) {
+        return Ok(StatFs::empty(PROC_SUPER_MAGIC, bsize, 0));
+    }
     let stat = StatHandle::new();
-    let dump = dump.unwrap_or(false);
-    StatFs {
-        bsize: unsafe { (*stat.0).bsize as usize },
-        blocks: unsafe { (*stat.0).blocks as usize },
-        bfree: unsafe { (*stat.0).bfree as usize },
-        bavail: unsafe { (*stat.0).bavail as usize },
-        files: unsafe { (*stat.0).files as usize },
-        ffree: unsafe { (*stat.0).ffree as usize },
-        dirs: unsafe { (*stat.0).dirs as usize },
-        json: if dump {
-            Some(json_path(path).to_string())
-        } else {
-            None
-        },
+    let (used, bfree) = unsafe { ((*stat.0).files + (*stat.0).dirs, (*stat.0).bfree) };
+    // LittleFS has no inode table. Nodes too large to be inlined in their
+    // directory take at least a block, so the free blocks bound what is left.
+    Ok(StatFs {
+        kind: unsafe { (*stat.0).type_ },
+        bsize: unsafe { (*stat.0).bsize },
+        blocks: unsafe { (*stat.0).blocks },
+        bfree,
+        bavail: unsafe { (*stat.0).bavail },
+        files: used + bfree,
+        ffree: bfree,
+    })
+}
+
+/// Bytes a node takes: its size as read back when `apparent`, otherwise the
+/// blocks it holds. LittleFS inlines files of up to an eighth of a block in
+/// the metadata of their directory, and every directory has a metadata pair.
+fn node_usage(path: &str, is_dir: bool, apparent: bool) -> usize {
+    let bsize = unsafe { lfs::lfs_sys_get_block_size() } as usize;
+    if is_dir {
+        return if apparent { 0 } else { 2 * bsize };
+    }
+    if apparent {
+        if let Some((_, size)) = Compression::of(path) {
+            return size;
+        }
+    }
+    let info = InfoHandle::new();
+    let c_path = match c_string(path) {
+        Ok(c_path) => c_path,
+        Err(_) => return 0,
+    };
+    if unsafe { lfs::lfs_stat(disk(), c_path.as_ptr(), info.0) } != lfs::lfs_error_LFS_ERR_OK {
+        return 0;
+    }
+    let size = unsafe { (*info.0).size } as usize;
+    if apparent {
+        size
+    } else if size <= bsize / 8 {
+        0
+    } else {
+        (size + bsize - 1) / bsize * bsize
     }
 }
 
-pub fn chmod_sync(path: &str, perm: i32) -> Option<()> {
+fn du_walk(
+    path: &str,
+    is_dir: bool,
+    apparent: bool,
+    depth: Option<usize>,
+    level: usize,
+    out: &mut Vec<DiskUsage>,
+) -> usize {
+    let mut size = node_usage(path, is_dir, apparent);
+    if is_dir {
+        for (child, is_dir) in children_raw(path) {
+            size += du_walk(&child, is_dir, apparent, depth, level + 1, out);
+        }
+    }
+    if level == 0 || is_dir && depth.map_or(true, |depth| level <= depth) {
+        out.push(DiskUsage {
+            path: path.to_string(),
+            size,
+        });
+    }
+    size
+}
+
+/// Disk usage of `path` and every directory under it, children before their
+/// parents like `du`. `depth` limits the directories listed (not counted) to
+/// that many levels below `path`. Symbolic links are not followed.
+pub fn du_sync(path: &str, apparent: bool, depth: Option<usize>) -> io::Result<Vec<DiskUsage>> {
+    cwd::check(path)?;
+    if !exists_sync_no_follow(path) && !is_virtual(path) {
+        return Err(sys_error("ENOENT", "no such file or directory", path, "lstat"));
+    }
+    let mut out = vec![];
+    if is_virtual(path) {
+        out.push(DiskUsage {
+            path: path.to_string(),
+            size: 0,
+        });
+    } else {
+        du_walk(path, is_directory(path), apparent, depth, 0, &mut out);
+    }
+    Ok(out)
+}
+
+pub fn chmod_sync(path: &str, perm: i32) -> io::Result<()> {
+    cwd::check(path)?;
+    if !exists_sync_no_follow(path) {
+        return Err(sys_error("ENOENT", "no such file or directory", path, "chmod"));
+    }
     let perm = sanitize_permissions(perm);
-    let c_path = CString::new(path).ok()?;
+    let c_path = c_string(path)?;
     let q = AttrQueryHandle::new(path);
     unsafe {
-        (*q.0).mode = if is_directory(path) {
+        // keep special file types, only plain nodes have their type derived
+        let kind = (*q.0).mode as u32 & S_IFMT;
+        (*q.0).mode = if kind == S_IFIFO {
+            S_IFIFO | perm
+        } else if is_directory(path) {
             S_IFDIR | perm
         } else {
             S_IFREG | perm
@@ -1213,11 +2604,16 @@ pub fn chmod_sync(path: &str, perm: i32) -> Option<()> {
         lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
     }
     Touch::ctime(path, None);
-    Some(())
+    changes::record(path, Change::Modified);
+    Ok(())
 }
 
-pub fn chown_sync(path: &str, uid: i32, gid: i32) -> Option<()> {
-    let c_path = CString::new(path).ok()?;
+pub fn chown_sync(path: &str, uid: i32, gid: i32) -> io::Result<()> {
+    cwd::check(path)?;
+    if !exists_sync_no_follow(path) {
+        return Err(sys_error("ENOENT", "no such file or directory", path, "chown"));
+    }
+    let c_path = c_string(path)?;
     let q = AttrQueryHandle::new(path);
     unsafe {
         (*q.0).uid = uid;
@@ -1225,130 +2621,139 @@ pub fn chown_sync(path: &str, uid: i32, gid: i32) -> Option<()> {
         lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
     }
     Touch::ctime(path, None);
-    Some(())
+    changes::record(path, Change::Modified);
+    Ok(())
 }
 
-pub fn truncate_sync(path: &str, size: usize) -> Option<()> {
-    let mut handle = FileHandle::open(path, None, None)?;
-    handle.truncate(size as u32);
-    Some(())
+pub fn truncate_sync(path: &str, size: usize) -> io::Result<()> {
+    cwd::check(path)?;
+    let path = &follow_link(path, None);
+    if is_directory(path) {
+        return Err(sys_error("EISDIR", "illegal operation on a directory", path, "truncate"));
+    }
+    if is_virtual(path) {
+        return Err(sys_error("EACCES", "permission denied", path, "truncate"));
+    }
+    let mut handle = FileHandle::open(path, Some("r+"), None)
+        .ok_or_else(|| sys_error("ENOENT", "no such file or directory", path, "truncate"))?;
+    resize(&mut handle, size, "truncate")
 }
 
-pub fn utimes_sync(path: &str, atime: f64, mtime: f64) -> Option<()> {
+/// Turns compression of `path` on or off. Files are rewritten on the spot,
+/// directories only pass it on to what gets created in them from now on.
+pub fn set_compression_sync(path: &str, compression: Option<Compression>) -> io::Result<()> {
+    cwd::check(path)?;
+    let path = &follow_link(path, None);
+    if !exists_sync_no_follow(path) {
+        return Err(sys_error("ENOENT", "no such file or directory", path, "chattr"));
+    }
+    if is_virtual(path) || is_fifo(path) {
+        return Err(sys_error("EOPNOTSUPP", "operation not supported", path, "chattr"));
+    }
+    if is_open(path) {
+        return Err(sys_error("EBUSY", "resource busy or locked", path, "chattr"));
+    }
+    if is_directory(path) {
+        Compression::store(path, compression.map(|compression| (compression, 0)));
+    } else if Compression::of(path).map(|(current, _)| current) != compression {
+        let data = read_raw(path).ok_or_else(|| sys_error("EIO", "i/o error", path, "chattr"))?;
+        let size = data.len();
+        let stored = match compression {
+            Some(compression) => compression.encode(&data),
+            None => data,
+        };
+        reserve(stored.len().saturating_sub(size), path, "chattr")?;
+        write_raw(path, &stored).ok_or_else(|| sys_error("EIO", "i/o error", path, "chattr"))?;
+        Compression::store(path, compression.map(|compression| (compression, size)));
+    }
+    Touch::ctime(path, None);
+    changes::record(path, Change::Modified);
+    Ok(())
+}
+
+pub fn compression_sync(path: &str) -> Option<Compression> {
+    let path = &follow_link(path, None);
+    Compression::of(path).map(|(compression, _)| compression)
+}
+
+pub fn utimes_sync(path: &str, atime: Timestamp, mtime: Timestamp) -> io::Result<()> {
+    cwd::check(path)?;
     let path = &follow_link(&path, None);
-    let atime = atime * 1000.0;
-    let mtime = mtime * 1000.0;
-    if exists_sync(path) {
-        Touch::atime(path, Some(atime));
-        Touch::mtime(path, Some(mtime));
-        Some(())
-    } else {
-        None
+    if !exists_sync(path) {
+        return Err(sys_error("ENOENT", "no such file or directory", path, "utime"));
     }
+    Touch::times(path, atime, mtime);
+    changes::record(path, Change::Modified);
+    Ok(())
 }
 
-pub fn unlink_sync(path: &str, force: Option<bool>) -> Result<JsValue, JsValue> {
+pub fn unlink_sync(path: &str, force: Option<bool>) -> io::Result<()> {
+    cwd::check(path)?;
     let force = force.unwrap_or(false);
     let path = &follow_link(path, None);
     if is_directory(path) {
-        let err: JsValue = JsError::new("EISDIR: illegal operation on a directory, unlink").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EISDIR"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        return Err(err);
+        return Err(errno("EISDIR", "illegal operation on a directory, unlink", Some(path), None));
     }
     if !force && is_open(path) {
-        let err: JsValue = JsError::new("EBUSY: resource busy or locked, unlink").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EBUSY"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        return Err(err);
+        return Err(errno("EBUSY", "resource busy or locked, unlink", Some(path), None));
     }
     let disk = disk();
-    let c_path = CString::new(path.clone()).unwrap();
+    let c_path = c_string(path)?;
     unsafe {
         lfs::lfs_remove(disk, c_path.as_ptr());
+        changes::record(path, Change::Removed);
         if LFS_SYS_HARD_LINKS.contains_key(path) {
             for link in LFS_SYS_HARD_LINKS.get(path).unwrap() {
-                let c_link = CString::new(link.clone()).unwrap();
+                let c_link = c_string(link)?;
                 lfs::lfs_remove(disk, c_link.as_ptr());
+                changes::record(link, Change::Removed);
             }
             LFS_SYS_HARD_LINKS.remove(path);
         }
     }
 
-    Ok(JsValue::undefined())
+    Ok(())
 }
 
-pub fn rmdir_sync(path: &str, force: Option<bool>) -> Result<JsValue, JsValue> {
+pub fn rmdir_sync(path: &str, force: Option<bool>) -> io::Result<()> {
+    cwd::check(path)?;
     let force = force.unwrap_or(false);
     let path = &follow_link(path, None);
     if is_file(path) {
-        let err: JsValue = JsError::new("ENOTDIR: not a directory, rmdir").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("ENOTDIR"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        return Err(err);
+        return Err(errno("ENOTDIR", "not a directory, rmdir", Some(path), None));
     }
     if !force && is_open(path) {
-        let err: JsValue = JsError::new("EBUSY: resource busy or locked, rmdir").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EBUSY"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        return Err(err);
+        return Err(errno("EBUSY", "resource busy or locked, rmdir", Some(path), None));
     }
     let disk = disk();
-    let c_path = CString::new(path.clone()).unwrap();
+    let c_path = c_string(path)?;
     let res = unsafe { lfs::lfs_remove(disk, c_path.as_ptr()) };
     if res == lfs::lfs_error_LFS_ERR_NOTEMPTY {
-        let err: JsValue = JsError::new("ENOTEMPTY: directory not empty, rmdir").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("ENOTEMPTY"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        return Err(err);
+        return Err(errno("ENOTEMPTY", "directory not empty, rmdir", Some(path), None));
     }
+    changes::record(path, Change::Removed);
     unsafe {
         if LFS_SYS_HARD_LINKS.contains_key(path) {
             for link in LFS_SYS_HARD_LINKS.get(path).unwrap() {
-                let c_link = CString::new(link.clone()).unwrap();
+                let c_link = c_string(link)?;
                 lfs::lfs_remove(disk, c_link.as_ptr());
+                changes::record(link, Change::Removed);
             }
             LFS_SYS_HARD_LINKS.remove(path);
         }
     }
-    Ok(JsValue::undefined())
+    Ok(())
 }
 
-pub fn rm_sync(path: &str, recursive: bool, force: bool) -> Result<JsValue, JsValue> {
+pub fn rm_sync(path: &str, recursive: bool, force: bool) -> io::Result<()> {
+    cwd::check(path)?;
     // todo: permission checks
     // todo: force is not fully implemented, once complete, should bypass thread locking
     if is_file(path) {
         return unlink_sync(path, Some(force));
     }
     if !force && is_open(path) {
-        let err: JsValue = JsError::new("EBUSY: resource busy or locked, unlink").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EBUSY"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        return Err(err);
+        return Err(errno("EBUSY", "resource busy or locked, unlink", Some(path), None));
     }
     if !recursive {
         return rmdir_sync(path, Some(force));
@@ -1357,80 +2762,59 @@ pub fn rm_sync(path: &str, recursive: bool, force: bool) -> Result<JsValue, JsVa
         rm_sync(&format!("{}/{}", path, ent.name), true, force)?;
     }
     rm_sync(path, false, force)?;
-    Ok(JsValue::undefined())
+    Ok(())
 }
 
-pub fn rename_sync(old_path: &str, new_path: &str) -> Option<bool> {
+pub fn rename_sync(old_path: &str, new_path: &str) -> io::Result<()> {
+    cwd::check(old_path)?;
+    cwd::check(new_path)?;
     if is_open(old_path) || is_open(new_path) {
-        None
-    } else {
-        let disk = disk();
-        let c_old_path = CString::new(old_path).unwrap();
-        let c_new_path = CString::new(new_path).unwrap();
-        let res = unsafe { lfs::lfs_rename(disk, c_old_path.as_ptr(), c_new_path.as_ptr()) };
-        Touch::ctime(new_path, None);
-        Some(res == lfs::lfs_error_LFS_ERR_OK)
+        return Err(sys_error("EBUSY", "resource busy or locked", old_path, "rename"));
+    }
+    let disk = disk();
+    let c_old_path = c_string(old_path)?;
+    let c_new_path = c_string(new_path)?;
+    let replaced = exists_sync_no_follow(new_path);
+    let res = unsafe { lfs::lfs_rename(disk, c_old_path.as_ptr(), c_new_path.as_ptr()) };
+    lfs_result(res, old_path, "rename")?;
+    Touch::ctime(new_path, None);
+    record_rename(old_path, new_path, replaced);
+    Ok(())
+}
+
+/// Records a rename. What was at `new_path` is gone, children included, so
+/// it is recorded as removed and created again rather than as modified.
+fn record_rename(old_path: &str, new_path: &str, replaced: bool) {
+    changes::record(old_path, Change::Removed);
+    if replaced {
+        changes::record(new_path, Change::Removed);
     }
+    changes::record(new_path, Change::Created);
 }
 
-pub fn copy_file_sync(src: &str, dst: &str, excl: bool) -> Result<JsValue, JsValue> {
+pub fn copy_file_sync(src: &str, dst: &str, excl: bool) -> io::Result<()> {
+    cwd::check(src)?;
+    cwd::check(dst)?;
     let src = &follow_link(src, None);
     let dst = &follow_link(dst, None);
     if is_open(src) {
-        let err: JsValue = JsError::new("EBUSY: resource busy or locked, copyFileSync").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EBUSY"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(src))?;
-        return Err(err);
+        return Err(errno("EBUSY", "resource busy or locked, copyFileSync", Some(src), None));
     }
     if is_open(dst) {
-        let err: JsValue = JsError::new("EBUSY: resource busy or locked, copyFileSync").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EBUSY"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(dst))?;
-        return Err(err);
+        return Err(errno("EBUSY", "resource busy or locked, copyFileSync", Some(dst), None));
     }
     if excl && exists_sync(dst) {
-        let err: JsValue = JsError::new("EEXIST: file already exists, copyFileSync").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EEXIST"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(dst))?;
-        return Err(err);
+        return Err(errno("EEXIST", "file already exists, copyFileSync", Some(dst), None));
     }
-    let src = read_file_sync(src).unwrap();
-    write_file_sync(dst, &src, None, None);
-    Ok(JsValue::undefined())
+    let src = read_file_sync(src)?;
+    write_file_sync(dst, &src, None, None)
 }
 
-pub fn access_sync(path: &str, mode: Option<i32>) -> Result<JsValue, JsValue> {
+pub fn access_sync(path: &str, mode: Option<i32>) -> io::Result<()> {
+    cwd::check(path)?;
     let path = &follow_link(path, None);
     if !exists_sync(path) {
-        let err: JsValue = JsError::new(&format!(
-            "ENOENT: no such file or directory, access '{}'",
-            path
-        ))
-        .into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("ENOENT"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        Reflect::set(
-            &err,
-            &JsValue::from_str("syscall"),
-            &JsValue::from_str("access"),
-        )?;
-        return Err(err);
+        return Err(sys_error("ENOENT", "no such file or directory", path, "access"));
     }
     let R_OK = 4;
     let W_OK = 2;
@@ -1438,23 +2822,25 @@ pub fn access_sync(path: &str, mode: Option<i32>) -> Result<JsValue, JsValue> {
     let F_OK = 0;
     let mode = mode.unwrap_or(F_OK);
     let info = InfoHandle::new();
-    let c_path = CString::new(path.clone()).unwrap();
-    let mut res = unsafe { lfs::lfs_stat(disk(), c_path.as_ptr(), info.0) };
+    let c_path = c_string(path)?;
+    let virtual_node = is_virtual(path);
+    let mut res = if virtual_node {
+        lfs::lfs_error_LFS_ERR_OK
+    } else {
+        unsafe { lfs::lfs_stat(disk(), c_path.as_ptr(), info.0) }
+    };
     if res != lfs::lfs_error_LFS_ERR_OK {
-        let err: JsValue =
-            JsError::new("EFAULT: bad address in system call argument, access").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EFAULT"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        return Err(err);
+        let message = "bad address in system call argument, access";
+        return Err(errno("EFAULT", message, Some(path), None));
     }
     res = 0;
     res |= F_OK;
-    let q = AttrQueryHandle::new(path);
-    let test = unsafe { (*q.0).mode };
+    let test = if virtual_node {
+        stat_sync(path)?.mode as i32
+    } else {
+        let q = AttrQueryHandle::new(path);
+        unsafe { (*q.0).mode }
+    };
     // mode is just regular posix file permissions
     // https://en.wikipedia.org/wiki/File_system_permissions#POSIX_permissions
     if test & 0o400 != 0 {
@@ -1467,80 +2853,176 @@ pub fn access_sync(path: &str, mode: Option<i32>) -> Result<JsValue, JsValue> {
         res |= X_OK;
     }
     if res & mode == mode {
-        Ok(JsValue::undefined())
+        Ok(())
     } else {
-        let err: JsValue = JsError::new("EACCES: permission denied, access").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EACCES"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        Err(err)
+        Err(errno("EACCES", "permission denied, access", Some(path), None))
     }
 }
 
-pub fn readlink_sync(path: &str) -> Result<JsValue, JsValue> {
+pub fn readlink_sync(path: &str) -> io::Result<String> {
+    cwd::check(path)?;
     // https://stackoverflow.com/a/1189582/388751
-    if path.starts_with("/proc/self/fd/") {
-        let fd = path
-            .split("/")
-            .last()
-            .unwrap()
-            .parse::<usize>()
-            .unwrap_or(0);
-        return if let Some(handle) = lookup_by_fd(fd) {
-            Ok(JsValue::from_str(&match handle {
-                Either::Left(file) => file.path.clone(),
-                Either::Right(dir) => dir.path.clone(),
-            }))
+    if path == format!("{}/self", PROC_DIR) {
+        return Ok(node_path(ProcNode::Worker(crate::worker_id())));
+    }
+    if let Some(ProcNode::Fd(_, fd)) = ProcNode::from_path(path) {
+        return if let Some(target) = path_by_fd(fd) {
+            Ok(target)
         } else {
-            let err: JsValue = JsError::new("EBADF: bad file descriptor, readlink").into();
-            Reflect::set(
-                &err,
-                &JsValue::from_str("code"),
-                &JsValue::from_str("EBADF"),
-            )?;
-            Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-            Err(err)
+            Err(errno("EBADF", "bad file descriptor, readlink", Some(path), None))
         };
     }
     if !is_symlink(path) {
-        let err: JsValue = JsError::new("EINVAL: invalid argument, readlink").into();
-        Reflect::set(
-            &err,
-            &JsValue::from_str("code"),
-            &JsValue::from_str("EINVAL"),
-        )?;
-        Reflect::set(&err, &JsValue::from_str("path"), &JsValue::from_str(path))?;
-        return Err(err);
+        return Err(errno("EINVAL", "invalid argument, readlink", Some(path), None));
     }
-    let path = realpath_sync(path, None);
-    let content = read_file_sync(&path).unwrap();
-    let content = String::from_utf8(content).unwrap();
-    Ok(JsValue::from_str(&content))
+    let content = read_file_sync_no_follow(path).unwrap_or_default();
+    Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+pub fn checksum_sync(
+    path: &str,
+    algorithm: ChecksumAlgorithm,
+    cache: bool,
+) -> io::Result<String> {
+    cwd::check(path)?;
+    let path = &follow_link(path, None);
+    if !exists_sync(path) {
+        return Err(sys_error("ENOENT", "no such file or directory", path, "checksum"));
+    }
+    if is_directory(path) {
+        let message = "illegal operation on a directory, checksum";
+        return Err(errno("EISDIR", message, Some(path), Some("checksum")));
+    }
+    if cache {
+        if let Some(digest) = Digest::cached(path, algorithm) {
+            return Ok(hex_string(&digest));
+        }
+    }
+    let handle = FileHandle::open(path, None, None)
+        .ok_or_else(|| errno("EBUSY", "resource busy or locked, checksum", Some(path), None))?;
+    let mut crc = 0xffff_ffff_u32;
+    let mut sha = Sha256::new();
+    let mut buf = vec![0; 4096];
+    while let Some(len) = handle.read(&mut buf, None, None, None) {
+        if len == 0 {
+            break;
+        }
+        match algorithm {
+            ChecksumAlgorithm::Crc32 => {
+                crc = unsafe { lfs::lfs_crc(crc, buf.as_ptr() as *const c_void, len) };
+            }
+            ChecksumAlgorithm::Sha256 => sha.update(&buf[..len]),
+        }
+    }
+    drop(handle);
+    let digest = match algorithm {
+        // littlefs' crc is the reflected CRC-32 without the final xor
+        ChecksumAlgorithm::Crc32 => (!crc).to_be_bytes().to_vec(),
+        ChecksumAlgorithm::Sha256 => sha.finalize().to_vec(),
+    };
+    if cache {
+        Digest::store(path, algorithm, &digest);
+    }
+    Ok(hex_string(&digest))
 }
 
 pub fn realpath_sync(path: &str, attempt: Option<usize>) -> String {
     let path = &follow_link(path, None);
     let attempt = attempt.unwrap_or(0);
     assert!(attempt < 100); // prevent infinite recursion by death.
+    if let Some(ProcNode::Fd(_, fd)) = proc_node(path) {
+        if let Some(target) = path_by_fd(fd) {
+            return realpath_sync(&target, Some(attempt + 1));
+        }
+    }
     if is_symlink(path) {
         if let Some(followed) = read_file_sync_no_follow(path) {
-            return realpath_sync(
-                String::from_utf8(followed).unwrap().as_str(),
-                Some(attempt + 1),
-            );
+            let target = symlink_target(path, &String::from_utf8_lossy(&followed));
+            return realpath_sync(&target, Some(attempt + 1));
         }
     }
     path.to_string()
 }
 
-pub fn stat_sync(path: &str) -> Option<NodeStats> {
+/// Canonical path of a /proc node, `self` resolved to the worker id.
+fn node_path(node: ProcNode) -> String {
+    match node {
+        ProcNode::Root => PROC_DIR.to_string(),
+        ProcNode::Mounts => format!("{}/mounts", PROC_DIR),
+        ProcNode::Meminfo => format!("{}/meminfo", PROC_DIR),
+        ProcNode::Locks => format!("{}/locks", PROC_DIR),
+        ProcNode::Worker(id) => format!("{}/{}", PROC_DIR, id),
+        ProcNode::Status(id) => format!("{}/{}/status", PROC_DIR, id),
+        ProcNode::FdDir(id) => format!("{}/{}/fd", PROC_DIR, id),
+        ProcNode::Fd(id, fd) => format!("{}/{}/fd/{}", PROC_DIR, id, fd),
+    }
+}
+
+/// Stats of nodes that only exist in memory, such as devices and their parent.
+fn virtual_stat(mode: u32, rdev: usize) -> NodeStats {
+    let time = BUILD_TIME.parse::<f64>().unwrap();
+    let ns = Timestamp::from_ms(time).as_nanos() as i64;
+    NodeStats {
+        dev: unsafe { lfs::lfs_sys_get_device_address() },
+        ino: 0.0,
+        mode: mode as u16,
+        nlink: 1,
+        uid: 0,
+        gid: 0,
+        rdev,
+        size: 0,
+        blksize: unsafe { lfs::lfs_sys_get_block_size() as usize },
+        blocks: 0,
+        atimeMs: time,
+        mtimeMs: time,
+        ctimeMs: time,
+        birthtimeMs: time,
+        atimeNs: ns,
+        mtimeNs: ns,
+        ctimeNs: ns,
+        birthtimeNs: ns,
+    }
+}
+
+fn device_stat(device: Device) -> NodeStats {
+    virtual_stat(S_IFCHR | DEFAULT_PERM_FILE as u32, device.rdev())
+}
+
+pub fn stat_sync(path: &str) -> io::Result<NodeStats> {
+    cwd::check(path)?;
     let path = &follow_link(path, None);
+    let enoent = || sys_error("ENOENT", "no such file or directory", path, "stat");
+    if let Some(device) = Device::from_path(path) {
+        return Ok(device_stat(device));
+    }
+    if path == DEV_DIR {
+        return Ok(virtual_stat(S_IFDIR | 0o755, 0));
+    }
+    if let Some(node) = proc_node(path) {
+        return match node {
+            ProcNode::Fd(_, fd) => stat_sync(&path_by_fd(fd).ok_or_else(enoent)?),
+            node if node.is_dir() => Ok(virtual_stat(S_IFDIR | 0o555, 0)),
+            node => {
+                let mut stat = virtual_stat(S_IFREG | 0o444, 0);
+                stat.size = proc_read(node).map_or(0, |data| data.len());
+                Ok(stat)
+            }
+        };
+    }
+    if !exists_sync_no_follow(path) {
+        return Err(enoent());
+    }
     let q = AttrQueryHandle::new(path);
-    Touch::atime(path, None);
-    Some(unsafe {
+    Touch::access(path);
+    let [atime, mtime, ctime, birthtime] = unsafe {
+        [
+            Timestamp::load((*q.0).atime, (*q.0).atime_ns),
+            Timestamp::load((*q.0).mtime, (*q.0).mtime_ns),
+            Timestamp::load((*q.0).ctime, (*q.0).ctime_ns),
+            Timestamp::load((*q.0).birthtime, (*q.0).birthtime_ns),
+        ]
+    };
+    Ok(unsafe {
         NodeStats {
             dev: lfs::lfs_sys_get_device_address(),
             ino: (*q.0).ino as f64,
@@ -1549,44 +3031,766 @@ pub fn stat_sync(path: &str) -> Option<NodeStats> {
             uid: (*q.0).uid,
             gid: (*q.0).gid,
             rdev: 0,
-            size: (*q.0).size,
+            size: match Compression::of(path) {
+                Some((_, size)) if !is_directory(path) => size,
+                _ => (*q.0).size,
+            },
             blksize: lfs::lfs_sys_get_block_size() as usize,
             blocks: lfs::lfs_sys_get_block_size() as usize,
-            atimeMs: (*q.0).atime,
-            mtimeMs: (*q.0).mtime,
-            ctimeMs: (*q.0).ctime,
-            birthtimeMs: (*q.0).birthtime,
+            atimeMs: atime.as_ms(),
+            mtimeMs: mtime.as_ms(),
+            ctimeMs: ctime.as_ms(),
+            birthtimeMs: birthtime.as_ms(),
+            atimeNs: atime.as_nanos() as i64,
+            mtimeNs: mtime.as_nanos() as i64,
+            ctimeNs: ctime.as_nanos() as i64,
+            birthtimeNs: birthtime.as_nanos() as i64,
         }
     })
 }
 
-pub fn lchmod_sync(path: &str, mode: i32) -> Option<()> {
-    let path = realpath_sync(path, None);
+/// The node the l* variants act on: a symlink itself rather than its target.
+/// Anything else resolves as it does for the following variants.
+fn symlink_or_target(path: &str) -> (String, bool) {
+    let link = path_normalize(path);
+    if !is_virtual(&link) && is_symlink(&link) {
+        (link, true)
+    } else {
+        (realpath_sync(path, None), false)
+    }
+}
+
+pub fn lchmod_sync(path: &str, mode: i32) -> io::Result<()> {
+    cwd::check(path)?;
+    let (path, _) = symlink_or_target(path);
     chmod_sync(&path, mode)
 }
 
-pub fn lchown_sync(path: &str, uid: i32, gid: i32) -> Option<()> {
-    let path = realpath_sync(path, None);
+pub fn lchown_sync(path: &str, uid: i32, gid: i32) -> io::Result<()> {
+    cwd::check(path)?;
+    let (path, _) = symlink_or_target(path);
     chown_sync(&path, uid, gid)
 }
 
-pub fn lutimes_sync(path: &str, atime: f64, mtime: f64) -> Option<()> {
-    let path = realpath_sync(path, None);
+pub fn lutimes_sync(path: &str, atime: Timestamp, mtime: Timestamp) -> io::Result<()> {
+    cwd::check(path)?;
+    let (path, _) = symlink_or_target(path);
     utimes_sync(&path, atime, mtime)
 }
 
-pub fn lstat_sync(path: &str) -> Option<NodeStats> {
-    let path = realpath_sync(path, None);
-    stat_sync(&path)
+pub fn lstat_sync(path: &str) -> io::Result<NodeStats> {
+    cwd::check(path)?;
+    let (path, symlink) = symlink_or_target(path);
+    let mut stat = stat_sync(&path)?;
+    if symlink {
+        // the node keeps the mode of a regular file, its contents the target
+        stat.mode = (S_IFLNK | (stat.mode as u32 & !S_IFMT)) as u16;
+    }
+    Ok(stat)
+}
+
+// ------------------------------------------------------------ Consistency Check
+
+#[derive(Debug, Serialize)]
+pub struct FsckProblem {
+    pub kind: &'static str,
+    pub path: String,
+    pub detail: String,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FsckReport {
+    pub blocks: usize,
+    pub orphans: usize,
+    pub files: usize,
+    pub dirs: usize,
+    pub problems: Vec<FsckProblem>,
+}
+
+impl FsckReport {
+    fn report(&mut self, kind: &'static str, path: &str, detail: String, repaired: bool) {
+        self.problems.push(FsckProblem {
+            kind,
+            path: path.to_string(),
+            detail,
+            repaired,
+        });
+    }
+}
+
+/// Lists the children of `path` without going through the handle api, so that
+/// walking the tree does not allocate fds or touch access times.
+pub(super) fn children_raw(path: &str) -> Vec<(String, bool)> {
+    let c_path = match c_string(path) {
+        Ok(c_path) => c_path,
+        Err(_) => return vec![],
+    };
+    let disk = disk();
+    let dir = unsafe { lfs::lfs_sys_dir_new() };
+    let info = InfoHandle::new();
+    if unsafe { lfs::lfs_dir_open(disk, dir, c_path.as_ptr()) } != lfs::lfs_error_LFS_ERR_OK {
+        unsafe { lfs::lfs_sys_dir_free(dir) };
+        return vec![];
+    }
+    let mut children = vec![];
+    while unsafe { lfs::lfs_dir_read(disk, dir, info.0) } > 0 {
+        let name = unsafe { CStr::from_ptr((*info.0).name.as_ptr()) };
+        let name = name.to_str().unwrap();
+        if name == "." || name == ".." {
+            continue;
+        }
+        let is_dir = unsafe { (*info.0).type_ } == lfs::lfs_type_LFS_TYPE_DIR as u8;
+        let child = if path == "/" {
+            format!("/{}", name)
+        } else {
+            format!("{}/{}", path, name)
+        };
+        children.push((child, is_dir));
+    }
+    unsafe {
+        lfs::lfs_dir_close(disk, dir);
+        lfs::lfs_sys_dir_free(dir);
+    }
+    children
+}
+
+/// Lists every path under `path`, parents before their children.
+pub(super) fn walk_raw(path: &str, out: &mut Vec<(String, bool)>) {
+    for (child, is_dir) in children_raw(path) {
+        out.push((child.clone(), is_dir));
+        if is_dir {
+            walk_raw(&child, out);
+        }
+    }
+}
+
+/// A file opened without going through the handle api, so that reading it
+/// leaves its access time alone and writing it leaves its attributes to the
+/// caller. Contents are what is stored, compressed or not.
+pub(super) struct RawFile(*mut lfs::lfs_file_t);
+
+impl RawFile {
+    /// Opens `path` for reading, or creates or truncates it for writing.
+    pub(super) fn open(path: &str, write: bool) -> Option<RawFile> {
+        let file = RawFile(unsafe { lfs::lfs_sys_file_new() });
+        let c_path = c_string(path).ok()?;
+        let existed = write && exists_sync_no_follow(path);
+        let flags = if write {
+            (O_WRONLY | O_CREAT | O_TRUNC) as i32
+        } else {
+            O_RDONLY as i32
+        };
+        let res = unsafe { lfs::lfs_file_open(disk(), file.0, c_path.as_ptr(), flags) };
+        if res != lfs::lfs_error_LFS_ERR_OK {
+            unsafe { lfs::lfs_sys_file_free(file.0) };
+            std::mem::forget(file);
+            return None;
+        }
+        if write {
+            let change = if existed { Change::Modified } else { Change::Created };
+            changes::record(path, change);
+        }
+        Some(file)
+    }
+    pub(super) fn size(&self) -> usize {
+        unsafe { lfs::lfs_file_size(disk(), self.0) }.max(0) as usize
+    }
+    /// Fills `buf` as far as the file goes, 0 at its end.
+    pub(super) fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let len = unsafe {
+            lfs::lfs_file_read(
+                disk(),
+                self.0,
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len() as u32,
+            )
+        };
+        usize::try_from(len).ok()
+    }
+    pub(super) fn write(&mut self, data: &[u8]) -> Option<()> {
+        let written = unsafe {
+            lfs::lfs_file_write(
+                disk(),
+                self.0,
+                data.as_ptr() as *const c_void,
+                data.len() as u32,
+            )
+        };
+        if written as usize == data.len() {
+            Some(())
+        } else {
+            None
+        }
+    }
+    /// Closes the file, a write that cannot be committed fails here.
+    pub(super) fn close(self) -> Option<()> {
+        let res = unsafe { lfs::lfs_file_close(disk(), self.0) };
+        unsafe { lfs::lfs_sys_file_free(self.0) };
+        std::mem::forget(self);
+        if res == lfs::lfs_error_LFS_ERR_OK {
+            Some(())
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for RawFile {
+    fn drop(&mut self) {
+        unsafe {
+            lfs::lfs_file_close(disk(), self.0);
+            lfs::lfs_sys_file_free(self.0);
+        }
+    }
+}
+
+/// Reads a whole file without touching its access time.
+pub(super) fn read_raw(path: &str) -> Option<Vec<u8>> {
+    let mut file = RawFile::open(path, false)?;
+    let mut data = Vec::with_capacity(file.size());
+    let mut buf = vec![0u8; 1024];
+    loop {
+        match file.read(&mut buf) {
+            Some(0) | None => break,
+            Some(len) => data.extend_from_slice(&buf[..len]),
+        }
+    }
+    drop(file);
+    match Compression::of(path) {
+        Some((compression, size)) => compression.decode(&data, size),
+        None => Some(data),
+    }
+}
+
+/// Reads where a link file leads without touching its access time.
+fn fsck_link_target(path: &str) -> Option<String> {
+    let data = read_raw(path)?;
+    String::from_utf8(data).ok().map(|t| symlink_target(path, &t))
+}
+
+/// Creates or replaces a file with `data`, leaving its attributes to the caller.
+pub(super) fn write_raw(path: &str, data: &[u8]) -> Option<()> {
+    let mut file = RawFile::open(path, true)?;
+    file.write(data)?;
+    file.close()
+}
+
+/// Moves a node without touching its attributes.
+pub(super) fn rename_raw(old_path: &str, new_path: &str) -> Option<()> {
+    let c_old_path = c_string(old_path).ok()?;
+    let c_new_path = c_string(new_path).ok()?;
+    let replaced = exists_sync_no_follow(new_path);
+    let res = unsafe { lfs::lfs_rename(disk(), c_old_path.as_ptr(), c_new_path.as_ptr()) };
+    if res == lfs::lfs_error_LFS_ERR_OK {
+        record_rename(old_path, new_path, replaced);
+        unsafe { LFS_SYS_SPARES.remove(old_path) };
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// The hard links made to `path`.
+pub(super) fn hard_links(path: &str) -> Vec<String> {
+    unsafe { LFS_SYS_HARD_LINKS.get(path).cloned().unwrap_or_default() }
+}
+
+/// Drops the record of the hard links made to `path`, once they are gone.
+pub(super) fn forget_hard_links(path: &str) {
+    unsafe { LFS_SYS_HARD_LINKS.remove(path) };
+}
+
+/// Creates a directory, leaving its attributes to the caller.
+pub(super) fn mkdir_raw(path: &str) -> Option<()> {
+    let c_path = c_string(path).ok()?;
+    let res = unsafe { lfs::lfs_mkdir(disk(), c_path.as_ptr()) };
+    if res == lfs::lfs_error_LFS_ERR_OK {
+        changes::record(path, Change::Created);
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Removes a node and everything under it, along with the hard links to any
+/// of the removed files, like `unlink_sync` does.
+pub(super) fn remove_raw(path: &str) {
+    let mut nodes = vec![(path.to_string(), is_directory(path))];
+    walk_raw(path, &mut nodes);
+    let disk = disk();
+    for (node, _) in nodes.iter().rev() {
+        let c_node = match c_string(node) {
+            Ok(c_node) => c_node,
+            Err(_) => continue,
+        };
+        unsafe {
+            lfs::lfs_remove(disk, c_node.as_ptr());
+            changes::record(node, Change::Removed);
+            LFS_SYS_SPARES.remove(node);
+            if let Some(links) = LFS_SYS_HARD_LINKS.remove(node) {
+                for link in links {
+                    if let Ok(c_link) = c_string(&link) {
+                        lfs::lfs_remove(disk, c_link.as_ptr());
+                    }
+                    changes::record(&link, Change::Removed);
+                }
+            }
+            for links in LFS_SYS_HARD_LINKS.values_mut() {
+                links.retain(|link| link != node);
+            }
+        }
+    }
+}
+
+/// A free name next to `path`, hidden by a leading dot, to write a node that
+/// is then renamed over `path`. `tag` tells what it is for. Changes to the
+/// node are not tracked until `rename_raw` or `remove_raw` lets go of it.
+pub(super) fn spare_name(path: &str, tag: &str) -> String {
+    let dir = path_dirname(path);
+    let name = path_basename(path);
+    let spare = (0..)
+        .map(|n| format!("{}/.{}.{}-{}", dir.trim_end_matches('/'), name, tag, n))
+        .find(|spare| !is_spare(spare) && !exists_sync_no_follow(spare))
+        .unwrap();
+    unsafe { LFS_SYS_SPARES.insert(spare.clone()) };
+    spare
+}
+
+/// Whether `path` is a spare node in use, see `spare_name`.
+pub(super) fn is_spare(path: &str) -> bool {
+    unsafe { LFS_SYS_SPARES.contains(path) }
+}
+
+/// The `lfs_sys` attributes of a node, as stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct RawAttrs {
+    pub ino: i32,
+    pub mode: i32,
+    pub uid: i32,
+    pub gid: i32,
+    pub nlink: i32,
+    pub birthtime: Timestamp,
+    pub atime: Timestamp,
+    pub mtime: Timestamp,
+    pub ctime: Timestamp,
+    pub link: bool,
+    pub symlink: bool,
+}
+
+pub(super) fn raw_attrs(path: &str) -> RawAttrs {
+    let q = AttrQueryHandle::new(path);
+    unsafe {
+        RawAttrs {
+            ino: (*q.0).ino,
+            mode: (*q.0).mode,
+            uid: (*q.0).uid,
+            gid: (*q.0).gid,
+            nlink: (*q.0).nlink,
+            birthtime: Timestamp::load((*q.0).birthtime, (*q.0).birthtime_ns),
+            atime: Timestamp::load((*q.0).atime, (*q.0).atime_ns),
+            mtime: Timestamp::load((*q.0).mtime, (*q.0).mtime_ns),
+            ctime: Timestamp::load((*q.0).ctime, (*q.0).ctime_ns),
+            link: (*q.0).link,
+            symlink: (*q.0).symlink,
+        }
+    }
+}
+
+/// Replaces every attribute of `path`, without any of the usual side effects.
+pub(super) fn set_raw_attrs(path: &str, attrs: &RawAttrs) {
+    let c_path = match c_string(path) {
+        Ok(c_path) => c_path,
+        Err(_) => return,
+    };
+    unsafe { lfs::lfs_sys_attr_reset(c_path.as_ptr()) };
+    let q = AttrQueryHandle::new(path);
+    unsafe {
+        (*q.0).ino = attrs.ino;
+        (*q.0).mode = attrs.mode;
+        (*q.0).uid = attrs.uid;
+        (*q.0).gid = attrs.gid;
+        (*q.0).nlink = attrs.nlink;
+        attrs.birthtime.store(&mut (*q.0).birthtime, &mut (*q.0).birthtime_ns);
+        attrs.atime.store(&mut (*q.0).atime, &mut (*q.0).atime_ns);
+        attrs.mtime.store(&mut (*q.0).mtime, &mut (*q.0).mtime_ns);
+        attrs.ctime.store(&mut (*q.0).ctime, &mut (*q.0).ctime_ns);
+        (*q.0).link = attrs.link;
+        (*q.0).symlink = attrs.symlink;
+        lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+    }
+}
+
+/// Takes inode numbers out of the pool so that nodes can keep the ones they
+/// were created with. Numbers the pool already handed out stay with it.
+pub(super) fn claim_inos(inos: &[usize]) {
+    let mut inos = inos.to_vec();
+    inos.sort_unstable();
+    inos.dedup();
+    let pool = unsafe { &mut LFS_SYS_INO_POOL };
+    let mut spare = vec![];
+    let mut next = pool.request_id();
+    for ino in inos {
+        while let Some(id) = next.filter(|id| *id < ino) {
+            spare.push(id);
+            next = pool.request_id();
+        }
+        if next == Some(ino) {
+            next = pool.request_id();
+        }
+    }
+    spare.extend(next);
+    for id in spare {
+        pool.return_id(id).ok();
+    }
+}
+
+/// Hands out an inode number the way node creation does.
+pub(super) fn request_ino() -> Option<i32> {
+    unsafe { LFS_SYS_INO_POOL.request_id().map(|id| id as i32) }
+}
+
+/// Records `link` as a hard link to `target`, like `link_sync` does.
+pub(super) fn record_hard_link(target: &str, link: &str) {
+    unsafe {
+        let links = LFS_SYS_HARD_LINKS.entry(target.to_string()).or_insert_with(Vec::new);
+        if !links.iter().any(|l| l == link) {
+            links.push(link.to_string());
+        }
+    }
+}
+
+/// Returns true if the raw attribute is present on disk for the given path.
+fn fsck_has_attr(path: &str, attr: lfs::lfs_sys_attr_type_t) -> bool {
+    let c_path = match c_string(path) {
+        Ok(c_path) => c_path,
+        Err(_) => return false,
+    };
+    let mut buf = [0u8; 8];
+    let res = unsafe {
+        lfs::lfs_getattr(
+            disk(),
+            c_path.as_ptr(),
+            attr as u8,
+            buf.as_mut_ptr() as *mut c_void,
+            buf.len() as u32,
+        )
+    };
+    res >= 0
+}
+
+unsafe extern "C" fn fsck_collect_block(p: *mut c_void, block: lfs::lfs_block_t) -> i32 {
+    (*(p as *mut HashSet<lfs::lfs_block_t>)).insert(block);
+    0
+}
+
+/// Adds the blocks of a file's skip-list, following it the way littlefs' own
+/// `lfs_ctz_traverse` does. Blocks an open file has not written out yet are read
+/// from its `cache`.
+fn fsck_ctz_blocks(
+    head: lfs::lfs_block_t,
+    size: u32,
+    cache: Option<&lfs::lfs_cache_t>,
+    out: &mut HashSet<lfs::lfs_block_t>,
+) {
+    if size == 0 {
+        return;
+    }
+    let cfg = unsafe { &*(*disk()).cfg };
+    let b = cfg.block_size - 2 * 4;
+    let mut index = (size - 1) / b;
+    if index != 0 {
+        index = (size - 1 - 4 * ((index - 1).count_ones() + 2)) / b;
+    }
+    let mut head = head;
+    let mut buf = vec![0u8; cfg.read_size as usize];
+    loop {
+        out.insert(head);
+        if index == 0 {
+            return;
+        }
+        match cache {
+            Some(c) if c.block == head && c.off == 0 && c.size >= 8 => unsafe {
+                std::ptr::copy_nonoverlapping(c.buffer, buf.as_mut_ptr(), 8)
+            },
+            _ => {
+                let read = cfg.read.unwrap();
+                let ptr = buf.as_mut_ptr() as *mut c_void;
+                if unsafe { read(cfg, head, 0, ptr, buf.len() as u32) } != 0 {
+                    return;
+                }
+            }
+        }
+        let heads = [
+            u32::from_le_bytes(buf[0..4].try_into().unwrap()),
+            u32::from_le_bytes(buf[4..8].try_into().unwrap()),
+        ];
+        let count = 2 - (index & 1);
+        for h in &heads[..count as usize - 1] {
+            out.insert(*h);
+        }
+        head = heads[count as usize - 1];
+        index -= count;
+    }
+}
+
+/// Blocks a walk of the tree reaches: the metadata pairs of every directory
+/// and the data blocks of every file, including those still being written.
+fn fsck_reachable_blocks(entries: &[(String, bool)]) -> HashSet<lfs::lfs_block_t> {
+    let disk = disk();
+    let mut reachable = HashSet::new();
+    for (path, is_dir) in entries {
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => continue,
+        };
+        if *is_dir {
+            let dir = unsafe { lfs::lfs_sys_dir_new() };
+            let info = InfoHandle::new();
+            if unsafe { lfs::lfs_dir_open(disk, dir, c_path.as_ptr()) } == lfs::lfs_error_LFS_ERR_OK {
+                // reading walks the directory's split metadata pairs in turn
+                loop {
+                    unsafe { reachable.extend((*dir).m.pair) };
+                    if unsafe { lfs::lfs_dir_read(disk, dir, info.0) } <= 0 {
+                        break;
+                    }
+                }
+                unsafe { lfs::lfs_dir_close(disk, dir) };
+            }
+            unsafe { lfs::lfs_sys_dir_free(dir) };
+        } else {
+            let file = unsafe { lfs::lfs_sys_file_new() };
+            let res = unsafe { lfs::lfs_file_open(disk, file, c_path.as_ptr(), O_RDONLY as i32) };
+            if res == lfs::lfs_error_LFS_ERR_OK {
+                let (flags, ctz) = unsafe { ((*file).flags, (*file).ctz) };
+                if flags & lfs::lfs_open_flags_LFS_F_INLINE == 0 {
+                    fsck_ctz_blocks(ctz.head, ctz.size, None, &mut reachable);
+                }
+                unsafe { lfs::lfs_file_close(disk, file) };
+            }
+            unsafe { lfs::lfs_sys_file_free(file) };
+        }
+    }
+    let mut node = unsafe { (*disk).mlist };
+    while !node.is_null() {
+        if unsafe { (*node).type_ } == lfs::lfs_type_LFS_TYPE_REG as u8 {
+            let file = unsafe { &*(node as *const lfs::lfs_file_t) };
+            let cache = Some(&file.cache);
+            if file.flags & lfs::lfs_open_flags_LFS_F_INLINE == 0 {
+                if file.flags & lfs::lfs_open_flags_LFS_F_DIRTY != 0 {
+                    fsck_ctz_blocks(file.ctz.head, file.ctz.size, cache, &mut reachable);
+                }
+                if file.flags & lfs::lfs_open_flags_LFS_F_WRITING != 0 {
+                    fsck_ctz_blocks(file.block, file.pos, cache, &mut reachable);
+                }
+            }
+        }
+        node = unsafe { (*node).next };
+    }
+    reachable
+}
+
+/// Every block the volume has in use, orphaned or not.
+fn fsck_used_blocks() -> HashSet<lfs::lfs_block_t> {
+    let mut used = HashSet::new();
+    unsafe {
+        lfs::lfs_fs_traverse(
+            disk(),
+            Some(fsck_collect_block),
+            &mut used as *mut HashSet<lfs::lfs_block_t> as *mut c_void,
+        )
+    };
+    used
+}
+
+/// Counts the blocks in use that no path reaches: metadata pairs left behind
+/// by an interrupted remove or rename, and whatever they still point to.
+fn fsck_orphans(entries: &[(String, bool)]) -> usize {
+    let reachable = fsck_reachable_blocks(entries);
+    fsck_used_blocks().difference(&reachable).count()
+}
+
+/// Walks the whole volume and checks the attribute store against the tree.
+/// With `repair`, every problem found is fixed in place where possible.
+pub fn fsck_sync(repair: bool) -> FsckReport {
+    let disk = disk();
+    let mut report = FsckReport {
+        blocks: 0,
+        orphans: 0,
+        files: 0,
+        dirs: 0,
+        problems: vec![],
+    };
+    let mut entries = vec![("/".to_string(), true)];
+    walk_raw("/", &mut entries);
+    report.orphans = fsck_orphans(&entries);
+    if report.orphans > 0 {
+        // littlefs drops orphans while making the volume consistent, which
+        // the next directory creation does. only a recount tells if it did.
+        let fixed = repair && {
+            if let Ok(scratch) = c_string(&format!("/.fsck-{}", tmp::suffix())) {
+                unsafe {
+                    lfs::lfs_mkdir(disk, scratch.as_ptr());
+                    lfs::lfs_remove(disk, scratch.as_ptr());
+                }
+            }
+            fsck_orphans(&entries) == 0
+        };
+        report.report(
+            "orphan",
+            "/",
+            format!("{} block(s) in use are not reachable from '/'", report.orphans),
+            fixed,
+        );
+    }
+    let mut inos: HashMap<i32, String> = HashMap::new();
+    let mut incoming: HashMap<String, i32> = HashMap::new();
+    let mut links: HashMap<String, Vec<String>> = HashMap::new();
+    for (path, is_dir) in entries.iter() {
+        if *is_dir {
+            report.dirs += 1;
+        } else {
+            report.files += 1;
+        }
+        let c_path = match c_string(path) {
+            Ok(c_path) => c_path,
+            Err(_) => continue,
+        };
+        let q = AttrQueryHandle::new(path);
+        let ino_ok = fsck_has_attr(path, lfs::lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_INO)
+            && (path == "/" || unsafe { (*q.0).ino } > 0);
+        if !ino_ok {
+            if repair {
+                unsafe {
+                    (*q.0).ino = LFS_SYS_INO_POOL.request_id().unwrap() as i32;
+                    lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+                }
+            }
+            report.report("ino", path, "inode number is missing".into(), repair);
+        }
+        let ino = unsafe { (*q.0).ino };
+        if ino > 0 {
+            if let Some(first) = inos.get(&ino) {
+                let detail = format!("inode {} is also used by '{}'", ino, first);
+                if repair {
+                    unsafe {
+                        (*q.0).ino = LFS_SYS_INO_POOL.request_id().unwrap() as i32;
+                        lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+                    }
+                }
+                report.report("duplicate-ino", path, detail, repair);
+            }
+            inos.insert(unsafe { (*q.0).ino }, path.clone());
+        }
+        let mode = unsafe { (*q.0).mode } as u32;
+        let expected = if *is_dir { S_IFDIR } else { S_IFREG };
+        let mode_ok = fsck_has_attr(path, lfs::lfs_sys_attr_type_t_LFS_SYS_ATTR_TYPE_MODE)
+            && if *is_dir {
+                mode & S_IFMT == S_IFDIR
+            } else {
+                mode & S_IFMT != 0 && mode & S_IFMT != S_IFDIR
+            };
+        if !mode_ok {
+            if repair {
+                let perm = if mode == 0 {
+                    get_default_permissions(*is_dir) as u32
+                } else {
+                    sanitize_permissions(mode as i32)
+                };
+                unsafe {
+                    (*q.0).mode = (expected | perm) as i32;
+                    lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+                }
+            }
+            report.report("mode", path, format!("invalid mode {:o}", mode), repair);
+        }
+        let (link, symlink) = unsafe { ((*q.0).link, (*q.0).symlink) };
+        if (link || symlink) && !*is_dir {
+            if let Some(target) = fsck_link_target(path) {
+                if link && !exists_sync_no_follow(&target) {
+                    if repair {
+                        unsafe { lfs::lfs_remove(disk, c_path.as_ptr()) };
+                    }
+                    let detail = format!("hard link to missing '{}'", target);
+                    report.report("dangling-link", path, detail, repair);
+                    continue;
+                }
+                *incoming.entry(target.clone()).or_insert(0) += 1;
+                if link {
+                    links.entry(target).or_insert_with(Vec::new).push(path.clone());
+                }
+            }
+        }
+    }
+    for (path, is_dir) in entries.iter() {
+        if *is_dir || !exists_sync_no_follow(path) {
+            continue;
+        }
+        let q = AttrQueryHandle::new(path);
+        // freshly created files have an nlink of zero, which counts as one
+        let stored = unsafe { (*q.0).nlink }.max(1);
+        let expected = 1 + incoming.get(path).copied().unwrap_or(0);
+        if stored != expected {
+            if repair {
+                if let Ok(c_path) = c_string(path) {
+                    unsafe {
+                        (*q.0).nlink = expected;
+                        lfs::lfs_sys_attr_patch(c_path.as_ptr(), q.0);
+                    }
+                }
+            }
+            let detail = format!("nlink is {} but {} link(s) exist", stored, expected);
+            report.report("nlink", path, detail, repair);
+        }
+    }
+    // hard link records are kept in memory and can drift from what is on disk.
+    // what they stand for on disk is the nlink of their target, fixed above,
+    // so a record only counts as repaired once its target's nlink is right.
+    let settled = |target: &str| {
+        !exists_sync_no_follow(target) || {
+            let q = AttrQueryHandle::new(target);
+            unsafe { (*q.0).nlink }.max(1) == 1 + incoming.get(target).copied().unwrap_or(0)
+        }
+    };
+    unsafe {
+        let recorded: Vec<(String, Vec<String>)> = LFS_SYS_HARD_LINKS
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        for (target, paths) in recorded.iter() {
+            for link in paths {
+                let on_disk = links.get(target).map_or(false, |l| l.contains(link));
+                if !on_disk {
+                    let detail = format!("recorded link to '{}' does not exist", target);
+                    report.report("dangling-record", link, detail, repair && settled(target));
+                }
+            }
+        }
+        for (target, paths) in links.iter() {
+            let known = LFS_SYS_HARD_LINKS.get(target).cloned().unwrap_or_default();
+            for link in paths.iter().filter(|l| !known.contains(l)) {
+                let detail = format!("link to '{}' is not recorded", target);
+                report.report("dangling-record", link, detail, repair && settled(target));
+            }
+        }
+        if repair {
+            LFS_SYS_HARD_LINKS.retain(|target, _| links.contains_key(target));
+            for (target, paths) in links.iter() {
+                LFS_SYS_HARD_LINKS.insert(target.clone(), paths.clone());
+            }
+        }
+    }
+    report.blocks = fsck_used_blocks().len();
+    report
 }
 
 // ---------------------------------------------------------- Utility Functions
 
-fn path_basename(path: &str) -> String {
+pub(super) fn path_basename(path: &str) -> String {
     path.split("/").last().unwrap_or("").to_string()
 }
 
-fn path_dirname(path: &str) -> String {
+pub(super) fn path_dirname(path: &str) -> String {
     if path == "/" {
         "/".to_string()
     } else {
@@ -1601,7 +3805,7 @@ fn path_dirname(path: &str) -> String {
     }
 }
 
-fn path_normalize(path: &str) -> String {
+pub fn path_normalize(path: &str) -> String {
     let path = path.to_string();
     if path == "." || path == ".." {
         return "/".to_string();
@@ -1643,65 +3847,7 @@ pub fn path_split(path: &str) -> Vec<String> {
     paths
 }
 
-fn json_file(path: &str) -> Value {
-    let content = read_file_sync(path).unwrap();
-    let attributes = AttrQueryHandle::new(path);
-    json!({
-            "path": path,
-            "ino": unsafe { (*attributes.0).ino },
-            "mode": unsafe { (*attributes.0).mode },
-            "uid": unsafe { (*attributes.0).uid },
-            "gid": unsafe { (*attributes.0).gid },
-            "birthtime": unsafe { (*attributes.0).birthtime },
-            "atime": unsafe { (*attributes.0).atime },
-            "mtime": unsafe { (*attributes.0).mtime },
-            "ctime": unsafe { (*attributes.0).ctime },
-            "link": unsafe { (*attributes.0).link },
-            "nlink": unsafe { (*attributes.0).nlink },
-            "symlink": unsafe { (*attributes.0).symlink },
-            "size": unsafe { (*attributes.0).size },
-            "content": content,
-    })
-}
-
-fn json_directory(path: &str) -> Value {
-    let mut handle = DirHandle::open(path).unwrap();
-    let mut children = vec![];
-    while let Some(entry) = handle.read() {
-        if entry.file {
-            children.push(json_file(entry.name.as_str()));
-        } else {
-            children.push(json_directory(entry.name.as_str()));
-        }
-    }
-    let attributes = AttrQueryHandle::new(path);
-    json!({
-            "path": path,
-            "ino": unsafe { (*attributes.0).ino },
-            "mode": unsafe { (*attributes.0).mode },
-            "uid": unsafe { (*attributes.0).uid },
-            "gid": unsafe { (*attributes.0).gid },
-            "birthtime": unsafe { (*attributes.0).birthtime },
-            "atime": unsafe { (*attributes.0).atime },
-            "mtime": unsafe { (*attributes.0).mtime },
-            "ctime": unsafe { (*attributes.0).ctime },
-            "link": unsafe { (*attributes.0).link },
-            "nlink": unsafe { (*attributes.0).nlink },
-            "symlink": unsafe { (*attributes.0).symlink },
-            "size": unsafe { (*attributes.0).size },
-            "content": children,
-    })
-}
-
-fn json_path(path: &str) -> Value {
-    if is_directory(path) {
-        json_directory(path)
-    } else {
-        json_file(path)
-    }
-}
-
-fn follow_link(path: &str, attempt: Option<usize>) -> String {
+pub(super) fn follow_link(path: &str, attempt: Option<usize>) -> String {
     let attempt = attempt.unwrap_or(0);
     let path = &path_normalize(path);
     assert!(attempt < 100); // prevent infinite recursion by death.
@@ -1716,23 +3862,33 @@ fn follow_link(path: &str, attempt: Option<usize>) -> String {
     path.to_string()
 }
 
-fn rand_string() -> String {
-    let now = Touch::time(None);
-    let mut bits: u64 = now.to_bits();
-    bits >>= 48;
-    bits &= 0b0011_1111_1111;
-    let base64_chars = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
-    let hash_str: String = (0..6)
-        .map(|i| {
-            base64_chars
-                .chars()
-                .nth((bits >> (6 * i)) as usize & 0x3F)
-                .unwrap()
-        })
-        .collect();
-    hash_str
+pub(super) fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 fn sanitize_permissions(p: i32) -> u32 {
     (p & (DEFAULT_PERM_DIR | DEFAULT_PERM_FILE)) as u32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_positions_read_at_the_cursor() {
+        let rdonly = OpenFlags(OpenFlags::RDONLY);
+        write_file_sync("/cursor.txt", b"abcdef", None, None).unwrap();
+        for path in ["/cursor.txt", "/proc/meminfo"] {
+            let fd = open_sync(path, rdonly, None).unwrap();
+            let mut whole = [0u8; 6];
+            assert_eq!(read_sync(fd, &mut whole, None, None, Some(0)), Some(6));
+            let mut head = [0u8; 3];
+            let mut tail = [0u8; 3];
+            assert_eq!(read_sync(fd, &mut head, None, None, Some(-1)), Some(3));
+            assert_eq!(read_sync(fd, &mut tail, None, None, Some(-1)), Some(3));
+            assert_eq!([head, tail].concat(), whole, "{}", path);
+            close_sync(fd).unwrap();
+        }
+        unlink_sync("/cursor.txt", None).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+// Minimal LZ4 block format (https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md)
+// used to compress file contents. Only the greedy single-probe matcher is
+// implemented, which is what matters for text-heavy files: fast, and within a
+// few percent of the reference `lz4 -1`.
+
+const MIN_MATCH: usize = 4;
+/// the last bytes of a block are always literals
+const LAST_LITERALS: usize = 5;
+/// no match may start this close to the end of a block
+const MF_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 0xffff;
+const HASH_LOG: u32 = 12;
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn write_literals(out: &mut Vec<u8>, token: u8, literals: &[u8]) {
+    out.push(token | (literals.len().min(15) as u8) << 4);
+    if literals.len() >= 15 {
+        write_len(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+}
+
+/// Compresses `input` into a single LZ4 block.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    // positions + 1 of the last 4-byte sequences seen, 0 when empty
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut pos = 0;
+    let limit = input.len().saturating_sub(MF_LIMIT);
+    while pos < limit {
+        let sequence = read_u32(input, pos);
+        let slot = hash(sequence);
+        let candidate = table[slot];
+        table[slot] = pos + 1;
+        if candidate == 0 || pos - (candidate - 1) > MAX_OFFSET {
+            pos += 1;
+            continue;
+        }
+        let candidate = candidate - 1;
+        if read_u32(input, candidate) != sequence {
+            pos += 1;
+            continue;
+        }
+        let end = input.len() - LAST_LITERALS;
+        let mut len = MIN_MATCH;
+        while pos + len < end && input[candidate + len] == input[pos + len] {
+            len += 1;
+        }
+        let extra = len - MIN_MATCH;
+        write_literals(&mut out, extra.min(15) as u8, &input[anchor..pos]);
+        out.extend_from_slice(&((pos - candidate) as u16).to_le_bytes());
+        if extra >= 15 {
+            write_len(&mut out, extra - 15);
+        }
+        pos += len;
+        anchor = pos;
+    }
+    write_literals(&mut out, 0, &input[anchor..]);
+    out
+}
+
+fn read_len(input: &[u8], at: &mut usize) -> Option<usize> {
+    let mut len = 0;
+    loop {
+        let byte = *input.get(*at)?;
+        *at += 1;
+        len += byte as usize;
+        if byte != 255 {
+            return Some(len);
+        }
+    }
+}
+
+/// Decompresses an LZ4 block that holds exactly `len` bytes. Returns None if
+/// the block is malformed.
+pub fn decompress(input: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    let mut at = 0;
+    loop {
+        let token = *input.get(at)?;
+        at += 1;
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_len(input, &mut at)?;
+        }
+        if out.len() + literals > len {
+            return None;
+        }
+        out.extend_from_slice(input.get(at..at + literals)?);
+        at += literals;
+        if at == input.len() {
+            break;
+        }
+        let offset = u16::from_le_bytes([*input.get(at)?, *input.get(at + 1)?]) as usize;
+        at += 2;
+        if offset == 0 || offset > out.len() {
+            return None;
+        }
+        let mut matched = (token & 15) as usize;
+        if matched == 15 {
+            matched += read_len(input, &mut at)?;
+        }
+        matched += MIN_MATCH;
+        if out.len() + matched > len {
+            return None;
+        }
+        // matches may overlap what they copy, so this goes a byte at a time
+        let start = out.len() - offset;
+        for i in start..start + matched {
+            out.push(out[i]);
+        }
+    }
+    if out.len() == len {
+        Some(out)
+    } else {
+        None
+    }
+}
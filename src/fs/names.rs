@@ -0,0 +1,191 @@
+//! Name matching for volumes brought over from macOS or Windows. The mount can
+//! match names regardless of case and of their Unicode normal form: `resolve`
+//! maps every component of a path to the entry on disk it matches, and new
+//! names are stored in the chosen normal form with their case preserved.
+
+use super::errno::errno;
+use super::lfs;
+use std::collections::HashMap;
+use std::io;
+use unicode_normalization::UnicodeNormalization;
+
+/// Unicode normal form new names are stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// names are stored and matched byte for byte (default)
+    None,
+    /// composed, as Linux and Windows tools usually write names
+    Nfc,
+    /// decomposed, as HFS+ stores names
+    Nfd,
+}
+
+impl Normalization {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Self::None),
+            "nfc" => Some(Self::Nfc),
+            "nfd" => Some(Self::Nfd),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Nfc => "nfc",
+            Self::Nfd => "nfd",
+        }
+    }
+}
+
+/// Mount options deciding which names refer to the same entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameMatching {
+    /// "README" finds "readme", whichever was created first keeps its case
+    pub case_insensitive: bool,
+    pub normalization: Normalization,
+}
+
+impl NameMatching {
+    pub const EXACT: Self = Self {
+        case_insensitive: false,
+        normalization: Normalization::None,
+    };
+
+    /// What two names have in common when they refer to the same entry.
+    fn key(&self, name: &str) -> String {
+        let name = if self.case_insensitive {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        };
+        match self.normalization {
+            Normalization::None => name,
+            _ => name.nfd().collect(),
+        }
+    }
+
+    /// How a new name goes to disk.
+    fn stored(&self, name: &str) -> String {
+        match self.normalization {
+            Normalization::None => name.to_string(),
+            Normalization::Nfc => name.nfc().collect(),
+            Normalization::Nfd => name.nfd().collect(),
+        }
+    }
+
+    /// The options as /proc/mounts lists them, nothing for exact matching.
+    pub fn mount_options(&self) -> String {
+        let mut options = vec![];
+        if self.case_insensitive {
+            options.push("casefold");
+        }
+        if self.normalization != Normalization::None {
+            options.push(self.normalization.name());
+        }
+        options.join(",")
+    }
+}
+
+static mut NAME_MATCHING: NameMatching = NameMatching::EXACT;
+
+/// Back to exact matching, the options are not stored on the disk. Rebooting
+/// forgets them like the other mount options, `initialize` applies them again.
+pub unsafe fn names_reset() {
+    NAME_MATCHING = NameMatching::EXACT;
+}
+
+pub fn name_matching() -> NameMatching {
+    unsafe { NAME_MATCHING }
+}
+
+/// Replaces the name matching options of the mount. Refuses with EEXIST when
+/// a directory holds two entries the new options could not tell apart.
+pub fn set_name_matching(options: NameMatching) -> io::Result<()> {
+    if options != NameMatching::EXACT {
+        let mut entries = vec![];
+        lfs::walk_raw("/", &mut entries);
+        let mut seen = HashMap::new();
+        for (path, _) in entries {
+            let name = lfs::path_basename(&path);
+            let key = (lfs::path_dirname(&path), options.key(&name));
+            if let Some(other) = seen.insert(key, path.clone()) {
+                let message = format!("file already exists as '{}', mount", other);
+                return Err(errno("EEXIST", &message, Some(&path), Some("mount")));
+            }
+        }
+    }
+    unsafe { NAME_MATCHING = options };
+    Ok(())
+}
+
+/// The entry of `parent` that `name` refers to. An exact match wins over one
+/// that only matches through the options.
+fn find(parent: &str, name: &str, options: NameMatching) -> Option<String> {
+    let children = lfs::children_raw(parent);
+    let exact = children
+        .iter()
+        .find(|(child, _)| lfs::path_basename(child) == name);
+    if let Some((child, _)) = exact {
+        return Some(child.clone());
+    }
+    let key = options.key(name);
+    children
+        .into_iter()
+        .map(|(child, _)| child)
+        .find(|child| options.key(&lfs::path_basename(child)) == key)
+}
+
+fn join(parent: &str, name: &str) -> String {
+    if parent == "/" {
+        format!("/{}", name)
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// Maps a normalized absolute path to the entries on disk it refers to. The
+/// components that match nothing are put in the form new names are stored in.
+pub fn resolve(path: &str) -> String {
+    let options = name_matching();
+    if options == NameMatching::EXACT
+        || !path.starts_with('/')
+        || path.contains('\0')
+        || lfs::is_virtual(path)
+    {
+        return path.to_string();
+    }
+    let mut resolved = "/".to_string();
+    let mut on_disk = true;
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let found = if on_disk {
+            find(&resolved, name, options)
+        } else {
+            None
+        };
+        on_disk = found.is_some();
+        resolved = found.unwrap_or_else(|| join(&resolved, &options.stored(name)));
+    }
+    resolved
+}
+
+/// Where `rename` puts `old_path` when asked to move it to `new_path`. Unlike
+/// `resolve` the last component keeps the case it is given, so that a rename
+/// can change just the case of a name. Landing on an entry that only matches
+/// through the options, other than `old_path` itself, fails with EEXIST.
+pub fn rename_target(old_path: &str, new_path: &str) -> io::Result<String> {
+    let options = name_matching();
+    if options == NameMatching::EXACT || new_path == "/" || lfs::is_virtual(new_path) {
+        return Ok(new_path.to_string());
+    }
+    let parent = resolve(&lfs::path_dirname(new_path));
+    let name = lfs::path_basename(new_path);
+    let target = join(&parent, &options.stored(&name));
+    match find(&parent, &name, options) {
+        Some(entry) if entry != old_path && entry != target => {
+            let message = format!("file already exists as '{}', rename", entry);
+            Err(errno("EEXIST", &message, Some(new_path), Some("rename")))
+        }
+        _ => Ok(target),
+    }
+}
@@ -0,0 +1,534 @@
+//! Typed Rust API over the filesystem, for Rust code compiled into the same
+//! module or built natively. It speaks `std::io` instead of `JsValue`; the
+//! `#[wasm_bindgen]` exports for descriptors are thin wrappers over it.
+
+use super::cwd;
+use super::errno::{errno, io_error};
+use super::lfs::{
+    self, ChecksumAlgorithm, Compression, Dirent, DiskUsage, NodeStats, OpenFlags, StatFs,
+    Timestamp,
+};
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// An error on a descriptor, Node leaves the path out of those.
+fn fd_error(code: &str, message: &str, syscall: &str) -> io::Error {
+    errno(code, &format!("{}, {}", message, syscall), None, Some(syscall))
+}
+
+/// Reads from any kind of descriptor: pipes, devices, /proc files and files.
+/// `position` reads at an absolute offset without moving the cursor.
+pub(crate) fn read_fd(fd: usize, buf: &mut [u8], position: Option<i32>) -> io::Result<usize> {
+    if let Some(read) = lfs::fifo_read_sync(fd, buf, None, None) {
+        return read;
+    }
+    if lfs::is_dir_fd(fd) {
+        return Err(fd_error("EISDIR", "illegal operation on a directory", "read"));
+    }
+    if !lfs::is_open_fd(fd) {
+        return Err(fd_error("EBADF", "bad file descriptor", "read"));
+    }
+    lfs::read_sync(fd, buf, None, None, position)
+        .ok_or_else(|| fd_error("EIO", "i/o error", "read"))
+}
+
+/// Writes to any kind of descriptor opened for writing.
+pub(crate) fn write_fd(fd: usize, buf: &[u8], position: Option<i32>) -> io::Result<usize> {
+    if let Some(written) = lfs::fifo_write_sync(fd, buf, None, None) {
+        return written;
+    }
+    if lfs::is_dir_fd(fd) {
+        return Err(fd_error("EISDIR", "illegal operation on a directory", "write"));
+    }
+    if !lfs::is_writable_fd(fd) {
+        return Err(fd_error("EBADF", "bad file descriptor", "write"));
+    }
+    lfs::write_sync(fd, buf, None, None, position)
+        .ok_or_else(|| fd_error("EIO", "i/o error", "write"))
+}
+
+pub(crate) fn close_fd(fd: usize) -> io::Result<()> {
+    lfs::close_sync(fd)
+}
+
+/// Runs `op` on `fd` after making sure it is open, so a stale descriptor
+/// fails with EBADF instead of whatever `op` makes of it.
+fn with_open_fd<T>(fd: usize, syscall: &str, op: impl FnOnce(usize) -> Option<T>) -> io::Result<T> {
+    if !lfs::is_open_fd(fd) {
+        return Err(fd_error("EBADF", "bad file descriptor", syscall));
+    }
+    op(fd).ok_or_else(|| fd_error("EIO", "i/o error", syscall))
+}
+
+pub(crate) fn fstat_fd(fd: usize) -> io::Result<NodeStats> {
+    with_open_fd(fd, "fstat", lfs::fstat)
+}
+
+pub(crate) fn fsync_fd(fd: usize) -> io::Result<()> {
+    with_open_fd(fd, "fsync", lfs::fsync)
+}
+
+pub(crate) fn fdatasync_fd(fd: usize) -> io::Result<()> {
+    with_open_fd(fd, "fdatasync", lfs::fdatasync)
+}
+
+pub(crate) fn fchmod_fd(fd: usize, mode: i32) -> io::Result<()> {
+    with_open_fd(fd, "fchmod", |fd| lfs::fchmod(fd, mode))
+}
+
+pub(crate) fn fchown_fd(fd: usize, uid: i32, gid: i32) -> io::Result<()> {
+    with_open_fd(fd, "fchown", |fd| lfs::fchown(fd, uid, gid))
+}
+
+pub(crate) fn futimes_fd(fd: usize, atime: Timestamp, mtime: Timestamp) -> io::Result<()> {
+    with_open_fd(fd, "futime", |fd| lfs::futimes(fd, atime, mtime))
+}
+
+/// Options and flags to open a `File` with, like `std::fs::OpenOptions`.
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+    mode: i32,
+    custom_flags: u32,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            mode: 0o666,
+            custom_flags: 0,
+        }
+    }
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+    /// Permissions of newly created files, before the umask.
+    pub fn mode(&mut self, mode: i32) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+    /// Extra O_* flags, such as O_NOFOLLOW or O_NOATIME.
+    pub fn custom_flags(&mut self, flags: u32) -> &mut Self {
+        self.custom_flags = flags;
+        self
+    }
+    fn flags(&self) -> io::Result<OpenFlags> {
+        let write = self.write || self.append;
+        let mut flags = match (self.read, write) {
+            (true, false) => OpenFlags::RDONLY,
+            (false, true) => OpenFlags::WRONLY,
+            (true, true) => OpenFlags::RDWR,
+            (false, false) => return Err(io_error("EINVAL", "neither read nor write access")),
+        };
+        if !write && (self.truncate || self.create || self.create_new) {
+            return Err(io_error("EINVAL", "creating or truncating needs write access"));
+        }
+        if self.append {
+            flags |= OpenFlags::APPEND;
+        }
+        if self.truncate {
+            flags |= OpenFlags::TRUNC;
+        }
+        if self.create || self.create_new {
+            flags |= OpenFlags::CREAT;
+        }
+        if self.create_new {
+            flags |= OpenFlags::EXCL;
+        }
+        Ok(OpenFlags(flags | self.custom_flags))
+    }
+    pub fn open(&self, path: &str) -> io::Result<File> {
+        let path = cwd::checked(path)?;
+        let fd = lfs::open_sync(&path, self.flags()?, Some(self.mode))?;
+        Ok(File { fd, path })
+    }
+}
+
+/// An open file, closed when dropped.
+#[derive(Debug)]
+pub struct File {
+    fd: usize,
+    path: String,
+}
+
+impl File {
+    /// Opens a file read only.
+    pub fn open(path: &str) -> io::Result<File> {
+        OpenOptions::new().read(true).open(path)
+    }
+    /// Opens a file for writing, creating or truncating it.
+    pub fn create(path: &str) -> io::Result<File> {
+        OpenOptions::new().write(true).create(true).truncate(true).open(path)
+    }
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
+    }
+    /// The descriptor, usable with the JavaScript API as well.
+    pub fn fd(&self) -> usize {
+        self.fd
+    }
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        fstat_fd(self.fd).map(Metadata)
+    }
+    /// Truncates or extends the file, extending fills with zeros.
+    pub fn set_len(&self, size: u64) -> io::Result<()> {
+        lfs::ftruncate(self.fd, size as usize)
+    }
+    pub fn sync_all(&self) -> io::Result<()> {
+        fsync_fd(self.fd)
+    }
+    pub fn sync_data(&self) -> io::Result<()> {
+        fdatasync_fd(self.fd)
+    }
+    /// Reads at `offset` without moving the cursor.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        read_fd(self.fd, buf, Some(position(offset)?))
+    }
+    /// Writes at `offset` without moving the cursor.
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        write_fd(self.fd, buf, Some(position(offset)?))
+    }
+    /// Closes the file. Dropping it does the same but cannot report that a
+    /// compressed file failed to be written back.
+    pub fn close(mut self) -> io::Result<()> {
+        let fd = self.fd;
+        // nothing left to free, the descriptor is closed here rather than on drop
+        self.path = String::new();
+        std::mem::forget(self);
+        close_fd(fd)
+    }
+}
+
+fn position(offset: u64) -> io::Result<i32> {
+    i32::try_from(offset).map_err(|_| io_error("EINVAL", "offset out of range"))
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        read_fd(self.fd, buf, None)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_fd(self.fd, buf, None)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        // pipes and devices have nothing to flush
+        lfs::fsync(self.fd);
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (position(offset)?, lfs::SEEK_SET),
+            SeekFrom::Current(offset) => (offset as i32, lfs::SEEK_CUR),
+            SeekFrom::End(offset) => (offset as i32, lfs::SEEK_END),
+        };
+        lfs::lseek_sync(self.fd, offset, whence)
+            .map(|pos| pos as u64)
+            .ok_or_else(|| io_error("EINVAL", "invalid seek"))
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = close_fd(self.fd);
+    }
+}
+
+/// Type of a node, like `std::fs::FileType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileType(u32);
+
+impl FileType {
+    pub fn is_dir(&self) -> bool {
+        self.0 == lfs::S_IFDIR
+    }
+    pub fn is_file(&self) -> bool {
+        self.0 == lfs::S_IFREG
+    }
+    pub fn is_symlink(&self) -> bool {
+        self.0 == lfs::S_IFLNK
+    }
+    pub fn is_fifo(&self) -> bool {
+        self.0 == lfs::S_IFIFO
+    }
+    pub fn is_char_device(&self) -> bool {
+        self.0 == lfs::S_IFCHR
+    }
+}
+
+/// Metadata of a node, like `std::fs::Metadata`.
+#[derive(Debug)]
+pub struct Metadata(NodeStats);
+
+impl Metadata {
+    pub fn file_type(&self) -> FileType {
+        FileType(self.0.mode as u32 & lfs::S_IFMT)
+    }
+    pub fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+    pub fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+    pub fn is_symlink(&self) -> bool {
+        self.file_type().is_symlink()
+    }
+    pub fn len(&self) -> u64 {
+        self.0.size as u64
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.size == 0
+    }
+    /// Permission bits, without the file type.
+    pub fn mode(&self) -> u32 {
+        self.0.mode as u32 & 0o7777
+    }
+    pub fn ino(&self) -> u64 {
+        self.0.ino as u64
+    }
+    pub fn nlink(&self) -> u64 {
+        self.0.nlink as u64
+    }
+    pub fn uid(&self) -> u32 {
+        self.0.uid as u32
+    }
+    pub fn gid(&self) -> u32 {
+        self.0.gid as u32
+    }
+    pub fn accessed(&self) -> io::Result<SystemTime> {
+        Ok(system_time(self.0.atimeNs))
+    }
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        Ok(system_time(self.0.mtimeNs))
+    }
+    pub fn changed(&self) -> io::Result<SystemTime> {
+        Ok(system_time(self.0.ctimeNs))
+    }
+    pub fn created(&self) -> io::Result<SystemTime> {
+        Ok(system_time(self.0.birthtimeNs))
+    }
+}
+
+fn system_time(ns: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(ns.max(0) as u64)
+}
+
+fn timestamp(time: SystemTime) -> Timestamp {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => Timestamp::from_nanos(since.as_nanos() as i128),
+        Err(before) => Timestamp::from_nanos(-(before.duration().as_nanos() as i128)),
+    }
+}
+
+/// Metadata of `path`, following symlinks.
+pub fn metadata(path: &str) -> io::Result<Metadata> {
+    let path = cwd::checked(path)?;
+    lfs::stat_sync(&path).map(Metadata)
+}
+
+/// Metadata of `path` itself, even if it is a symlink.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    let path = cwd::checked(path)?;
+    lfs::lstat_sync(&path).map(Metadata)
+}
+
+/// Sets the access and modification times of `path`, following symlinks.
+pub fn set_times(path: &str, accessed: SystemTime, modified: SystemTime) -> io::Result<()> {
+    let path = cwd::checked(path)?;
+    lfs::utimes_sync(&path, timestamp(accessed), timestamp(modified))
+}
+
+/// Sets the access and modification times of `path` itself, even if it is a
+/// symlink.
+pub fn set_symlink_times(path: &str, accessed: SystemTime, modified: SystemTime) -> io::Result<()> {
+    let path = cwd::checked(path)?;
+    lfs::lutimes_sync(&path, timestamp(accessed), timestamp(modified))
+}
+
+/// Entry of a directory listing.
+#[derive(Debug)]
+pub struct DirEntry(Dirent);
+
+impl DirEntry {
+    /// Full path of the entry (`Dirent.path` is the directory it is in).
+    pub fn path(&self) -> String {
+        if self.0.path == "/" {
+            format!("/{}", self.0.name)
+        } else {
+            format!("{}/{}", self.0.path, self.0.name)
+        }
+    }
+    pub fn file_name(&self) -> String {
+        self.0.name.clone()
+    }
+    pub fn file_type(&self) -> io::Result<FileType> {
+        let kind = if self.0.symlink {
+            lfs::S_IFLNK
+        } else if self.0.fifo {
+            lfs::S_IFIFO
+        } else if self.0.device {
+            lfs::S_IFCHR
+        } else if self.0.file {
+            lfs::S_IFREG
+        } else {
+            lfs::S_IFDIR
+        };
+        Ok(FileType(kind))
+    }
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        symlink_metadata(&self.path())
+    }
+}
+
+/// Iterator over the entries of a directory.
+#[derive(Debug)]
+pub struct ReadDir(std::vec::IntoIter<Dirent>);
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|dirent| Ok(DirEntry(dirent)))
+    }
+}
+
+/// Lists a directory. Entries come without "." and "..".
+pub fn read_dir(path: &str) -> io::Result<ReadDir> {
+    let path = cwd::checked(path)?;
+    if !metadata(&path)?.is_dir() {
+        return Err(io_error("ENOTDIR", "not a directory"));
+    }
+    Ok(ReadDir(lfs::readdir_sync(&path).into_iter()))
+}
+
+/// Reads a whole file. Devices hand out one block, see `readFileSync`.
+pub fn read(path: &str) -> io::Result<Vec<u8>> {
+    lfs::read_file_sync(&cwd::checked(path)?)
+}
+
+/// Writes a whole file, creating or truncating it.
+pub fn write(path: &str, data: &[u8]) -> io::Result<()> {
+    lfs::write_file_sync(&cwd::checked(path)?, data, None, None)
+}
+
+/// Appends to a file, creating it if needed. See `appendFileSync`.
+pub fn append(path: &str, data: &[u8]) -> io::Result<()> {
+    lfs::append_file_sync(&cwd::checked(path)?, data, None, None)
+}
+
+/// Copies the contents of `from` over `to`. See `copyFileSync`.
+pub fn copy(from: &str, to: &str) -> io::Result<()> {
+    lfs::copy_file_sync(&cwd::checked(from)?, &cwd::checked(to)?, false)
+}
+
+/// Sets the permission bits of `path`. See `chmodSync`.
+pub fn set_permissions(path: &str, mode: u32) -> io::Result<()> {
+    lfs::chmod_sync(&cwd::checked(path)?, mode as i32)
+}
+
+/// Changes the owner and group of `path`. See `chownSync`.
+pub fn chown(path: &str, uid: u32, gid: u32) -> io::Result<()> {
+    lfs::chown_sync(&cwd::checked(path)?, uid as i32, gid as i32)
+}
+
+/// Hex digest of the contents of `path`, `cache` keeps it in the file's
+/// attributes until the next write. See `checksumSync`.
+pub fn checksum(path: &str, algorithm: ChecksumAlgorithm, cache: bool) -> io::Result<String> {
+    lfs::checksum_sync(&cwd::checked(path)?, algorithm, cache)
+}
+
+pub fn create_dir(path: &str) -> io::Result<()> {
+    lfs::mkdir_sync(&cwd::checked(path)?, false, 0o777)
+        .map(|_| ())
+}
+
+pub fn create_dir_all(path: &str) -> io::Result<()> {
+    lfs::mkdir_sync(&cwd::checked(path)?, true, 0o777)
+        .map(|_| ())
+}
+
+pub fn remove_file(path: &str) -> io::Result<()> {
+    lfs::unlink_sync(&cwd::checked(path)?, None)
+        .map(|_| ())
+}
+
+pub fn remove_dir(path: &str) -> io::Result<()> {
+    lfs::rmdir_sync(&cwd::checked(path)?, None)
+        .map(|_| ())
+}
+
+/// Creates a directory named `prefix` followed by six random characters, only
+/// its owner can access it. See `mkdtempSync`.
+pub fn create_temp_dir(prefix: &str) -> io::Result<String> {
+    lfs::mkdtemp_sync(&cwd::checked(prefix)?)
+}
+
+/// Creates and opens a file named `prefix` followed by six random characters,
+/// only its owner can access it. See `mkstempSync`.
+pub fn create_temp_file(prefix: &str) -> io::Result<File> {
+    let (path, fd) = lfs::mkstemp_sync(&cwd::checked(prefix)?)?;
+    Ok(File { fd, path })
+}
+
+/// Reports on the mount containing `path`, like `statfs(2)`.
+pub fn statfs(path: &str) -> io::Result<StatFs> {
+    lfs::statfs_sync(&cwd::checked(path)?)
+}
+
+/// Disk usage of `path` and of the directories under it down to `depth`, see
+/// `duSync`. `apparent` sums the sizes of files instead of the blocks they take.
+pub fn disk_usage(path: &str, apparent: bool, depth: Option<usize>) -> io::Result<Vec<DiskUsage>> {
+    lfs::du_sync(&cwd::checked(path)?, apparent, depth)
+}
+
+/// Turns transparent compression of a file or directory on or off. See
+/// `setCompressionSync`.
+pub fn set_compression(path: &str, compression: Option<Compression>) -> io::Result<()> {
+    lfs::set_compression_sync(&cwd::checked(path)?, compression)
+}
@@ -0,0 +1,143 @@
+/// Virtual directory exposing runtime state. Like /dev it does not exist on
+/// disk and is therefore not listed under "/".
+pub const PROC_DIR: &str = "/proc";
+
+/// Nodes of the synthetic /proc tree. Workers share one fd table, like the
+/// threads of a process, so every `/proc/<id>/fd` lists the same descriptors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcNode {
+    Root,
+    Mounts,
+    Meminfo,
+    Locks,
+    /// `/proc/<id>`, or `/proc/self` for the calling worker
+    Worker(usize),
+    Status(usize),
+    FdDir(usize),
+    /// `/proc/<id>/fd/<n>`, a link to whatever `n` refers to
+    Fd(usize, usize),
+}
+
+impl ProcNode {
+    /// Parses a path below /proc. File descriptors are not checked for being
+    /// open, that is up to the caller.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let rest = path.strip_prefix(PROC_DIR)?;
+        if rest.is_empty() || rest == "/" {
+            return Some(ProcNode::Root);
+        }
+        let mut parts = rest.strip_prefix('/')?.split('/');
+        let node = match parts.next()? {
+            "mounts" => ProcNode::Mounts,
+            "meminfo" => ProcNode::Meminfo,
+            "locks" => ProcNode::Locks,
+            "self" => Self::worker(parts, crate::worker_id())?,
+            id => {
+                let id = id.parse::<usize>().ok()?;
+                if id >= crate::worker_count() {
+                    return None;
+                }
+                Self::worker(parts, id)?
+            }
+        };
+        Some(node)
+    }
+
+    fn worker<'a>(mut parts: impl Iterator<Item = &'a str>, id: usize) -> Option<Self> {
+        let node = match (parts.next(), parts.next(), parts.next()) {
+            (None, ..) => ProcNode::Worker(id),
+            (Some("status"), None, _) => ProcNode::Status(id),
+            (Some("fd"), None, _) => ProcNode::FdDir(id),
+            (Some("fd"), Some(fd), None) => ProcNode::Fd(id, fd.parse().ok()?),
+            _ => return None,
+        };
+        Some(node)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(
+            self,
+            ProcNode::Root | ProcNode::Worker(_) | ProcNode::FdDir(_)
+        )
+    }
+
+    /// Names of the entries of a directory node, given the open descriptors.
+    pub fn children(&self, fds: &[usize]) -> Vec<String> {
+        match self {
+            ProcNode::Root => {
+                let mut names: Vec<String> = ["mounts", "meminfo", "locks", "self"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect();
+                names.extend((0..crate::worker_count()).map(|id| id.to_string()));
+                names
+            }
+            ProcNode::Worker(_) => vec!["fd".to_string(), "status".to_string()],
+            ProcNode::FdDir(_) => fds.iter().map(|fd| fd.to_string()).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+pub fn mounts(block_size: usize, block_count: usize, options: &str) -> String {
+    format!(
+        "littlefs / littlefs rw,{},block_size={},block_count={} 0 0\n\
+         dev /dev devtmpfs rw 0 0\n\
+         proc {} proc ro 0 0\n",
+        options, block_size, block_count, PROC_DIR
+    )
+}
+
+pub fn meminfo() -> String {
+    let pages = crate::host::memory_pages();
+    let mut out = format!(
+        "MemTotal:       {:>10} kB\n\
+         WasmPages:      {:>10}\n\
+         WasmPageSize:   {:>10}\n",
+        pages * 64,
+        pages,
+        65536
+    );
+    if let Some(heap) = crate::heap_stats() {
+        out.push_str(&format!(
+            "HeapAllocated:  {:>10} kB\n\
+             HeapPeak:       {:>10} kB\n\
+             HeapLive:       {:>10}\n\
+             HeapTotal:      {:>10}\n",
+            heap.allocated / 1024,
+            heap.peak / 1024,
+            heap.live,
+            heap.total
+        ));
+    }
+    out
+}
+
+/// One line per lock slot, in the spirit of the Linux format. A free slot
+/// still locked has nobody left to release it and shows as POISONED.
+pub fn locks() -> String {
+    let mut out = String::new();
+    for (id, held, locked) in unsafe { crate::lock::sab_lock_entries() } {
+        let state = match (held, locked) {
+            (_, false) => "UNLOCKED",
+            (true, true) => "LOCKED",
+            (false, true) => "POISONED",
+        };
+        out.push_str(&format!(
+            "{}: SPINLOCK {} {}\n",
+            id,
+            if held { "ACTIVE" } else { "FREE" },
+            state
+        ));
+    }
+    out
+}
+
+pub fn status(worker: usize, fds: &[usize]) -> String {
+    format!(
+        "Name:\twasabio\nPid:\t{}\nSelf:\t{}\nFDSize:\t{}\n",
+        worker,
+        (worker == crate::worker_id()) as u8,
+        fds.len()
+    )
+}
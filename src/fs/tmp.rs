@@ -0,0 +1,53 @@
+//! Temporary entries. Their names get random suffixes that workers racing for
+//! the same prefix cannot collide on, and they can be registered to be removed
+//! the next time the filesystem reboots.
+
+use super::lfs;
+use crate::{guard, host, lock::Lock};
+use once_cell::sync::Lazy;
+
+/// Characters of the suffix, 64 of them so that every one carries 6 bits.
+const SUFFIX_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+/// Length of the suffix, `mkdtemp` in Node adds six characters too.
+pub const SUFFIX_LEN: usize = 6;
+/// Names tried before giving up with EEXIST.
+pub const ATTEMPTS: usize = 100;
+
+static mut CLEANUP: Lazy<Vec<String>> = Lazy::new(Vec::new);
+static mut CLEANUP_LOCK: Lazy<Lock> = Lazy::new(|| Lock::new().unwrap());
+
+pub unsafe fn tmp_diag() {
+    for path in CLEANUP.iter() {
+        host::log(&format!("[WASABIO:TMP] cleanup on reboot: {}", path));
+    }
+}
+
+/// Removes the entries registered with `cleanup_on_reboot`, whatever is left
+/// of them, and forgets about them.
+pub unsafe fn tmp_reset() {
+    CLEANUP_LOCK = Lazy::new(|| Lock::new().unwrap());
+    for path in std::mem::take(&mut *CLEANUP) {
+        if lfs::exists_sync_no_follow(&path) {
+            lfs::rm_sync(&path, true, true).unwrap_or(());
+        }
+    }
+}
+
+/// Six random characters from a cryptographically strong source.
+pub fn suffix() -> String {
+    let mut bytes = [0u8; SUFFIX_LEN];
+    host::random_fill(&mut bytes);
+    bytes
+        .iter()
+        .map(|b| SUFFIX_CHARS[(b & 0x3F) as usize] as char)
+        .collect()
+}
+
+/// Has `path` (recursively, for a directory) removed on the next reboot.
+pub fn cleanup_on_reboot(path: &str) {
+    guard!(CLEANUP_LOCK);
+    let cleanup = unsafe { &mut *CLEANUP };
+    if !cleanup.iter().any(|p| p == path) {
+        cleanup.push(path.to_string());
+    }
+}
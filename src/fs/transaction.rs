@@ -0,0 +1,334 @@
+//! Transactions: changes to several files staged in memory and applied in one
+//! go. Other workers are kept off the disk while they are applied, so they see
+//! either none or all of them. Every new file is written next to its target
+//! and renamed over it, which LittleFS does atomically, and whatever the
+//! transaction replaces or removes is only set aside until it is done. When a
+//! step fails everything done so far is put back.
+
+use super::cwd;
+use super::errno::sys_error;
+use super::lfs::{self, DiskGuard};
+use std::io;
+
+/// A change staged by a transaction, paths are absolute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    WriteFile {
+        path: String,
+        data: Vec<u8>,
+        mode: i32,
+    },
+    Mkdir {
+        path: String,
+        mode: i32,
+    },
+    Rename {
+        old_path: String,
+        new_path: String,
+    },
+    Unlink {
+        path: String,
+    },
+}
+
+impl Op {
+    /// Paths the change shows up at, with whether their contents changed
+    /// (true) or they came or went (false).
+    fn paths(&self) -> Vec<(String, bool)> {
+        match self {
+            Op::WriteFile { path, .. } => vec![(path.clone(), true)],
+            Op::Mkdir { path, .. } | Op::Unlink { path } => vec![(path.clone(), false)],
+            Op::Rename { old_path, new_path } => {
+                vec![(old_path.clone(), false), (new_path.clone(), false)]
+            }
+        }
+    }
+}
+
+/// Changes to apply atomically with `commit`. Nothing touches the disk before
+/// then, dropping the transaction discards them.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    ops: Vec<Op>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates or replaces `path` with `data`. Replaced files keep their mode
+    /// and owner, new ones get `mode` (0o666 by default) minus the umask.
+    pub fn write_file(&mut self, path: &str, data: &[u8], mode: Option<i32>) -> &mut Self {
+        self.ops.push(Op::WriteFile {
+            path: cwd::resolve(path),
+            data: data.to_vec(),
+            mode: mode.unwrap_or(lfs::DEFAULT_PERM_FILE),
+        });
+        self
+    }
+
+    pub fn mkdir(&mut self, path: &str, mode: Option<i32>) -> &mut Self {
+        self.ops.push(Op::Mkdir {
+            path: cwd::resolve(path),
+            mode: mode.unwrap_or(lfs::DEFAULT_PERM_DIR),
+        });
+        self
+    }
+
+    pub fn rename(&mut self, old_path: &str, new_path: &str) -> &mut Self {
+        self.ops.push(Op::Rename {
+            old_path: cwd::resolve(old_path),
+            new_path: cwd::resolve(new_path),
+        });
+        self
+    }
+
+    pub fn unlink(&mut self, path: &str) -> &mut Self {
+        self.ops.push(Op::Unlink {
+            path: cwd::resolve(path),
+        });
+        self
+    }
+
+    /// The staged changes, in the order they apply.
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// Every path the transaction changes, once, with whether only its
+    /// contents changed (true) or it came or went (false).
+    pub fn paths(&self) -> Vec<(String, bool)> {
+        let mut out: Vec<(String, bool)> = vec![];
+        for (path, contents) in self.ops.iter().flat_map(Op::paths) {
+            match out.iter_mut().find(|(seen, _)| *seen == path) {
+                Some(seen) => seen.1 &= contents,
+                None => out.push((path, contents)),
+            }
+        }
+        out
+    }
+
+    /// Applies every change in order while no other worker can see the disk.
+    /// The first one to fail undoes the ones before it and is returned.
+    pub fn commit(self) -> io::Result<()> {
+        for (path, _) in self.paths() {
+            cwd::check(&path)?;
+        }
+        let _disk = DiskGuard::new()?;
+        let mut staged = 0;
+        for op in self.ops.iter() {
+            if let Op::WriteFile { path, data, .. } = op {
+                staged += data.len();
+                lfs::reserve(staged, path, "write")?;
+            }
+        }
+        let mut commit = Commit::default();
+        for op in self.ops.iter() {
+            if let Err(err) = commit.apply(op) {
+                commit.rollback();
+                return Err(err);
+            }
+        }
+        commit.finish();
+        Ok(())
+    }
+
+    /// Discards the staged changes, same as dropping the transaction.
+    pub fn abort(self) {}
+}
+
+/// How to revert one step of a commit.
+#[derive(Debug)]
+enum Undo {
+    /// the step created the node
+    Remove(String),
+    /// the step moved the node at the first path to the second
+    Rename(String, String),
+}
+
+/// A commit in progress, either finished or rolled back once its steps ran.
+#[derive(Debug, Default)]
+struct Commit {
+    undo: Vec<Undo>,
+    /// nodes replaced or removed by the transaction, set aside until it is done
+    set_aside: Vec<String>,
+    /// files whose hard links were removed with them
+    unlinked: Vec<String>,
+}
+
+/// A free name next to `path` to set a node aside or write a new one at.
+/// Nobody sees it, the disk is held until it is gone again.
+fn spare(path: &str) -> String {
+    lfs::spare_name(path, "tx")
+}
+
+fn eio(path: &str, syscall: &str) -> io::Error {
+    sys_error("EIO", "i/o error", path, syscall)
+}
+
+impl Commit {
+    fn apply(&mut self, op: &Op) -> io::Result<()> {
+        match op {
+            Op::WriteFile { path, data, mode } => self.write_file(path, data, *mode),
+            Op::Mkdir { path, mode } => self.mkdir(path, *mode),
+            Op::Rename { old_path, new_path } => self.rename(old_path, new_path),
+            Op::Unlink { path } => self.unlink(path),
+        }
+    }
+
+    /// Moves `path` out of the way until the transaction is done.
+    fn set_aside(&mut self, path: &str, syscall: &str) -> io::Result<()> {
+        let spare = spare(path);
+        lfs::rename_raw(path, &spare).ok_or_else(|| eio(path, syscall))?;
+        self.undo
+            .push(Undo::Rename(path.to_string(), spare.clone()));
+        self.set_aside.push(spare);
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &str, data: &[u8], mode: i32) -> io::Result<()> {
+        let path = lfs::follow_link(path, None);
+        if lfs::is_virtual(&path) || lfs::is_fifo(&path) {
+            let message = "operation not supported in a transaction";
+            return Err(sys_error("EOPNOTSUPP", message, &path, "open"));
+        }
+        if lfs::is_directory(&path) {
+            let message = "illegal operation on a directory";
+            return Err(sys_error("EISDIR", message, &path, "open"));
+        }
+        if !lfs::is_directory(&lfs::path_dirname(&path)) {
+            let message = "no such file or directory";
+            return Err(sys_error("ENOENT", message, &path, "open"));
+        }
+        if lfs::is_open(&path) {
+            return Err(sys_error("EBUSY", "resource busy or locked", &path, "open"));
+        }
+        let temp = spare(&path);
+        if lfs::write_file_sync(&temp, data, Some("wx"), Some(mode)).is_err() {
+            lfs::remove_raw(&temp);
+            return Err(eio(&path, "write"));
+        }
+        if lfs::exists_sync_no_follow(&path) {
+            let attrs = lfs::raw_attrs(&path);
+            let _ = lfs::chmod_sync(&temp, attrs.mode & 0o7777);
+            let _ = lfs::chown_sync(&temp, attrs.uid, attrs.gid);
+            if let Err(err) = self.set_aside(&path, "rename") {
+                lfs::remove_raw(&temp);
+                return Err(err);
+            }
+        }
+        if lfs::rename_raw(&temp, &path).is_none() {
+            lfs::remove_raw(&temp);
+            return Err(eio(&path, "rename"));
+        }
+        self.undo.push(Undo::Remove(path));
+        Ok(())
+    }
+
+    fn mkdir(&mut self, path: &str, mode: i32) -> io::Result<()> {
+        let parent = lfs::path_dirname(&lfs::follow_link(path, None));
+        if !lfs::is_directory(&parent) || lfs::is_virtual(&parent) {
+            let message = "no such file or directory";
+            return Err(sys_error("ENOENT", message, path, "mkdir"));
+        }
+        if let Some(created) = lfs::mkdir_sync(path, false, mode)? {
+            self.undo.push(Undo::Remove(created));
+        }
+        Ok(())
+    }
+
+    fn rename(&mut self, old_path: &str, new_path: &str) -> io::Result<()> {
+        if !lfs::exists_sync_no_follow(old_path) || lfs::is_virtual(old_path) {
+            let message = "no such file or directory";
+            return Err(sys_error("ENOENT", message, old_path, "rename"));
+        }
+        if !lfs::is_directory(&lfs::path_dirname(new_path)) || lfs::is_virtual(new_path) {
+            let message = "no such file or directory";
+            return Err(sys_error("ENOENT", message, new_path, "rename"));
+        }
+        if new_path.starts_with(&format!("{}/", old_path)) {
+            let message = "invalid argument";
+            return Err(sys_error("EINVAL", message, new_path, "rename"));
+        }
+        if lfs::is_open(old_path) || lfs::is_open(new_path) {
+            let message = "resource busy or locked";
+            return Err(sys_error("EBUSY", message, old_path, "rename"));
+        }
+        if old_path == new_path {
+            return Ok(());
+        }
+        if lfs::exists_sync_no_follow(new_path) {
+            let is_dir = lfs::is_directory(old_path);
+            if is_dir && !lfs::is_directory(new_path) {
+                let message = "not a directory";
+                return Err(sys_error("ENOTDIR", message, new_path, "rename"));
+            }
+            if !is_dir && lfs::is_directory(new_path) {
+                let message = "illegal operation on a directory";
+                return Err(sys_error("EISDIR", message, new_path, "rename"));
+            }
+            if is_dir && !lfs::readdir_sync(new_path).is_empty() {
+                let message = "directory not empty";
+                return Err(sys_error("ENOTEMPTY", message, new_path, "rename"));
+            }
+            self.set_aside(new_path, "rename")?;
+        }
+        lfs::rename_sync(old_path, new_path)?;
+        self.undo
+            .push(Undo::Rename(old_path.to_string(), new_path.to_string()));
+        Ok(())
+    }
+
+    /// Sets the file aside along with its hard links, like `unlink_sync`
+    /// removes them all.
+    fn unlink(&mut self, path: &str) -> io::Result<()> {
+        let path = lfs::follow_link(path, None);
+        if !lfs::exists_sync_no_follow(&path) || lfs::is_virtual(&path) {
+            let message = "no such file or directory";
+            return Err(sys_error("ENOENT", message, &path, "unlink"));
+        }
+        if lfs::is_directory(&path) {
+            let message = "illegal operation on a directory";
+            return Err(sys_error("EISDIR", message, &path, "unlink"));
+        }
+        if lfs::is_open(&path) {
+            return Err(sys_error(
+                "EBUSY",
+                "resource busy or locked",
+                &path,
+                "unlink",
+            ));
+        }
+        self.set_aside(&path, "unlink")?;
+        for link in lfs::hard_links(&path) {
+            if lfs::exists_sync_no_follow(&link) {
+                self.set_aside(&link, "unlink")?;
+            }
+        }
+        self.unlinked.push(path);
+        Ok(())
+    }
+
+    /// Throws away what the transaction replaced or removed.
+    fn finish(self) {
+        for path in self.set_aside.iter() {
+            lfs::remove_raw(path);
+        }
+        for path in self.unlinked.iter() {
+            lfs::forget_hard_links(path);
+        }
+    }
+
+    /// Reverts every step done so far, the last one first.
+    fn rollback(self) {
+        for undo in self.undo.iter().rev() {
+            match undo {
+                Undo::Remove(path) => lfs::remove_raw(path),
+                Undo::Rename(from, to) => {
+                    lfs::rename_raw(to, from);
+                }
+            }
+        }
+    }
+}
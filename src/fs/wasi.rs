@@ -0,0 +1,883 @@
+//! `wasi_snapshot_preview1` over the shared filesystem. A `WasiContext` is the
+//! descriptor table of one WASI instance; its methods are the syscalls, they
+//! take the guest's memory and return a WASI errno. The JavaScript side turns
+//! them into an import object.
+//! See: https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md
+
+use super::clock;
+use super::cwd;
+use super::errno::error_code;
+use super::lfs::{self, NodeStats, OpenFlags, Timestamp, S_IFCHR, S_IFDIR, S_IFLNK, S_IFMT, S_IFREG};
+use super::names;
+use super::native::{File, OpenOptions};
+use crate::host;
+use js_sys::{Array, Uint8Array, WebAssembly::Memory};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use wasm_bindgen::prelude::*;
+
+type Errno = u16;
+
+const ESUCCESS: Errno = 0;
+const E2BIG: Errno = 1;
+const EACCES: Errno = 2;
+const EAGAIN: Errno = 6;
+const EBADF: Errno = 8;
+const EBUSY: Errno = 10;
+const EEXIST: Errno = 20;
+const EFAULT: Errno = 21;
+const EINVAL: Errno = 28;
+const EIO: Errno = 29;
+const EISDIR: Errno = 31;
+const ELOOP: Errno = 32;
+const ENAMETOOLONG: Errno = 37;
+const ENOENT: Errno = 44;
+const ENOSPC: Errno = 51;
+const ENOSYS: Errno = 52;
+const ENOTDIR: Errno = 54;
+const ENOTEMPTY: Errno = 55;
+const ENOTSUP: Errno = 58;
+const EPERM: Errno = 63;
+const EPIPE: Errno = 64;
+const ESPIPE: Errno = 70;
+const ENOTCAPABLE: Errno = 76;
+
+const FILETYPE_UNKNOWN: u8 = 0;
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+const FILETYPE_SYMBOLIC_LINK: u8 = 7;
+
+const FDFLAGS_APPEND: u16 = 1;
+const OFLAGS_CREAT: u16 = 1;
+const OFLAGS_DIRECTORY: u16 = 2;
+const OFLAGS_EXCL: u16 = 4;
+const OFLAGS_TRUNC: u16 = 8;
+const LOOKUPFLAGS_SYMLINK_FOLLOW: u32 = 1;
+const FSTFLAGS_ATIM: u16 = 1;
+const FSTFLAGS_ATIM_NOW: u16 = 2;
+const FSTFLAGS_MTIM: u16 = 4;
+const FSTFLAGS_MTIM_NOW: u16 = 8;
+
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+/// every right preview1 defines, descriptors are not restricted any further
+const RIGHTS_ALL: u64 = (1 << 29) - 1;
+
+/// Maps the errno names of the filesystem onto WASI's numbering.
+fn errno(err: &io::Error) -> Errno {
+    match error_code(err).unwrap_or("EIO") {
+        "E2BIG" => E2BIG,
+        "EACCES" => EACCES,
+        "EAGAIN" => EAGAIN,
+        "EBADF" => EBADF,
+        "EBUSY" => EBUSY,
+        "EEXIST" => EEXIST,
+        "EFAULT" => EFAULT,
+        "EINVAL" | "ERR_INVALID_ARG_VALUE" => EINVAL,
+        "EISDIR" => EISDIR,
+        "ELOOP" => ELOOP,
+        "ENAMETOOLONG" => ENAMETOOLONG,
+        "ENOENT" => ENOENT,
+        "ENOSPC" => ENOSPC,
+        "ENOSYS" => ENOSYS,
+        "ENOTDIR" => ENOTDIR,
+        "ENOTEMPTY" => ENOTEMPTY,
+        "EOPNOTSUPP" => ENOTSUP,
+        "EPERM" => EPERM,
+        "EPIPE" => EPIPE,
+        _ => EIO,
+    }
+}
+
+fn done(result: Result<(), Errno>) -> Errno {
+    result.err().unwrap_or(ESUCCESS)
+}
+
+fn filetype(mode: u32) -> u8 {
+    match mode & S_IFMT {
+        S_IFDIR => FILETYPE_DIRECTORY,
+        S_IFREG => FILETYPE_REGULAR_FILE,
+        S_IFLNK => FILETYPE_SYMBOLIC_LINK,
+        S_IFCHR => FILETYPE_CHARACTER_DEVICE,
+        _ => FILETYPE_UNKNOWN,
+    }
+}
+
+/// View of the guest's linear memory. It is taken anew for every call since
+/// the guest may have grown its memory in between.
+struct Guest(Uint8Array);
+
+impl Guest {
+    fn new(memory: &Memory) -> Self {
+        Self(Uint8Array::new(&memory.buffer()))
+    }
+    fn view(&self, ptr: u32, len: u32) -> Result<Uint8Array, Errno> {
+        let end = ptr.checked_add(len).ok_or(EFAULT)?;
+        if end > self.0.length() {
+            return Err(EFAULT);
+        }
+        Ok(self.0.subarray(ptr, end))
+    }
+    fn bytes(&self, ptr: u32, len: u32) -> Result<Vec<u8>, Errno> {
+        Ok(self.view(ptr, len)?.to_vec())
+    }
+    fn set_bytes(&self, ptr: u32, data: &[u8]) -> Result<(), Errno> {
+        self.view(ptr, data.len() as u32)?.copy_from(data);
+        Ok(())
+    }
+    fn u32(&self, ptr: u32) -> Result<u32, Errno> {
+        let mut word = [0; 4];
+        self.view(ptr, 4)?.copy_to(&mut word);
+        Ok(u32::from_le_bytes(word))
+    }
+    fn set_u32(&self, ptr: u32, value: u32) -> Result<(), Errno> {
+        self.set_bytes(ptr, &value.to_le_bytes())
+    }
+    fn set_u64(&self, ptr: u32, value: u64) -> Result<(), Errno> {
+        self.set_bytes(ptr, &value.to_le_bytes())
+    }
+    fn string(&self, ptr: u32, len: u32) -> Result<String, Errno> {
+        String::from_utf8(self.bytes(ptr, len)?).map_err(|_| EINVAL)
+    }
+    /// `(buf, buf_len)` pairs of an iovec array.
+    fn iovs(&self, ptr: u32, len: u32) -> Result<Vec<(u32, u32)>, Errno> {
+        (0..len)
+            .map(|i| Ok((self.u32(ptr + i * 8)?, self.u32(ptr + i * 8 + 4)?)))
+            .collect()
+    }
+    /// Writes strings back to back, each NUL terminated, and their addresses.
+    fn set_strings(&self, ptrs: u32, buf: u32, strings: &[String]) -> Result<(), Errno> {
+        let mut offset = buf;
+        for (i, string) in strings.iter().enumerate() {
+            self.set_u32(ptrs + i as u32 * 4, offset)?;
+            self.set_bytes(offset, string.as_bytes())?;
+            self.set_bytes(offset + string.len() as u32, &[0])?;
+            offset += string.len() as u32 + 1;
+        }
+        Ok(())
+    }
+    fn set_filestat(&self, ptr: u32, stat: &NodeStats) -> Result<(), Errno> {
+        let mut buf = [0u8; 64];
+        buf[0..8].copy_from_slice(&(stat.dev as u64).to_le_bytes());
+        buf[8..16].copy_from_slice(&(stat.ino as u64).to_le_bytes());
+        buf[16] = filetype(stat.mode as u32);
+        buf[24..32].copy_from_slice(&(stat.nlink.max(0) as u64).to_le_bytes());
+        buf[32..40].copy_from_slice(&(stat.size as u64).to_le_bytes());
+        buf[40..48].copy_from_slice(&(stat.atimeNs.max(0) as u64).to_le_bytes());
+        buf[48..56].copy_from_slice(&(stat.mtimeNs.max(0) as u64).to_le_bytes());
+        buf[56..64].copy_from_slice(&(stat.ctimeNs.max(0) as u64).to_le_bytes());
+        self.set_bytes(ptr, &buf)
+    }
+}
+
+#[derive(Debug)]
+struct Descriptor {
+    file: File,
+    filetype: u8,
+    /// name the guest sees for a preopened directory
+    preopen: Option<String>,
+    append: bool,
+}
+
+impl Descriptor {
+    fn path(&self) -> &str {
+        self.file.path()
+    }
+    fn directory(&self) -> Result<&str, Errno> {
+        if self.filetype == FILETYPE_DIRECTORY {
+            Ok(self.path())
+        } else {
+            Err(ENOTDIR)
+        }
+    }
+}
+
+/// Descriptor table and process environment of a WASI instance.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WasiContext {
+    args: Vec<String>,
+    env: Vec<String>,
+    fds: BTreeMap<u32, Descriptor>,
+}
+
+fn strings(array: &Array) -> Vec<String> {
+    array.iter().filter_map(|v| v.as_string()).collect()
+}
+
+fn open(path: &str, options: &OpenOptions) -> Result<Descriptor, Errno> {
+    let file = options.open(path).map_err(|e| errno(&e))?;
+    let stat = file.metadata().map_err(|e| errno(&e))?;
+    Ok(Descriptor {
+        filetype: filetype(stat.mode()),
+        file,
+        preopen: None,
+        append: false,
+    })
+}
+
+/// Joins a guest path onto a directory. Like other runtimes, absolute paths
+/// and ".." above the directory are refused so a preopen stays a sandbox.
+fn resolve(dir: &str, path: &str) -> Result<String, Errno> {
+    if path.starts_with('/') {
+        return Err(ENOTCAPABLE);
+    }
+    let mut depth = 0i32;
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+        if depth < 0 {
+            return Err(ENOTCAPABLE);
+        }
+    }
+    cwd::check(path).map_err(|e| errno(&e))?;
+    Ok(names::resolve(&lfs::path_normalize(&format!("{}/{}", dir, path))))
+}
+
+/// Checks a symlink target like `resolve` checks paths. The target is followed
+/// from the link's directory, so that is where it must not climb out from.
+fn resolve_target(dir: &str, link: &str, target: &str) -> Result<String, Errno> {
+    if target.starts_with('/') {
+        return Err(ENOTCAPABLE);
+    }
+    match link.rfind('/') {
+        Some(slash) => resolve(dir, &format!("{}/{}", &link[..slash], target)),
+        None => resolve(dir, target),
+    }
+}
+
+fn stat(path: &str, follow: bool) -> Result<NodeStats, Errno> {
+    let found = if follow {
+        lfs::exists_sync(path)
+    } else {
+        lfs::exists_sync_no_follow(path)
+    };
+    if !found {
+        return Err(ENOENT);
+    }
+    let stat = if follow {
+        lfs::stat_sync(path)
+    } else {
+        lfs::lstat_sync(path)
+    };
+    stat.map_err(|_| ENOENT)
+}
+
+fn set_times(path: &str, follow: bool, atim: u64, mtim: u64, flags: u16) -> Result<(), Errno> {
+    let current = stat(path, follow)?;
+    let now = clock::now();
+    let pick = |set: u16, now_flag: u16, value: u64, current: i64| {
+        if flags & now_flag != 0 {
+            Timestamp::from_ms(now)
+        } else if flags & set != 0 {
+            Timestamp::from_nanos(value as i128)
+        } else {
+            Timestamp::from_nanos(current as i128)
+        }
+    };
+    let atime = pick(FSTFLAGS_ATIM, FSTFLAGS_ATIM_NOW, atim, current.atimeNs);
+    let mtime = pick(FSTFLAGS_MTIM, FSTFLAGS_MTIM_NOW, mtim, current.mtimeNs);
+    let done = if follow {
+        lfs::utimes_sync(path, atime, mtime)
+    } else {
+        lfs::lutimes_sync(path, atime, mtime)
+    };
+    done.map_err(|_| ENOENT)
+}
+
+impl WasiContext {
+    fn fd(&mut self, fd: u32) -> Result<&mut Descriptor, Errno> {
+        self.fds.get_mut(&fd).ok_or(EBADF)
+    }
+    fn path(&mut self, dirfd: u32, ptr: u32, len: u32, guest: &Guest) -> Result<String, Errno> {
+        let path = guest.string(ptr, len)?;
+        resolve(self.fd(dirfd)?.directory()?, &path)
+    }
+    fn insert(&mut self, descriptor: Descriptor) -> u32 {
+        let fd = (0..).find(|fd| !self.fds.contains_key(fd)).unwrap();
+        self.fds.insert(fd, descriptor);
+        fd
+    }
+}
+
+#[wasm_bindgen]
+impl WasiContext {
+    /// `preopens` is a flat list of pairs: the name the guest sees and the
+    /// directory it maps to. 0, 1 and 2 are /dev/null, /dev/stdout and
+    /// /dev/stderr, the preopens follow from 3.
+    #[wasm_bindgen(constructor)]
+    pub fn new(args: Array, env: Array, preopens: Array) -> Result<WasiContext, JsValue> {
+        let mut ctx = WasiContext {
+            args: strings(&args),
+            env: strings(&env),
+            fds: BTreeMap::new(),
+        };
+        let stdio = [
+            ("/dev/null", OpenOptions::new().read(true).clone()),
+            ("/dev/stdout", OpenOptions::new().write(true).clone()),
+            ("/dev/stderr", OpenOptions::new().write(true).clone()),
+        ];
+        for (path, options) in stdio.iter() {
+            let descriptor = open(path, options).map_err(|_| JsError::new(path))?;
+            ctx.insert(descriptor);
+        }
+        for pair in strings(&preopens).chunks(2) {
+            let (name, dir) = match pair {
+                [name, dir] => (name, dir),
+                _ => return Err(JsError::new("EINVAL: preopens come in pairs").into()),
+            };
+            let mut options = OpenOptions::new();
+            options.read(true).custom_flags(OpenFlags::DIRECTORY);
+            let mut descriptor = open(dir, &options).map_err(|_| {
+                JsError::new(&format!("ENOENT: no such directory to preopen '{}'", dir))
+            })?;
+            descriptor.preopen = Some(name.to_string());
+            ctx.insert(descriptor);
+        }
+        Ok(ctx)
+    }
+
+    pub fn args_get(&self, memory: &Memory, argv: u32, argv_buf: u32) -> Errno {
+        done(Guest::new(memory).set_strings(argv, argv_buf, &self.args))
+    }
+
+    pub fn args_sizes_get(&self, memory: &Memory, argc: u32, argv_buf_size: u32) -> Errno {
+        let guest = Guest::new(memory);
+        let size = self.args.iter().map(|a| a.len() + 1).sum::<usize>();
+        done(
+            guest
+                .set_u32(argc, self.args.len() as u32)
+                .and_then(|_| guest.set_u32(argv_buf_size, size as u32)),
+        )
+    }
+
+    pub fn environ_get(&self, memory: &Memory, environ: u32, environ_buf: u32) -> Errno {
+        done(Guest::new(memory).set_strings(environ, environ_buf, &self.env))
+    }
+
+    pub fn environ_sizes_get(&self, memory: &Memory, count: u32, buf_size: u32) -> Errno {
+        let guest = Guest::new(memory);
+        let size = self.env.iter().map(|e| e.len() + 1).sum::<usize>();
+        done(
+            guest
+                .set_u32(count, self.env.len() as u32)
+                .and_then(|_| guest.set_u32(buf_size, size as u32)),
+        )
+    }
+
+    /// All clocks tick in milliseconds.
+    pub fn clock_res_get(&self, memory: &Memory, _id: u32, resolution: u32) -> Errno {
+        done(Guest::new(memory).set_u64(resolution, 1_000_000))
+    }
+
+    /// Every clock reads the filesystem clock, so a frozen or manual clock is
+    /// what the guest sees too.
+    pub fn clock_time_get(&self, memory: &Memory, _id: u32, _precision: u64, time: u32) -> Errno {
+        done(Guest::new(memory).set_u64(time, nanos(clock::now())))
+    }
+
+    pub fn fd_advise(&mut self, fd: u32, _offset: u64, _len: u64, _advice: u32) -> Errno {
+        done(self.fd(fd).map(|_| ()))
+    }
+
+    pub fn fd_allocate(&mut self, fd: u32, offset: u64, len: u64) -> Errno {
+        done(self.fd(fd).and_then(|d| {
+            lfs::fallocate(d.file.fd(), offset as usize, len as usize, false, false)
+                .map_err(|e| errno(&e))
+        }))
+    }
+
+    pub fn fd_close(&mut self, fd: u32) -> Errno {
+        let descriptor = self.fds.remove(&fd).ok_or(EBADF);
+        done(descriptor.and_then(|d| d.file.close().map_err(|e| errno(&e))))
+    }
+
+    pub fn fd_datasync(&mut self, fd: u32) -> Errno {
+        done(
+            self.fd(fd)
+                .and_then(|d| d.file.sync_data().map_err(|e| errno(&e))),
+        )
+    }
+
+    pub fn fd_sync(&mut self, fd: u32) -> Errno {
+        done(
+            self.fd(fd)
+                .and_then(|d| d.file.sync_all().map_err(|e| errno(&e))),
+        )
+    }
+
+    pub fn fd_fdstat_get(&mut self, memory: &Memory, fd: u32, buf: u32) -> Errno {
+        done(self.fd(fd).and_then(|d| {
+            let mut stat = [0u8; 24];
+            stat[0] = d.filetype;
+            let flags = if d.append { FDFLAGS_APPEND } else { 0 };
+            stat[2..4].copy_from_slice(&flags.to_le_bytes());
+            stat[8..16].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+            stat[16..24].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+            Guest::new(memory).set_bytes(buf, &stat)
+        }))
+    }
+
+    pub fn fd_fdstat_set_flags(&mut self, fd: u32, flags: u16) -> Errno {
+        done(self.fd(fd).map(|d| d.append = flags & FDFLAGS_APPEND != 0))
+    }
+
+    pub fn fd_fdstat_set_rights(&mut self, fd: u32, _base: u64, _inheriting: u64) -> Errno {
+        done(self.fd(fd).map(|_| ()))
+    }
+
+    pub fn fd_filestat_get(&mut self, memory: &Memory, fd: u32, buf: u32) -> Errno {
+        done(self.fd(fd).and_then(|d| {
+            let stat = stat(d.path(), true)?;
+            Guest::new(memory).set_filestat(buf, &stat)
+        }))
+    }
+
+    pub fn fd_filestat_set_size(&mut self, fd: u32, size: u64) -> Errno {
+        done(
+            self.fd(fd)
+                .and_then(|d| d.file.set_len(size).map_err(|e| errno(&e))),
+        )
+    }
+
+    pub fn fd_filestat_set_times(&mut self, fd: u32, atim: u64, mtim: u64, flags: u16) -> Errno {
+        done(
+            self.fd(fd)
+                .and_then(|d| set_times(d.path(), true, atim, mtim, flags)),
+        )
+    }
+
+    pub fn fd_pread(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        offset: u64,
+        nread: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done(self.fd(fd).and_then(|d| {
+            let mut total = 0;
+            for (buf, len) in guest.iovs(iovs, iovs_len)? {
+                let mut data = vec![0; len as usize];
+                let read = d
+                    .file
+                    .read_at(&mut data, offset + total as u64)
+                    .map_err(|e| errno(&e))?;
+                guest.set_bytes(buf, &data[..read])?;
+                total += read;
+                if read < len as usize {
+                    break;
+                }
+            }
+            guest.set_u32(nread, total as u32)
+        }))
+    }
+
+    pub fn fd_pwrite(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        offset: u64,
+        nwritten: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done(self.fd(fd).and_then(|d| {
+            let mut total = 0;
+            for (buf, len) in guest.iovs(iovs, iovs_len)? {
+                let data = guest.bytes(buf, len)?;
+                total += d
+                    .file
+                    .write_at(&data, offset + total as u64)
+                    .map_err(|e| errno(&e))?;
+            }
+            guest.set_u32(nwritten, total as u32)
+        }))
+    }
+
+    pub fn fd_read(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nread: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done(self.fd(fd).and_then(|d| {
+            let mut total = 0;
+            for (buf, len) in guest.iovs(iovs, iovs_len)? {
+                let mut data = vec![0; len as usize];
+                let read = d.file.read(&mut data).map_err(|e| errno(&e))?;
+                guest.set_bytes(buf, &data[..read])?;
+                total += read;
+                if read < len as usize {
+                    break;
+                }
+            }
+            guest.set_u32(nread, total as u32)
+        }))
+    }
+
+    pub fn fd_write(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nwritten: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done(self.fd(fd).and_then(|d| {
+            if d.append && d.filetype == FILETYPE_REGULAR_FILE {
+                d.file.seek(SeekFrom::End(0)).map_err(|e| errno(&e))?;
+            }
+            let mut total = 0;
+            for (buf, len) in guest.iovs(iovs, iovs_len)? {
+                let data = guest.bytes(buf, len)?;
+                d.file.write_all(&data).map_err(|e| errno(&e))?;
+                total += data.len();
+            }
+            guest.set_u32(nwritten, total as u32)
+        }))
+    }
+
+    pub fn fd_prestat_get(&mut self, memory: &Memory, fd: u32, buf: u32) -> Errno {
+        done(self.fd(fd).and_then(|d| {
+            let name = d.preopen.as_ref().ok_or(EBADF)?;
+            let guest = Guest::new(memory);
+            // tag 0 is the only kind of preopen: a directory
+            guest.set_u32(buf, 0)?;
+            guest.set_u32(buf + 4, name.len() as u32)
+        }))
+    }
+
+    pub fn fd_prestat_dir_name(&mut self, memory: &Memory, fd: u32, path: u32, len: u32) -> Errno {
+        done(self.fd(fd).and_then(|d| {
+            let name = d.preopen.as_ref().ok_or(EBADF)?;
+            let name = &name.as_bytes()[..name.len().min(len as usize)];
+            Guest::new(memory).set_bytes(path, name)
+        }))
+    }
+
+    /// Entries are ".", ".." and then the listing; the cookie is an index.
+    pub fn fd_readdir(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        buf: u32,
+        buf_len: u32,
+        cookie: u64,
+        bufused: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done(self.fd(fd).and_then(|d| {
+            let dir = d.directory()?.to_string();
+            let mut entries = vec![
+                (".".to_string(), FILETYPE_DIRECTORY),
+                ("..".to_string(), FILETYPE_DIRECTORY),
+            ];
+            for ent in lfs::readdir_sync(&dir) {
+                let kind = if ent.symlink {
+                    FILETYPE_SYMBOLIC_LINK
+                } else if ent.device {
+                    FILETYPE_CHARACTER_DEVICE
+                } else if ent.fifo {
+                    FILETYPE_UNKNOWN
+                } else if ent.file {
+                    FILETYPE_REGULAR_FILE
+                } else {
+                    FILETYPE_DIRECTORY
+                };
+                entries.push((ent.name, kind));
+            }
+            let mut out = vec![];
+            for (i, (name, kind)) in entries.iter().enumerate().skip(cookie as usize) {
+                let ino = stat(&lfs::path_normalize(&format!("{}/{}", dir, name)), false)
+                    .map_or(0, |s| s.ino as u64);
+                out.extend_from_slice(&(i as u64 + 1).to_le_bytes());
+                out.extend_from_slice(&ino.to_le_bytes());
+                out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+                out.extend_from_slice(&[*kind, 0, 0, 0]);
+                out.extend_from_slice(name.as_bytes());
+                if out.len() >= buf_len as usize {
+                    break;
+                }
+            }
+            // a full buffer tells the guest to call again from the last cookie
+            out.truncate(buf_len as usize);
+            guest.set_bytes(buf, &out)?;
+            guest.set_u32(bufused, out.len() as u32)
+        }))
+    }
+
+    pub fn fd_renumber(&mut self, fd: u32, to: u32) -> Errno {
+        done(self.fds.remove(&fd).ok_or(EBADF).map(|d| {
+            self.fds.insert(to, d);
+        }))
+    }
+
+    pub fn fd_seek(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        offset: i64,
+        whence: u8,
+        newoffset: u32,
+    ) -> Errno {
+        done(self.fd(fd).and_then(|d| {
+            if d.filetype != FILETYPE_REGULAR_FILE {
+                return Err(ESPIPE);
+            }
+            let pos = match whence {
+                0 if offset >= 0 => SeekFrom::Start(offset as u64),
+                1 => SeekFrom::Current(offset),
+                2 => SeekFrom::End(offset),
+                _ => return Err(EINVAL),
+            };
+            let pos = d.file.seek(pos).map_err(|e| errno(&e))?;
+            Guest::new(memory).set_u64(newoffset, pos)
+        }))
+    }
+
+    pub fn fd_tell(&mut self, memory: &Memory, fd: u32, offset: u32) -> Errno {
+        self.fd_seek(memory, fd, 0, 1, offset)
+    }
+
+    pub fn path_create_directory(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        path: u32,
+        len: u32,
+    ) -> Errno {
+        done(
+            self.path(fd, path, len, &Guest::new(memory))
+                .and_then(|path| {
+                    if lfs::exists_sync_no_follow(&path) {
+                        return Err(EEXIST);
+                    }
+                    lfs::mkdir_sync(&path, false, lfs::DEFAULT_PERM_DIR)
+                        .map(drop)
+                        .map_err(|e| errno(&e))
+                }),
+        )
+    }
+
+    pub fn path_filestat_get(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        flags: u32,
+        path: u32,
+        len: u32,
+        buf: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done(self.path(fd, path, len, &guest).and_then(|path| {
+            let stat = stat(&path, flags & LOOKUPFLAGS_SYMLINK_FOLLOW != 0)?;
+            guest.set_filestat(buf, &stat)
+        }))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn path_filestat_set_times(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        flags: u32,
+        path: u32,
+        len: u32,
+        atim: u64,
+        mtim: u64,
+        fst_flags: u16,
+    ) -> Errno {
+        done(
+            self.path(fd, path, len, &Guest::new(memory))
+                .and_then(|path| {
+                    let follow = flags & LOOKUPFLAGS_SYMLINK_FOLLOW != 0;
+                    set_times(&path, follow, atim, mtim, fst_flags)
+                }),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn path_link(
+        &mut self,
+        memory: &Memory,
+        old_fd: u32,
+        _old_flags: u32,
+        old_path: u32,
+        old_len: u32,
+        new_fd: u32,
+        new_path: u32,
+        new_len: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done((|| {
+            let old_path = self.path(old_fd, old_path, old_len, &guest)?;
+            let new_path = self.path(new_fd, new_path, new_len, &guest)?;
+            lfs::link_sync(&old_path, &new_path).map_err(|e| errno(&e))
+        })())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn path_open(
+        &mut self,
+        memory: &Memory,
+        dirfd: u32,
+        dirflags: u32,
+        path: u32,
+        len: u32,
+        oflags: u16,
+        rights: u64,
+        _inheriting: u64,
+        fdflags: u16,
+        opened: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done((|| {
+            let path = self.path(dirfd, path, len, &guest)?;
+            let mut custom = 0;
+            if dirflags & LOOKUPFLAGS_SYMLINK_FOLLOW == 0 {
+                custom |= OpenFlags::NOFOLLOW;
+            }
+            let mut options = OpenOptions::new();
+            let directory = oflags & OFLAGS_DIRECTORY != 0 || lfs::is_directory(&path);
+            if directory {
+                options
+                    .read(true)
+                    .custom_flags(custom | OpenFlags::DIRECTORY);
+            } else {
+                let append = fdflags & FDFLAGS_APPEND != 0;
+                let write = rights & RIGHTS_FD_WRITE != 0 || append;
+                options
+                    .read(rights & RIGHTS_FD_READ != 0 || !write)
+                    .write(write)
+                    .append(append)
+                    .create(oflags & OFLAGS_CREAT != 0)
+                    .create_new(oflags & OFLAGS_CREAT != 0 && oflags & OFLAGS_EXCL != 0)
+                    .truncate(oflags & OFLAGS_TRUNC != 0)
+                    .custom_flags(custom);
+            }
+            let mut descriptor = open(&path, &options)?;
+            descriptor.append = fdflags & FDFLAGS_APPEND != 0;
+            let fd = self.insert(descriptor);
+            guest.set_u32(opened, fd)
+        })())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn path_readlink(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        path: u32,
+        len: u32,
+        buf: u32,
+        buf_len: u32,
+        bufused: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done(self.path(fd, path, len, &guest).and_then(|path| {
+            let target = lfs::readlink_sync(&path).map_err(|e| errno(&e))?;
+            let target = &target.as_bytes()[..target.len().min(buf_len as usize)];
+            guest.set_bytes(buf, target)?;
+            guest.set_u32(bufused, target.len() as u32)
+        }))
+    }
+
+    pub fn path_remove_directory(
+        &mut self,
+        memory: &Memory,
+        fd: u32,
+        path: u32,
+        len: u32,
+    ) -> Errno {
+        done(
+            self.path(fd, path, len, &Guest::new(memory))
+                .and_then(|path| {
+                    if !lfs::exists_sync_no_follow(&path) {
+                        return Err(ENOENT);
+                    }
+                    lfs::rmdir_sync(&path, None).map_err(|e| errno(&e))
+                }),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn path_rename(
+        &mut self,
+        memory: &Memory,
+        old_fd: u32,
+        old_path: u32,
+        old_len: u32,
+        new_fd: u32,
+        new_path: u32,
+        new_len: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done((|| {
+            let old_path = self.path(old_fd, old_path, old_len, &guest)?;
+            let new_path = self.path(new_fd, new_path, new_len, &guest)?;
+            if !lfs::exists_sync_no_follow(&old_path) {
+                return Err(ENOENT);
+            }
+            lfs::rename_sync(&old_path, &new_path).map_err(|e| errno(&e))
+        })())
+    }
+
+    pub fn path_symlink(
+        &mut self,
+        memory: &Memory,
+        old_path: u32,
+        old_len: u32,
+        fd: u32,
+        new_path: u32,
+        new_len: u32,
+    ) -> Errno {
+        let guest = Guest::new(memory);
+        done((|| {
+            let target = guest.string(old_path, old_len)?;
+            let name = guest.string(new_path, new_len)?;
+            let dir = self.fd(fd)?.directory()?.to_string();
+            let link = resolve(&dir, &name)?;
+            resolve_target(&dir, &name, &target)?;
+            lfs::symlink_sync(&target, &link).map_err(|e| errno(&e))
+        })())
+    }
+
+    pub fn path_unlink_file(&mut self, memory: &Memory, fd: u32, path: u32, len: u32) -> Errno {
+        done(
+            self.path(fd, path, len, &Guest::new(memory))
+                .and_then(|path| {
+                    if !lfs::exists_sync_no_follow(&path) {
+                        return Err(ENOENT);
+                    }
+                    lfs::unlink_sync(&path, None).map_err(|e| errno(&e))
+                }),
+        )
+    }
+
+    pub fn random_get(&self, memory: &Memory, buf: u32, len: u32) -> Errno {
+        let mut data = vec![0; len as usize];
+        host::random_fill(&mut data);
+        done(Guest::new(memory).set_bytes(buf, &data))
+    }
+
+    pub fn sched_yield(&self) -> Errno {
+        ESUCCESS
+    }
+
+    /// Waiting on descriptors or timers would block the worker, not supported.
+    pub fn poll_oneoff(&self) -> Errno {
+        ENOSYS
+    }
+}
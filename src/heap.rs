@@ -0,0 +1,50 @@
+use crate::HeapStats;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts what goes through the system allocator so it can be inspected.
+struct CountingAllocator;
+
+static HEAP_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static HEAP_PEAK: AtomicUsize = AtomicUsize::new(0);
+static HEAP_LIVE: AtomicUsize = AtomicUsize::new(0);
+static HEAP_TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let now = HEAP_ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            HEAP_PEAK.fetch_max(now, Ordering::Relaxed);
+            HEAP_LIVE.fetch_add(1, Ordering::Relaxed);
+            HEAP_TOTAL.fetch_add(1, Ordering::Relaxed);
+        }
+        ptr
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        HEAP_ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        HEAP_LIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            HEAP_ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            let now = HEAP_ALLOCATED.fetch_add(new_size, Ordering::Relaxed) + new_size;
+            HEAP_PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        new
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+pub fn stats() -> HeapStats {
+    HeapStats {
+        allocated: HEAP_ALLOCATED.load(Ordering::Relaxed),
+        peak: HEAP_PEAK.load(Ordering::Relaxed),
+        live: HEAP_LIVE.load(Ordering::Relaxed),
+        total: HEAP_TOTAL.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,99 @@
+//! What the cores need from whatever is running them. In the browser that is
+//! JavaScript; built for the host (`cargo test`) it is the standard library.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Milliseconds since the Unix epoch, like `Date.now()`.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let since = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since.as_micros() / 1000) as f64
+}
+
+/// Prints a diagnostic line (`console.log`).
+#[cfg(target_arch = "wasm32")]
+pub fn log(message: &str) {
+    web_sys::console::log_1(&message.into());
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn log(message: &str) {
+    println!("{}", message);
+}
+
+/// Prints an error line (`console.error`).
+#[cfg(target_arch = "wasm32")]
+pub fn error(message: &str) {
+    web_sys::console::error_1(&message.into());
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn error(message: &str) {
+    eprintln!("{}", message);
+}
+
+/// Pages of 64KiB currently backing the linear memory, 0 off WebAssembly.
+#[cfg(target_arch = "wasm32")]
+pub fn memory_pages() -> usize {
+    core::arch::wasm32::memory_size(0)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn memory_pages() -> usize {
+    0
+}
+
+/// Sleeps until `word` no longer holds `expected` and `notify` is called on it,
+/// like `Atomics.wait`. Browsers do not let their main thread block.
+#[cfg(target_arch = "wasm32")]
+pub fn wait(word: &AtomicI32, expected: i32) {
+    let ptr = word as *const AtomicI32 as *mut i32;
+    unsafe { core::arch::wasm32::memory_atomic_wait32(ptr, expected, -1) };
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn wait(word: &AtomicI32, expected: i32) {
+    while word.load(Ordering::SeqCst) == expected {
+        std::thread::yield_now();
+    }
+}
+
+/// Wakes everyone sleeping in `wait` on `word`, like `Atomics.notify`.
+#[cfg(target_arch = "wasm32")]
+pub fn notify(word: &AtomicI32) {
+    let ptr = word as *const AtomicI32 as *mut i32;
+    unsafe { core::arch::wasm32::memory_atomic_notify(ptr, u32::MAX) };
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn notify(_: &AtomicI32) {}
+
+/// Fills `buf` with cryptographically strong random bytes.
+#[cfg(target_arch = "wasm32")]
+pub fn random_fill(buf: &mut [u8]) {
+    use js_sys::{Function, Reflect, Uint8Array};
+    use wasm_bindgen::JsCast;
+    // `getRandomValues` refuses views of shared memory, so the bytes are
+    // produced in a private buffer and copied over.
+    let scratch = Uint8Array::new_with_length(buf.len() as u32);
+    let crypto = Reflect::get(&js_sys::global(), &"crypto".into()).unwrap();
+    let fill = Reflect::get(&crypto, &"getRandomValues".into())
+        .unwrap()
+        .unchecked_into::<Function>();
+    fill.call1(&crypto, &scratch).unwrap();
+    scratch.copy_to(buf);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn random_fill(buf: &mut [u8]) {
+    use std::io::Read;
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(buf))
+        .unwrap();
+}
@@ -9,13 +9,13 @@ import init, {
 	wasabio_diag,
 	NodeStats,
 	Dirent,
-	StatFs,
 	sab_kv_set,
 	sab_kv_get,
 	sab_kv_del,
 	sab_kv_key,
 	sab_kv_clear,
 	sab_kv_length,
+	wasabio_snapshot_header,
 } from "../pkg";
 // @ts-ignore - handled by webpack, turns into base64
 import WASM_BASE64 from "../pkg/wasabio_bg.wasm";
@@ -106,6 +106,12 @@ function copyUint8ArrayToWebAssemblyMemory(buffer: Uint8Array): WebAssembly.Memo
 export interface InitializeOptions {
 	readonly sync?: boolean;
 	readonly reboot?: boolean;
+	/** shared clock to start with, see `setClock()` */
+	readonly clock?: ClockOptions;
+	/** when accesses update the atime, see `atimePolicy()` */
+	readonly atime?: AtimePolicy;
+	/** which names refer to the same entry, see `nameMatching()`. a reboot goes back to exact matching first */
+	readonly names?: NameMatchingOptions;
 }
 
 let MEMORY: WebAssembly.Memory | undefined;
@@ -115,16 +121,100 @@ export function memory(): WebAssembly.Memory | undefined {
 	return MEMORY;
 }
 
+const SNAPSHOT_MAGIC = "WASABIO\0";
+const SNAPSHOT_FORMAT = 1;
+const SNAPSHOT_PAGE_SIZE = 64 * 1024;
+
+/** 64-bit FNV-1a of `data`, as the low and high 32 bits. */
+function fnv1a(data: Uint8Array): [number, number] {
+	let lo = 0x84222325;
+	let hi = 0xcbf29ce4;
+	for (let i = 0; i < data.length; i++) {
+		lo = (lo ^ data[i]) >>> 0;
+		// multiplies by the prime 0x100000001b3, keeping the low 64 bits
+		const low = lo * 0x1b3;
+		hi = (Math.imul(hi, 0x1b3) + (lo << 8) + Math.floor(low / 0x100000000)) >>> 0;
+		lo = low >>> 0;
+	}
+	return [lo, hi];
+}
+
+interface VerifiedSnapshot {
+	/** where the image starts */
+	readonly offset: number;
+	/** address of the thread counter in the build that took it */
+	readonly threadCounter: number;
+}
+
+/**
+ * Checks the header and every section checksum of a snapshot, undefined for a
+ * raw memory image. This runs before the image is copied into memory or the
+ * module is instantiated on it, so it mirrors `decode` in snapshot.rs instead
+ * of calling into the module.
+ */
+function verifySnapshot(buffer: Uint8Array): VerifiedSnapshot | undefined {
+	const text = (from: number, to: number) => String.fromCharCode(...buffer.subarray(from, to));
+	if (buffer.byteLength < 20 || text(0, 8) !== SNAPSHOT_MAGIC) return undefined;
+	const view = new DataView(buffer.buffer, buffer.byteOffset, buffer.byteLength);
+	const checksumAt = (at: number, [lo, hi]: [number, number]) =>
+		view.getUint32(at, true) === lo && view.getUint32(at + 4, true) === hi;
+	const format = view.getUint32(8, true);
+	if (format > SNAPSHOT_FORMAT) {
+		throw new Error(`EOPNOTSUPP: snapshot format v${format} is newer than v${SNAPSHOT_FORMAT}`);
+	}
+	if (format < SNAPSHOT_FORMAT) {
+		throw new Error(`EOPNOTSUPP: snapshot format v${format} is no longer supported`);
+	}
+	const len = view.getUint32(12, true);
+	const buildLen = view.getUint32(16, true);
+	if (len < 20 + buildLen + 28 || len > buffer.byteLength) {
+		throw new Error("EINVAL: snapshot header is truncated");
+	}
+	const build = text(20, 20 + buildLen);
+	if (build !== METADATA.checksum) {
+		throw new Error(`EOPNOTSUPP: snapshot was taken by build ${build}, this is build ${METADATA.checksum}`);
+	}
+	if (!checksumAt(len - 8, fnv1a(buffer.subarray(0, len - 8)))) {
+		throw new Error("EIO: snapshot header is corrupt");
+	}
+	let at = 20 + buildLen;
+	const field = () => ((at += 4), view.getUint32(at - 4, true));
+	if (field() !== SNAPSHOT_PAGE_SIZE) throw new Error("EOPNOTSUPP: snapshot uses a different page size");
+	const end = len + field() * SNAPSHOT_PAGE_SIZE;
+	const threadCounter = field();
+	const sectionSize = field();
+	const count = field();
+	if (sectionSize === 0 || at + count * 8 !== len - 8 || count !== Math.ceil((end - len) / sectionSize)) {
+		throw new Error("EINVAL: snapshot sections do not cover its pages");
+	}
+	if (buffer.byteLength !== end) throw new Error("EINVAL: snapshot image is truncated");
+	for (let index = 0, start = len; start < end; index++, start += sectionSize) {
+		const stop = Math.min(start + sectionSize, end);
+		if (!checksumAt(at + index * 8, fnv1a(buffer.subarray(start, stop)))) {
+			throw new Error(`EIO: snapshot section ${index} (${start - len}..${stop - len}) is corrupt`);
+		}
+	}
+	return { offset: len, threadCounter };
+}
+
 export function initialize(
 	mem?: WebAssembly.Memory | Uint8Array,
 	opts?: InitializeOptions,
 ): Promise<WebAssembly.Memory> | WebAssembly.Memory {
 	if (MEMORY) return MEMORY;
+	let snapshot: VerifiedSnapshot | undefined;
 	if (ArrayBuffer.isView(mem)) {
-		mem = copyUint8ArrayToWebAssemblyMemory(mem);
+		snapshot = verifySnapshot(mem);
+		mem = copyUint8ArrayToWebAssemblyMemory(mem.subarray(snapshot?.offset ?? 0));
 	}
 	const post = (memory: WebAssembly.Memory, address: number) => {
+		if (snapshot && snapshot.threadCounter !== address) {
+			throw new Error("EOPNOTSUPP: snapshot memory layout does not match this build");
+		}
 		if (opts?.reboot) wasabio_reboot();
+		if (opts?.clock) setClock(opts.clock);
+		if (opts?.atime) atimePolicy(opts.atime);
+		if (opts?.names) nameMatching(opts.names);
 		THREAD_COUNTER_ADDRESS = address;
 		MEMORY = memory;
 		return memory;
@@ -142,12 +232,19 @@ export function initialize(
 	}
 }
 
+/**
+ * Takes a snapshot of `memory`: a header naming this build, its memory layout
+ * and checksums, followed by a copy of the memory with the thread counter reset.
+ */
 export function serialize(memory: WebAssembly.Memory): Uint8Array {
+	ok(THREAD_COUNTER_ADDRESS !== undefined, "not initialized");
 	const data = copyWebAssemblyMemoryToUint8Array(memory);
-	if (THREAD_COUNTER_ADDRESS === undefined) return data;
-	const view = new DataView(memory.buffer);
-	view.setInt32(THREAD_COUNTER_ADDRESS, 0);
-	return data;
+	new DataView(data.buffer).setInt32(THREAD_COUNTER_ADDRESS, 0, true);
+	const header = wasabio_snapshot_header(METADATA.checksum, THREAD_COUNTER_ADDRESS, data);
+	const snapshot = new Uint8Array(header.byteLength + data.byteLength);
+	snapshot.set(header);
+	snapshot.set(data, header.byteLength);
+	return snapshot;
 }
 
 export async function compress(buffer: Uint8Array): Promise<Uint8Array> {
@@ -158,7 +255,16 @@ export async function compress(buffer: Uint8Array): Promise<Uint8Array> {
 	return zipBuffer;
 }
 
+/**
+ * Turns a snapshot into memory to `initialize()` with. Snapshots of other builds
+ * and corrupt ones are refused before anything is copied.
+ */
 export function deserialize(buffer: Uint8Array): WebAssembly.Memory {
+	const snapshot = verifySnapshot(buffer);
+	if (snapshot && THREAD_COUNTER_ADDRESS !== undefined && snapshot.threadCounter !== THREAD_COUNTER_ADDRESS) {
+		throw new Error("EOPNOTSUPP: snapshot memory layout does not match this build");
+	}
+	buffer = buffer.subarray(snapshot?.offset ?? 0);
 	const memory = new WebAssembly.Memory({ initial: 18, maximum: 16384, shared: true });
 	while (memory.buffer.byteLength < buffer.byteLength) memory.grow(1);
 	const memoryView = new Uint8Array(memory.buffer);
@@ -189,13 +295,17 @@ export function diagnostics(): void {
 	else throw new Error("not initialized");
 }
 
-function decodeWasmFromBase64String(encoded: string) {
+function decodeBase64(encoded: string) {
 	const binaryString = atob(encoded);
 	const bytes = new Uint8Array(binaryString.length);
 	for (let i = 0; i < binaryString.length; i++) {
 		bytes[i] = binaryString.charCodeAt(i);
 	}
-	return bytes.buffer;
+	return bytes;
+}
+
+function decodeWasmFromBase64String(encoded: string) {
+	return decodeBase64(encoded).buffer;
 }
 
 interface BusDatum {
@@ -353,22 +463,76 @@ export class EventEmitter implements IEventEmitter {
 	}
 }
 
-const INVALID_PATH_ERROR_MESSAGE = "ERR_INVALID_PATH_TYPE";
 
 export const F_OK = 0;
 export const X_OK = 1;
 export const W_OK = 2;
 export const R_OK = 4;
 
+/** same values Node reports on Linux */
 export namespace constants {
+	// file access
+	export const F_OK = 0;
+	export const X_OK = 1;
+	export const W_OK = 2;
+	export const R_OK = 4;
+	// file open
 	export const O_RDONLY = 0;
 	export const O_WRONLY = 1;
 	export const O_RDWR = 2;
 	export const O_CREAT = 64;
 	export const O_EXCL = 128;
+	export const O_NOCTTY = 256;
 	export const O_TRUNC = 512;
 	export const O_APPEND = 1024;
+	export const O_NONBLOCK = 2048;
+	export const O_DSYNC = 4096;
+	export const O_DIRECT = 16384;
+	export const O_DIRECTORY = 65536;
+	export const O_NOFOLLOW = 131072;
+	export const O_NOATIME = 262144;
 	export const O_SYNC = 1052672;
+	export const UV_FS_O_FILEMAP = 0;
+	// file type
+	export const S_IFMT = 0o170000;
+	export const S_IFREG = 0o100000;
+	export const S_IFDIR = 0o040000;
+	export const S_IFCHR = 0o020000;
+	export const S_IFBLK = 0o060000;
+	export const S_IFIFO = 0o010000;
+	export const S_IFLNK = 0o120000;
+	export const S_IFSOCK = 0o140000;
+	// file mode
+	export const S_IRWXU = 0o700;
+	export const S_IRUSR = 0o400;
+	export const S_IWUSR = 0o200;
+	export const S_IXUSR = 0o100;
+	export const S_IRWXG = 0o070;
+	export const S_IRGRP = 0o040;
+	export const S_IWGRP = 0o020;
+	export const S_IXGRP = 0o010;
+	export const S_IRWXO = 0o007;
+	export const S_IROTH = 0o004;
+	export const S_IWOTH = 0o002;
+	export const S_IXOTH = 0o001;
+	// file copy
+	export const COPYFILE_EXCL = 1;
+	export const COPYFILE_FICLONE = 2;
+	export const COPYFILE_FICLONE_FORCE = 4;
+	export const UV_FS_COPYFILE_EXCL = COPYFILE_EXCL;
+	export const UV_FS_COPYFILE_FICLONE = COPYFILE_FICLONE;
+	export const UV_FS_COPYFILE_FICLONE_FORCE = COPYFILE_FICLONE_FORCE;
+	// symlink and dirent
+	export const UV_FS_SYMLINK_DIR = 1;
+	export const UV_FS_SYMLINK_JUNCTION = 2;
+	export const UV_DIRENT_UNKNOWN = 0;
+	export const UV_DIRENT_FILE = 1;
+	export const UV_DIRENT_DIR = 2;
+	export const UV_DIRENT_LINK = 3;
+	export const UV_DIRENT_FIFO = 4;
+	export const UV_DIRENT_SOCKET = 5;
+	export const UV_DIRENT_CHAR = 6;
+	export const UV_DIRENT_BLOCK = 7;
 }
 
 export enum OpenMode {
@@ -394,31 +558,72 @@ export enum OpenMode {
 
 type encoding = "utf8" | "utf-8" | "buffer";
 
+/** an error carrying one of Node's `ERR_*` codes */
+function codedError(type: ErrorConstructor, code: string, message: string): Error {
+	const err: any = new type(message);
+	err.code = code;
+	return err;
+}
+
+/** the path of a `file:` URL, as `url.fileURLToPath()` gives it on Linux */
+function fileUrlToPath(url: URL): string {
+	if (url.protocol !== "file:") throw codedError(TypeError, "ERR_INVALID_URL_SCHEME", "The URL must be of scheme file");
+	if (url.hostname !== "" && url.hostname !== "localhost")
+		throw codedError(TypeError, "ERR_INVALID_FILE_URL_HOST", 'File URL host must be "localhost" or empty on linux');
+	if (/%2f/i.test(url.pathname))
+		throw codedError(TypeError, "ERR_INVALID_FILE_URL_PATH", "File URL path must not include encoded / characters");
+	return decodeURIComponent(url.pathname);
+}
+
+import { checkPath as _checkPath } from "../pkg";
+/**
+ * turns any path Node takes (string, Buffer or Uint8Array, `file:` URL) into a string and
+ * checks it before it gets near the disk: NUL bytes throw ERR_INVALID_ARG_VALUE, names
+ * longer than LittleFS takes and paths longer than PATH_MAX throw ENAMETOOLONG.
+ */
 function normalizePathLikeToString(path: fs.PathLike): string {
-	let p: string | undefined;
+	let p: string;
 	if (typeof path === "string") p = path;
-	if (Buffer.isBuffer(path)) p = path.toString();
-	p = p?.replace(/\\/g, "/");
-	if (!p?.startsWith("/")) p = `/${p}`;
-	if (p) return p;
-	throw new Error(INVALID_PATH_ERROR_MESSAGE);
+	else if (path instanceof Uint8Array) p = Buffer.from(path.buffer, path.byteOffset, path.byteLength).toString();
+	else if (path instanceof URL) p = fileUrlToPath(path);
+	else {
+		const message = `The "path" argument must be of type string or an instance of Buffer or URL. Received ${String(path)}`;
+		throw codedError(TypeError, "ERR_INVALID_ARG_TYPE", message);
+	}
+	if (p === "") throw Object.assign(codedError(Error, "ENOENT", "ENOENT: no such file or directory"), { path: p });
+	if (p.includes("\0")) {
+		const message = `The argument 'path' must be a string, Uint8Array, or URL without null bytes. Received ${JSON.stringify(p)}`;
+		throw codedError(TypeError, "ERR_INVALID_ARG_VALUE", message);
+	}
+	p = p.replace(/\\/g, "/");
+	_checkPath(p);
+	return p;
 }
 
-function timeLikeToSeconds(time: fs.TimeLike): number {
-	return typeof time === "number" ? time : (typeof time === "string" ? Date.parse(time) : time.getTime()) / 1000;
+/** resolves relative paths against the working directory of this worker */
+function absolutePathLike(path: fs.PathLike): string {
+	const segments: string[] = [];
+	const p = normalizePathLikeToString(path);
+	for (const segment of (p.startsWith("/") ? p : `${cwd()}/${p}`).split("/")) {
+		if (segment === "..") segments.pop();
+		else if (segment && segment !== ".") segments.push(segment);
+	}
+	return `/${segments.join("/")}`;
 }
 
-function openModeLikeToString(flags?: fs.OpenMode): string {
-	flags = flags ?? "r";
-	flags =
-		typeof flags === "number"
-			? (Object.keys(OpenMode).find((key) => (OpenMode as any)[key] === flags) as string)
-			: flags;
-	return flags;
+/** seconds since the epoch, taking the same time arguments as Node */
+function timeLikeToSeconds(time: fs.TimeLike): number {
+	// numeric strings, and only those, are seconds too
+	if (typeof time === "string" && +time == (time as any)) return +time;
+	if (typeof time === "number" && Number.isFinite(time)) return time < 0 ? Date.now() / 1000 : time;
+	if (time instanceof Date) return time.getTime() / 1000;
+	const message = `The "time" argument must be of type Date or Time in seconds. Received ${String(time)}`;
+	throw codedError(TypeError, "ERR_INVALID_ARG_TYPE", message);
 }
 
-function moveNodeStatsToJsMemory(rustStatStruct?: NodeStats): fs.Stats | undefined {
+function moveNodeStatsToJsMemory(rustStatStruct?: NodeStats, options?: { bigint?: boolean }): any {
 	if (!rustStatStruct) return undefined;
+	if (options?.bigint) return moveBigIntStatsToJsMemory(rustStatStruct);
 	const _isDirectory = rustStatStruct.isDirectory();
 	const _isFile = rustStatStruct.isFile();
 	const _isBlockDevice = rustStatStruct.isBlockDevice();
@@ -457,6 +662,24 @@ function moveNodeStatsToJsMemory(rustStatStruct?: NodeStats): fs.Stats | undefin
 	return clone;
 }
 
+/** the `bigint: true` flavour of stats, which carries the times to the nanosecond */
+function moveBigIntStatsToJsMemory(rustStatStruct: NodeStats): fs.BigIntStats {
+	const times: Record<string, bigint> = {
+		atime: rustStatStruct.atimeNs,
+		mtime: rustStatStruct.mtimeNs,
+		ctime: rustStatStruct.ctimeNs,
+		birthtime: rustStatStruct.birthtimeNs,
+	};
+	const stats = moveNodeStatsToJsMemory(rustStatStruct);
+	for (const key of ["dev", "ino", "mode", "nlink", "uid", "gid", "rdev", "size", "blksize", "blocks"])
+		stats[key] = BigInt(stats[key]);
+	for (const [time, ns] of Object.entries(times)) {
+		stats[`${time}Ns`] = ns;
+		stats[`${time}Ms`] = ns / BigInt(1000000);
+	}
+	return stats as fs.BigIntStats;
+}
+
 function moveDirentToJsMemory(rustDirentStruct?: Dirent): fs.Dirent | undefined {
 	if (!rustDirentStruct) return undefined;
 	const _isDirectory = rustDirentStruct.isDirectory();
@@ -481,34 +704,28 @@ function moveDirentToJsMemory(rustDirentStruct?: Dirent): fs.Dirent | undefined
 	return clone;
 }
 
-function moveStatFsToJsMemory(rustStatFsStruct: StatFs): fs.StatsFs {
-	const clone = {
-		bfree: rustStatFsStruct.bfree,
-		bavail: rustStatFsStruct.bavail,
-		blocks: rustStatFsStruct.blocks,
-		bsize: rustStatFsStruct.bsize,
-		ffree: rustStatFsStruct.ffree,
-		files: rustStatFsStruct.files,
-		json: rustStatFsStruct.json,
-	};
-	rustStatFsStruct.free();
-	return clone as unknown as fs.StatsFs;
-}
-
 // re-export all of the fs functions
-import { linkSync } from "../pkg";
-export { linkSync };
-import { symlinkSync } from "../pkg";
-export { symlinkSync };
+import { linkSync as _linkSync } from "../pkg";
+export function linkSync(existingPath: fs.PathLike, newPath: fs.PathLike): void {
+	_linkSync(normalizePathLikeToString(existingPath), normalizePathLikeToString(newPath));
+}
+import { symlinkSync as _symlinkSync } from "../pkg";
+export function symlinkSync(target: fs.PathLike, path: fs.PathLike): void {
+	_symlinkSync(normalizePathLikeToString(target), normalizePathLikeToString(path));
+}
 import { openSync as _openSync } from "../pkg";
 export function openSync(path: fs.PathLike, flags?: fs.OpenMode, mode?: fs.Mode): number {
 	path = normalizePathLikeToString(path);
-	return _openSync(path, openModeLikeToString(flags), mode);
+	return _openSync(path, flags ?? "r", mode);
+}
+import { opendirSync as _opendirSync } from "../pkg";
+export function opendirSync(path: fs.PathLike): number {
+	return _opendirSync(normalizePathLikeToString(path));
+}
+import { openfileSync as _openfileSync } from "../pkg";
+export function openfileSync(path: fs.PathLike, flags?: string | number, mode?: string | number): number {
+	return _openfileSync(normalizePathLikeToString(path), flags, mode);
 }
-import { opendirSync } from "../pkg";
-export { opendirSync };
-import { openfileSync } from "../pkg";
-export { openfileSync };
 import { closeSync } from "../pkg";
 export { closeSync };
 import { readSync as _readSync } from "../pkg";
@@ -547,19 +764,21 @@ export function writeSync(fd: number, data: string | Uint8Array, ...args: any[])
 	} else if (typeof args?.[0] === "object") {
 		buffer = data;
 		offset = args?.[0]?.offset || 0;
-		length = args?.[0]?.length || buffer.length;
+		length = args?.[0]?.length ?? buffer.length - offset;
 		position = args?.[0]?.position || 0;
 	} else {
 		buffer = data;
 		offset = args?.[0] || 0;
-		length = args?.[1] || buffer.length;
+		length = args?.[1] ?? buffer.length - offset;
 		position = args?.[2] || 0;
 	}
 	return _writeSync(fd, buffer, offset, length, position);
 }
 import { fstatSync as _fstatSync } from "../pkg";
-export function fstatSync(fd: number): Partial<fs.Stats> | undefined {
-	return moveNodeStatsToJsMemory(_fstatSync(fd));
+export function fstatSync(fd: number, options: { bigint: true }): Partial<fs.BigIntStats> | undefined;
+export function fstatSync(fd: number, options?: { bigint?: false }): Partial<fs.Stats> | undefined;
+export function fstatSync(fd: number, options?: fs.StatOptions): Partial<fs.Stats | fs.BigIntStats> | undefined {
+	return moveNodeStatsToJsMemory(_fstatSync(fd), options);
 }
 import { fchmodSync } from "../pkg";
 export { fchmodSync };
@@ -567,6 +786,16 @@ import { fchownSync } from "../pkg";
 export { fchownSync };
 import { ftruncateSync } from "../pkg";
 export { ftruncateSync };
+import { fallocateSync as _fallocateSync } from "../pkg";
+export interface FallocateOptions {
+	/** only check that the range fits, do not grow the file */
+	keepSize?: boolean;
+	/** zero the range, requires keepSize */
+	punchHole?: boolean;
+}
+export function fallocateSync(fd: number, offset: number, len: number, options?: FallocateOptions): void {
+	_fallocateSync(fd, offset, len, options);
+}
 import { futimesSync as _futimesSync } from "../pkg";
 export function futimesSync(fd: number, atime: fs.TimeLike, mtime: fs.TimeLike): void {
 	_futimesSync(fd, timeLikeToSeconds(atime), timeLikeToSeconds(mtime));
@@ -579,11 +808,11 @@ import { existsSync as _existsSync } from "../pkg";
 export function existsSync(path: fs.PathLike): boolean {
 	try {
 		path = normalizePathLikeToString(path);
-		return _existsSync(path);
-	} catch (e: any) {
-		if (e?.message === INVALID_PATH_ERROR_MESSAGE) return false;
-		throw e;
+	} catch {
+		// like Node, paths that cannot exist do not
+		return false;
 	}
+	return _existsSync(path);
 }
 import { freaddirSync as _freaddirSync } from "../pkg";
 export function freaddirSync(fd: number): fs.Dirent | undefined {
@@ -608,8 +837,37 @@ export function mkdirSync(path: fs.PathLike, options?: fs.MakeDirectoryOptions |
 	path = normalizePathLikeToString(path);
 	return _mkdirSync(path, options);
 }
-import { mkdtempSync } from "../pkg";
-export { mkdtempSync };
+import { mkdtempSync as _mkdtempSync, mkstempSync as _mkstempSync, tmpCleanupOnReboot } from "../pkg";
+export function mkdtempSync(prefix: fs.PathLike): string {
+	return _mkdtempSync(normalizePathLikeToString(prefix));
+}
+export interface TempFile {
+	path: string;
+	/** opened for reading and writing */
+	fd: number;
+}
+/**
+ * creates and opens a file named `prefix` followed by six random characters,
+ * like `mkstemp(3)`. the file is new (O_EXCL) and only its owner can access it.
+ */
+export function mkstempSync(prefix: fs.PathLike): TempFile {
+	return _mkstempSync(normalizePathLikeToString(prefix));
+}
+export namespace tmp {
+	/** removes `path` (recursively for a directory) the next time wasabio reboots, if it is still there */
+	export function cleanupOnReboot(path: fs.PathLike): void {
+		tmpCleanupOnReboot(normalizePathLikeToString(path));
+	}
+}
+import { mkfifoSync as _mkfifoSync } from "../pkg";
+/**
+ * creates a named pipe at `path`. bytes written into one end by a worker can be
+ * read from the other end by any worker. reads block while writers are open.
+ */
+export function mkfifoSync(path: fs.PathLike, mode?: fs.Mode): void {
+	path = normalizePathLikeToString(path);
+	_mkfifoSync(path, typeof mode === "string" ? parseInt(mode, 8) : mode);
+}
 import { writeFileSync as _writeFileSync } from "../pkg";
 export function writeFileSync(
 	pathOrFd: fs.PathLike | number,
@@ -654,8 +912,174 @@ export function appendFileSync(
 		_appendFileSync(pathOrFd, toUInt8(data), options);
 	}
 }
-import { statfsSync } from "../pkg";
-export { statfsSync };
+import { statfsSync as _statfsSync, duSync as _duSync } from "../pkg";
+/** reports on the mount containing `path`: the volume itself, /dev or /proc */
+export function statfsSync(path: fs.PathLike, options?: { bigint?: false }): fs.StatsFs;
+export function statfsSync(path: fs.PathLike, options: { bigint: true }): fs.BigIntStatsFs;
+export function statfsSync(path: fs.PathLike, options?: fs.StatFsOptions): fs.StatsFs | fs.BigIntStatsFs {
+	const stat: fs.StatsFs = _statfsSync(normalizePathLikeToString(path));
+	if (!options?.bigint) return stat;
+	const big = {} as any;
+	for (const [key, value] of Object.entries(stat)) big[key] = BigInt(value);
+	return big as fs.BigIntStatsFs;
+}
+export interface DiskUsage {
+	path: string;
+	/** bytes taken by the subtree, or the sum of its file sizes with `apparent` */
+	size: number;
+}
+export interface DuOptions {
+	/** sums the sizes of files as read back instead of the blocks they take */
+	apparent?: boolean;
+	/** lists directories down to this many levels below `path`, all of them by default */
+	depth?: number;
+}
+/**
+ * disk usage of `path` and of every directory under it, children before their
+ * parents like `du`. symbolic links are not followed.
+ */
+export function duSync(path: fs.PathLike, options?: DuOptions): DiskUsage[] {
+	return _duSync(normalizePathLikeToString(path), options);
+}
+import { fsckSync as _fsckSync } from "../pkg";
+export interface FsckProblem {
+	kind: "orphan" | "ino" | "duplicate-ino" | "mode" | "dangling-link" | "dangling-record" | "nlink";
+	path: string;
+	detail: string;
+	repaired: boolean;
+}
+export interface FsckReport {
+	blocks: number;
+	orphans: number;
+	files: number;
+	dirs: number;
+	problems: FsckProblem[];
+}
+export function fsckSync(options?: { repair?: boolean }): FsckReport {
+	return _fsckSync(options);
+}
+import { backupSync as _backupSync, restoreSync as _restoreSync } from "../pkg";
+/**
+ * Logical backup of the whole volume: every file, directory, symlink and hard
+ * link with its attributes. Unlike a snapshot it does not depend on the build
+ * that made it and can be restored by any later version. With `onChunk` the
+ * backup is handed over in pieces while it is taken instead of being returned;
+ * other workers wait for the disk meanwhile, so `onChunk` must not write to it.
+ */
+export function backupSync(onChunk: (chunk: Uint8Array) => void): void;
+export function backupSync(): Uint8Array;
+export function backupSync(onChunk?: (chunk: Uint8Array) => void): Uint8Array | void {
+	return _backupSync(onChunk);
+}
+/**
+ * Rebuilds a volume from a backup and returns how many nodes it restored. Meant
+ * for a fresh volume, nodes that already exist (other than directories) fail
+ * the restore with EEXIST.
+ */
+export function restoreSync(data: Uint8Array): number {
+	return _restoreSync(data);
+}
+import {
+	backupIncrementalSync as _backupIncrementalSync,
+	changesSince as _changesSince,
+	changesGeneration as _changesGeneration,
+} from "../pkg";
+export interface Changes {
+	/** generation the changes go up to */
+	generation: number;
+	added: string[];
+	modified: string[];
+	removed: string[];
+}
+/**
+ * Paths added, modified and removed after `generation`, however they were written. Only the recent history is kept,
+ * asking for changes older than that fails with ESTALE.
+ */
+export function changesSince(generation = 0): Changes {
+	return _changesSince(generation);
+}
+/** Generation of the most recent change. Take it before a full backup to export deltas from it later. */
+export function changesGeneration(): number {
+	return _changesGeneration();
+}
+export interface IncrementalBackup {
+	/** generation the delta goes up to, the one to export the next delta from */
+	generation: number;
+	data: Uint8Array;
+}
+/**
+ * Delta holding what changed after `generation`. Restoring it with `restoreSync` on top of a backup taken at that
+ * generation (and every delta before it) brings the volume up to date, removed paths included. Fails with ESTALE once
+ * `generation` is too old for the kept history, a full backup is needed then.
+ */
+export function exportIncremental(generation: number): IncrementalBackup {
+	const data: Uint8Array = _backupIncrementalSync(generation);
+	const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+	return { generation: view.getUint32(20, true) + view.getUint32(24, true) * 2 ** 32, data };
+}
+import { setCompressionSync as _setCompressionSync, getCompressionSync as _getCompressionSync } from "../pkg";
+export type Compression = "lz4";
+/**
+ * Turns transparent compression on (or off with null) for a file or directory, like `chattr +c`. Files are rewritten
+ * compressed right away, directories pass it on to the files and directories created in them afterwards. Sizes and
+ * contents read back as usual, `duSync` with and without `apparent` shows how much they saved.
+ */
+export function setCompressionSync(path: fs.PathLike, compression: Compression | null): void {
+	path = normalizePathLikeToString(path);
+	_setCompressionSync(path, compression ?? undefined);
+}
+export function getCompressionSync(path: fs.PathLike): Compression | null {
+	path = normalizePathLikeToString(path);
+	return (_getCompressionSync(path) as Compression | undefined) ?? null;
+}
+import { FsTransaction } from "../pkg";
+/**
+ * Changes to several files staged in memory. `commit` applies them all while other workers are kept off the disk, so
+ * they see either none or all of them. When a change fails the ones before it are undone and the error is thrown.
+ * Nothing touches the disk before `commit`, `abort` discards the changes. Either of them ends the transaction.
+ */
+export class Transaction {
+	private tx: FsTransaction | null = new FsTransaction();
+	private get staging(): FsTransaction {
+		ok(this.tx, "transaction already committed or aborted");
+		return this.tx;
+	}
+	writeFile(path: fs.PathLike, data: Buffer | Uint8Array | string, options?: { mode?: fs.Mode }): this {
+		const mode = options?.mode;
+		this.staging.writeFile(
+			normalizePathLikeToString(path),
+			toUInt8(data),
+			typeof mode === "string" ? parseInt(mode, 8) : mode,
+		);
+		return this;
+	}
+	mkdir(path: fs.PathLike, options?: { mode?: fs.Mode }): this {
+		const mode = options?.mode;
+		this.staging.mkdir(normalizePathLikeToString(path), typeof mode === "string" ? parseInt(mode, 8) : mode);
+		return this;
+	}
+	rename(oldPath: fs.PathLike, newPath: fs.PathLike): this {
+		this.staging.rename(normalizePathLikeToString(oldPath), normalizePathLikeToString(newPath));
+		return this;
+	}
+	unlink(path: fs.PathLike): this {
+		this.staging.unlink(normalizePathLikeToString(path));
+		return this;
+	}
+	commit(): void {
+		const tx = this.staging;
+		this.tx = null;
+		tx.commit();
+	}
+	abort(): void {
+		const tx = this.staging;
+		this.tx = null;
+		tx.abort();
+	}
+}
+export function fsTransaction(): Transaction {
+	return new Transaction();
+}
 import { chmodSync as _chmodSync } from "../pkg";
 export function chmodSync(path: fs.PathLike, mode: fs.Mode): void {
 	path = normalizePathLikeToString(path);
@@ -669,7 +1093,7 @@ export function chownSync(path: fs.PathLike, uid: number, gid: number): void {
 import { truncateSync as _truncateSync } from "../pkg";
 export function truncateSync(pathOrFd: fs.PathLike | number, len?: number): void {
 	if (typeof pathOrFd === "number") {
-		throw new Error("not implemented, use truncateSync");
+		ftruncateSync(pathOrFd, len);
 	} else {
 		pathOrFd = normalizePathLikeToString(pathOrFd);
 		_truncateSync(pathOrFd, len);
@@ -691,25 +1115,123 @@ export function renameSync(oldPath: fs.PathLike, newPath: fs.PathLike): void {
 	newPath = normalizePathLikeToString(newPath);
 	_renameSync(oldPath, newPath);
 }
-import { copyFileSync } from "../pkg";
-export { copyFileSync };
-import { rmdirSync } from "../pkg";
-export { rmdirSync };
-import { rmSync } from "../pkg";
-export { rmSync };
+import { copyFileSync as _copyFileSync } from "../pkg";
+export function copyFileSync(src: fs.PathLike, dest: fs.PathLike, options?: Parameters<typeof _copyFileSync>[2]): void {
+	_copyFileSync(normalizePathLikeToString(src), normalizePathLikeToString(dest), options);
+}
+import { rmdirSync as _rmdirSync } from "../pkg";
+export function rmdirSync(path: fs.PathLike): void {
+	_rmdirSync(normalizePathLikeToString(path));
+}
+import { rmSync as _rmSync } from "../pkg";
+export function rmSync(path: fs.PathLike, options?: fs.RmOptions): void {
+	_rmSync(normalizePathLikeToString(path), options);
+}
 import { accessSync as _accessSync } from "../pkg";
 export function accessSync(path: fs.PathLike, mode?: number): void {
 	path = normalizePathLikeToString(path);
 	_accessSync(path, mode);
 }
-import { realpathSync } from "../pkg";
-export { realpathSync };
-import { readlinkSync } from "../pkg";
-export { readlinkSync };
+import { checksumSync as _checksumSync } from "../pkg";
+export function checksumSync(
+	path: fs.PathLike,
+	algorithm: "crc32" | "sha256" = "sha256",
+	options?: { cache?: boolean },
+): string {
+	path = normalizePathLikeToString(path);
+	return _checksumSync(path, algorithm, options);
+}
+import { realpathSync as _realpathSync } from "../pkg";
+export function realpathSync(path: fs.PathLike): string {
+	return _realpathSync(normalizePathLikeToString(path));
+}
+import { readlinkSync as _readlinkSync } from "../pkg";
+export function readlinkSync(path: fs.PathLike): string {
+	return _readlinkSync(normalizePathLikeToString(path));
+}
+import { cwd } from "../pkg";
+/** returns the working directory of the calling worker, like `process.cwd()` */
+export { cwd };
+import { chdir as _chdir } from "../pkg";
+/**
+ * changes the working directory of the calling worker, like `process.chdir()`.
+ * relative paths given to any function are resolved against it.
+ */
+export function chdir(directory: fs.PathLike): void {
+	_chdir(normalizePathLikeToString(directory));
+}
+import { umask as _umask } from "../pkg";
+/**
+ * returns the file mode creation mask of the calling worker, like `process.umask()`.
+ * when `mask` is given it replaces the current mask and the previous one is returned.
+ */
+export function umask(mask?: string | number): number {
+	return _umask(mask);
+}
+import { atimePolicy as _atimePolicy } from "../pkg";
+export type AtimePolicy = "strictatime" | "relatime" | "noatime";
+/**
+ * returns the mount option deciding when opens, reads and stats update the atime:
+ * - strictatime: on every access (default)
+ * - relatime: only if the atime is older than the mtime or ctime, or than a day
+ * - noatime: never, only `utimesSync()` changes it
+ * when `policy` is given it replaces the current one.
+ */
+export function atimePolicy(policy?: AtimePolicy): AtimePolicy {
+	return _atimePolicy(policy) as AtimePolicy;
+}
+import { nameMatching as _nameMatching } from "../pkg";
+export interface NameMatchingOptions {
+	/** "README" finds "readme", whichever was created first keeps its case */
+	readonly caseInsensitive?: boolean;
+	/**
+	 * - none: names are matched byte for byte (default)
+	 * - nfc: new names are stored composed, composed and decomposed forms match
+	 * - nfd: new names are stored decomposed, composed and decomposed forms match
+	 */
+	readonly normalization?: "none" | "nfc" | "nfd";
+}
+/**
+ * returns the mount options deciding which names refer to the same entry, for
+ * projects coming from macOS or Windows. options that are given replace the
+ * current ones; turning matching on fails with EEXIST if a directory holds two
+ * names it could not tell apart, and so does renaming onto such a name. the
+ * options are not stored on the volume: a reboot goes back to exact matching,
+ * pass them to `initialize` with `reboot` to keep them.
+ */
+export function nameMatching(options?: NameMatchingOptions): Required<NameMatchingOptions> {
+	return _nameMatching(options);
+}
+import { setClock as _setClock, advanceClock, clockNow } from "../pkg";
+export interface ClockOptions {
+	/**
+	 * - system: wall clock of the host (default)
+	 * - frozen: always reports `time`
+	 * - manual: starts at `time` and only moves with `advanceClock()`
+	 * - monotonic: wall clock that never goes backwards
+	 * - inherit: drops the worker's own clock (only with `worker`)
+	 */
+	readonly kind: "system" | "frozen" | "manual" | "monotonic" | "inherit";
+	/** starting point of frozen and manual clocks, in milliseconds or as a Date */
+	readonly time?: number | Date;
+	/** only switch the clock of the calling worker */
+	readonly worker?: boolean;
+}
+/** selects the clock used for all atime, mtime, ctime and birthtime updates */
+export function setClock(opts: ClockOptions): void {
+	const time = opts.time instanceof Date ? opts.time.getTime() : opts.time;
+	_setClock(opts.kind, time, opts.worker);
+}
+/** moves a manual clock forward by `ms` milliseconds and returns the new time */
+export { advanceClock };
+/** current time of the clock the calling worker uses, in milliseconds */
+export { clockNow };
 import { statSync as _statSync } from "../pkg";
-export function statSync(path: fs.PathLike, options?: { throwIfNoEntry: boolean }): Partial<fs.Stats> | undefined {
+export function statSync(path: fs.PathLike, options: { bigint: true; throwIfNoEntry?: boolean }): Partial<fs.BigIntStats> | undefined;
+export function statSync(path: fs.PathLike, options?: { bigint?: false; throwIfNoEntry?: boolean }): Partial<fs.Stats> | undefined;
+export function statSync(path: fs.PathLike, options?: fs.StatSyncOptions): Partial<fs.Stats | fs.BigIntStats> | undefined {
 	path = normalizePathLikeToString(path);
-	return moveNodeStatsToJsMemory(_statSync(path, options));
+	return moveNodeStatsToJsMemory(_statSync(path, options), options);
 }
 import { lchmodSync as _lchmodSync } from "../pkg";
 export function lchmodSync(path: fs.PathLike, mode: fs.Mode): void {
@@ -727,9 +1249,11 @@ export function lutimesSync(path: fs.PathLike, atime: fs.TimeLike, mtime: fs.Tim
 	_lutimesSync(path, timeLikeToSeconds(atime), timeLikeToSeconds(mtime));
 }
 import { lstatSync as _lstatSync } from "../pkg";
-export function lstatSync(path: fs.PathLike, options?: { bigint: boolean }): Partial<fs.Stats> | undefined {
+export function lstatSync(path: fs.PathLike, options: { bigint: true; throwIfNoEntry?: boolean }): Partial<fs.BigIntStats> | undefined;
+export function lstatSync(path: fs.PathLike, options?: { bigint?: false; throwIfNoEntry?: boolean }): Partial<fs.Stats> | undefined;
+export function lstatSync(path: fs.PathLike, options?: fs.StatSyncOptions): Partial<fs.Stats | fs.BigIntStats> | undefined {
 	path = normalizePathLikeToString(path);
-	return moveNodeStatsToJsMemory(_lstatSync(path, options));
+	return moveNodeStatsToJsMemory(_lstatSync(path, options), options);
 }
 import { lseekSync as _lseekSync } from "../pkg";
 export function lseekSync(fd: number, offset: number, whence: number): number {
@@ -785,6 +1309,7 @@ export namespace promises {
 	export const fchmod = promisify(fchmodSync);
 	export const fchown = promisify(fchownSync);
 	export const ftruncate = promisify(ftruncateSync);
+	export const fallocate = promisify(fallocateSync);
 	export const futimes = promisify(futimesSync);
 	export const fsync = promisify(fsyncSync);
 	export const fdatasync = promisify(fdatasyncSync);
@@ -793,10 +1318,18 @@ export namespace promises {
 	export const readdir = promisify(readdirSync);
 	export const mkdir = promisify(mkdirSync);
 	export const mkdtemp = promisify(mkdtempSync);
+	export const mkstemp = promisify(mkstempSync);
+	export const mkfifo = promisify(mkfifoSync);
 	export const writeFile = promisify(writeFileSync);
 	export const readFile = promisify(readFileSync);
 	export const appendFile = promisify(appendFileSync);
 	export const statfs = promisify(statfsSync);
+	export const du = promisify(duSync);
+	export const fsck = promisify(fsckSync);
+	export const backup = promisify(backupSync);
+	export const restore = promisify(restoreSync);
+	export const setCompression = promisify(setCompressionSync);
+	export const getCompression = promisify(getCompressionSync);
 	export const chmod = promisify(chmodSync);
 	export const chown = promisify(chownSync);
 	export const truncate = promisify(truncateSync);
@@ -807,6 +1340,7 @@ export namespace promises {
 	export const rmdir = promisify(rmdirSync);
 	export const rm = promisify(rmSync);
 	export const access = promisify(accessSync);
+	export const checksum = promisify(checksumSync);
 	export const realpath = promisify(realpathSync);
 	export const readlink = promisify(readlinkSync);
 	export const stat = promisify(statSync);
@@ -829,6 +1363,7 @@ export const fstat = callbackify(promises.fstat);
 export const fchmod = callbackify(promises.fchmod);
 export const fchown = callbackify(promises.fchown);
 export const ftruncate = callbackify(promises.ftruncate);
+export const fallocate = callbackify(promises.fallocate);
 export const futimes = callbackify(promises.futimes);
 export const fsync = callbackify(promises.fsync);
 export const fdatasync = callbackify(promises.fdatasync);
@@ -837,10 +1372,18 @@ export const freaddir = callbackify(promises.freaddir);
 export const readdir = callbackify(promises.readdir);
 export const mkdir = callbackify(promises.mkdir);
 export const mkdtemp = callbackify(promises.mkdtemp);
+export const mkstemp = callbackify(promises.mkstemp);
+export const mkfifo = callbackify(promises.mkfifo);
 export const writeFile = callbackify(promises.writeFile);
 export const readFile = callbackify(promises.readFile);
 export const appendFile = callbackify(promises.appendFile);
 export const statfs = callbackify(promises.statfs);
+export const du = callbackify(promises.du);
+export const fsck = callbackify(promises.fsck);
+export const backup = callbackify(promises.backup);
+export const restore = callbackify(promises.restore);
+export const setCompression = callbackify(promises.setCompression);
+export const getCompression = callbackify(promises.getCompression);
 export const chmod = callbackify(promises.chmod);
 export const chown = callbackify(promises.chown);
 export const truncate = callbackify(promises.truncate);
@@ -851,6 +1394,7 @@ export const copyFile = callbackify(promises.copyFile);
 export const rmdir = callbackify(promises.rmdir);
 export const rm = callbackify(promises.rm);
 export const access = callbackify(promises.access);
+export const checksum = callbackify(promises.checksum);
 export const realpath = callbackify(promises.realpath);
 export const readlink = callbackify(promises.readlink);
 export const stat = callbackify(promises.stat);
@@ -859,6 +1403,186 @@ export const lchown = callbackify(promises.lchown);
 export const lutimes = callbackify(promises.lutimes);
 export const lstat = callbackify(promises.lstat);
 
+import { setDeviceSink as _setDeviceSink } from "../pkg";
+/**
+ * redirects writes to /dev/stdout (fd 1) or /dev/stderr (fd 2) of the calling
+ * worker to `sink`. without a sink, output goes to the console.
+ */
+export function setDeviceSink(device: "stdout" | "stderr", sink?: (text: string) => void): void {
+	_setDeviceSink(device, sink);
+}
+import { journalRead, journalClear, journalCapacity } from "../pkg";
+export interface JournalEntry {
+	seq: number;
+	op: string;
+	args: any[];
+	/**
+	 * the bytes the call wrote, base64 encoded, with their length and hex
+	 * sha256. bytes that did not fit the budget are left out as `truncated`.
+	 */
+	data: { length: number; sha256: string; bytes: string | null; truncated: boolean } | null;
+	time: number;
+	worker: number;
+	depth: number;
+	status: "pending" | "ok" | "error";
+	result: any;
+	error: string | null;
+}
+export interface JournalReplayResult {
+	replayed: number;
+	skipped: number;
+	failed: { seq: number; op: string; error: string }[];
+}
+export namespace journal {
+	/** returns all journal entries newer than the given sequence number */
+	export function read(since?: number): JournalEntry[] {
+		return journalRead(since);
+	}
+	/** serializes the journal so it can be shipped elsewhere and replayed */
+	export function serialize(since?: number): string {
+		return JSON.stringify(read(since));
+	}
+	export function clear(): void {
+		journalClear();
+	}
+	/**
+	 * returns the budget of the journal in bytes, optionally changing it first.
+	 * it starts out at 1 MiB, 0 turns journaling off.
+	 */
+	export function capacity(bytes?: number): number {
+		return journalCapacity(bytes);
+	}
+	/**
+	 * replays journaled mutations onto another volume (this one by default).
+	 * nested calls are skipped since their top level call replays them, and file
+	 * descriptors are remapped to the ones opened during the replay. entries
+	 * carry the bytes they wrote, `payload` provides those of truncated entries.
+	 */
+	export function replay(
+		entries: JournalEntry[] | string,
+		target?: any,
+		payload?: (entry: JournalEntry) => Uint8Array | undefined,
+	): JournalReplayResult {
+		const t = target || {
+			linkSync,
+			symlinkSync,
+			openSync,
+			opendirSync,
+			openfileSync,
+			closeSync,
+			lseekSync,
+			readSync,
+			writeSync,
+			fchmodSync,
+			fchownSync,
+			ftruncateSync,
+			fallocateSync,
+			futimesSync,
+			fsyncSync,
+			fdatasyncSync,
+			mkdirSync,
+			mkfifoSync,
+			writeFileSync,
+			appendFileSync,
+			chmodSync,
+			chownSync,
+			truncateSync,
+			utimesSync,
+			unlinkSync,
+			renameSync,
+			copyFileSync,
+			rmdirSync,
+			rmSync,
+			lchmodSync,
+			lchownSync,
+			lutimesSync,
+			fsckSync,
+			setCompressionSync,
+			fsTransaction,
+		};
+		const list: JournalEntry[] = typeof entries === "string" ? JSON.parse(entries) : entries;
+		const fds = new Map<number, number>();
+		const fd = (n: number) => fds.get(n) ?? n;
+		const bytes = (e: JournalEntry) => {
+			if (!e.data) return new Uint8Array(0);
+			const data = e.data.bytes !== null ? decodeBase64(e.data.bytes) : payload?.(e);
+			if (data?.length !== e.data.length) throw new Error(`no payload of ${e.data.length} bytes for entry ${e.seq}`);
+			return data;
+		};
+		const ops: Record<string, (a: any[], e: JournalEntry) => any> = {
+			linkSync: (a) => t.linkSync(a[0], a[1]),
+			symlinkSync: (a) => t.symlinkSync(a[0], a[1]),
+			openSync: (a, e) => fds.set(e.result, t.openSync(a[0], a[1] ?? undefined, a[2] ?? undefined)),
+			opendirSync: (a, e) => fds.set(e.result, t.opendirSync(a[0])),
+			openfileSync: (a, e) => fds.set(e.result, t.openfileSync(a[0], a[1] ?? undefined, a[2] ?? undefined)),
+			closeSync: (a) => t.closeSync(fd(a[0])),
+			lseekSync: (a) => t.lseekSync(fd(a[0]), a[1], a[2]),
+			readSync: (a, e) => t.readSync(fd(a[0]), new Uint8Array(e.result || 0), 0, e.result || 0, a[3] ?? undefined),
+			writeSync: (a, e) => t.writeSync(fd(a[0]), bytes(e), 0, e.data?.length ?? 0, a[3] ?? undefined),
+			fchmodSync: (a) => t.fchmodSync(fd(a[0]), a[1]),
+			fchownSync: (a) => t.fchownSync(fd(a[0]), a[1], a[2]),
+			ftruncateSync: (a) => t.ftruncateSync(fd(a[0]), a[1]),
+			fallocateSync: (a) => t.fallocateSync(fd(a[0]), a[1], a[2], { keepSize: a[3], punchHole: a[4] }),
+			futimesSync: (a) => t.futimesSync(fd(a[0]), a[1], a[2]),
+			fsyncSync: (a) => t.fsyncSync(fd(a[0])),
+			fdatasyncSync: (a) => t.fdatasyncSync(fd(a[0])),
+			mkdirSync: (a) => t.mkdirSync(a[0], { recursive: a[1], mode: a[2] }),
+			mkdtempSync: (a) => t.mkdirSync(a[1]),
+			mkstempSync: (a, e) => fds.set(e.result, t.openSync(a[1], "wx+", 0o600)),
+			mkfifoSync: (a) => t.mkfifoSync(a[0], a[1]),
+			writeFileSync: (a, e) => t.writeFileSync(a[0], bytes(e), { flag: a[1], mode: a[2] }),
+			appendFileSync: (a, e) => t.appendFileSync(a[0], bytes(e), { flag: a[1], mode: a[2] }),
+			chmodSync: (a) => t.chmodSync(a[0], a[1]),
+			chownSync: (a) => t.chownSync(a[0], a[1], a[2]),
+			truncateSync: (a) => t.truncateSync(a[0], a[1]),
+			utimesSync: (a) => t.utimesSync(a[0], a[1], a[2]),
+			unlinkSync: (a) => t.unlinkSync(a[0]),
+			renameSync: (a) => t.renameSync(a[0], a[1]),
+			copyFileSync: (a) => t.copyFileSync(a[0], a[1], { mode: a[2] }),
+			rmdirSync: (a) => t.rmdirSync(a[0]),
+			rmSync: (a) => t.rmSync(a[0], { recursive: a[1], force: a[2] }),
+			lchmodSync: (a) => t.lchmodSync(a[0], a[1]),
+			lchownSync: (a) => t.lchownSync(a[0], a[1], a[2]),
+			lutimesSync: (a) => t.lutimesSync(a[0], a[1], a[2]),
+			fsckSync: (a) => a[0] && t.fsckSync({ repair: true }),
+			setCompressionSync: (a) => t.setCompressionSync(a[0], a[1]),
+			transactionCommit: (a, e) => {
+				const tx = t.fsTransaction();
+				const data = bytes(e);
+				let at = 0;
+				for (const op of a[0]) {
+					if (op[0] === "writeFile") {
+						tx.writeFile(op[1], data.subarray(at, (at += op[3])), { mode: op[2] });
+					} else if (op[0] === "mkdir") {
+						tx.mkdir(op[1], { mode: op[2] });
+					} else if (op[0] === "rename") {
+						tx.rename(op[1], op[2]);
+					} else {
+						tx.unlink(op[1]);
+					}
+				}
+				tx.commit();
+			},
+		};
+		const out: JournalReplayResult = { replayed: 0, skipped: 0, failed: [] };
+		for (const entry of list) {
+			const op = ops[entry.op];
+			if (entry.depth > 0 || !op) {
+				out.skipped++;
+				continue;
+			}
+			try {
+				op(entry.args, entry);
+				out.replayed++;
+			} catch (err: any) {
+				if (entry.status !== "error") {
+					out.failed.push({ seq: entry.seq, op: entry.op, error: `${err?.message || err}` });
+				}
+			}
+		}
+		return out;
+	}
+}
 const emptyVolume = new Volume();
 
 export function createReadStream(path: fs.PathLike, options?: string | object): Readable {
@@ -924,7 +1648,7 @@ class Watcher extends EventEmitter implements fs.FSWatcher, fs.StatWatcher {
 export const watch: typeof fs.watch = (filename, ...args): Watcher => {
 	const opts = (args.find((arg) => typeof arg === "object") || {}) as fs.WatchOptions;
 	const listener = (args.find((arg) => typeof arg === "function") || (() => {})) as Function;
-	const _filename = normalizePathLikeToString(filename);
+	const _filename = absolutePathLike(filename);
 	const watcher = new Watcher(_filename, listener);
 	watcher.on("change", listener as any);
 	const _listener = (event: string, file: string) => {
@@ -952,7 +1676,7 @@ export const watch: typeof fs.watch = (filename, ...args): Watcher => {
 export const watchFile: typeof fs.watchFile = (filename, ...args): Watcher => {
 	// const opts = (args.find((arg) => typeof arg === "object") || {}) as fs.WatchFileOptions;
 	const listener = (args.find((arg) => typeof arg === "function") || (() => {})) as Function;
-	const watcher = new Watcher(normalizePathLikeToString(filename), listener);
+	const watcher = new Watcher(absolutePathLike(filename), listener);
 	watcher.on("change", listener as any);
 	const _listener = (curr?: Partial<fs.Stats>, prev?: Partial<fs.Stats>) => {
 		const now = new Date();
@@ -1001,7 +1725,7 @@ export const unwatchFile: typeof fs.unwatchFile = (filename, ...args): void => {
 		fileSystemSharedEmitter.release();
 		return exposedSurfacedEmitters.clear();
 	}
-	const _filename = normalizePathLikeToString(filename);
+	const _filename = absolutePathLike(filename);
 	const listener = (args.find((arg) => typeof arg === "function") || (() => {})) as Function;
 	for (const emitter of exposedSurfacedEmitters) {
 		if (listener ? emitter.watcher === listener : emitter.path === _filename) {
@@ -1010,6 +1734,141 @@ export const unwatchFile: typeof fs.unwatchFile = (filename, ...args): void => {
 	}
 };
 
+import { WasiContext } from "../pkg";
+export interface WASIOptions {
+	readonly args?: string[];
+	/** environment variables of the guest */
+	readonly env?: Record<string, string>;
+	/** directories the guest may reach: the name it sees mapped to a path in this filesystem */
+	readonly preopens?: Record<string, string>;
+	/** `start()` returns the exit code of `proc_exit` instead of throwing it, true by default */
+	readonly returnOnExit?: boolean;
+}
+class WASIExit {
+	constructor(readonly code: number) {}
+}
+/** syscalls of `WasiContext` that do not touch the guest's memory */
+const WASI_NO_MEMORY = [
+	"fd_advise",
+	"fd_allocate",
+	"fd_close",
+	"fd_datasync",
+	"fd_sync",
+	"fd_fdstat_set_flags",
+	"fd_fdstat_set_rights",
+	"fd_filestat_set_size",
+	"fd_filestat_set_times",
+	"fd_renumber",
+	"sched_yield",
+	"poll_oneoff",
+];
+const WASI_WITH_MEMORY = [
+	"args_get",
+	"args_sizes_get",
+	"environ_get",
+	"environ_sizes_get",
+	"clock_res_get",
+	"clock_time_get",
+	"fd_fdstat_get",
+	"fd_filestat_get",
+	"fd_pread",
+	"fd_pwrite",
+	"fd_read",
+	"fd_write",
+	"fd_prestat_get",
+	"fd_prestat_dir_name",
+	"fd_readdir",
+	"fd_seek",
+	"fd_tell",
+	"path_create_directory",
+	"path_filestat_get",
+	"path_filestat_set_times",
+	"path_link",
+	"path_open",
+	"path_readlink",
+	"path_remove_directory",
+	"path_rename",
+	"path_symlink",
+	"path_unlink_file",
+	"random_get",
+];
+const WASI_ENOSYS = 52;
+/**
+ * `wasi_snapshot_preview1` for WebAssembly guests, backed by this filesystem.
+ * Files a guest writes are visible to every worker, and the other way round.
+ * Descriptors 0, 1 and 2 are /dev/null, /dev/stdout and /dev/stderr.
+ */
+export class WASI {
+	private readonly context: WasiContext;
+	private memory?: WebAssembly.Memory;
+	readonly wasiImport: Record<string, (...args: any[]) => number>;
+
+	constructor(private readonly opts: WASIOptions = {}) {
+		const { args = [], env = {}, preopens = { "/": "/" } } = opts;
+		this.context = new WasiContext(
+			args,
+			Object.entries(env).map(([key, value]) => `${key}=${value}`),
+			Object.entries(preopens).flat(),
+		);
+		const wasiImport: Record<string, (...args: any[]) => number> = {};
+		for (const name of WASI_NO_MEMORY) {
+			wasiImport[name] = (...args) => (this.context as any)[name](...args);
+		}
+		for (const name of WASI_WITH_MEMORY) {
+			wasiImport[name] = (...args) => (this.context as any)[name](this.guestMemory(), ...args);
+		}
+		wasiImport.proc_exit = (code: number) => {
+			throw new WASIExit(code);
+		};
+		wasiImport.proc_raise = () => WASI_ENOSYS;
+		for (const name of ["sock_accept", "sock_recv", "sock_send", "sock_shutdown"]) {
+			wasiImport[name] = () => WASI_ENOSYS;
+		}
+		this.wasiImport = wasiImport;
+	}
+
+	getImportObject(): WebAssembly.Imports {
+		return { wasi_snapshot_preview1: this.wasiImport };
+	}
+
+	/** runs a command: calls its `_start` export and returns the exit code */
+	start(instance: WebAssembly.Instance): number {
+		this.bind(instance);
+		const _start = instance.exports._start as Function;
+		ok(typeof _start === "function", "EINVAL: instance has no _start export");
+		try {
+			_start();
+			return 0;
+		} catch (err) {
+			if (err instanceof WASIExit && this.opts.returnOnExit !== false) return err.code;
+			throw err;
+		}
+	}
+
+	/** prepares a reactor: binds its memory and calls `_initialize` if it exports one */
+	initialize(instance: WebAssembly.Instance): void {
+		this.bind(instance);
+		const _initialize = instance.exports._initialize;
+		if (typeof _initialize === "function") _initialize();
+	}
+
+	/** closes every descriptor the guest left open */
+	dispose(): void {
+		this.context.free();
+	}
+
+	private bind(instance: WebAssembly.Instance): void {
+		const memory = instance.exports.memory;
+		ok(memory instanceof WebAssembly.Memory, "EINVAL: instance does not export its memory");
+		this.memory = memory;
+	}
+
+	private guestMemory(): WebAssembly.Memory {
+		ok(this.memory, "EINVAL: WASI used before start() or initialize()");
+		return this.memory;
+	}
+}
+
 export const join = (path: string, ...paths: string[]) => resolve([path, ...paths].join("/"));
 export const dirname = (path: string) => resolve(path).split("/").slice(0, -1).join("/") || "/";
 export const basename = (path: string) => resolve(path).split("/").pop() || "";
@@ -1,79 +1,113 @@
 #![allow(non_snake_case)]
 
 use crate::bus::EventEmitter;
+use crate::{guard, host, lock::Lock};
 use once_cell::sync::Lazy;
 use serde_json::json;
 use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 
 static mut STORE: Lazy<HashMap<String, String>> = Lazy::new(|| HashMap::new());
+static mut STORE_LOCK: Lazy<Lock> = Lazy::new(|| Lock::new().unwrap());
 static mut EMITTER: Lazy<EventEmitter> = Lazy::new(|| EventEmitter::new("kv"));
 
 #[wasm_bindgen]
 pub unsafe fn sab_kv_diag() {
-    web_sys::console::log_1(&format!("[WASABIO:KV] STORE: {:?}", STORE).into());
+    host::log(&format!("[WASABIO:KV] STORE: {:?}", STORE));
 }
 
 #[wasm_bindgen]
 pub unsafe fn sab_kv_reboot() {
     EMITTER = Lazy::new(|| EventEmitter::new("kv"));
+    STORE_LOCK = Lazy::new(|| Lock::new().unwrap());
 }
 
-#[wasm_bindgen]
-pub unsafe fn sab_kv_get(key: &str) -> JsValue {
-    if let Some(out) = STORE.get(key) {
-        return JsValue::from_str(&out);
-    } else {
-        JsValue::NULL
-    }
+/// Returns the value stored under `key`.
+pub fn get(key: &str) -> Option<String> {
+    guard!(STORE_LOCK);
+    unsafe { STORE.get(key).cloned() }
 }
 
-#[wasm_bindgen]
-pub unsafe fn sab_kv_set(key: &str, value: &str) -> JsValue {
-    let old = STORE.insert(key.into(), value.into());
-    if old.is_none() {
-        let ev = json!({
+/// Stores `value` under `key` and emits "set" with the previous value, if any.
+pub fn set(key: &str, value: &str) {
+    let old = {
+        guard!(STORE_LOCK);
+        unsafe { STORE.insert(key.into(), value.into()) }
+    };
+    let ev = if old.is_none() {
+        json!({
             "key": key,
             "newValue": value,
-        });
-        let _ = EMITTER.emit("set".into(), ev.to_string());
+        })
     } else {
-        let ev = json!({
+        json!({
             "key": key,
             "oldValue": old,
             "newValue": value,
-        });
-        let _ = EMITTER.emit("set".into(), ev.to_string());
-    }
-    JsValue::UNDEFINED
+        })
+    };
+    let _ = unsafe { EMITTER.emit("set".into(), ev.to_string()) };
 }
 
-#[wasm_bindgen]
-pub unsafe fn sab_kv_del(key: &str) -> JsValue {
-    let old = STORE.remove(key);
+/// Removes `key` and emits "del" with its value.
+pub fn del(key: &str) {
+    let old = {
+        guard!(STORE_LOCK);
+        unsafe { STORE.remove(key) }
+    };
     let ev = json!({
         "key": key,
         "oldValue": old,
     });
-    let _ = EMITTER.emit("del".into(), ev.to_string());
+    let _ = unsafe { EMITTER.emit("del".into(), ev.to_string()) };
+}
+
+/// Returns the key at `index`. The order is arbitrary but stable until the
+/// next change.
+pub fn key(index: usize) -> Option<String> {
+    guard!(STORE_LOCK);
+    unsafe { STORE.keys().nth(index).cloned() }
+}
+
+pub fn clear() {
+    guard!(STORE_LOCK);
+    unsafe { STORE.clear() };
+}
+
+pub fn len() -> usize {
+    guard!(STORE_LOCK);
+    unsafe { STORE.len() }
+}
+
+#[wasm_bindgen]
+pub fn sab_kv_get(key: &str) -> JsValue {
+    get(key).map_or(JsValue::NULL, |out| JsValue::from_str(&out))
+}
+
+#[wasm_bindgen]
+pub fn sab_kv_set(key: &str, value: &str) -> JsValue {
+    set(key, value);
+    JsValue::UNDEFINED
+}
+
+#[wasm_bindgen]
+pub fn sab_kv_del(key: &str) -> JsValue {
+    del(key);
     JsValue::UNDEFINED
 }
 
 #[wasm_bindgen]
-pub unsafe fn sab_kv_key(index: usize) -> JsValue {
-    if let Some((key, _)) = STORE.iter().nth(index) {
-        return JsValue::from_str(&key);
-    }
-    JsValue::NULL
+pub fn sab_kv_key(index: usize) -> JsValue {
+    self::key(index).map_or(JsValue::NULL, |key| JsValue::from_str(&key))
 }
 
 #[wasm_bindgen]
-pub unsafe fn sab_kv_clear() -> JsValue {
-    STORE.clear();
+pub fn sab_kv_clear() -> JsValue {
+    clear();
     JsValue::UNDEFINED
 }
 
 #[wasm_bindgen]
-pub unsafe fn sab_kv_length() -> usize {
-    STORE.len()
+pub fn sab_kv_length() -> usize {
+    len()
 }
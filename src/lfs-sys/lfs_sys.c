@@ -210,6 +210,15 @@ lfs_sys_query_t *lfs_sys_attr_query_new(const char *path) {
 	lfs_getattr(&DISK, path, LFS_SYS_ATTR_TYPE_CTIME, &attr,
 		    sizeof(attributes->ctime));
 	attributes->ctime = *(double *)attr;
+	int *ns[] = {&attributes->birthtime_ns, &attributes->atime_ns,
+		     &attributes->mtime_ns, &attributes->ctime_ns};
+	for (int i = 0; i < 4; i++) {
+		// older nodes do not have these, they read as zero
+		memset(attr, 0, sizeof(attr));
+		lfs_getattr(&DISK, path, LFS_SYS_ATTR_TYPE_BIRTHTIME_NS + i,
+			    &attr, sizeof(int));
+		*ns[i] = *(int *)attr;
+	}
 	lfs_getattr(&DISK, path, LFS_SYS_ATTR_TYPE_LINK, &attr,
 		    sizeof(attributes->link));
 	attributes->link = *(bool *)attr;
@@ -266,6 +275,23 @@ void lfs_sys_attr_patch(const char *path, lfs_sys_query_t *attributes) {
 		lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_CTIME,
 			    &attributes->ctime, sizeof(attributes->ctime));
 	}
+	if (current->birthtime_ns != attributes->birthtime_ns) {
+		lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_BIRTHTIME_NS,
+			    &attributes->birthtime_ns,
+			    sizeof(attributes->birthtime_ns));
+	}
+	if (current->atime_ns != attributes->atime_ns) {
+		lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_ATIME_NS,
+			    &attributes->atime_ns, sizeof(attributes->atime_ns));
+	}
+	if (current->mtime_ns != attributes->mtime_ns) {
+		lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_MTIME_NS,
+			    &attributes->mtime_ns, sizeof(attributes->mtime_ns));
+	}
+	if (current->ctime_ns != attributes->ctime_ns) {
+		lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_CTIME_NS,
+			    &attributes->ctime_ns, sizeof(attributes->ctime_ns));
+	}
 	if (current->link != attributes->link) {
 		lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_LINK,
 			    &attributes->link, sizeof(attributes->link));
@@ -300,6 +326,11 @@ void lfs_sys_attr_reset(const char *path) {
 		    sizeof(double));
 	lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_CTIME, &zero,
 		    sizeof(double));
+	lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_BIRTHTIME_NS, &zero,
+		    sizeof(int));
+	lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_ATIME_NS, &zero, sizeof(int));
+	lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_MTIME_NS, &zero, sizeof(int));
+	lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_CTIME_NS, &zero, sizeof(int));
 	lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_NLINK, &zero, sizeof(int));
 	lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_LINK, &zero, sizeof(bool));
 	lfs_setattr(&DISK, path, LFS_SYS_ATTR_TYPE_SYMLINK, &zero,
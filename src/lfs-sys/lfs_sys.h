@@ -9,6 +9,7 @@ extern "C" {
 
 typedef enum lfs_sys_file_type {
 	LFS_SYS_S_IFIFO = 0010000,
+	LFS_SYS_S_IFCHR = 0020000,
 	LFS_SYS_S_IFREG = 0100000,
 	LFS_SYS_S_IFDIR = 0040000,
 	LFS_SYS_S_IFLNK = 0120000,
@@ -38,6 +39,13 @@ typedef enum {
 	LFS_SYS_ATTR_TYPE_NLINK = 8,
 	LFS_SYS_ATTR_TYPE_LINK = 9,
 	LFS_SYS_ATTR_TYPE_SYMLINK = 10,
+	LFS_SYS_ATTR_TYPE_CRC32 = 11,
+	LFS_SYS_ATTR_TYPE_SHA256 = 12,
+	LFS_SYS_ATTR_TYPE_COMPRESSION = 13,
+	LFS_SYS_ATTR_TYPE_BIRTHTIME_NS = 14,
+	LFS_SYS_ATTR_TYPE_ATIME_NS = 15,
+	LFS_SYS_ATTR_TYPE_MTIME_NS = 16,
+	LFS_SYS_ATTR_TYPE_CTIME_NS = 17,
 } lfs_sys_attr_type_t;
 typedef struct {
 	int ino;
@@ -48,6 +56,11 @@ typedef struct {
 	double atime;
 	double mtime;
 	double ctime;
+	// nanoseconds past the milliseconds of the time above
+	int birthtime_ns;
+	int atime_ns;
+	int mtime_ns;
+	int ctime_ns;
 	int nlink;
 	bool link;
 	bool symlink;
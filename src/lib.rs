@@ -1,14 +1,15 @@
+#![feature(io_error_more)]
 #![feature(mutex_unpoison)]
-use once_cell::sync::Lazy;
-use std::collections::HashMap;
-use wasm_bindgen::__rt::__wbindgen_malloc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use wasm_bindgen::prelude::*;
 
 #[allow(unused_unsafe)]
 pub mod bus;
 pub mod fs;
+mod host;
 pub mod kv;
 pub mod lock;
+pub mod snapshot;
 
 #[wasm_bindgen]
 /// Prints a diagnostic message about the WASABIO internal global state.
@@ -17,14 +18,8 @@ pub unsafe fn wasabio_diag() {
     bus::sab_bus_diag();
     fs::sab_fs_diag();
     kv::sab_kv_diag();
-    web_sys::console::log_1(
-        &format!("[WASABIO:TLS] COUNTER_ADDRESS: {:?}", COUNTER_ADDRESS).into(),
-    );
-    for (meta, data) in TLS_ALLOCATIONS.iter() {
-        web_sys::console::log_1(
-            &format!("[WASABIO:TLS] TLS_ALLOCATIONS: {:?} {:?}", meta, data).into(),
-        );
-    }
+    #[cfg(target_arch = "wasm32")]
+    tls::tls_diag();
 }
 
 #[wasm_bindgen]
@@ -41,55 +36,52 @@ pub unsafe fn wasabio_locked() -> bool {
     fs::sab_fs_locked()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct AllocationMeta {
-    pub id: i32,
-    pub kind: i32,
+static WORKER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static WORKER_ID: usize = WORKER_COUNTER.fetch_add(1, Ordering::SeqCst);
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct AllocationData {
-    pub base: usize,
-    pub size: usize,
-    pub align: usize,
+/// Returns an id unique to the calling worker (or the main thread).
+pub fn worker_id() -> usize {
+    WORKER_ID.with(|id| *id)
 }
 
-static mut TLS_ALLOCATIONS: Lazy<HashMap<AllocationMeta, AllocationData>> =
-    Lazy::new(|| HashMap::new());
-static mut COUNTER_ADDRESS: Option<i32> = None;
+#[wasm_bindgen]
+pub fn wasabio_worker_id() -> usize {
+    worker_id()
+}
 
-fn get_thread_count() -> i32 {
-    (unsafe { *(COUNTER_ADDRESS.unwrap() as *mut u8) }) as i32
+/// Number of workers (including the main thread) that have asked for an id.
+pub fn worker_count() -> usize {
+    WORKER_COUNTER.load(Ordering::SeqCst)
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __wbindgen_tls_malloc(
-    size: usize,
-    align: usize,
-    thread_counter_addr: i32,
-    kind: i32,
-) -> *mut u8 {
-    if COUNTER_ADDRESS.is_none() {
-        COUNTER_ADDRESS = Some(thread_counter_addr);
-    } else {
-        assert!(COUNTER_ADDRESS.unwrap() == thread_counter_addr);
-    }
-    let id = get_thread_count();
-    if let Some((_, data)) = TLS_ALLOCATIONS.iter().find(|(meta, data)| {
-        meta.id == id && meta.kind == kind && data.size == size && data.align == align
-    }) {
-        let mut ptr = data.base;
-        let end = data.base + data.size;
-        while ptr < end {
-            *(ptr as *mut u8) = 0;
-            ptr += 1;
-        }
-        return data.base as *mut u8;
-    } else {
-        let base = __wbindgen_malloc(size, align) as usize;
-        let data = AllocationData { base, size, align };
-        let meta = AllocationMeta { id, kind };
-        TLS_ALLOCATIONS.insert(meta, data);
-        return base as *mut u8;
-    }
+/// Allocation counters for /proc/meminfo. The counting allocator takes over
+/// the global allocator, so it is only built with the "heap-stats" feature.
+#[cfg(feature = "heap-stats")]
+mod heap;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    /// bytes currently allocated
+    pub allocated: usize,
+    /// highest value `allocated` ever reached
+    pub peak: usize,
+    /// allocations not yet freed
+    pub live: usize,
+    /// allocations made since startup
+    pub total: usize,
 }
+
+/// What the counting allocator saw, None without the "heap-stats" feature.
+pub fn heap_stats() -> Option<HeapStats> {
+    #[cfg(feature = "heap-stats")]
+    return Some(heap::stats());
+    #[cfg(not(feature = "heap-stats"))]
+    None
+}
+
+/// Thread local storage of workers, only exists on WebAssembly.
+#[cfg(target_arch = "wasm32")]
+mod tls;
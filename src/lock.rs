@@ -1,3 +1,4 @@
+use crate::host;
 use once_cell::sync::Lazy;
 use std::hint;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -18,15 +19,19 @@ impl AtomicLock {
     pub fn unpoison(&mut self) {
         self.resource.store(0, Ordering::SeqCst);
     }
-    pub fn poison(&mut self) {
-        self.resource.store(1, Ordering::SeqCst);
+    /// Takes the lock if it is free. Checking and taking is a single atomic
+    /// step, so two threads can never both see it free.
+    pub fn try_poison(&mut self) -> bool {
+        self.resource
+            .compare_exchange_weak(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
     }
     pub fn poisoned(&self) -> bool {
         self.resource.load(Ordering::SeqCst) != 0
     }
     pub fn block(&mut self) {
         let mut attempts = 0;
-        while self.poisoned() {
+        while !self.try_poison() {
             attempts += 1;
             hint::spin_loop();
             if ENABLE_DEADLOCK_DETECTION && attempts > MAX_SPINS {
@@ -51,13 +56,21 @@ type LockId = usize;
 static mut LOCKS: Lazy<Vec<AtomicLock>> = Lazy::new(|| Vec::new());
 
 pub unsafe fn sab_lock_diag() {
-    web_sys::console::log_1(&format!("[WASABIO:LOCK] LOCKS: {:?}", LOCKS).into());
+    host::log(&format!("[WASABIO:LOCK] LOCKS: {:?}", LOCKS));
 }
 
 pub unsafe fn sab_lock_reboot() {
     LOCKS = Lazy::new(|| Vec::new());
 }
 
+/// Returns `(id, held, locked)` for every lock slot ever allocated.
+pub unsafe fn sab_lock_entries() -> Vec<(LockId, bool, bool)> {
+    LOCKS
+        .iter()
+        .map(|lock| (lock.id, lock.held, lock.poisoned()))
+        .collect()
+}
+
 #[wasm_bindgen]
 /// Allocates a new lock and returns its id. Recycles old locks if possible.
 pub unsafe fn sab_lock_new() -> Option<LockId> {
@@ -89,7 +102,6 @@ pub unsafe fn sab_lock_free(lock: LockId) {
 pub unsafe fn sab_lock_acquire(lock: LockId) {
     LOCKS.get_mut(lock).and_then(|lock| {
         lock.block();
-        lock.poison();
         Some(())
     });
 }
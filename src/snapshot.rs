@@ -0,0 +1,286 @@
+//! Snapshots of the whole shared memory. A raw memory image is only usable by
+//! the exact build that produced it, so it is prefixed with a header telling
+//! which build and layout it belongs to, with checksums of every section:
+//!
+//! | offset | size | field                                        |
+//! |--------|------|----------------------------------------------|
+//! | 0      | 8    | magic, `WASABIO\0`                           |
+//! | 8      | 4    | format version                               |
+//! | 12     | 4    | header length, the image starts right after  |
+//! | 16     | 4    | build id length `n`                          |
+//! | 20     | n    | build id                                     |
+//! | 20+n   | 4    | page size                                    |
+//! |        | 4    | pages in the image                           |
+//! |        | 4    | address of the thread counter                |
+//! |        | 4    | section size                                 |
+//! |        | 4    | section count `s`                            |
+//! |        | 8*s  | checksum of every section                    |
+//! |        | 8    | checksum of all the header bytes above       |
+//!
+//! All integers are little endian, checksums are 64-bit FNV-1a.
+
+use crate::fs::errno::io_error;
+use js_sys::Uint8Array;
+use std::convert::TryInto;
+use std::io;
+use wasm_bindgen::prelude::*;
+
+pub const MAGIC: &[u8; 8] = b"WASABIO\0";
+/// Version of the layout above, bumped whenever it changes.
+pub const FORMAT: u32 = 1;
+pub const PAGE_SIZE: usize = 64 * 1024;
+pub const SECTION_SIZE: usize = 1024 * 1024;
+
+/// How the image maps onto the memory of a running build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub pages: u32,
+    /// address of wasm-bindgen's thread counter, differs from build to build
+    pub thread_counter: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub format: u32,
+    /// identifies the build, the checksum of its WebAssembly binary
+    pub build: String,
+    pub layout: Layout,
+    pub section_size: u32,
+    pub sections: Vec<u64>,
+}
+
+/// Where a checksum starts before any data went into it.
+pub const CHECKSUM_SEED: u64 = 0xcbf29ce484222325;
+
+/// 64-bit FNV-1a, continuing from `hash`.
+pub fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    data.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn checksum(data: &[u8]) -> u64 {
+    fnv1a(CHECKSUM_SEED, data)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(io_error("EINVAL", "snapshot header is truncated"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl Header {
+    /// Describes `image`, a copy of memory with `layout.pages` pages.
+    pub fn new(build: &str, layout: Layout, image: &[u8]) -> io::Result<Header> {
+        if image.len() != layout.pages as usize * PAGE_SIZE {
+            return Err(io_error(
+                "EINVAL",
+                "image size does not match its page count",
+            ));
+        }
+        let sections = image.chunks(SECTION_SIZE).map(checksum).collect();
+        Ok(Header::with_sections(build, layout, sections))
+    }
+
+    /// Like `new` for images too large to hold in one piece: `sections` are
+    /// the checksums of consecutive `SECTION_SIZE` chunks.
+    pub fn with_sections(build: &str, layout: Layout, sections: Vec<u64>) -> Header {
+        Header {
+            format: FORMAT,
+            build: build.to_string(),
+            layout,
+            section_size: SECTION_SIZE as u32,
+            sections,
+        }
+    }
+
+    /// Bytes of the header, the image goes right after them.
+    pub fn encode(&self) -> Vec<u8> {
+        let len = 20 + self.build.len() + 20 + self.sections.len() * 8 + 8;
+        let mut out = Vec::with_capacity(len);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.format.to_le_bytes());
+        out.extend_from_slice(&(len as u32).to_le_bytes());
+        out.extend_from_slice(&(self.build.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.build.as_bytes());
+        out.extend_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+        out.extend_from_slice(&self.layout.pages.to_le_bytes());
+        out.extend_from_slice(&self.layout.thread_counter.to_le_bytes());
+        out.extend_from_slice(&self.section_size.to_le_bytes());
+        out.extend_from_slice(&(self.sections.len() as u32).to_le_bytes());
+        for section in &self.sections {
+            out.extend_from_slice(&section.to_le_bytes());
+        }
+        out.extend_from_slice(&checksum(&out).to_le_bytes());
+        out
+    }
+
+    /// Parses the header at the start of `snapshot` and returns it with its
+    /// length. Older formats are migrated here as they come to exist.
+    pub fn decode(snapshot: &[u8]) -> io::Result<(Header, usize)> {
+        let mut reader = Reader(snapshot);
+        if reader.take(8).ok() != Some(&MAGIC[..]) {
+            return Err(io_error("EINVAL", "not a wasabio snapshot"));
+        }
+        let format = reader.u32()?;
+        if format > FORMAT {
+            let message = format!("snapshot format v{} is newer than v{}", format, FORMAT);
+            return Err(io_error("EOPNOTSUPP", &message));
+        }
+        if format < FORMAT {
+            let message = format!("snapshot format v{} is no longer supported", format);
+            return Err(io_error("EOPNOTSUPP", &message));
+        }
+        let len = reader.u32()? as usize;
+        if len < 16 + 8 || len > snapshot.len() {
+            return Err(io_error("EINVAL", "snapshot header is truncated"));
+        }
+        let stored = u64::from_le_bytes(snapshot[len - 8..len].try_into().unwrap());
+        if checksum(&snapshot[..len - 8]) != stored {
+            return Err(io_error("EIO", "snapshot header is corrupt"));
+        }
+        let mut reader = Reader(&snapshot[16..len - 8]);
+        let build_len = reader.u32()? as usize;
+        let build = String::from_utf8(reader.take(build_len)?.to_vec())
+            .map_err(|_| io_error("EINVAL", "snapshot build id is not UTF-8"))?;
+        if reader.u32()? as usize != PAGE_SIZE {
+            return Err(io_error(
+                "EOPNOTSUPP",
+                "snapshot uses a different page size",
+            ));
+        }
+        let layout = Layout {
+            pages: reader.u32()?,
+            thread_counter: reader.u32()?,
+        };
+        let section_size = reader.u32()?;
+        let count = reader.u32()? as usize;
+        let sections = (0..count)
+            .map(|_| reader.u64())
+            .collect::<io::Result<_>>()?;
+        let header = Header {
+            format,
+            build,
+            layout,
+            section_size,
+            sections,
+        };
+        Ok((header, len))
+    }
+
+    /// Refuses snapshots a build with `build` and `thread_counter` cannot load.
+    pub fn check(&self, build: &str, thread_counter: u32) -> io::Result<()> {
+        if self.build != build {
+            let message = format!(
+                "snapshot was taken by build {}, this is build {}",
+                self.build, build
+            );
+            return Err(io_error("EOPNOTSUPP", &message));
+        }
+        if self.layout.thread_counter != thread_counter {
+            return Err(io_error(
+                "EOPNOTSUPP",
+                "snapshot memory layout does not match this build",
+            ));
+        }
+        let size = self.section_size as usize;
+        if size == 0 || self.sections.len() != (self.image_len() + size - 1) / size {
+            return Err(io_error(
+                "EINVAL",
+                "snapshot sections do not cover its pages",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Compares section `index` of the image with its checksum.
+    pub fn verify_section(&self, index: usize, data: &[u8]) -> io::Result<()> {
+        if self.sections.get(index) != Some(&checksum(data)) {
+            let start = index * self.section_size as usize;
+            let message = format!(
+                "snapshot section {} ({}..{}) is corrupt",
+                index,
+                start,
+                start + data.len()
+            );
+            return Err(io_error("EIO", &message));
+        }
+        Ok(())
+    }
+
+    /// Length of the image that follows the header.
+    pub fn image_len(&self) -> usize {
+        self.layout.pages as usize * PAGE_SIZE
+    }
+}
+
+/// Prefixes `image` with its header.
+pub fn encode(build: &str, layout: Layout, image: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Header::new(build, layout, image)?.encode();
+    out.extend_from_slice(image);
+    Ok(out)
+}
+
+/// Checks `snapshot` against the loading build and returns its image.
+pub fn decode<'a>(build: &str, thread_counter: u32, snapshot: &'a [u8]) -> io::Result<&'a [u8]> {
+    let (header, len) = Header::decode(snapshot)?;
+    header.check(build, thread_counter)?;
+    let image = &snapshot[len..];
+    if image.len() != header.image_len() {
+        return Err(io_error("EINVAL", "snapshot image is truncated"));
+    }
+    for (index, section) in image.chunks(header.section_size as usize).enumerate() {
+        header.verify_section(index, section)?;
+    }
+    Ok(image)
+}
+
+fn to_js(err: io::Error) -> JsValue {
+    JsError::new(&err.to_string()).into()
+}
+
+#[wasm_bindgen]
+/// Header of a snapshot of `image`, a copy of the whole memory. The image is
+/// read a section at a time instead of being copied into the memory it is a
+/// picture of.
+pub fn wasabio_snapshot_header(
+    build: &str,
+    thread_counter: u32,
+    image: &Uint8Array,
+) -> Result<Vec<u8>, JsValue> {
+    let len = image.length() as usize;
+    if len % PAGE_SIZE != 0 {
+        return Err(to_js(io_error(
+            "EINVAL",
+            "image size is not a whole number of pages",
+        )));
+    }
+    let layout = Layout {
+        pages: (len / PAGE_SIZE) as u32,
+        thread_counter,
+    };
+    let mut chunk = vec![0; SECTION_SIZE];
+    let sections = (0..len)
+        .step_by(SECTION_SIZE)
+        .map(|start| {
+            let end = (start + SECTION_SIZE).min(len);
+            let chunk = &mut chunk[..end - start];
+            image.subarray(start as u32, end as u32).copy_to(chunk);
+            checksum(chunk)
+        })
+        .collect();
+    Ok(Header::with_sections(build, layout, sections).encode())
+}
@@ -0,0 +1,64 @@
+use crate::host;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use wasm_bindgen::__rt::__wbindgen_malloc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct AllocationMeta {
+    pub id: i32,
+    pub kind: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct AllocationData {
+    pub base: usize,
+    pub size: usize,
+    pub align: usize,
+}
+
+static mut TLS_ALLOCATIONS: Lazy<HashMap<AllocationMeta, AllocationData>> =
+    Lazy::new(|| HashMap::new());
+static mut COUNTER_ADDRESS: Option<i32> = None;
+
+fn get_thread_count() -> i32 {
+    (unsafe { *(COUNTER_ADDRESS.unwrap() as *mut u8) }) as i32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn __wbindgen_tls_malloc(
+    size: usize,
+    align: usize,
+    thread_counter_addr: i32,
+    kind: i32,
+) -> *mut u8 {
+    if COUNTER_ADDRESS.is_none() {
+        COUNTER_ADDRESS = Some(thread_counter_addr);
+    } else {
+        assert!(COUNTER_ADDRESS.unwrap() == thread_counter_addr);
+    }
+    let id = get_thread_count();
+    if let Some((_, data)) = TLS_ALLOCATIONS.iter().find(|(meta, data)| {
+        meta.id == id && meta.kind == kind && data.size == size && data.align == align
+    }) {
+        let mut ptr = data.base;
+        let end = data.base + data.size;
+        while ptr < end {
+            *(ptr as *mut u8) = 0;
+            ptr += 1;
+        }
+        return data.base as *mut u8;
+    } else {
+        let base = __wbindgen_malloc(size, align) as usize;
+        let data = AllocationData { base, size, align };
+        let meta = AllocationMeta { id, kind };
+        TLS_ALLOCATIONS.insert(meta, data);
+        return base as *mut u8;
+    }
+}
+
+pub unsafe fn tls_diag() {
+    host::log(&format!("[WASABIO:TLS] COUNTER_ADDRESS: {:?}", COUNTER_ADDRESS));
+    for (meta, data) in TLS_ALLOCATIONS.iter() {
+        host::log(&format!("[WASABIO:TLS] TLS_ALLOCATIONS: {:?} {:?}", meta, data));
+    }
+}
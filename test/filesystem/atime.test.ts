@@ -0,0 +1,64 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.atime policy tests", () => {
+	const tmpdir = "/tmp/atime_dir";
+	const file = join(tmpdir, "file.txt");
+	const day = 24 * 60 * 60 * 1000;
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir, { recursive: true });
+		fs.setClock({ kind: "manual", time: 0 });
+		fs.writeFileSync(file, "atime");
+	});
+
+	after(() => {
+		fs.atimePolicy("strictatime");
+		fs.setClock({ kind: "system" });
+	});
+
+	it("should default to strictatime", () => {
+		assert.equal(fs.atimePolicy(), "strictatime");
+		assert.match(fs.readFileSync("/proc/mounts", "utf8") as string, /^littlefs \/ littlefs rw,strictatime,/);
+		fs.advanceClock(1000);
+		fs.readFileSync(file);
+		assert.equal(fs.statSync(file)!.atimeMs, 1000);
+	});
+
+	it("should leave the atime alone with noatime", () => {
+		assert.equal(fs.atimePolicy("noatime"), "noatime");
+		fs.advanceClock(1000);
+		fs.readFileSync(file);
+		fs.statSync(file);
+		assert.equal(fs.statSync(file)!.atimeMs, 1000);
+		fs.utimesSync(file, 5, 5);
+		assert.equal(fs.statSync(file)!.atimeMs, 5000);
+	});
+
+	it("should only update stale atimes with relatime", () => {
+		fs.atimePolicy("relatime");
+		fs.utimesSync(file, 3, 2);
+		fs.advanceClock(2000);
+		fs.readFileSync(file);
+		assert.equal(fs.statSync(file)!.atimeMs, 3000);
+		fs.writeFileSync(file, "modified");
+		const now = fs.advanceClock(1);
+		fs.readFileSync(file);
+		assert.equal(fs.statSync(file)!.atimeMs, now);
+		fs.advanceClock(1000);
+		fs.readFileSync(file);
+		assert.equal(fs.statSync(file)!.atimeMs, now);
+		fs.advanceClock(day);
+		fs.readFileSync(file);
+		assert.equal(fs.statSync(file)!.atimeMs, now + 1000 + day);
+		assert.throws(() => fs.atimePolicy("lazytime" as any), /EINVAL/);
+	});
+});
@@ -0,0 +1,47 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.backup tests", () => {
+	const tmpdir = "/tmp";
+	const target = join(tmpdir, "backup_target");
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir);
+		fs.writeFileSync(target, "backup");
+	});
+
+	it("should start with a versioned header", () => {
+		const backup = fs.backupSync();
+		assert.equal(String.fromCharCode(...backup.subarray(0, 8)), "WSBACKUP");
+		assert.equal(new DataView(backup.buffer, backup.byteOffset).getUint32(8, true), 3);
+	});
+
+	it("should hand the backup over in chunks", () => {
+		const chunks: Uint8Array[] = [];
+		fs.backupSync((chunk) => chunks.push(chunk.slice()));
+		const streamed = new Uint8Array(chunks.reduce((len, chunk) => len + chunk.byteLength, 0));
+		chunks.reduce((at, chunk) => (streamed.set(chunk, at), at + chunk.byteLength), 0);
+		assert.deepEqual(streamed, fs.backupSync());
+	});
+
+	it("should refuse to restore over existing files", () => {
+		const backup = fs.backupSync();
+		assert.throws(() => fs.restoreSync(backup), /EEXIST/);
+	});
+
+	it("should refuse corrupt backups", () => {
+		const backup = fs.backupSync();
+		// the inode number of the first record, the root directory
+		backup[22] ^= 0xff;
+		assert.throws(() => fs.restoreSync(backup), /EIO/);
+		assert.throws(() => fs.restoreSync(new Uint8Array(16)), /EINVAL/);
+	});
+});
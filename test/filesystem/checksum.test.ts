@@ -0,0 +1,46 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.checksum tests", () => {
+	const tmpdir = "/tmp";
+	const file = join(tmpdir, "checksum_file");
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir);
+	});
+
+	it("should compute known digests", () => {
+		fs.writeFileSync(file, "hello world");
+		assert.equal(fs.checksumSync(file, "crc32"), "0d4a1185");
+		assert.equal(fs.checksumSync(file, "sha256"), "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9");
+		assert.equal(fs.checksumSync(file), fs.checksumSync(file, "sha256"));
+	});
+
+	it("should invalidate cached digests on write", () => {
+		fs.writeFileSync(file, "hello world");
+		assert.equal(fs.checksumSync(file, "crc32", { cache: true }), "0d4a1185");
+		fs.writeFileSync(file, "");
+		assert.equal(fs.checksumSync(file, "crc32", { cache: true }), "00000000");
+		fs.appendFileSync(file, "hello world");
+		assert.equal(fs.checksumSync(file, "crc32", { cache: true }), "0d4a1185");
+		fs.truncateSync(file, 0);
+		assert.equal(
+			fs.checksumSync(file, "sha256", { cache: true }),
+			"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+		);
+	});
+
+	it("should throw on missing files and directories", () => {
+		assert.throws(() => fs.checksumSync(join(tmpdir, "__does_not_exist")), /ENOENT/);
+		assert.throws(() => fs.checksumSync(tmpdir), /EISDIR/);
+		assert.throws(() => fs.checksumSync(file, "md5" as any), /EINVAL/);
+	});
+});
@@ -0,0 +1,60 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.clock tests", () => {
+	const tmpdir = "/tmp/clock_dir";
+	const epoch = Date.UTC(2000, 0, 1);
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir, { recursive: true });
+	});
+
+	afterEach(() => {
+		fs.setClock({ kind: "inherit", worker: true });
+		fs.setClock({ kind: "system" });
+	});
+
+	it("should stamp nodes with a frozen clock", () => {
+		fs.setClock({ kind: "frozen", time: new Date(epoch) });
+		assert.equal(fs.clockNow(), epoch);
+		fs.writeFileSync(join(tmpdir, "frozen.txt"), "frozen");
+		const stat = fs.statSync(join(tmpdir, "frozen.txt"))!;
+		assert.equal(stat.birthtimeMs, epoch);
+		assert.equal(stat.mtimeMs, epoch);
+		assert.equal(stat.ctimeMs, epoch);
+		assert.throws(() => fs.advanceClock(1000), /EINVAL/);
+	});
+
+	it("should only move a manual clock when advanced", () => {
+		fs.setClock({ kind: "manual", time: epoch });
+		fs.writeFileSync(join(tmpdir, "manual.txt"), "manual");
+		assert.equal(fs.advanceClock(60_000), epoch + 60_000);
+		fs.appendFileSync(join(tmpdir, "manual.txt"), "!");
+		const stat = fs.statSync(join(tmpdir, "manual.txt"))!;
+		assert.equal(stat.birthtimeMs, epoch);
+		assert.equal(stat.mtimeMs, epoch + 60_000);
+	});
+
+	it("should let a worker pick its own clock", () => {
+		fs.setClock({ kind: "frozen", time: epoch });
+		fs.setClock({ kind: "manual", time: 0, worker: true });
+		assert.equal(fs.clockNow(), 0);
+		fs.setClock({ kind: "inherit", worker: true });
+		assert.equal(fs.clockNow(), epoch);
+	});
+
+	it("should never go backwards with a monotonic clock", () => {
+		fs.setClock({ kind: "monotonic" });
+		const first = fs.clockNow();
+		assert.isAtLeast(fs.clockNow(), first);
+		assert.throws(() => fs.setClock({ kind: "sundial" as any }), /EINVAL/);
+	});
+});
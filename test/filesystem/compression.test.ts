@@ -0,0 +1,46 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.compression tests", () => {
+	const tmpdir = "/tmp";
+	const dir = join(tmpdir, "compressed");
+	const source = join(dir, "source.js");
+	const text = "export const answer = () => 42;\n".repeat(2000);
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(dir, { recursive: true });
+	});
+
+	it("should be inherited by new files and read back transparently", () => {
+		fs.setCompressionSync(dir, "lz4");
+		assert.equal(fs.getCompressionSync(dir), "lz4");
+		fs.writeFileSync(source, text);
+		assert.equal(fs.getCompressionSync(source), "lz4");
+		assert.equal(fs.readFileSync(source, "utf8"), text);
+		assert.equal(fs.statSync(source)!.size, text.length);
+		assert.isBelow(fs.duSync(source)[0].size, fs.duSync(source, { apparent: true })[0].size);
+	});
+
+	it("should write through descriptors", () => {
+		const fd = fs.openSync(source, "a+");
+		fs.writeSync(fd, "// appended\n");
+		assert.equal(fs.fstatSync(fd)!.size, text.length + 12);
+		fs.closeSync(fd);
+		assert.equal(fs.readFileSync(source, "utf8"), text + "// appended\n");
+	});
+
+	it("should be turned off and refuse unknown algorithms", () => {
+		fs.setCompressionSync(source, null);
+		assert.isNull(fs.getCompressionSync(source));
+		assert.equal(fs.readFileSync(source, "utf8"), text + "// appended\n");
+		assert.throws(() => fs.setCompressionSync(source, "zip" as any), /EINVAL/);
+	});
+});
@@ -0,0 +1,55 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.cwd tests", () => {
+	const tmpdir = "/tmp/cwd_dir";
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir, { recursive: true });
+	});
+
+	after(() => {
+		fs.chdir("/");
+	});
+
+	it("should start at the root", () => {
+		assert.equal(fs.cwd(), "/");
+		assert.equal(fs.realpathSync("."), "/");
+	});
+
+	it("should resolve relative paths against the working directory", () => {
+		fs.chdir(tmpdir);
+		assert.equal(fs.cwd(), tmpdir);
+		fs.writeFileSync("relative.txt", "relative");
+		assert.isTrue(fs.existsSync(join(tmpdir, "relative.txt")));
+		assert.equal(fs.readFileSync("./relative.txt", "utf8"), "relative");
+		fs.mkdirSync("sub");
+		fs.chdir("sub");
+		assert.equal(fs.cwd(), join(tmpdir, "sub"));
+		assert.equal(fs.readFileSync("../relative.txt", "utf8"), "relative");
+		assert.equal(fs.realpathSync(".."), tmpdir);
+		assert.isTrue(fs.mkdtempSync("tmp-").startsWith(join(tmpdir, "sub", "tmp-")));
+		fs.symlinkSync("../relative.txt", "link");
+		assert.equal(fs.realpathSync("link"), join(tmpdir, "relative.txt"));
+		assert.equal(fs.readlinkSync("link"), "../relative.txt");
+		fs.chdir("..");
+		assert.equal(fs.cwd(), tmpdir);
+		// relative targets start at the link, wherever the caller is
+		fs.symlinkSync("../relative.txt", join("sub", "link2"));
+		assert.equal(fs.realpathSync(join(tmpdir, "sub", "link2")), join(tmpdir, "relative.txt"));
+	});
+
+	it("should refuse missing directories and files", () => {
+		assert.throws(() => fs.chdir("__does_not_exist"), /ENOENT/);
+		assert.throws(() => fs.chdir(join(tmpdir, "relative.txt")), /ENOTDIR/);
+		assert.equal(fs.cwd(), tmpdir);
+	});
+});
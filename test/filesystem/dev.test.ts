@@ -0,0 +1,65 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.dev tests", () => {
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+	});
+
+	after(() => {
+		fs.setDeviceSink("stdout");
+	});
+
+	it("should list character devices", () => {
+		assert.isTrue(fs.existsSync("/dev"));
+		assert.includeMembers(fs.readdirSync("/dev") as string[], ["null", "zero", "urandom", "stdout", "stderr"]);
+		const stats = fs.statSync("/dev/null")!;
+		assert.isTrue(stats.isCharacterDevice!());
+		assert.isFalse(stats.isFile!());
+		assert.isFalse(stats.isDirectory!());
+	});
+
+	it("should discard writes to /dev/null", () => {
+		fs.writeFileSync("/dev/null", "discarded");
+		assert.equal(fs.readFileSync("/dev/null", "utf8"), "");
+	});
+
+	it("should read zeros and random bytes", () => {
+		const fd = fs.openSync("/dev/zero", "r");
+		const zeros = new Uint8Array(16).fill(1);
+		assert.equal(fs.readSync(fd, zeros), 16);
+		assert.isTrue(zeros.every((b) => b === 0));
+		assert.isTrue(fs.fstatSync(fd)!.isCharacterDevice!());
+		fs.closeSync(fd);
+		const rfd = fs.openSync("/dev/urandom", "r");
+		const random = new Uint8Array(64);
+		assert.equal(fs.readSync(rfd, random), 64);
+		assert.isTrue(random.some((b) => b !== 0));
+		fs.closeSync(rfd);
+	});
+
+	it("should refuse offsets and lengths past the buffer", () => {
+		const fd = fs.openSync("/dev/zero", "r");
+		const buffer = new Uint8Array(8);
+		assert.throws(() => fs.readSync(fd, buffer, 9, 0), /ERR_OUT_OF_RANGE/);
+		assert.throws(() => fs.readSync(fd, buffer, 4, 5), /ERR_OUT_OF_RANGE/);
+		assert.throws(() => fs.readSync(fd, buffer, 4, Number.MAX_SAFE_INTEGER), /ERR_OUT_OF_RANGE/);
+		assert.equal(fs.readSync(fd, buffer, 4, 4), 4);
+		fs.closeSync(fd);
+		assert.throws(() => fs.writeSync(1, buffer, 2, 7), /ERR_OUT_OF_RANGE/);
+	});
+
+	it("should forward stdout to a registered sink", () => {
+		const lines: string[] = [];
+		fs.setDeviceSink("stdout", (text) => lines.push(text));
+		fs.writeFileSync("/dev/stdout", "hello\n");
+		fs.writeSync(1, "world\n");
+		assert.deepEqual(lines, ["hello\n", "world\n"]);
+	});
+});
@@ -0,0 +1,67 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.fifo tests", () => {
+	const tmpdir = "/tmp";
+	const fifo = join(tmpdir, "fifo_pipe");
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir);
+	});
+
+	after(() => {
+		if (fs.existsSync(fifo)) fs.unlinkSync(fifo);
+	});
+
+	it("should create a visible fifo node", () => {
+		fs.mkfifoSync(fifo, 0o644);
+		const stats = fs.statSync(fifo)!;
+		assert.isTrue(stats.isFIFO!());
+		assert.isFalse(stats.isFile!());
+		assert.equal(stats.mode! & 0o777, 0o644);
+		const dirent = (fs.readdirSync(tmpdir, { withFileTypes: true }) as any[]).find((d) => d.name === "fifo_pipe");
+		assert.isTrue(dirent.isFIFO());
+		assert.isFalse(dirent.isFile());
+		assert.isFalse(dirent.isDirectory());
+		assert.throws(() => fs.mkfifoSync(fifo), /EEXIST/);
+		assert.throws(() => fs.mkfifoSync(join(tmpdir, "__missing", "pipe")), /ENOENT/);
+	});
+
+	it("should pass bytes from writer to reader", () => {
+		const reader = fs.openSync(fifo, "r");
+		const writer = fs.openSync(fifo, "w");
+		assert.isTrue(fs.fstatSync(writer)!.isFIFO!());
+		assert.equal(fs.writeSync(writer, new TextEncoder().encode("hello ")), 6);
+		assert.equal(fs.writeSync(writer, new TextEncoder().encode("pipe")), 4);
+		const buf = new Uint8Array(8);
+		assert.equal(fs.readSync(reader, buf), 8);
+		assert.equal(new TextDecoder().decode(buf), "hello pi");
+		assert.equal(fs.readSync(reader, buf), 2);
+		assert.equal(new TextDecoder().decode(buf.subarray(0, 2)), "pe");
+		fs.closeSync(writer);
+		assert.equal(fs.readSync(reader, buf), 0);
+		fs.closeSync(reader);
+		assert.equal(fs.statSync(fifo)!.size, 0);
+	});
+
+	it("should fail writes without a reader", () => {
+		const writer = fs.openSync(fifo, "w");
+		assert.throws(() => fs.writeSync(writer, new Uint8Array(1)), /EPIPE/);
+		fs.closeSync(writer);
+	});
+
+	it("should keep the fifo type across chmod", () => {
+		fs.chmodSync(fifo, 0o600);
+		const stats = fs.statSync(fifo)!;
+		assert.isTrue(stats.isFIFO!());
+		assert.equal(stats.mode! & 0o777, 0o600);
+	});
+});
@@ -0,0 +1,47 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.fsck tests", () => {
+	const tmpdir = "/tmp";
+	const target = join(tmpdir, "fsck_target");
+	const symlink = join(tmpdir, "fsck_symlink");
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir);
+		fs.writeFileSync(target, "fsck");
+	});
+
+	it("should report volume usage", () => {
+		const report = fs.fsckSync();
+		assert.isAbove(report.blocks, 0);
+		assert.isAbove(report.dirs, 0);
+		assert.isAbove(report.files, 0);
+		assert.equal(report.orphans, 0);
+		assert.isArray(report.problems);
+	});
+
+	it("should detect and repair nlink mismatches", () => {
+		// a fresh file has an nlink of zero, two links put it out of step
+		fs.symlinkSync(target, symlink);
+		fs.symlinkSync(target, symlink + "2");
+		fs.unlinkSync(symlink);
+		fs.unlinkSync(symlink + "2");
+		const before = fs.fsckSync();
+		const problem = before.problems.find((p) => p.kind === "nlink" && p.path === target);
+		assert.isDefined(problem);
+		assert.isFalse(problem!.repaired);
+		const repaired = fs.fsckSync({ repair: true });
+		assert.isTrue(repaired.problems.every((p) => p.repaired));
+		const after = fs.fsckSync();
+		assert.isUndefined(after.problems.find((p) => p.path === target));
+		assert.equal(fs.statSync(target)!.nlink, 1);
+	});
+});
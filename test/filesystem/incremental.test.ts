@@ -0,0 +1,51 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.incremental tests", () => {
+	const tmpdir = "/tmp";
+	const kept = join(tmpdir, "incremental_kept");
+	const removed = join(tmpdir, "incremental_removed");
+	const added = join(tmpdir, "incremental_added");
+	const dir = join(tmpdir, "incremental_dir");
+	let generation: number;
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir);
+		fs.writeFileSync(kept, "kept");
+		fs.writeFileSync(removed, "removed");
+		generation = fs.changesGeneration();
+	});
+
+	it("should track changes since a generation", () => {
+		fs.writeFileSync(kept, "modified");
+		fs.unlinkSync(removed);
+		fs.writeFileSync(added, "added");
+		fs.mkdirSync(join(dir, "nested"), { recursive: true });
+		const changes = fs.changesSince(generation);
+		assert.isAbove(changes.generation, generation);
+		assert.include(changes.modified, kept);
+		assert.include(changes.removed, removed);
+		assert.include(changes.added, added);
+		assert.include(changes.added, join(dir, "nested"));
+		assert.isEmpty(fs.changesSince(changes.generation).added);
+	});
+
+	it("should export a delta that applies on top of a backup", () => {
+		const delta = fs.exportIncremental(generation);
+		assert.equal(String.fromCharCode(...delta.data.subarray(0, 8)), "WSDELTA\0");
+		assert.equal(delta.generation, fs.changesGeneration());
+		assert.isAbove(fs.restoreSync(delta.data), 0);
+		assert.equal(fs.readFileSync(kept, "utf8"), "modified");
+		assert.equal(fs.readFileSync(added, "utf8"), "added");
+		assert.isFalse(fs.existsSync(removed));
+		assert.isTrue(fs.statSync(join(dir, "nested"))!.isDirectory());
+	});
+});
@@ -0,0 +1,109 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.journal tests", () => {
+	const tmpdir = "/tmp";
+	const dir = join(tmpdir, "journal_dir");
+	const file = join(dir, "journal_file");
+
+	let initial: number;
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir);
+		initial = fs.journal.capacity();
+		fs.journal.capacity(1 << 20);
+	});
+
+	after(() => {
+		fs.journal.capacity(initial);
+	});
+
+	beforeEach(() => {
+		fs.journal.clear();
+	});
+
+	it("should be on by default and off without a budget", () => {
+		assert.isAbove(initial, 0);
+		fs.journal.capacity(0);
+		fs.existsSync(file);
+		assert.isEmpty(fs.journal.read());
+		fs.journal.capacity(1 << 20);
+	});
+
+	it("should record operations with their payload", () => {
+		fs.mkdirSync(dir);
+		fs.writeFileSync(file, "journal");
+		const entries = fs.journal.read();
+		const write = entries.find((e) => e.op === "writeFileSync");
+		assert.isDefined(write);
+		assert.equal(write!.args[0], file);
+		assert.equal(write!.status, "ok");
+		assert.deepEqual(write!.data, {
+			length: 7,
+			sha256: "81dd6b775afcccb6dbb8a25a58ea844271bbefaeea7cb1d91c1687d7450f850c",
+			bytes: "am91cm5hbA==",
+			truncated: false,
+		});
+		assert.isBelow(entries.find((e) => e.op === "mkdirSync")!.seq, write!.seq);
+	});
+
+	it("should record failures", () => {
+		assert.throws(() => fs.accessSync(join(tmpdir, "__does_not_exist")));
+		const access = fs.journal.read().find((e) => e.op === "accessSync");
+		assert.equal(access!.status, "error");
+		assert.equal(access!.error, "ENOENT");
+	});
+
+	it("should stay within its budget in bytes", () => {
+		const capacity = fs.journal.capacity();
+		fs.journal.capacity(1024);
+		for (let i = 0; i < 50; i++) fs.existsSync(file);
+		const entries = fs.journal.read();
+		assert.isAbove(entries.length, 0);
+		assert.isBelow(entries.length, 50);
+		assert.equal(entries[entries.length - 1].op, "existsSync");
+		fs.journal.capacity(capacity);
+	});
+
+	it("should replay a serialized journal", () => {
+		fs.rmSync(dir, { recursive: true, force: true });
+		fs.mkdirSync(dir);
+		const fd = fs.openSync(file, "w");
+		fs.writeSync(fd, "replayed");
+		fs.closeSync(fd);
+		const shipped = fs.journal.serialize();
+		fs.rmSync(dir, { recursive: true, force: true });
+		assert.isFalse(fs.existsSync(file));
+		assert.isEmpty(fs.journal.replay(shipped).failed);
+		assert.equal(fs.readFileSync(file, "utf8"), "replayed");
+	});
+
+	it("should leave out payloads beyond its budget", () => {
+		const capacity = fs.journal.capacity();
+		fs.journal.capacity(1024);
+		fs.rmSync(dir, { recursive: true, force: true });
+		fs.mkdirSync(dir);
+		fs.journal.clear();
+		const big = "x".repeat(2048);
+		fs.writeFileSync(file, big);
+		const shipped = fs.journal.serialize();
+		fs.journal.capacity(capacity);
+		const write = JSON.parse(shipped).find((e: any) => e.op === "writeFileSync");
+		assert.include(write.data, { length: 2048, bytes: null, truncated: true });
+		fs.rmSync(dir, { recursive: true, force: true });
+		fs.mkdirSync(dir);
+		assert.lengthOf(fs.journal.replay(shipped).failed, 1);
+		const payload = new TextEncoder().encode(big);
+		const result = fs.journal.replay(shipped, undefined, (e) => (e.op === "writeFileSync" ? payload : undefined));
+		assert.isEmpty(result.failed);
+		assert.equal(fs.readFileSync(file, "utf8"), big);
+	});
+});
@@ -30,6 +30,54 @@ describe("fs.mkdtemp tests", () => {
 		assert(fs.existsSync(utf8));
 	});
 
+	it("should never hand out the same name twice", () => {
+		const names = new Set<string>();
+		for (let i = 0; i < 200; i++) names.add(fs.mkdtempSync(join(tmpdir, "same.")));
+		assert.strictEqual(names.size, 200);
+	});
+
+	it("should try another name when the first one is taken by a directory", () => {
+		const taken = join(tmpdir, "collide.AAAAAA");
+		fs.mkdirSync(taken, { mode: 0o755 });
+		const crypto = globalThis.crypto;
+		const getRandomValues = crypto.getRandomValues;
+		let calls = 0;
+		// the first suffix drawn is "AAAAAA", the next one "BBBBBB"
+		crypto.getRandomValues = <T extends ArrayBufferView | null>(array: T): T => {
+			(array as any as Uint8Array).fill(calls++ === 0 ? 0 : 1);
+			return array;
+		};
+		try {
+			const fresh = fs.mkdtempSync(join(tmpdir, "collide."));
+			assert.strictEqual(fresh, join(tmpdir, "collide.BBBBBB"));
+			assert.strictEqual(fs.statSync(fresh)!.mode & 0o777, 0o700);
+			assert.strictEqual(fs.statSync(taken)!.mode & 0o777, 0o755);
+		} finally {
+			crypto.getRandomValues = getRandomValues;
+		}
+	});
+
+	it("should fail when the parent does not exist", () => {
+		assert.throws(() => fs.mkdtempSync(join(tmpdir, "missing", "foo.")), /ENOENT/);
+	});
+
+	it("should create and open a new file", () => {
+		const { path, fd } = fs.mkstempSync(join(tmpdir, "bar."));
+		assert.strictEqual(basename(path).length, "bar.XXXXXX".length);
+		assert.strictEqual(fs.statSync(path)!.mode & 0o777, 0o600);
+		fs.writeSync(fd, "scratch");
+		fs.closeSync(fd);
+		assert.strictEqual(fs.readFileSync(path, "utf8"), "scratch");
+		assert.throws(() => fs.openSync(path, "wx"), /EEXIST/);
+	});
+
+	it("should accept temp entries to clean up on reboot", () => {
+		const { path, fd } = fs.mkstempSync(join(tmpdir, "baz."));
+		fs.closeSync(fd);
+		fs.tmp.cleanupOnReboot(path);
+		assert(fs.existsSync(path));
+	});
+
 	after(() => {
 		fs.rmSync(tmpdir, { recursive: true, force: true });
 	});
@@ -0,0 +1,67 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.nameMatching tests", () => {
+	const tmpdir = "/tmp/names_dir";
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir, { recursive: true });
+	});
+
+	after(() => {
+		fs.nameMatching({ caseInsensitive: false, normalization: "none" });
+		fs.rmSync(tmpdir, { recursive: true, force: true });
+	});
+
+	it("should match names exactly by default", () => {
+		assert.deepEqual(fs.nameMatching(), { caseInsensitive: false, normalization: "none" });
+		fs.writeFileSync(join(tmpdir, "exact.txt"), "exact");
+		assert.isFalse(fs.existsSync(join(tmpdir, "EXACT.txt")));
+	});
+
+	it("should find names regardless of case and keep the first case", () => {
+		fs.nameMatching({ caseInsensitive: true });
+		assert.match(fs.readFileSync("/proc/mounts", "utf8") as string, /^littlefs \/ littlefs rw,\w+,casefold,/);
+		fs.writeFileSync(join(tmpdir, "ReadMe.md"), "hello");
+		assert.equal(fs.readFileSync(join(tmpdir, "README.MD"), "utf8"), "hello");
+		fs.writeFileSync(join(tmpdir, "readme.md"), "again");
+		assert.deepEqual(fs.readdirSync(tmpdir).sort(), ["ReadMe.md", "exact.txt"]);
+		assert.equal(fs.readFileSync(join(tmpdir, "ReadMe.md"), "utf8"), "again");
+		assert.throws(() => fs.mkdirSync(join(tmpdir, "EXACT.TXT")), /EEXIST/);
+	});
+
+	it("should rename to another case but not onto another entry", () => {
+		fs.renameSync(join(tmpdir, "readme.MD"), join(tmpdir, "README.md"));
+		assert.deepEqual(fs.readdirSync(tmpdir).sort(), ["README.md", "exact.txt"]);
+		assert.throws(() => fs.renameSync(join(tmpdir, "README.md"), join(tmpdir, "Exact.txt")), /EEXIST/);
+		assert.throws(() => fs.renameSync(join(tmpdir, "missing"), join(tmpdir, "found")), /ENOENT/);
+		assert.throws(() => fs.renameSync(join(tmpdir, "README.md"), join(tmpdir, "nowhere", "README.md")), /ENOENT/);
+	});
+
+	it("should store and match names in one normal form", () => {
+		fs.nameMatching({ normalization: "nfc" });
+		const decomposed = "Cafe\u0301.txt";
+		const composed = "Caf\u00e9.txt";
+		fs.writeFileSync(join(tmpdir, decomposed), "coffee");
+		assert.include(fs.readdirSync(tmpdir), composed);
+		assert.equal(fs.readFileSync(join(tmpdir, composed), "utf8"), "coffee");
+		assert.equal(fs.readFileSync(join(tmpdir, "CAF\u00c9.TXT"), "utf8"), "coffee");
+	});
+
+	it("should refuse to turn matching on over names it cannot tell apart", () => {
+		fs.nameMatching({ caseInsensitive: false, normalization: "none" });
+		fs.writeFileSync(join(tmpdir, "twin"), "1");
+		fs.writeFileSync(join(tmpdir, "TWIN"), "2");
+		assert.throws(() => fs.nameMatching({ caseInsensitive: true }), /EEXIST/);
+		assert.isFalse(fs.nameMatching().caseInsensitive);
+		assert.throws(() => fs.nameMatching({ normalization: "nfkc" as any }), /EINVAL/);
+	});
+});
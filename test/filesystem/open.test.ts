@@ -0,0 +1,95 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.open flags tests", () => {
+	const tmpdir = "/tmp/open_dir";
+	const file = join(tmpdir, "file.txt");
+	const { constants } = fs;
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir, { recursive: true });
+		fs.writeFileSync(file, "open");
+	});
+
+	it("should export the fs constants", () => {
+		assert.equal(constants.O_WRONLY | constants.O_CREAT | constants.O_TRUNC, 577);
+		assert.equal(constants.S_IFMT & constants.S_IFIFO, constants.S_IFIFO);
+		assert.equal(constants.F_OK, fs.F_OK);
+		assert.equal(constants.COPYFILE_EXCL, 1);
+		assert.equal(constants.UV_DIRENT_DIR, 2);
+	});
+
+	it("should accept numeric flags", () => {
+		const fd = fs.openSync(join(tmpdir, "numeric.txt"), constants.O_WRONLY | constants.O_CREAT | constants.O_EXCL);
+		assert.equal(fs.writeSync(fd, new TextEncoder().encode("numeric")), 7);
+		fs.closeSync(fd);
+		assert.equal(fs.readFileSync(join(tmpdir, "numeric.txt"), "utf8"), "numeric");
+		assert.throws(() => fs.openSync(join(tmpdir, "numeric.txt"), constants.O_CREAT | constants.O_EXCL), /EEXIST/);
+		const rd = fs.openSync(file, constants.O_RDONLY | constants.O_NOATIME | constants.O_SYNC);
+		fs.closeSync(rd);
+	});
+
+	it("should refuse invalid flags", () => {
+		assert.throws(() => fs.openSync(file, "rw" as any), /EINVAL/);
+		assert.throws(() => fs.openSync(file, constants.O_WRONLY | constants.O_RDWR), /EINVAL/);
+		assert.throws(() => fs.writeFileSync(file, "x", { flag: "q" }), /EINVAL/);
+		assert.equal(fs.readFileSync(file, "utf8"), "open");
+	});
+
+	it("should honor O_DIRECTORY and O_NOFOLLOW", () => {
+		const fd = fs.openSync(tmpdir, constants.O_RDONLY | constants.O_DIRECTORY);
+		fs.closeSync(fd);
+		assert.throws(() => fs.openSync(file, constants.O_RDONLY | constants.O_DIRECTORY), /ENOTDIR/);
+		assert.throws(() => fs.openSync(tmpdir, "w"), /EISDIR/);
+		fs.symlinkSync(file, join(tmpdir, "link"));
+		assert.throws(() => fs.openSync(join(tmpdir, "link"), constants.O_RDONLY | constants.O_NOFOLLOW), /ELOOP/);
+		fs.closeSync(fs.openSync(join(tmpdir, "link"), constants.O_RDONLY));
+	});
+
+	it("should not block on O_NONBLOCK fifos", () => {
+		const fifo = join(tmpdir, "fifo");
+		fs.mkfifoSync(fifo);
+		const rd = fs.openSync(fifo, constants.O_RDONLY | constants.O_NONBLOCK);
+		const wr = fs.openSync(fifo, "w");
+		assert.throws(() => fs.readSync(rd, new Uint8Array(1)), /EAGAIN/);
+		fs.writeSync(wr, new Uint8Array([42]));
+		const buf = new Uint8Array(1);
+		assert.equal(fs.readSync(rd, buf), 1);
+		assert.equal(buf[0], 42);
+		fs.closeSync(wr);
+		assert.equal(fs.readSync(rd, buf), 0);
+		fs.closeSync(rd);
+	});
+
+	it("should report descriptor errors", () => {
+		const rd = fs.openSync(file, "r");
+		assert.throws(() => fs.writeSync(rd, new Uint8Array([1])), /EBADF/);
+		fs.closeSync(rd);
+		assert.throws(() => fs.readSync(rd, new Uint8Array(1)), /EBADF/);
+		assert.throws(() => fs.closeSync(rd), /EBADF/);
+		assert.throws(() => fs.fstatSync(rd), /EBADF/);
+		assert.throws(() => fs.fsyncSync(rd), /EBADF/);
+		assert.throws(() => fs.fchmodSync(rd, 0o644), /EBADF/);
+		assert.throws(() => fs.futimesSync(rd, 0, 0), /EBADF/);
+		assert.throws(() => fs.ftruncateSync(rd, 0), /EBADF/);
+		const dir = fs.openSync(tmpdir, "r");
+		assert.throws(() => fs.readSync(dir, new Uint8Array(1)), /EISDIR/);
+		fs.closeSync(dir);
+	});
+
+	it("should only refuse existing copy targets with COPYFILE_EXCL", () => {
+		const copy = join(tmpdir, "copy.txt");
+		fs.writeFileSync(copy, "old");
+		fs.copyFileSync(file, copy);
+		assert.equal(fs.readFileSync(copy, "utf8"), "open");
+		assert.throws(() => fs.copyFileSync(file, copy, constants.COPYFILE_EXCL), /EEXIST/);
+	});
+});
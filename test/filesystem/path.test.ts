@@ -0,0 +1,66 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("path validation tests", () => {
+	const tmpdir = "/tmp/path_dir";
+	const file = join(tmpdir, "file.txt");
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir, { recursive: true });
+		fs.writeFileSync(file, "path");
+	});
+
+	after(() => {
+		fs.rmSync(tmpdir, { recursive: true, force: true });
+	});
+
+	it("should take Buffers, Uint8Arrays and file URLs", () => {
+		assert.equal(fs.readFileSync(Buffer.from(file), "utf8"), "path");
+		assert.equal(fs.readFileSync(new TextEncoder().encode(file), "utf8"), "path");
+		assert.equal(fs.readFileSync(new URL(`file://${file}`), "utf8"), "path");
+		fs.writeFileSync(new URL(`file://${tmpdir}/with%20space`), "space");
+		assert.isTrue(fs.existsSync(join(tmpdir, "with space")));
+	});
+
+	it("should refuse URLs that are not plain file paths", () => {
+		assert.throws(() => fs.readFileSync(new URL("https://example.com/file")), /must be of scheme file/);
+		assert.throws(() => fs.readFileSync(new URL("file://host/file")), /File URL host/);
+		assert.throws(() => fs.readFileSync(new URL(`file://${tmpdir}/a%2Fb`)), /encoded \/ characters/);
+	});
+
+	it("should refuse NUL bytes before touching the disk", () => {
+		try {
+			fs.writeFileSync(join(tmpdir, "nul\0byte"), "");
+			assert.fail("should have thrown");
+		} catch (err: any) {
+			assert.instanceOf(err, TypeError);
+			assert.equal(err.code, "ERR_INVALID_ARG_VALUE");
+		}
+		assert.isFalse(fs.existsSync(join(tmpdir, "nul\0byte")));
+		assert.deepEqual(fs.readdirSync(tmpdir).sort(), ["file.txt", "with space"]);
+	});
+
+	it("should refuse NUL bytes in symlink targets", () => {
+		assert.throws(() => fs.symlinkSync("file.txt\0", join(tmpdir, "link")), /ERR_INVALID_ARG_VALUE|null bytes/);
+		assert.isFalse(fs.existsSync(join(tmpdir, "link")));
+	});
+
+	it("should refuse names and paths that are too long", () => {
+		const name = "n".repeat(1023);
+		assert.throws(() => fs.writeFileSync(join(tmpdir, name), ""), /ENAMETOOLONG/);
+		assert.throws(() => fs.mkdirSync(join(tmpdir, name)), /ENAMETOOLONG/);
+		const deep = `${tmpdir}/${"d/".repeat(2048)}`;
+		assert.throws(() => fs.statSync(deep), /ENAMETOOLONG/);
+		assert.isFalse(fs.existsSync(join(tmpdir, name)));
+		fs.writeFileSync(join(tmpdir, "n".repeat(1022)), "longest");
+		assert.equal(fs.readFileSync(join(tmpdir, "n".repeat(1022)), "utf8"), "longest");
+	});
+});
@@ -0,0 +1,60 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.proc tests", () => {
+	const tmpdir = "/tmp";
+	const file = join(tmpdir, "proc_file");
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir);
+		fs.writeFileSync(file, "proc");
+	});
+
+	it("should list the proc tree", () => {
+		const self = fs.readlinkSync("/proc/self");
+		assert.match(self, /^\/proc\/\d+$/);
+		assert.includeMembers(fs.readdirSync("/proc") as string[], ["mounts", "meminfo", "locks", "self", self.slice(6)]);
+		assert.sameMembers(fs.readdirSync(self) as string[], ["fd", "status"]);
+		assert.isTrue(fs.statSync("/proc/self/fd")!.isDirectory!());
+		assert.isTrue(fs.statSync("/proc/mounts")!.isFile!());
+		assert.isFalse(fs.existsSync("/proc/nope"));
+		assert.notInclude(fs.readdirSync("/") as string[], "proc");
+	});
+
+	it("should list open descriptors", () => {
+		const fd = fs.openSync(file, "r");
+		const fds = fs.readdirSync("/proc/self/fd") as string[];
+		assert.includeMembers(fds, ["1", "2", `${fd}`]);
+		assert.equal(fs.readlinkSync(`/proc/self/fd/${fd}`), file);
+		assert.equal(fs.readFileSync(`/proc/self/fd/${fd}`, "utf8"), "proc");
+		fs.closeSync(fd);
+		assert.notInclude(fs.readdirSync("/proc/self/fd") as string[], `${fd}`);
+		assert.isFalse(fs.existsSync(`/proc/self/fd/${fd}`));
+	});
+
+	it("should report runtime state", () => {
+		assert.match(fs.readFileSync("/proc/mounts", "utf8") as string, /^littlefs \/ littlefs rw/);
+		const meminfo = fs.readFileSync("/proc/meminfo", "utf8") as string;
+		assert.match(meminfo, /WasmPages:\s+\d+/);
+		assert.notMatch(meminfo, /HeapAllocated/);
+		assert.match(fs.readFileSync("/proc/locks", "utf8") as string, /^0: SPINLOCK (ACTIVE|FREE) (UNLOCKED|LOCKED|POISONED)$/m);
+		assert.match(fs.readFileSync("/proc/self/status", "utf8") as string, /^Self:\t1$/m);
+	});
+
+	it("should read proc files through descriptors", () => {
+		const fd = fs.openSync("/proc/mounts", "r");
+		const buf = new Uint8Array(8);
+		assert.equal(fs.readSync(fd, buf), 8);
+		assert.equal(new TextDecoder().decode(buf), "littlefs");
+		fs.closeSync(fd);
+		assert.throws(() => fs.writeFileSync("/proc/mounts", "x"), /EACCES/);
+	});
+});
@@ -0,0 +1,44 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.snapshot tests", () => {
+	let snapshot: Uint8Array;
+	let imageOffset: number;
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		snapshot = fs.serialize(fs.memory()!);
+		imageOffset = new DataView(snapshot.buffer).getUint32(12, true);
+	});
+
+	it("should prefix the memory with a versioned header", () => {
+		assert.equal(String.fromCharCode(...snapshot.subarray(0, 8)), "WASABIO\0");
+		assert.equal(new DataView(snapshot.buffer).getUint32(8, true), 1);
+		assert.equal(snapshot.byteLength - imageOffset, fs.memory()!.buffer.byteLength);
+		const memory = fs.deserialize(snapshot);
+		assert.equal(memory.buffer.byteLength, snapshot.byteLength - imageOffset);
+	});
+
+	it("should refuse snapshots of other builds", () => {
+		const other = snapshot.slice();
+		other[20] ^= 0xff;
+		assert.throws(() => fs.deserialize(other), /EOPNOTSUPP: snapshot was taken by build/);
+	});
+
+	it("should refuse corrupt snapshots", () => {
+		const corrupt = snapshot.slice();
+		corrupt[imageOffset + 1024] ^= 0xff;
+		assert.throws(() => fs.deserialize(corrupt), /EIO: snapshot section 0/);
+		const header = snapshot.slice();
+		header[imageOffset - 9] ^= 0xff;
+		assert.throws(() => fs.deserialize(header), /EIO: snapshot header is corrupt/);
+		const truncated = snapshot.subarray(0, snapshot.byteLength - 1);
+		assert.throws(() => fs.deserialize(truncated), /EINVAL: snapshot image is truncated/);
+	});
+});
@@ -0,0 +1,54 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.statfs tests", () => {
+	const tmpdir = "/tmp/statfs_dir";
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(join(tmpdir, "a", "b"), { recursive: true });
+		fs.writeFileSync(join(tmpdir, "a", "big.bin"), new Uint8Array(10000));
+		fs.writeFileSync(join(tmpdir, "a", "b", "small.txt"), "small");
+	});
+
+	it("should report the volume like Node's StatFs", () => {
+		const stat = fs.statfsSync(tmpdir);
+		assert.hasAllKeys(stat, ["type", "bsize", "blocks", "bfree", "bavail", "files", "ffree"]);
+		assert.strictEqual(stat.type, 0x858458f6);
+		assert.isAbove(stat.blocks, stat.bfree);
+		assert.isAbove(stat.files, stat.ffree);
+		const big = fs.statfsSync(tmpdir, { bigint: true });
+		assert.strictEqual(big.bsize, BigInt(stat.bsize));
+	});
+
+	it("should report the mount containing the path", () => {
+		assert.strictEqual(fs.statfsSync("/dev/null").type, 0x01021994);
+		assert.strictEqual(fs.statfsSync("/proc/mounts").type, 0x9fa0);
+		assert.throws(() => fs.statfsSync(join(tmpdir, "missing")), /ENOENT/);
+	});
+
+	it("should report disk usage per subtree", () => {
+		const usage = fs.duSync(tmpdir);
+		assert.deepEqual(
+			usage.map((u) => u.path),
+			[join(tmpdir, "a", "b"), join(tmpdir, "a"), tmpdir],
+		);
+		const { bsize } = fs.statfsSync(tmpdir);
+		// big.bin takes three blocks, small.txt is inlined, every directory has a metadata pair
+		assert.strictEqual(usage[2].size, 3 * bsize + 3 * 2 * bsize);
+		const apparent = fs.duSync(tmpdir, { apparent: true, depth: 0 });
+		assert.deepEqual(apparent, [{ path: tmpdir, size: 10005 }]);
+		assert.throws(() => fs.duSync(join(tmpdir, "missing")), /ENOENT/);
+	});
+
+	after(() => {
+		fs.rmSync(tmpdir, { recursive: true, force: true });
+	});
+});
@@ -0,0 +1,51 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.transaction tests", () => {
+	const tmpdir = "/tmp";
+	const dir = join(tmpdir, "transaction");
+	const index = join(dir, "index.json");
+	const data = join(dir, "data.bin");
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(dir, { recursive: true });
+		fs.writeFileSync(index, "[]");
+	});
+
+	it("should apply every change on commit", () => {
+		const tx = fs.fsTransaction();
+		tx.writeFile(join(dir, "data.tmp"), new Uint8Array([1, 2, 3]));
+		tx.rename(join(dir, "data.tmp"), data);
+		tx.writeFile(index, '["data.bin"]');
+		assert.isFalse(fs.existsSync(data));
+		tx.commit();
+		assert.deepEqual([...fs.readFileSync(data)], [1, 2, 3]);
+		assert.equal(fs.readFileSync(index, "utf8"), '["data.bin"]');
+		assert.throws(() => tx.commit(), /already committed/);
+	});
+
+	it("should discard every change on abort", () => {
+		const tx = fs.fsTransaction();
+		tx.writeFile(index, "[]").unlink(data);
+		tx.abort();
+		assert.isTrue(fs.existsSync(data));
+		assert.equal(fs.readFileSync(index, "utf8"), '["data.bin"]');
+	});
+
+	it("should undo what it did when a change fails", () => {
+		const tx = fs.fsTransaction();
+		tx.writeFile(index, "[]").unlink(data).unlink(join(dir, "missing"));
+		assert.throws(() => tx.commit(), /ENOENT/);
+		assert.isTrue(fs.existsSync(data));
+		assert.equal(fs.readFileSync(index, "utf8"), '["data.bin"]');
+		assert.sameMembers(fs.readdirSync(dir) as string[], ["index.json", "data.bin"]);
+	});
+});
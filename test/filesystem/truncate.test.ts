@@ -53,6 +53,48 @@ describe("fs.truncate tests", () => {
 		fs.closeSync(fd);
 	});
 
+	it("should extend files with zeros", function () {
+		fs.writeFileSync(filename, "abc");
+		fs.truncateSync(filename, 8);
+		assert.deepEqual([...(fs.readFileSync(filename, { encoding: "buffer" }) as Uint8Array)], [97, 98, 99, 0, 0, 0, 0, 0]);
+
+		const fd = fs.openSync(filename, "r+");
+		fs.writeSync(fd, Buffer.from("z"), 0, 1, 12);
+		fs.closeSync(fd);
+		const sparse = fs.readFileSync(filename, { encoding: "buffer" }) as Uint8Array;
+		assert.strictEqual(sparse.length, 13);
+		assert.deepEqual([...sparse.subarray(8, 12)], [0, 0, 0, 0]);
+		assert.strictEqual(sparse[12], 122);
+	});
+
+	it("should check fallocateSync functionality", function () {
+		fs.writeFileSync(filename, "abcdef");
+		const fd = fs.openSync(filename, "r+");
+
+		fs.fallocateSync(fd, 0, 4096);
+		assert.strictEqual(fs.statSync(filename)!.size, 4096);
+
+		fs.fallocateSync(fd, 4096, 4096, { keepSize: true });
+		assert.strictEqual(fs.statSync(filename)!.size, 4096);
+
+		fs.fallocateSync(fd, 1, 2, { keepSize: true, punchHole: true });
+		const data = fs.readFileSync(filename, { encoding: "buffer" }) as Uint8Array;
+		assert.deepEqual([...data.subarray(0, 4)], [97, 0, 0, 100]);
+
+		assert.throws(() => fs.fallocateSync(fd, 0, 1, { punchHole: true }), /EOPNOTSUPP/);
+		assert.throws(() => fs.fallocateSync(fd, 0, 0), /EINVAL/);
+		assert.throws(() => fs.fallocateSync(fd, 0, 1 << 30), /ENOSPC/);
+		assert.throws(() => fs.fallocateSync(fd, 0xffffffff, 0xffffffff), /EINVAL/);
+		assert.throws(() => fs.ftruncateSync(fd, 1 << 30), /ENOSPC/);
+		assert.strictEqual(fs.statSync(filename)!.size, 4096);
+		fs.closeSync(fd);
+
+		const rd = fs.openSync(filename, "r");
+		assert.throws(() => fs.ftruncateSync(rd, 0), /EINVAL/);
+		assert.throws(() => fs.fallocateSync(rd, 0, 1), /EBADF/);
+		fs.closeSync(rd);
+	});
+
 	after(() => {
 		fs.rmSync(tmpdir, { recursive: true, force: true });
 	});
@@ -0,0 +1,49 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.umask tests", () => {
+	const tmpdir = "/tmp/umask_dir";
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir, { recursive: true });
+	});
+
+	afterEach(() => {
+		fs.umask(0o022);
+	});
+
+	it("should default to 0o022", () => {
+		assert.equal(fs.umask(), 0o022);
+		fs.writeFileSync(join(tmpdir, "default.txt"), "");
+		assert.equal(fs.statSync(join(tmpdir, "default.txt"))!.mode! & 0o777, 0o644);
+		fs.mkdirSync(join(tmpdir, "default"));
+		assert.equal(fs.statSync(join(tmpdir, "default"))!.mode! & 0o777, 0o755);
+	});
+
+	it("should mask newly created nodes", () => {
+		assert.equal(fs.umask(0o077), 0o022);
+		assert.equal(fs.umask(), 0o077);
+		fs.writeFileSync(join(tmpdir, "masked.txt"), "", { mode: 0o666 });
+		assert.equal(fs.statSync(join(tmpdir, "masked.txt"))!.mode! & 0o777, 0o600);
+		fs.mkdirSync(join(tmpdir, "masked"));
+		assert.equal(fs.statSync(join(tmpdir, "masked"))!.mode! & 0o777, 0o700);
+		const temp = fs.mkdtempSync(join(tmpdir, "masked-"));
+		assert.equal(fs.statSync(temp)!.mode! & 0o777, 0o700);
+		assert.equal(fs.umask("0"), 0o077);
+		fs.mkdirSync(join(tmpdir, "unmasked"));
+		assert.equal(fs.statSync(join(tmpdir, "unmasked"))!.mode! & 0o777, 0o777);
+	});
+
+	it("should not mask explicit chmod", () => {
+		fs.chmodSync(join(tmpdir, "masked.txt"), 0o666);
+		assert.equal(fs.statSync(join(tmpdir, "masked.txt"))!.mode! & 0o777, 0o666);
+	});
+});
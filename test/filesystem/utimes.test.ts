@@ -48,6 +48,43 @@ describe("fs.utimes tests", () => {
 		assert.strictEqual(overflow_stats?.mtime?.getTime(), overflow_mtime);
 	});
 
+	it("should accept every time argument Node does", () => {
+		const testPath = join(tmpdir, "test-utimes-arguments");
+		fs.writeFileSync(testPath, "");
+
+		fs.utimesSync(testPath, "1234567890", new Date(1234567890123));
+		let stats = fs.statSync(testPath);
+		assert.strictEqual(stats?.atimeMs, 1234567890000);
+		assert.strictEqual(stats?.mtimeMs, 1234567890123);
+
+		fs.utimesSync(testPath, 1234567890.5, 1234567890.000001);
+		stats = fs.statSync(testPath, { bigint: true });
+		assert.strictEqual(stats?.atimeNs, BigInt(1234567890500000000));
+		assert.strictEqual(stats?.mtimeNs, BigInt(1234567890000001000));
+
+		assert.throws(() => fs.utimesSync(testPath, "yesterday", 0), /Date or Time in seconds/);
+		assert.throws(() => fs.utimesSync(testPath, NaN, 0), /Date or Time in seconds/);
+	});
+
+	it("should change the link itself with the l* variants", () => {
+		const target = join(tmpdir, "test-lutimes-target");
+		const link = join(tmpdir, "test-lutimes-link");
+		fs.writeFileSync(target, "");
+		fs.symlinkSync(target, link);
+		fs.utimesSync(target, 1000, 1000);
+		fs.chmodSync(target, 0o644);
+
+		fs.lutimesSync(link, 2000, 2000);
+		fs.lchmodSync(link, 0o600);
+		const linkStats = fs.lstatSync(link);
+		assert.isTrue(linkStats?.isSymbolicLink?.());
+		assert.strictEqual(linkStats?.mtimeMs, 2000000);
+		assert.strictEqual(linkStats!.mode! & 0o777, 0o600);
+		const targetStats = fs.statSync(target);
+		assert.strictEqual(targetStats?.mtimeMs, 1000000);
+		assert.strictEqual(targetStats!.mode! & 0o777, 0o644);
+	});
+
 	after(() => {
 		fs.rmSync(tmpdir, { recursive: true, force: true });
 	});
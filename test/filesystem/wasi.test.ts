@@ -0,0 +1,137 @@
+import * as wasabio from "../../dist";
+import { assert } from "chai";
+import { join } from "path";
+
+declare global {
+	var WASABIO: typeof wasabio;
+}
+
+const fs = globalThis.WASABIO !== undefined ? globalThis.WASABIO : wasabio;
+
+describe("fs.wasi tests", () => {
+	const tmpdir = "/tmp/wasi_dir";
+	const memory = new WebAssembly.Memory({ initial: 1 });
+	const view = () => new DataView(memory.buffer);
+	const text = (ptr: number, len: number) => new TextDecoder().decode(new Uint8Array(memory.buffer, ptr, len));
+	const put = (ptr: number, str: string) => {
+		const bytes = new TextEncoder().encode(str);
+		new Uint8Array(memory.buffer).set(bytes, ptr);
+		return bytes.length;
+	};
+	const RIGHTS_READ_WRITE = BigInt((1 << 1) | (1 << 6));
+	let wasi: InstanceType<typeof fs.WASI>;
+	let sys: Record<string, (...args: any[]) => number>;
+
+	before(async () => {
+		if (!fs.available()) await fs.initialize();
+		fs.mkdirSync(tmpdir, { recursive: true });
+		fs.writeFileSync(join(tmpdir, "hello.txt"), "hello from the host");
+		wasi = new fs.WASI({ args: ["guest", "--flag"], env: { HOME: "/sandbox" }, preopens: { "/sandbox": tmpdir } });
+		wasi.initialize({ exports: { memory } } as unknown as WebAssembly.Instance);
+		sys = wasi.wasiImport;
+	});
+
+	after(() => {
+		wasi.dispose();
+	});
+
+	it("should describe args, environment and preopens", () => {
+		assert.equal(sys.args_sizes_get(0, 4), 0);
+		assert.equal(view().getUint32(0, true), 2);
+		assert.equal(view().getUint32(4, true), "guest\0--flag\0".length);
+		assert.equal(sys.args_get(0, 64), 0);
+		assert.equal(text(view().getUint32(4, true), 6), "--flag");
+		assert.equal(sys.environ_get(0, 64), 0);
+		assert.equal(text(64, 13), "HOME=/sandbox");
+		assert.equal(sys.fd_prestat_get(3, 0), 0);
+		assert.equal(view().getUint32(4, true), "/sandbox".length);
+		assert.equal(sys.fd_prestat_dir_name(3, 16, 8), 0);
+		assert.equal(text(16, 8), "/sandbox");
+		assert.equal(sys.fd_prestat_get(4, 0), 8); // EBADF
+	});
+
+	it("should read what the host wrote", () => {
+		const len = put(256, "hello.txt");
+		assert.equal(sys.path_open(3, 1, 256, len, 0, RIGHTS_READ_WRITE, 0n, 0, 0), 0);
+		const fd = view().getUint32(0, true);
+		view().setUint32(16, 512, true);
+		view().setUint32(20, 5, true);
+		assert.equal(sys.fd_read(fd, 16, 1, 8), 0);
+		assert.equal(view().getUint32(8, true), 5);
+		assert.equal(text(512, 5), "hello");
+		assert.equal(sys.fd_seek(fd, -4n, 2, 8), 0);
+		assert.equal(view().getBigUint64(8, true), 15n);
+		assert.equal(sys.fd_close(fd), 0);
+		assert.equal(sys.fd_close(fd), 8); // EBADF
+	});
+
+	it("should let the host read what the guest wrote", () => {
+		const len = put(256, "out/guest.txt");
+		assert.equal(sys.path_create_directory(3, 256, 3), 0);
+		assert.equal(sys.path_create_directory(3, 256, 3), 20); // EEXIST
+		// O_CREAT | O_EXCL
+		assert.equal(sys.path_open(3, 1, 256, len, 1 | 4, RIGHTS_READ_WRITE, 0n, 0, 0), 0);
+		const fd = view().getUint32(0, true);
+		view().setUint32(16, 512, true);
+		view().setUint32(20, put(512, "written by "), true);
+		view().setUint32(24, 768, true);
+		view().setUint32(28, put(768, "the guest"), true);
+		assert.equal(sys.fd_write(fd, 16, 2, 8), 0);
+		assert.equal(view().getUint32(8, true), 20);
+		assert.equal(sys.fd_close(fd), 0);
+		assert.equal(fs.readFileSync(join(tmpdir, "out/guest.txt"), "utf8"), "written by the guest");
+		assert.equal(sys.path_open(3, 1, 256, len, 1 | 4, RIGHTS_READ_WRITE, 0n, 0, 0), 20); // EEXIST
+
+		assert.equal(sys.path_filestat_get(3, 1, 256, len, 1024), 0);
+		assert.equal(view().getUint8(1024 + 16), 4); // regular file
+		assert.equal(view().getBigUint64(1024 + 32, true), 20n);
+	});
+
+	it("should list, rename and remove entries", () => {
+		fs.writeFileSync(join(tmpdir, "listed.txt"), "listed");
+		const len = put(256, ".");
+		assert.equal(sys.path_open(3, 1, 256, len, 2, 0n, 0n, 0, 0), 0);
+		const fd = view().getUint32(0, true);
+		assert.equal(sys.fd_readdir(fd, 1024, 4096, 0n, 8), 0);
+		const used = view().getUint32(8, true);
+		const names: string[] = [];
+		for (let at = 1024; at < 1024 + used; ) {
+			const namlen = view().getUint32(at + 16, true);
+			names.push(text(at + 24, namlen));
+			at += 24 + namlen;
+		}
+		assert.includeMembers(names, [".", "..", "hello.txt", "listed.txt"]);
+		assert.equal(sys.fd_close(fd), 0);
+
+		const from = put(256, "listed.txt");
+		const to = put(512, "renamed.txt");
+		assert.equal(sys.path_rename(3, 256, from, 3, 512, to), 0);
+		const lost = put(768, "missing/renamed.txt");
+		assert.equal(sys.path_rename(3, 512, to, 3, 768, lost), 44); // ENOENT
+		assert.isFalse(fs.existsSync(join(tmpdir, "listed.txt")));
+		assert.equal(fs.readFileSync(join(tmpdir, "renamed.txt"), "utf8"), "listed");
+		assert.equal(sys.path_unlink_file(3, 512, to), 0);
+		assert.equal(sys.path_unlink_file(3, 512, to), 44); // ENOENT
+		assert.isFalse(fs.existsSync(join(tmpdir, "renamed.txt")));
+	});
+
+	it("should keep the guest inside its preopens", () => {
+		let len = put(256, "../escape.txt");
+		assert.equal(sys.path_open(3, 1, 256, len, 1, RIGHTS_READ_WRITE, 0n, 0, 0), 76); // ENOTCAPABLE
+		len = put(256, "/etc/passwd");
+		assert.equal(sys.path_filestat_get(3, 1, 256, len, 1024), 76);
+		assert.isFalse(fs.existsSync("/tmp/escape.txt"));
+
+		const link = put(512, "sub/link");
+		len = put(256, "../../escape.txt");
+		assert.equal(sys.path_symlink(256, len, 3, 512, link), 76);
+		len = put(256, "/etc/passwd");
+		assert.equal(sys.path_symlink(256, len, 3, 512, link), 76);
+		assert.isFalse(fs.existsSync(join(tmpdir, "sub/link")));
+	});
+
+	it("should report the exit code of proc_exit", () => {
+		const instance = { exports: { memory, _start: () => sys.proc_exit(3) } };
+		assert.equal(wasi.start(instance as unknown as WebAssembly.Instance), 3);
+	});
+});
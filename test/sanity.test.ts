@@ -11,6 +11,7 @@ const {
 	initialize,
 	existsSync,
 	statfsSync,
+	duSync,
 	readFileSync,
 	mkdirSync,
 	accessSync,
@@ -65,11 +66,10 @@ describe("sanity testing", () => {
 		expect(existsSync("/test")).to.be.false;
 		mkdirSync("/test");
 		expect(existsSync("/test")).to.be.true;
-		const { json } = statfsSync("/", true);
-		const parsed = JSON.parse(json!);
-		expect(parsed).to.have.property("content");
-		expect(parsed.content).to.be.an("array");
-		expect(parsed.content[0].path).to.equal("test");
+		const usage = duSync("/");
+		expect(usage).to.be.an("array");
+		expect(usage[0].path).to.equal("/test");
+		expect(statfsSync("/").bfree).to.be.lessThan(statfsSync("/").blocks);
 		const content2 = readdirSync("/");
 		expect(content2).to.be.an("array");
 		expect(content2).to.be.lengthOf(1);
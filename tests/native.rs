@@ -0,0 +1,572 @@
+//! Exercises the cores on real OS threads: `cargo test-native`.
+//! The registries behind locks and buses grow without synchronization, so
+//! every test allocates what it needs before spawning, and the tests take
+//! turns through `SERIAL` instead of sharing the process state.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+use wasabio::bus::Bus;
+use wasabio::lock::{Guard, Lock};
+use wasabio::snapshot::{self, Layout, PAGE_SIZE, SECTION_SIZE};
+use wasabio::{fs, kv};
+
+const THREADS: usize = 8;
+
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn serial() -> std::sync::MutexGuard<'static, ()> {
+    SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[test]
+fn lock_excludes_other_threads() {
+    let _serial = serial();
+    let lock = Lock::new().unwrap();
+    let counter = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..100 {
+                    let _guard = Guard::new(&lock);
+                    // a read-modify-write that only adds up if nobody interleaves
+                    let seen = counter.load(Ordering::Relaxed);
+                    thread::yield_now();
+                    counter.store(seen + 1, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.load(Ordering::SeqCst), THREADS * 100);
+    assert!(!lock.held());
+}
+
+#[test]
+fn bus_delivers_across_threads() {
+    let _serial = serial();
+    let buses: Vec<Bus> = (0..THREADS).map(|_| Bus::new()).collect();
+    thread::scope(|s| {
+        for (i, bus) in buses.iter().enumerate() {
+            s.spawn(move || {
+                for n in 0..100 {
+                    bus.broadcast(format!("{}:{}", i, n));
+                }
+            });
+        }
+    });
+    for (i, bus) in buses.iter().enumerate() {
+        let mut received = vec![];
+        loop {
+            let event = bus.receive();
+            if event.is_empty() {
+                break;
+            }
+            received.push(event);
+        }
+        assert_eq!(received.len(), (THREADS - 1) * 100);
+        assert!(!received.iter().any(|e| e.starts_with(&format!("{}:", i))));
+        // per sender, events arrive in the order they were sent
+        for from in (0..THREADS).filter(|from| *from != i) {
+            let prefix = format!("{}:", from);
+            let order: Vec<usize> = received
+                .iter()
+                .filter_map(|e| e.strip_prefix(&prefix))
+                .map(|n| n.parse().unwrap())
+                .collect();
+            assert_eq!(order, (0..100).collect::<Vec<_>>());
+        }
+    }
+}
+
+#[test]
+fn kv_is_shared_by_threads() {
+    let _serial = serial();
+    kv::clear();
+    thread::scope(|s| {
+        for i in 0..THREADS {
+            s.spawn(move || {
+                for n in 0..100 {
+                    kv::set(&format!("{}/{}", i, n), &n.to_string());
+                }
+                kv::del(&format!("{}/0", i));
+            });
+        }
+    });
+    assert_eq!(kv::len(), THREADS * 99);
+    assert_eq!(kv::get("3/42").as_deref(), Some("42"));
+    assert_eq!(kv::get("3/0"), None);
+    assert!(kv::key(0).is_some());
+    kv::clear();
+    assert_eq!(kv::len(), 0);
+}
+
+#[test]
+fn fs_files_and_directories() {
+    let _serial = serial();
+    let dir = "/native";
+    fs::create_dir_all(&format!("{}/nested/deeper", dir)).unwrap();
+    let path = format!("{}/file.txt", dir);
+
+    let mut file = fs::File::create(&path).unwrap();
+    file.write_all(b"hello native world").unwrap();
+    file.seek(SeekFrom::Start(6)).unwrap();
+    let mut word = String::new();
+    file.take(6).read_to_string(&mut word).unwrap();
+    assert_eq!(word, "native");
+
+    let meta = fs::metadata(&path).unwrap();
+    assert!(meta.is_file());
+    assert_eq!(meta.len(), 18);
+    assert!(fs::metadata(dir).unwrap().is_dir());
+
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["file.txt", "nested"]);
+
+    let err = fs::File::open(&format!("{}/missing", dir)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert_eq!(fs::error_code(&err), Some("ENOENT"));
+    let err = fs::create_dir(&path).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EEXIST"));
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_dir(&format!("{}/nested/deeper", dir)).unwrap();
+    assert!(fs::metadata(&path).is_err());
+}
+
+#[test]
+fn fs_serves_threads() {
+    let _serial = serial();
+    let dir = "/threads";
+    fs::create_dir_all(dir).unwrap();
+    let lock = Lock::new().unwrap();
+    thread::scope(|s| {
+        for i in 0..THREADS {
+            let lock = &lock;
+            s.spawn(move || {
+                let path = format!("{}/{}.txt", dir, i);
+                let data = format!("written by thread {}", i).repeat(100);
+                // descriptor bookkeeping is shared, the same way workers share it
+                let _guard = Guard::new(lock);
+                fs::write(&path, data.as_bytes()).unwrap();
+                assert_eq!(fs::read(&path).unwrap(), data.as_bytes());
+            });
+        }
+    });
+    assert_eq!(fs::read_dir(dir).unwrap().count(), THREADS);
+}
+
+#[test]
+fn snapshot_refuses_what_it_cannot_load() {
+    let layout = Layout {
+        pages: 20,
+        thread_counter: 1024,
+    };
+    let image: Vec<u8> = (0..20 * PAGE_SIZE).map(|i| (i % 251) as u8).collect();
+    let mut shot = snapshot::encode("build-a", layout, &image).unwrap();
+    assert_eq!(snapshot::decode("build-a", 1024, &shot).unwrap(), &image[..]);
+
+    let err = snapshot::decode("build-b", 1024, &shot).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EOPNOTSUPP"));
+    assert!(err.to_string().contains("build-a"));
+    let err = snapshot::decode("build-a", 2048, &shot).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EOPNOTSUPP"));
+    let err = snapshot::decode("build-a", 1024, &image).unwrap_err();
+    assert_eq!(err.to_string(), "EINVAL: not a wasabio snapshot");
+
+    let start = shot.len() - image.len();
+    shot[start + SECTION_SIZE + 7] ^= 1;
+    let err = snapshot::decode("build-a", 1024, &shot).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EIO"));
+    assert!(err.to_string().contains("section 1"));
+    shot[20] ^= 1;
+    let err = snapshot::decode("build-a", 1024, &shot).unwrap_err();
+    assert_eq!(err.to_string(), "EIO: snapshot header is corrupt");
+    shot[8] = 2;
+    let err = snapshot::decode("build-a", 1024, &shot).unwrap_err();
+    assert_eq!(err.to_string(), "EOPNOTSUPP: snapshot format v2 is newer than v1");
+}
+
+/// Empties `path`, leaving the virtual /dev and /proc alone.
+fn remove_all(path: &str) {
+    for entry in fs::read_dir(path).unwrap() {
+        let entry = entry.unwrap();
+        if entry.path() == "/dev" || entry.path() == "/proc" {
+            continue;
+        }
+        if entry.file_type().unwrap().is_dir() {
+            remove_all(&entry.path());
+            fs::remove_dir(&entry.path()).unwrap();
+        } else {
+            fs::remove_file(&entry.path()).unwrap();
+        }
+    }
+}
+
+#[test]
+fn backup_restores_an_emptied_volume() {
+    let _serial = serial();
+    fs::create_dir_all("/backup/nested").unwrap();
+    fs::write("/backup/nested/data.bin", &[7u8; 5000]).unwrap();
+    fs::write("/backup/empty.txt", b"").unwrap();
+    fs::create_dir_all("/backup/packed").unwrap();
+    fs::set_compression("/backup/packed", Some(fs::Compression::Lz4)).unwrap();
+    let text = "compressible ".repeat(10_000);
+    fs::write("/backup/packed/text.txt", text.as_bytes()).unwrap();
+    let before = fs::metadata("/backup/nested/data.bin").unwrap();
+
+    let mut archive = vec![];
+    let count = fs::backup(&mut archive).unwrap();
+    assert!(count >= 5);
+    remove_all("/");
+    assert!(fs::metadata("/backup").is_err());
+
+    assert_eq!(fs::restore(&archive[..]).unwrap(), count);
+    assert_eq!(fs::read("/backup/nested/data.bin").unwrap(), vec![7u8; 5000]);
+    assert!(fs::read("/backup/empty.txt").unwrap().is_empty());
+    // compressed files come back as stored, along with their compression
+    assert_eq!(fs::read("/backup/packed/text.txt").unwrap(), text.as_bytes());
+    let packed = fs::metadata("/backup/packed/text.txt").unwrap();
+    assert_eq!(packed.len(), text.len() as u64);
+    fs::write("/backup/packed/later.txt", text.as_bytes()).unwrap();
+    assert_eq!(fs::read("/backup/packed/later.txt").unwrap(), text.as_bytes());
+    let after = fs::metadata("/backup/nested/data.bin").unwrap();
+    assert_eq!(after.ino(), before.ino());
+    assert_eq!(after.mode(), before.mode());
+    assert_eq!(after.modified().unwrap(), before.modified().unwrap());
+    assert_eq!(after.created().unwrap(), before.created().unwrap());
+
+    // a fresh node does not get an inode number a restored one kept
+    fs::write("/backup/fresh.txt", b"fresh").unwrap();
+    let fresh = fs::metadata("/backup/fresh.txt").unwrap();
+    assert_ne!(fresh.ino(), before.ino());
+
+    let err = fs::restore(&archive[..]).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EEXIST"));
+    let mut corrupt = archive.clone();
+    let at = corrupt.len() / 2;
+    corrupt[at] ^= 1;
+    remove_all("/");
+    let err = fs::restore(&corrupt[..]).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EIO"));
+    remove_all("/");
+    fs::restore(&archive[..]).unwrap();
+}
+
+#[test]
+fn incremental_backups_see_every_writer() {
+    let _serial = serial();
+    fs::create_dir_all("/delta").unwrap();
+    fs::write("/delta/kept.txt", b"kept").unwrap();
+    let generation = fs::backup_incremental(0, std::io::sink()).unwrap();
+
+    // reads leave no trace
+    fs::metadata("/delta/kept.txt").unwrap();
+    fs::read("/delta/kept.txt").unwrap();
+    assert_eq!(fs::backup_incremental(generation, std::io::sink()).unwrap(), generation);
+
+    // writes through a descriptor are seen as well as whole-file ones
+    let file = fs::File::options().write(true).open("/delta/kept.txt").unwrap();
+    file.write_at(b"KEPT", 0).unwrap();
+    drop(file);
+    let mut delta = vec![];
+    let next = fs::backup_incremental(generation, &mut delta).unwrap();
+    assert!(next > generation);
+    assert!(delta.windows(15).any(|w| w == b"/delta/kept.txt"));
+
+    fs::remove_file("/delta/kept.txt").unwrap();
+    fs::remove_dir("/delta").unwrap();
+}
+
+#[test]
+fn compression_is_transparent() {
+    let _serial = serial();
+    let dir = "/compressed";
+    fs::create_dir_all(dir).unwrap();
+    fs::set_compression(dir, Some(fs::Compression::Lz4)).unwrap();
+    let path = format!("{}/source.txt", dir);
+    let text = "fn main() { println!(\"hello\"); }\n".repeat(4000);
+    fs::write(&path, text.as_bytes()).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), text.len() as u64);
+    assert_eq!(fs::read(&path).unwrap(), text.as_bytes());
+
+    // descriptors see the logical contents, seeking and writing in place
+    let before = fs::metadata(&path).unwrap();
+    let generation = fs::backup_incremental(0, std::io::sink()).unwrap();
+    let mut file = fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    // each handle holds its own copy, so a writer keeps the file to itself
+    let err = fs::File::open(&path).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EBUSY"));
+    let err = fs::append(&path, b"more").unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EBUSY"));
+    file.seek(SeekFrom::Start(3)).unwrap();
+    file.write_all(b"MAIN").unwrap();
+    file.seek(SeekFrom::End(0)).unwrap();
+    file.write_all(b"// end").unwrap();
+    assert_eq!(file.metadata().unwrap().len(), text.len() as u64 + 6);
+    file.close().unwrap();
+    let expected = format!("fn MAIN{}// end", &text[7..]);
+    assert_eq!(fs::read(&path).unwrap(), expected.as_bytes());
+    // written back in place of the old contents, which it keeps the node of
+    let after = fs::metadata(&path).unwrap();
+    assert_eq!((after.ino(), after.mode()), (before.ino(), before.mode()));
+    let names: Vec<String> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(names, vec!["source.txt".to_string()]);
+    // the file it was written to first is not a change of its own
+    let mut delta = vec![];
+    fs::backup_incremental(generation, &mut delta).unwrap();
+    assert!(!delta.windows(8).any(|w| w == b"deflate-"));
+    let readers = [fs::File::open(&path).unwrap(), fs::File::open(&path).unwrap()];
+    let err = fs::OpenOptions::new().write(true).open(&path).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EBUSY"));
+    drop(readers);
+
+    // data that does not compress, spanning several blocks
+    let mut seed = 0x2545_f491_u32;
+    let noise: Vec<u8> = (0..200_000)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        })
+        .collect();
+    let binary = format!("{}/noise.bin", dir);
+    fs::write(&binary, &noise).unwrap();
+    assert_eq!(fs::read(&binary).unwrap(), noise);
+
+    fs::set_compression(&path, None).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), expected.as_bytes());
+    fs::set_compression(&path, Some(fs::Compression::Lz4)).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), expected.len() as u64);
+    assert_eq!(fs::read(&path).unwrap(), expected.as_bytes());
+}
+
+#[test]
+fn transaction_applies_all_or_nothing() {
+    let _serial = serial();
+    fs::create_dir_all("/tx").unwrap();
+    fs::write("/tx/index", b"old").unwrap();
+    fs::write("/tx/data", b"old data").unwrap();
+
+    let mut tx = fs::Transaction::new();
+    tx.write_file("/tx/index", b"new", None)
+        .unlink("/tx/data")
+        .rename("/tx/missing", "/tx/data");
+    let err = tx.commit().unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("ENOENT"));
+    assert_eq!(fs::read("/tx/index").unwrap(), b"old");
+    assert_eq!(fs::read("/tx/data").unwrap(), b"old data");
+    let mut names: Vec<_> = fs::read_dir("/tx")
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["/tx/data", "/tx/index"]);
+
+    // readers on other threads never see the index without its data
+    let mut tx = fs::Transaction::new();
+    tx.mkdir("/tx/v2", None)
+        .write_file("/tx/v2/data", &[2u8; 4096], None)
+        .write_file("/tx/index", b"v2", None)
+        .unlink("/tx/data");
+    thread::scope(|s| {
+        let reader = s.spawn(|| loop {
+            let index = fs::read("/tx/index").unwrap();
+            if index == b"v2" {
+                assert_eq!(fs::read("/tx/v2/data").unwrap(), vec![2u8; 4096]);
+                assert!(fs::metadata("/tx/data").is_err());
+                break;
+            }
+            assert!(fs::metadata("/tx/data").is_ok());
+            thread::yield_now();
+        });
+        tx.commit().unwrap();
+        reader.join().unwrap();
+    });
+}
+
+#[test]
+fn temp_names_do_not_collide() {
+    let _serial = serial();
+    fs::create_dir_all("/tmp").unwrap();
+    let dirs = Mutex::new(vec![]);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..20 {
+                    let dir = fs::create_temp_dir("/tmp/race.").unwrap();
+                    dirs.lock().unwrap().push(dir);
+                }
+            });
+        }
+    });
+    let mut dirs = dirs.into_inner().unwrap();
+    assert_eq!(dirs.len(), THREADS * 20);
+    dirs.sort();
+    dirs.dedup();
+    assert_eq!(dirs.len(), THREADS * 20);
+    assert_eq!(fs::metadata(&dirs[0]).unwrap().mode() & 0o777, 0o700);
+
+    let mut file = fs::create_temp_file("/tmp/data.").unwrap();
+    assert_eq!(file.path().len(), "/tmp/data.XXXXXX".len());
+    assert_eq!(file.metadata().unwrap().mode() & 0o777, 0o600);
+    file.write_all(b"scratch").unwrap();
+    let err = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(file.path())
+        .unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EEXIST"));
+    let err = fs::create_temp_file("/missing/data.").unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("ENOENT"));
+
+    let path = file.path().to_string();
+    drop(file);
+    fs::tmpCleanupOnReboot(dirs[0].clone());
+    fs::tmpCleanupOnReboot(path.clone());
+    unsafe { wasabio::wasabio_reboot() };
+    assert!(fs::metadata(&dirs[0]).is_err());
+    assert!(fs::metadata(&path).is_err());
+    assert!(fs::metadata(&dirs[1]).is_ok());
+    for dir in &dirs[1..] {
+        fs::remove_dir(dir).unwrap();
+    }
+}
+
+#[test]
+fn statfs_and_disk_usage() {
+    let _serial = serial();
+    fs::create_dir_all("/du/a/b").unwrap();
+    fs::write("/du/a/big.bin", &[0u8; 10000]).unwrap();
+    fs::write("/du/a/b/small.txt", b"small").unwrap();
+
+    let stat = fs::statfs("/du").unwrap();
+    assert!(stat.bfree < stat.blocks);
+    assert!(stat.ffree < stat.files);
+    assert_eq!(fs::statfs("/dev/null").unwrap().kind, 0x0102_1994);
+    assert_eq!(fs::statfs("/proc/mounts").unwrap().kind, 0x9fa0);
+    let err = fs::statfs("/du/missing").unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("ENOENT"));
+
+    let usage = fs::disk_usage("/du", false, None).unwrap();
+    let paths: Vec<_> = usage.iter().map(|u| u.path.as_str()).collect();
+    assert_eq!(paths, ["/du/a/b", "/du/a", "/du"]);
+    // three blocks of data, the small file is inlined, a metadata pair per directory
+    assert_eq!(usage[2].size, 3 * stat.bsize + 3 * 2 * stat.bsize);
+    let apparent = fs::disk_usage("/du", true, Some(0)).unwrap();
+    assert_eq!(apparent.len(), 1);
+    assert_eq!(apparent[0].size, 10005);
+
+    fs::remove_file("/du/a/b/small.txt").unwrap();
+    fs::remove_file("/du/a/big.bin").unwrap();
+    fs::remove_dir("/du/a/b").unwrap();
+    fs::remove_dir("/du/a").unwrap();
+    fs::remove_dir("/du").unwrap();
+}
+
+#[test]
+fn times_keep_nanoseconds_and_links_keep_their_own() {
+    let _serial = serial();
+    let time = UNIX_EPOCH + Duration::new(1_234_567_890, 123_456_789);
+    fs::write("/times.txt", b"").unwrap();
+    fs::set_times("/times.txt", time, time).unwrap();
+    let meta = fs::metadata("/times.txt").unwrap();
+    assert_eq!(meta.accessed().unwrap(), time);
+    assert_eq!(meta.modified().unwrap(), time);
+
+    fs::symlinkSync("/times.txt".into(), "/times.lnk".into()).unwrap();
+    let later = time + Duration::from_secs(60);
+    fs::set_symlink_times("/times.lnk", later, later).unwrap();
+    let link = fs::symlink_metadata("/times.lnk").unwrap();
+    assert!(link.file_type().is_symlink());
+    assert_eq!(link.modified().unwrap(), later);
+    assert_eq!(fs::metadata("/times.txt").unwrap().modified().unwrap(), time);
+
+    fs::remove_file("/times.lnk").unwrap();
+    fs::remove_file("/times.txt").unwrap();
+}
+
+#[test]
+fn names_match_across_case_and_normal_forms() {
+    let _serial = serial();
+    fs::create_dir("/names").unwrap();
+    fs::write("/names/Twin", b"1").unwrap();
+    fs::write("/names/twin", b"2").unwrap();
+    let folding = fs::NameMatching {
+        case_insensitive: true,
+        normalization: fs::Normalization::Nfc,
+    };
+    let err = fs::set_name_matching(folding).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("EEXIST"));
+    fs::remove_file("/names/twin").unwrap();
+
+    fs::set_name_matching(folding).unwrap();
+    assert_eq!(fs::read("/NAMES/TWIN").unwrap(), b"1");
+    fs::write("/names/Cafe\u{301}", b"coffee").unwrap();
+    assert_eq!(fs::read("/names/CAF\u{c9}").unwrap(), b"coffee");
+    let mut names: Vec<_> = fs::read_dir("/Names")
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["Caf\u{e9}", "Twin"]);
+    fs::remove_file("/names/twin").unwrap();
+    fs::remove_file("/names/caf\u{e9}").unwrap();
+    fs::remove_dir("/NAMES").unwrap();
+    fs::set_name_matching(fs::NameMatching::EXACT).unwrap();
+}
+
+#[test]
+fn paths_are_checked_before_reaching_the_disk() {
+    let _serial = serial();
+    let err = fs::write("/nul\0byte", b"").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(fs::error_code(&err), Some("ERR_INVALID_ARG_VALUE"));
+
+    let long = format!("/{}", "n".repeat(fs::NAME_MAX + 1));
+    let err = fs::write(&long, b"").unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("ENAMETOOLONG"));
+    let deep = "/d".repeat(fs::PATH_MAX / 2);
+    let err = fs::metadata(&deep).unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("ENAMETOOLONG"));
+
+    type Call = fn(&str) -> std::io::Result<()>;
+    let calls: [(&str, Call); 10] = [
+        ("write", |p| fs::write(p, b"")),
+        ("read", |p| fs::read(p).map(drop)),
+        ("append", |p| fs::append(p, b"")),
+        ("metadata", |p| fs::metadata(p).map(drop)),
+        ("set_permissions", |p| fs::set_permissions(p, 0o644)),
+        ("chown", |p| fs::chown(p, 0, 0)),
+        ("set_times", |p| fs::set_times(p, UNIX_EPOCH, UNIX_EPOCH)),
+        ("copy from", |p| fs::copy(p, "/copy")),
+        ("copy to", |p| fs::copy("/dev/null", p)),
+        ("checksum", |p| fs::checksum(p, fs::ChecksumAlgorithm::Sha256, false).map(drop)),
+    ];
+    for (name, call) in calls {
+        let err = call("/nul\0byte").unwrap_err();
+        assert_eq!(fs::error_code(&err), Some("ERR_INVALID_ARG_VALUE"), "{}", name);
+        let err = call(&long).unwrap_err();
+        assert_eq!(fs::error_code(&err), Some("ENAMETOOLONG"), "{}", name);
+    }
+    assert!(fs::metadata("/copy").is_err());
+
+    // the transaction's paths are checked before it takes the disk
+    let mut tx = fs::Transaction::new();
+    tx.rename("/nul\0old", "/new");
+    let err = tx.commit().unwrap_err();
+    assert_eq!(fs::error_code(&err), Some("ERR_INVALID_ARG_VALUE"));
+
+    let longest = format!("/{}", "n".repeat(fs::NAME_MAX));
+    fs::write(&longest, b"longest").unwrap();
+    assert_eq!(fs::read(&longest).unwrap(), b"longest");
+    fs::remove_file(&longest).unwrap();
+}